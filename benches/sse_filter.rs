@@ -0,0 +1,64 @@
+use std::hint::black_box;
+
+use acquire_rs_web::{
+    authentication::Urid,
+    events::PublishedEvent,
+    game::{game_instance::{GameCode, GameInstance, LobbySettings, PlayerListEntry, MAX_PLAYERS}, User},
+    request_data::{EventData, GameEvent},
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rocket::serde::json::to_string;
+use uuid::Uuid;
+
+/// The `PlayerList` event of a full lobby, the largest event that is send to all streams of a game.
+fn player_list() -> GameEvent {
+    let players = (1..=MAX_PLAYERS as u32)
+        .map(|player_id| PlayerListEntry { player_id, seat: player_id as u8, name: player_id.to_string(), connected: true, game_master: player_id == 1, vacancy: None })
+        .collect();
+    GameEvent::PlayerList(players)
+}
+
+/// Decides for `subscribers` streams spread over games with [MAX_PLAYERS]() streams each if they receive an event of
+/// the first game, like the sse handler does for every published event.
+fn visibility_filter(c: &mut Criterion) {
+    let game_code = GameCode::new(['A'; 8]).unwrap();
+    let published = PublishedEvent::new(1, game_code, None, EventData::new(None, game_code, player_list()).unwrap());
+    let mut group = c.benchmark_group("visibility_filter");
+    for subscribers in [6, 36, 216] {
+        let streams: Vec<(GameCode, Uuid)> = (0..subscribers)
+            .map(|stream| (GameCode::new([char::from(b'A' + (stream / MAX_PLAYERS) as u8); 8]).unwrap(), Uuid::new_v4()))
+            .collect();
+        group.bench_with_input(BenchmarkId::from_parameter(subscribers), &streams, |b, streams| {
+            b.iter(|| streams.iter().filter(|(game_code, uuid)| published.is_for(*game_code, *uuid)).map(|_| published.json().len()).sum::<usize>())
+        });
+    }
+    group.finish();
+}
+
+/// Serializes the `PlayerList` event of a full lobby, the channels do this once for all streams of a game.
+fn serialization(c: &mut Criterion) {
+    let game_code = GameCode::new(['A'; 8]).unwrap();
+    let data = EventData::new(None, game_code, player_list()).unwrap();
+    c.bench_function("event_data_serialization", |b| b.iter(|| to_string(black_box(&data)).unwrap()));
+}
+
+/// Builds the events that describe a full lobby to a client that just connected.
+fn lobby_snapshot(c: &mut Criterion) {
+    let game_code = GameCode::new(['A'; 8]).unwrap();
+    let mut game = GameInstance::new(game_code);
+    game.set_settings(LobbySettings::new(2, MAX_PLAYERS, false)).unwrap();
+    for player in 0..MAX_PLAYERS {
+        let uuid = Uuid::new_v4();
+        assert!(game.add_user(User::new(format!("player {}", player), uuid, Urid::from_uuid(Uuid::new_v4()), game_code)));
+        game.user_connected(uuid);
+    }
+    c.bench_function("full_lobby_snapshot", |b| b.iter(|| {
+        let mut events = game.player_list_events();
+        events.append(game.lobby_status_events());
+        events.append(game.lobby_settings_events());
+        events
+    }));
+}
+
+criterion_group!(benches, visibility_filter, serialization, lobby_snapshot);
+criterion_main!(benches);
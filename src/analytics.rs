@@ -0,0 +1,231 @@
+use std::{collections::BTreeMap, convert::Infallible, sync::Mutex};
+
+use rocket::{request::{FromRequest, Outcome}, Request};
+use serde::{Deserialize, Serialize};
+
+/// The number of different languages that are counted, further languages are counted as [OTHER_LANGUAGE]().
+pub const MAX_LANGUAGES: usize = 50;
+
+/// Counts the languages that are missing, that are not a valid primary tag or that exceed [MAX_LANGUAGES]()
+pub const OTHER_LANGUAGE: &str = "other";
+
+/// The page from which a game was created or joined, send by the client in the `source` field.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JoinSource {
+    /// The main lobby page `/lobby`
+    MainPage,
+    /// A shared link to the lobby of a game `/lobby/<game_code>`
+    DeepLink,
+}
+
+/// What the request tells about the client, used by [Analytics]().
+///
+/// This guard never fails, missing or invalid headers are left out.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientHints {
+    /// The primary tag of the first language in the `Accept-Language` header, lowercase
+    pub language: Option<String>,
+    /// Set when the `DNT` header is `1`
+    pub do_not_track: bool,
+}
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for ClientHints {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(ClientHints {
+            language: request.headers().get_one("Accept-Language").and_then(primary_language),
+            do_not_track: request.headers().get_one("DNT").is_some_and(|dnt| dnt.trim() == "1"),
+        })
+    }
+}
+
+/// Returns the primary tag of the first language in an `Accept-Language` header, for example `de` for `de-DE,de;q=0.9,en;q=0.8`.
+///
+/// # Returns
+/// `None` when the header does not start with a tag of 2 or 3 letters.
+fn primary_language(header: &str) -> Option<String> {
+    let first = header.split(',').next()?.split(';').next()?.trim();
+    let primary = first.split('-').next()?;
+    if !(2..=3).contains(&primary.len()) || !primary.chars().all(|char| char.is_ascii_alphabetic()) {
+        return None;
+    }
+    Some(primary.to_ascii_lowercase())
+}
+
+/// How many games were created or joined from each page.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SourceCounts {
+    pub main_page: u64,
+    pub deep_link: u64,
+    /// The client did not send a `source`
+    pub unknown: u64,
+}
+
+impl SourceCounts {
+    fn add(&mut self, source: Option<JoinSource>) {
+        match source {
+            Some(JoinSource::MainPage) => self.main_page += 1,
+            Some(JoinSource::DeepLink) => self.deep_link += 1,
+            None => self.unknown += 1,
+        }
+    }
+}
+
+/// The counters of [Analytics]() as they are included in the [ServerStatus](../request_data/struct.ServerStatus.html).
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnalyticsReport {
+    pub created: SourceCounts,
+    pub joined: SourceCounts,
+    /// How often each language was used when a game was created or joined, see [MAX_LANGUAGES]()
+    pub languages: BTreeMap<String, u64>,
+}
+
+/// Aggregated counters of how people arrive at a game and which languages they use, managed by rocket.
+///
+/// Only counters are stored, never anything that identifies a user or a game. Requests with `DNT: 1` are not counted.
+///
+/// The collection can be turned off with `analytics = false` in the rocket configuration, the counters then stay empty
+/// and are not included in the status.
+pub struct Analytics {
+    enabled: bool,
+    report: Mutex<AnalyticsReport>,
+}
+
+impl Analytics {
+    /// Creates empty counters, nothing is counted when `enabled` is `false`.
+    pub fn new(enabled: bool) -> Self {
+        Self { enabled, report: Mutex::new(AnalyticsReport::default()) }
+    }
+
+    /// Counts a game that was created from `source`.
+    pub fn record_created(&self, source: Option<JoinSource>, hints: &ClientHints) {
+        self.record(hints, |report| report.created.add(source));
+    }
+
+    /// Counts a user that joined a game from `source`.
+    pub fn record_joined(&self, source: Option<JoinSource>, hints: &ClientHints) {
+        self.record(hints, |report| report.joined.add(source));
+    }
+
+    fn record(&self, hints: &ClientHints, count: impl FnOnce(&mut AnalyticsReport)) {
+        if !self.enabled || hints.do_not_track {
+            return;
+        }
+        let mut report = self.report.lock().unwrap();
+        count(&mut report);
+        let language = match &hints.language {
+            Some(language) if report.languages.contains_key(language) || report.languages.len() < MAX_LANGUAGES => language.as_str(),
+            _ => OTHER_LANGUAGE,
+        };
+        *report.languages.entry(String::from(language)).or_default() += 1;
+    }
+
+    /// Returns the counters, `None` when the collection is turned off.
+    pub fn report(&self) -> Option<AnalyticsReport> {
+        self.enabled.then(|| self.report.lock().unwrap().clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::{
+        http::{ContentType, Header, Status},
+        local::blocking::Client,
+        serde::json::{to_string, Value},
+    };
+
+    use super::{primary_language, Analytics, ClientHints, JoinSource, MAX_LANGUAGES, OTHER_LANGUAGE};
+
+    fn hints(language: &str) -> ClientHints {
+        ClientHints { language: Some(String::from(language)), do_not_track: false }
+    }
+
+    #[test]
+    fn test_primary_language() {
+        assert_eq!(Some(String::from("de")), primary_language("de-DE,de;q=0.9,en;q=0.8"));
+        assert_eq!(Some(String::from("en")), primary_language(" EN;q=0.5"));
+        assert_eq!(Some(String::from("fil")), primary_language("fil-PH"));
+        for invalid in ["", "*", "e", "english", "d3", "🦀"] {
+            assert_eq!(None, primary_language(invalid), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_counters() {
+        let analytics = Analytics::new(true);
+        analytics.record_created(Some(JoinSource::MainPage), &hints("de"));
+        analytics.record_joined(Some(JoinSource::DeepLink), &hints("de"));
+        analytics.record_joined(Some(JoinSource::DeepLink), &hints("en"));
+        analytics.record_joined(None, &ClientHints::default());
+        analytics.record_joined(Some(JoinSource::MainPage), &ClientHints { language: Some(String::from("fr")), do_not_track: true });
+        let report = analytics.report().unwrap();
+        assert_eq!((1, 0, 0), (report.created.main_page, report.created.deep_link, report.created.unknown));
+        assert_eq!((0, 2, 1), (report.joined.main_page, report.joined.deep_link, report.joined.unknown));
+        let languages: Vec<(&str, u64)> = report.languages.iter().map(|(language, count)| (language.as_str(), *count)).collect();
+        assert_eq!(vec![("de", 2), ("en", 1), (OTHER_LANGUAGE, 1)], languages);
+
+        // the number of languages is limited
+        for i in 0..MAX_LANGUAGES {
+            analytics.record_created(None, &hints(&format!("{}{}", char::from(b'a' + (i / 26) as u8), char::from(b'a' + (i % 26) as u8))));
+        }
+        let report = analytics.report().unwrap();
+        assert_eq!(MAX_LANGUAGES, report.languages.len());
+        assert_eq!(Some(&4), report.languages.get(OTHER_LANGUAGE));
+        analytics.record_created(None, &hints("de"));
+        assert_eq!(Some(&3), analytics.report().unwrap().languages.get("de"));
+
+        let disabled = Analytics::new(false);
+        disabled.record_created(Some(JoinSource::MainPage), &hints("de"));
+        assert_eq!(None, disabled.report());
+        assert!(disabled.report.lock().unwrap().languages.is_empty());
+    }
+
+    #[test]
+    fn test_no_identifiers_are_collected() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        let registration: Value = client.post("/api/create_game")
+            .header(ContentType::JSON)
+            .header(Header::new("Accept-Language", "de-DE"))
+            .header(Header::new("X-Real-IP", "192.0.2.7"))
+            .body(r#"{"username":"gm","source":"main_page"}"#)
+            .dispatch()
+            .into_json()
+            .unwrap();
+        let report = client.rocket().state::<Analytics>().unwrap().report().unwrap();
+        assert_eq!(1, report.created.main_page);
+        let report = to_string(&report).unwrap();
+        for identifier in [&registration["uuid"], &registration["urid"]["uuid"], &registration["game_code"]] {
+            assert!(!report.contains(identifier.as_str().unwrap()), "{} in {}", identifier, report);
+        }
+        assert!(!report.contains("192.0.2.7"));
+
+        let join = |dnt: &str| client.post("/api/join_game")
+            .header(Header::new("game_code", String::from(registration["game_code"].as_str().unwrap())))
+            .header(Header::new("DNT", String::from(dnt)))
+            .header(ContentType::JSON)
+            .body(format!(r#"{{"username":"dnt {}","source":"deep_link"}}"#, dnt))
+            .dispatch();
+        join("1");
+        join("0");
+        let report = client.rocket().state::<Analytics>().unwrap().report().unwrap();
+        assert_eq!(1, report.joined.deep_link);
+        assert_eq!(1, report.languages["other"]);
+    }
+
+    #[test]
+    fn test_disabled_by_config() {
+        let figment = rocket::Config::figment().merge(("analytics", false)).merge(("admin_token", "secret"));
+        let client = Client::tracked(crate::server(rocket::custom(figment))).unwrap();
+        let response = client.post("/api/create_game").header(ContentType::JSON).body(r#"{"username":"gm","source":"main_page"}"#).dispatch();
+        assert_eq!(Status::Ok, response.status());
+        let status: Value = client.get("/api/status").header(Header::new("admin_token", "secret")).dispatch().into_json().unwrap();
+        assert!(status.get("analytics").is_none());
+        assert!(status.get("abandonment").is_some());
+        // unknown sources are rejected
+        let response = client.post("/api/create_game").header(ContentType::JSON).body(r#"{"username":"gm","source":"newsletter"}"#).dispatch();
+        assert_eq!(Status::BadRequest, response.status());
+    }
+}
@@ -1,14 +1,15 @@
-use std::{sync::{RwLock, RwLockReadGuard}, clone, collections::{HashSet, HashMap}, net::IpAddr};
+use std::{collections::{HashSet, HashMap}, net::IpAddr, time::{Duration, SystemTime, UNIX_EPOCH}, hash::{Hash, Hasher}};
 
 use rocket::{
-    http::{Status, CookieJar},
+    http::Status,
     request::{FromRequest, Outcome},
+    tokio::time::sleep,
 };
 use serde::{Serialize, Deserialize};
 use uuid::Uuid;
 
 use crate::{
-    game::{GameManager, game_instance::GameCode}, paths::utils::get_gm_read_guard,
+    game::{shards::ShardedGameManager, game_instance::GameCode}, utils::get_gm_read_guard, rate_limit::CodeGuessLimiter,
 };
 
 /// Errors that can occur when the user tries to authenticate a request
@@ -52,14 +53,13 @@ pub struct UserAuth {
 impl UserAuth {
     
     /// Constructs a new [UserAuth]() by checking if the `user_id` exists and is assigned to a game.
-    pub fn from_uuid(game_manager: RwLockReadGuard<GameManager>, user_id: Uuid) -> Option<Self> {
-        match game_manager.game_by_uuid_read(user_id) {
-            Some(game) => Some(UserAuth {
-                uuid: user_id,
-                game_code: game.game_code().clone(),
-            }),
-            None => None,
-        }
+    pub fn from_uuid(game_manager: &ShardedGameManager, user_id: Uuid) -> Option<Self> {
+        let game_manager = get_gm_read_guard(game_manager.shard_by_uuid(user_id)?, "user_auth: from uuid");
+        let game_code = *game_manager.game_by_uuid_read(user_id)?.game_code();
+        Some(UserAuth {
+            uuid: user_id,
+            game_code,
+        })
     }
 }
 
@@ -70,15 +70,43 @@ impl<'r> FromRequest<'r> for UserAuth {
     async fn from_request(request: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
         let user_id = match request.headers().get_one("user_id") {
             Some(header) => header,
-            None => return Outcome::Failure((Status::Forbidden, FromRequestError::Missing(String::from("The user_id header is missing")))),
+            None => return Outcome::Error((Status::Forbidden, FromRequestError::Missing(String::from("The user_id header is missing")))),
         };
         let user_id = match user_id.parse::<Uuid>() {
             Ok(id) => id,
-            Err(_e) => return Outcome::Failure((Status::Forbidden, FromRequestError::Invalid(String::from("user_id is not a number"))))
+            Err(_e) => return Outcome::Error((Status::Forbidden, FromRequestError::Invalid(String::from("user_id is not a number"))))
         };
-        match UserAuth::from_uuid(get_gm_read_guard(request.rocket().state::<RwLock<GameManager>>().unwrap(), "user_auth: from request"), user_id) {
+        match UserAuth::from_uuid(request.rocket().state::<ShardedGameManager>().unwrap(), user_id) {
             Some(auth) => Outcome::Success(auth),
-            None => return Outcome::Failure((Status::Forbidden, FromRequestError::Invalid(String::from("game not found")))),
+            None => return Outcome::Error((Status::Forbidden, FromRequestError::Invalid(String::from("game not found")))),
+        }
+    }
+}
+
+/// The token that admins have to send in the `admin_token` header, managed by rocket.
+/// 
+/// The token is set with `admin_token` in the rocket configuration, when it is not set all admin routes are disabled.
+pub struct AdminToken(pub Option<String>);
+
+/// Symbolizes the authentication of an admin.
+/// 
+/// # Request Guard
+/// For an `AdminAuth` to succeed the `admin_token` header has to match the configured [AdminToken]().
+pub struct AdminAuth;
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for AdminAuth {
+    type Error = FromRequestError;
+
+    async fn from_request(request: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
+        let token = match request.rocket().state::<AdminToken>() {
+            Some(AdminToken(Some(token))) => token,
+            _ => return Outcome::Error((Status::Forbidden, FromRequestError::Invalid(String::from("Admin routes are disabled")))),
+        };
+        match request.headers().get_one("admin_token") {
+            Some(header) if header == token => Outcome::Success(AdminAuth),
+            Some(_) => Outcome::Error((Status::Forbidden, FromRequestError::Invalid(String::from("admin_token is invalid")))),
+            None => Outcome::Error((Status::Forbidden, FromRequestError::Missing(String::from("The admin_token header is missing")))),
         }
     }
 }
@@ -92,6 +120,8 @@ pub enum GameCodeError {
     ParseError,
     /// No game was found for the game code
     NotFound,
+    /// The client has failed too many lookups, see [CodeGuessLimiter](../rate_limit/struct.CodeGuessLimiter.html)
+    TooManyGuesses,
 }
 
 #[rocket::async_trait]
@@ -99,28 +129,28 @@ impl<'r> FromRequest<'r> for GameCode {
     type Error = GameCodeError;
 
     async fn from_request(request: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
-        let mut game_manager = request
-            .rocket()
-            .state::<RwLock<GameManager>>()
-            .unwrap()
-            .write()
-            .unwrap();
+        let game_manager = request.rocket().state::<ShardedGameManager>().unwrap();
         // Check if header was submitted
         let game_code_string = match request.headers().get_one("game_code") {
             Some(header) => header,
-            None => return Outcome::Failure((Status::Forbidden, GameCodeError::Missing)),
-        };
-        // Check if the game code can be parsed
-        let game_code = match GameCode::from_string(game_code_string) {
-            Some(code) => code,
-            None => return Outcome::Failure((Status::Forbidden, GameCodeError::ParseError,))
+            None => return Outcome::Error((Status::Forbidden, GameCodeError::Missing)),
         };
-        // Check if a game with the game code exists
-        if game_manager.does_game_exist(&game_code) {
-            Outcome::Success(game_code)
-        } else {
-            Outcome::Failure((Status::Forbidden, GameCodeError::NotFound))
+        // Reject clients that already guessed too many codes, see CodeGuessLimiter
+        let limiter = request.rocket().state::<CodeGuessLimiter>().unwrap();
+        let ip_addr = request.client_ip();
+        if limiter.is_locked(ip_addr) {
+            return Outcome::Error((Status::TooManyRequests, GameCodeError::TooManyGuesses));
         }
+        // Check if the game code can be parsed and if a game with the game code exists
+        let error = match GameCode::from_string(game_code_string) {
+            Some(game_code) if game_manager.does_game_exist(&game_code) => return Outcome::Success(game_code),
+            Some(_) => GameCodeError::NotFound,
+            None => GameCodeError::ParseError,
+        };
+        // Failures are delayed so that they can not be told apart from successful lookups by the response time
+        limiter.record_failure(ip_addr);
+        sleep(limiter.failure_delay()).await;
+        Outcome::Error((Status::Forbidden, error))
     }
 }
 
@@ -160,46 +190,117 @@ impl<'r> FromRequest<'r> for UserRecovery {
     type Error = FromRequestError;
 
     async fn from_request(request: &'r rocket::Request<'_>) -> Outcome<Self, Self::Error> {
-        let ur = match request.cookies().get("urid").map(|cookie| cookie.value().parse::<String>().unwrap()) {
-            Some(value) => {
-                match Uuid::parse_str(&value) {
-                    Ok(uuid) => Ok(UserRecovery::new(Urid::from_uuid(uuid), request.client_ip())),
-                    Err(_err) => Err(FromRequestError::Invalid(String::from("Unable to construct ruid from cookie, value invalid"))),
+        let ur = match request.cookies().get("urid") {
+            Some(cookie) => {
+                match Urid::from_cookie_value(cookie.value()) {
+                    Some(urid) => Ok(UserRecovery::new(urid, request.client_ip())),
+                    None => Err(FromRequestError::Invalid(String::from("Unable to construct ruid from cookie, value invalid"))),
                 }
             }
             None => Err(FromRequestError::Missing(String::from("Cookie named urid missing"))),
         };
         match ur {
             Ok(urid) => Outcome::Success(urid),
-            Err(_err) => Outcome::Forward(()),
+            Err(_err) => Outcome::Forward(Status::Forbidden),
         }
     }
 
 }
 
+/// The maximum age of a [Urid]() that can still be used to recover a lost connection.
+///
+/// Recovery attempts with older urids are refused with the error code `recovery_expired`.
+pub const URID_MAX_AGE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+
 /// User recovery id that is used to recover a lost connection.
-#[derive(Hash, PartialEq, Eq, Serialize, Deserialize, Clone, Copy)]
+///
+/// Two urids are equal when their `uuid` is equal, the `issued_at` timestamp is not compared.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
 pub struct Urid {
     uuid: Uuid,
+    /// The time at which this urid was issued, serialized as unix seconds.
+    ///
+    /// When this field is missing (urids issued before the timestamp was added), the urid is treated as freshly issued.
+    #[serde(with = "unix_seconds", default = "SystemTime::now")]
+    issued_at: SystemTime,
 }
 
 impl Urid {
-    /// Creates a new `Urid`
-    pub fn new() -> Self {
-        Self {
-            uuid: Uuid::new_v4(),
-        }
-    }
-
+    /// Creates a new urid that is issued now.
     pub fn from_uuid(uuid: Uuid) -> Self {
         Self {
             uuid,
+            issued_at: SystemTime::now(),
         }
     }
 
-    /// Returns the value of this urid.
-    pub fn value(&self) -> Uuid {
-        self.uuid
+    /// Constructs a urid from the value of the `urid` cookie.
+    ///
+    /// The value is formatted like the result of [cookie_value()](#method.cookie_value): `<uuid>:<issued at in unix seconds>`.
+    ///
+    /// Cookies that where set before the timestamp was added only contain the uuid,
+    /// these are accepted and the urid is treated as freshly issued, because the age is unknown.
+    ///
+    /// # Returns
+    /// `None` when the value could not be parsed.
+    pub fn from_cookie_value(value: &str) -> Option<Self> {
+        let (uuid, issued_at) = match value.split_once(':') {
+            Some((uuid, secs)) => (uuid, UNIX_EPOCH + Duration::from_secs(secs.parse().ok()?)),
+            None => (value, SystemTime::now()),
+        };
+        Some(Self {
+            uuid: Uuid::parse_str(uuid).ok()?,
+            issued_at,
+        })
+    }
+
+    /// Returns the value that is stored in the `urid` cookie.
+    pub fn cookie_value(&self) -> String {
+        format!("{}:{}", self.uuid, unix_seconds::secs(self.issued_at))
+    }
+
+    /// Returns how long ago this urid was issued.
+    pub fn age(&self) -> Duration {
+        SystemTime::now().duration_since(self.issued_at).unwrap_or_default()
+    }
+
+    /// Checks if this urid is too old to be used for recovery, see [URID_MAX_AGE]().
+    pub fn is_expired(&self) -> bool {
+        self.age() > URID_MAX_AGE
+    }
+}
+
+impl PartialEq for Urid {
+    fn eq(&self, other: &Self) -> bool {
+        self.uuid == other.uuid
+    }
+}
+
+impl Eq for Urid {}
+
+impl Hash for Urid {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.uuid.hash(state);
+    }
+}
+
+/// (De)serializes a [SystemTime]() as unix seconds.
+mod unix_seconds {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    /// Returns the unix seconds of `time`, times before the unix epoch are returned as 0.
+    pub fn secs(time: SystemTime) -> u64 {
+        time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+    }
+
+    pub fn serialize<S: Serializer>(time: &SystemTime, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(secs(*time))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SystemTime, D::Error> {
+        Ok(UNIX_EPOCH + Duration::from_secs(u64::deserialize(deserializer)?))
     }
 }
 
@@ -209,6 +310,17 @@ pub struct Urids {
     used_urids: HashSet<Urid>,
     /// All user recovery ids mapped to an ip address
     urid_by_ip: HashMap<IpAddr, Urid>,
+    /// The name that was last used with a user recovery id.
+    ///
+    /// Names are kept when the game of the urid is deleted so that returning users do not have to enter their name again,
+    /// they are removed when the urid expires, see [URID_MAX_AGE]().
+    names: HashMap<Urid, String>,
+}
+
+impl Default for Urids {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Urids {
@@ -216,25 +328,10 @@ impl Urids {
         Self {
             used_urids: HashSet::new(),
             urid_by_ip: HashMap::new(),
+            names: HashMap::new(),
         }
     }
 
-    /// Adds the urid to the `used_urids` set.
-    /// 
-    /// If an [IpAddr]() is provided, the urid will also be added to the
-    /// `urid_by_ip` map.
-    /// If the map already contains this address the value is not updated.
-    /// 
-    /// Returns whether the urid was newly added. That is:
-    /// - If the urid was newly added, `true` is returned.
-    /// - If the urid was already added, `false` is returned.
-    pub fn add_urid(&mut self, urid: Urid, ip_addr: Option<IpAddr>) -> bool {
-        if ip_addr.is_some() && !self.urid_by_ip.contains_key(&ip_addr.unwrap()) {
-            self.urid_by_ip.insert(ip_addr.unwrap(), urid);
-        }
-        self.used_urids.insert(urid)
-    }
-
     /// Generate a new [Urid]() and register it or return the [Urid]() linked to
     /// the `ip_addr`.
     /// 
@@ -250,8 +347,8 @@ impl Urids {
         let urid = self.generate_urid();
         match ip_addr {
             Some(value) => {
-                if self.urid_by_ip.contains_key(&value) {
-                    *self.urid_by_ip.get(&value).unwrap()
+                if let Some(registered) = self.urid_by_ip.get(&value) {
+                    *registered
                 } else {
                     self.urid_by_ip.insert(value, urid);
                     self.used_urids.insert(urid);
@@ -265,29 +362,13 @@ impl Urids {
         }
     }
 
-    /// Unregisters the `urid`.
-    /// 
-    /// Takes O(n) time, `n` being the amount of elements in the `urid_by_ip` field.
-    pub fn unregister(&mut self, urid: Urid) {
-        self.used_urids.remove(&urid);
-        let mut ip_to_remove: Option<IpAddr> = None;
-        for (k, v) in &self.urid_by_ip {
-            if *v == urid {
-                ip_to_remove = Some(*k);
-            }
-        }
-        if ip_to_remove.is_some() {
-            self.urid_by_ip.remove(&ip_to_remove.unwrap());
-        }
-    }
-
     /// Unregisters all provided `urids`.
     /// 
     /// Takes O(n) time, `n` being the amount of elements in the `urid_by_ip` field.
     pub fn unregister_all(&mut self, urids: &HashSet<Urid>) {
         let mut ips_to_remove: HashSet<IpAddr> = HashSet::new();
         for urid in urids {
-            self.used_urids.remove(&urid);
+            self.used_urids.remove(urid);
         }
         for (k, v) in &self.urid_by_ip {
             for urid in urids {
@@ -301,15 +382,101 @@ impl Urids {
         }
     }
 
+    /// Remembers `name` as the last name that was used with `urid`, names of expired urids are removed.
+    pub fn remember_name(&mut self, urid: Urid, name: String) {
+        self.names.retain(|urid, _| !urid.is_expired());
+        self.names.insert(urid, name);
+    }
+
+    /// Returns the name that was last used with `urid`.
+    ///
+    /// # Returns
+    /// `None` when no name is known for the urid or the urid has expired.
+    pub fn name(&self, urid: &Urid) -> Option<&String> {
+        self.names.get_key_value(urid)
+            .filter(|(urid, _)| !urid.is_expired())
+            .map(|(_, name)| name)
+    }
+
     /// Generates a uniqe recovery id that is not yet in use.
     /// 
     /// This does not add the generated id to the `used_urid` set.
     pub fn generate_urid(&self) -> Urid {
         let mut urid = Urid::from_uuid(Uuid::new_v4());
-        while self.used_urids.contains(&urid) {
+        while self.used_urids.contains(&urid) || self.names.contains_key(&urid) {
             urid = Urid::from_uuid(Uuid::new_v4());
         }
         urid
     }
 
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+    use uuid::Uuid;
+
+    use crate::game::{game_instance::{GameCode, GameInstance}, User, UserRegistrationError};
+
+    use super::{Urid, Urids, UserRecovery, URID_MAX_AGE};
+
+    /// Returns the unix seconds of a time that lies `age` in the past.
+    fn secs_ago(age: Duration) -> u64 {
+        (SystemTime::now() - age).duration_since(UNIX_EPOCH).unwrap().as_secs()
+    }
+
+    #[test]
+    fn test_urid_equality_ignores_issued_at() {
+        let uuid = Uuid::new_v4();
+        let fresh = Urid::from_uuid(uuid);
+        let old = Urid::from_cookie_value(&format!("{}:{}", uuid, secs_ago(URID_MAX_AGE * 2))).unwrap();
+        assert_eq!(fresh, old);
+        assert!(old.is_expired());
+        assert!(!fresh.is_expired());
+        let set: HashSet<Urid> = [fresh, old].into_iter().collect();
+        assert_eq!(1, set.len());
+    }
+
+    #[test]
+    fn test_urid_cookie_value() {
+        let urid = Urid::from_uuid(Uuid::new_v4());
+        assert_eq!(Some(urid), Urid::from_cookie_value(&urid.cookie_value()));
+        let uuid = Uuid::new_v4();
+        let legacy = Urid::from_cookie_value(&uuid.to_string()).unwrap();
+        assert_eq!(Urid::from_uuid(uuid), legacy);
+        assert!(!legacy.is_expired());
+        assert_eq!(None, Urid::from_cookie_value("not a uuid"));
+        assert_eq!(None, Urid::from_cookie_value(&format!("{}:abc", uuid)));
+    }
+
+    #[test]
+    fn test_expired_urid_recovery_refused() {
+        let game_code = GameCode::new(['A'; 8]).unwrap();
+        let mut game = GameInstance::new(game_code);
+        let uuid = Uuid::new_v4();
+        let fresh = Urid::from_uuid(Uuid::new_v4());
+        let expired = Urid::from_cookie_value(&format!("{}:{}", uuid, secs_ago(URID_MAX_AGE + Duration::from_secs(60)))).unwrap();
+        game.add_user(User::new(String::from("fresh"), Uuid::new_v4(), fresh, game_code));
+        game.add_user(User::new(String::from("old"), Uuid::new_v4(), expired, game_code));
+        let recovery = |urid: Urid, name: &str| UserRecovery { urid, name: Some(String::from(name)), ip_addr: None };
+        assert_eq!(Ok(()), game.validate_urid(&recovery(fresh, "fresh")));
+        assert_eq!(Err(UserRegistrationError::NameTaken), game.validate_urid(&recovery(fresh, "old")));
+        // the timestamp in the cookie is not trusted, the stored urid decides
+        assert_eq!(Err(UserRegistrationError::RecoveryExpired), game.validate_urid(&recovery(Urid::from_uuid(uuid), "old")));
+    }
+
+    #[test]
+    fn test_names_are_purged_on_expiry() {
+        let mut urids = Urids::new();
+        let uuid = Uuid::new_v4();
+        let expired = Urid::from_cookie_value(&format!("{}:{}", uuid, secs_ago(URID_MAX_AGE + Duration::from_secs(60)))).unwrap();
+        urids.remember_name(expired, String::from("old"));
+        // the timestamp in the cookie is not trusted, the stored urid decides
+        assert_eq!(None, urids.name(&Urid::from_uuid(uuid)));
+        let fresh = Urid::from_uuid(Uuid::new_v4());
+        urids.remember_name(fresh, String::from("Alice"));
+        assert_eq!(Some(&String::from("Alice")), urids.name(&fresh));
+        assert!(!urids.names.contains_key(&expired));
+    }
+}
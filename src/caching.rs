@@ -0,0 +1,221 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+    path::Path,
+    time::UNIX_EPOCH,
+};
+
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    fs::NamedFile,
+    http::{ContentType, Header, Status},
+    response::{self, Responder},
+    Request, Response,
+};
+
+/// How long a static asset that is not content hashed may be cached by the browser.
+///
+/// This is kept short because the file names of these assets do not change when the content changes.
+const ASSET_MAX_AGE_SECS: u64 = 300;
+
+/// How long a content hashed static asset may be cached by the browser.
+const HASHED_ASSET_MAX_AGE_SECS: u64 = 31_536_000;
+
+/// The caching policy that is used for a [CachedFile]().
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CachePolicy {
+    /// The browser has to revalidate the file each time it is used.
+    ///
+    /// Used for the html pages so that players always get the current version of the frontend.
+    NoCache,
+    /// The file can be cached for [ASSET_MAX_AGE_SECS]() or, when the file name contains a content hash, indefinitely.
+    Asset,
+}
+
+impl CachePolicy {
+    /// Returns the value of the `Cache-Control` header for a file at `path`.
+    fn header_value(&self, path: &Path) -> String {
+        match self {
+            CachePolicy::NoCache => String::from("no-cache"),
+            CachePolicy::Asset if is_hashed_asset(path) => format!("public, max-age={}, immutable", HASHED_ASSET_MAX_AGE_SECS),
+            CachePolicy::Asset => format!("public, max-age={}", ASSET_MAX_AGE_SECS),
+        }
+    }
+}
+
+/// A [NamedFile]() that is send with an `ETag` and a `Cache-Control` header.
+///
+/// The `ETag` is derived from the modification time and size of the file.
+/// When the request contains an `If-None-Match` header that matches the `ETag`
+/// the file is not send again and `304 Not Modified` is returned instead.
+pub struct CachedFile {
+    file: NamedFile,
+    policy: CachePolicy,
+}
+
+impl CachedFile {
+    /// Wraps a html page, see [CachePolicy::NoCache]().
+    pub fn page(file: NamedFile) -> Self {
+        Self {
+            file,
+            policy: CachePolicy::NoCache,
+        }
+    }
+
+    /// Wraps a static asset, see [CachePolicy::Asset]().
+    pub fn asset(file: NamedFile) -> Self {
+        Self {
+            file,
+            policy: CachePolicy::Asset,
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for CachedFile {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let cache_control = Header::new("Cache-Control", self.policy.header_value(self.file.path()));
+        let etag = etag(self.file.path());
+        if let Some(etag) = &etag {
+            if request.headers().get("If-None-Match").any(|value| etag_matches(value, etag)) {
+                return Response::build()
+                    .status(Status::NotModified)
+                    .header(Header::new("ETag", etag.clone()))
+                    .header(cache_control)
+                    .ok();
+            }
+        }
+        let mut response = self.file.respond_to(request)?;
+        response.set_header(cache_control);
+        if let Some(etag) = etag {
+            response.set_header(Header::new("ETag", etag));
+        }
+        Ok(response)
+    }
+}
+
+/// A html page that was rendered by the server, see [pages](../paths/pages/index.html).
+///
+/// Like a [CachedFile::page]() the page has to be revalidated each time, the `ETag` is derived from the rendered content.
+pub struct RenderedPage(pub String);
+
+impl<'r> Responder<'r, 'static> for RenderedPage {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let mut hasher = DefaultHasher::new();
+        self.0.hash(&mut hasher);
+        let etag = format!("\"{:x}-{:x}\"", hasher.finish(), self.0.len());
+        let cache_control = Header::new("Cache-Control", CachePolicy::NoCache.header_value(Path::new("")));
+        if request.headers().get("If-None-Match").any(|value| etag_matches(value, &etag)) {
+            return Response::build()
+                .status(Status::NotModified)
+                .header(Header::new("ETag", etag))
+                .header(cache_control)
+                .ok();
+        }
+        let mut response = (ContentType::HTML, self.0).respond_to(request)?;
+        response.set_header(cache_control);
+        response.set_header(Header::new("ETag", etag));
+        Ok(response)
+    }
+}
+
+/// Fairing that makes sure that no html response is cached without revalidation.
+///
+/// Html responses that already contain a `Cache-Control` header (for example the ones send by [CachedFile]()) are not modified.
+pub struct CacheHeaders;
+
+#[rocket::async_trait]
+impl Fairing for CacheHeaders {
+    fn info(&self) -> Info {
+        Info {
+            name: "Cache headers",
+            kind: Kind::Response,
+        }
+    }
+
+    async fn on_response<'r>(&self, _request: &'r Request<'_>, response: &mut Response<'r>) {
+        if response.content_type() == Some(ContentType::HTML) && !response.headers().contains("Cache-Control") {
+            response.set_header(Header::new("Cache-Control", "no-cache"));
+        }
+    }
+}
+
+/// Computes the `ETag` for the file at `path` from its modification time and size.
+///
+/// Returns `None` when the metadata of the file could not be read.
+fn etag(path: &Path) -> Option<String> {
+    let metadata = std::fs::metadata(path).ok()?;
+    let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+    Some(format!("\"{:x}-{:x}-{:x}\"", modified.as_secs(), modified.subsec_nanos(), metadata.len()))
+}
+
+/// Checks if the value of an `If-None-Match` header matches the `etag`.
+///
+/// The header value can contain multiple (possibly weak) tags separated by commas or `*`.
+fn etag_matches(header_value: &str, etag: &str) -> bool {
+    header_value
+        .split(',')
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+/// Checks if the file name contains a content hash, for example `acquire_rs_wasm.3f2a9c1b.js`.
+fn is_hashed_asset(path: &Path) -> bool {
+    match path.file_name().and_then(|name| name.to_str()) {
+        Some(name) => name
+            .split('.')
+            .skip(1)
+            .any(|part| part.len() >= 8 && part.chars().all(|c| c.is_ascii_hexdigit())),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use rocket::{http::{Header, Status}, local::blocking::Client};
+
+    use super::{etag_matches, is_hashed_asset};
+
+    #[test]
+    fn test_etag_round_trip() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        let response = client.get("/lobby").dispatch();
+        assert_eq!(Status::Ok, response.status());
+        let etag = response.headers().get_one("ETag").unwrap().to_string();
+        let response = client.get("/lobby").header(Header::new("If-None-Match", etag.clone())).dispatch();
+        assert_eq!(Status::NotModified, response.status());
+        assert_eq!(Some(etag.as_str()), response.headers().get_one("ETag"));
+        assert!(response.into_string().unwrap_or_default().is_empty());
+    }
+
+    #[test]
+    fn test_html_is_not_cached() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        for path in ["/lobby", "/", "/index.html"] {
+            let response = client.get(path).dispatch();
+            assert_eq!(Status::Ok, response.status());
+            assert_eq!(Some("no-cache"), response.headers().get_one("Cache-Control"), "{}", path);
+        }
+    }
+
+    #[test]
+    fn test_assets_are_cached() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        let response = client.get("/styles/lobby_style.css").dispatch();
+        assert_eq!(Status::Ok, response.status());
+        assert!(response.headers().get_one("Cache-Control").unwrap().contains("max-age="));
+        assert!(response.headers().get_one("ETag").is_some());
+        assert_eq!(Status::NotFound, client.get("/styles/missing.css").dispatch().status());
+    }
+
+    #[test]
+    fn test_etag_matching() {
+        assert!(etag_matches("\"a\"", "\"a\""));
+        assert!(etag_matches("\"b\", W/\"a\"", "\"a\""));
+        assert!(etag_matches("*", "\"a\""));
+        assert!(!etag_matches("\"b\"", "\"a\""));
+        assert!(is_hashed_asset(Path::new("wasm/acquire_rs_wasm.3f2a9c1b.js")));
+        assert!(!is_hashed_asset(Path::new("scripts/lobby_script.js")));
+    }
+}
@@ -0,0 +1,381 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    log::private::info,
+    tokio::{self, sync::Notify, time::{interval, MissedTickBehavior}},
+    Orbit, Rocket,
+};
+use serde::Deserialize;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::{authentication::UserAuth, events::EventBus, game::{game_instance::GameCode, shards::ShardedGameManager, GAME_INSTANCE_TIMEOUT}};
+
+/// The default time after which a keep-alive comment is send on a quiet sse stream, the comment is also the heartbeat of the stream.
+/// 
+/// Some reverse proxies close sse connections on which nothing was send for 30 to 60 seconds.
+/// The interval can be changed with `sse_keep_alive_ms` in the rocket configuration.
+pub const DEFAULT_KEEP_ALIVE: Duration = Duration::from_secs(15);
+
+/// The number of keep-alive intervals after which a stream that has not send anything is treated as dead, see [DisconnectDeadStreams]().
+pub const DEAD_STREAM_INTERVALS: u32 = 2;
+
+/// What happens when a user opens more sse streams than allowed by [StreamLimits::per_user]().
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UserLimitPolicy {
+    /// The new stream is rejected.
+    Reject,
+    /// The stream of the user that was seen the longest time ago (usually the oldest stream) is closed and the new stream is accepted.
+    ///
+    /// Useful when browsers do not close old streams reliably when the page is reloaded.
+    ReplaceOldest,
+}
+
+/// The maximum number of sse streams that can be open at the same time.
+/// 
+/// The limits can be changed in the `stream_limits` table of the rocket configuration, for example in `Rocket.toml`:
+/// 
+/// ```toml
+/// [default.stream_limits]
+/// per_user = 1
+/// user_policy = "replace_oldest"
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct StreamLimits {
+    /// Streams per user
+    pub per_user: usize,
+    /// Streams per client ip address
+    pub per_ip: usize,
+    /// Streams per game, as multiple of the maximum number of players of the game
+    pub per_game_factor: usize,
+    /// What happens when [per_user](#structfield.per_user) is exceeded
+    pub user_policy: UserLimitPolicy,
+}
+
+impl Default for StreamLimits {
+    fn default() -> Self {
+        Self {
+            per_user: 2,
+            per_ip: 10,
+            per_game_factor: 2,
+            user_policy: UserLimitPolicy::Reject,
+        }
+    }
+}
+
+/// The limit that would have been exceeded by opening a new sse stream.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum StreamLimitError {
+    #[error("too many streams for this user")]
+    User,
+    #[error("too many streams from this ip address")]
+    Ip,
+    #[error("too many streams for this game")]
+    Game,
+}
+
+/// A single open sse stream.
+struct OpenStream {
+    id: u64,
+    uuid: Uuid,
+    ip_addr: Option<IpAddr>,
+    game_code: GameCode,
+    /// The last time something was send on the stream, see [StreamSlot::touch]()
+    last_seen: Instant,
+    /// Used to close the stream when it is replaced.
+    close: Arc<Notify>,
+}
+
+/// The last time something was send to a user on any of their streams.
+///
+/// Kept after the streams are gone, so that streams that were dropped without disconnecting the user are noticed.
+struct Heartbeat {
+    game_code: GameCode,
+    at: Instant,
+}
+
+#[derive(Default)]
+struct Streams {
+    next_id: u64,
+    open: Vec<OpenStream>,
+    /// See [dead_streams](struct.ConnectionTracker.html#method.dead_streams)
+    heartbeats: HashMap<Uuid, Heartbeat>,
+}
+
+/// Keeps track of all open sse streams so that a single client can not open an unlimited number of streams.
+///
+/// One `ConnectionTracker` is managed by rocket and used by the [events](../paths/sse/fn.events.html) route.
+/// Clones share the same streams, they are used by background tasks that outlive a request.
+#[derive(Clone)]
+pub struct ConnectionTracker {
+    limits: StreamLimits,
+    /// See [DEFAULT_KEEP_ALIVE]()
+    keep_alive: Duration,
+    streams: Arc<Mutex<Streams>>,
+}
+
+impl ConnectionTracker {
+    pub fn new(limits: StreamLimits) -> Self {
+        Self {
+            limits,
+            keep_alive: DEFAULT_KEEP_ALIVE,
+            streams: Arc::new(Mutex::new(Streams::default())),
+        }
+    }
+
+    /// Sets the time after which a keep-alive comment is send on a quiet stream.
+    pub fn with_keep_alive(mut self, keep_alive: Duration) -> Self {
+        self.keep_alive = keep_alive;
+        self
+    }
+
+    /// Returns the time after which a keep-alive comment is send on a quiet stream.
+    pub fn keep_alive(&self) -> Duration {
+        self.keep_alive
+    }
+
+    /// Registers a new sse stream.
+    ///
+    /// The stream is counted as open until the returned [StreamSlot]() is dropped,
+    /// so the slot has to be moved into the stream.
+    ///
+    /// # Params
+    /// - `max_players` the maximum number of players of the game, used to compute the limit per game.
+    ///
+    /// # Returns
+    /// - `Ok(StreamSlot)` when the stream can be opened.
+    /// - `Err(StreamLimitError)` when a limit would be exceeded.
+    pub fn open(&self, uuid: Uuid, ip_addr: Option<IpAddr>, game_code: GameCode, max_players: usize) -> Result<StreamSlot<'_>, StreamLimitError> {
+        let mut streams = self.streams.lock().unwrap();
+        if streams.open.iter().filter(|stream| stream.game_code == game_code).count() >= self.limits.per_game_factor * max_players {
+            return Err(StreamLimitError::Game);
+        }
+        if ip_addr.is_some() && streams.open.iter().filter(|stream| stream.ip_addr == ip_addr).count() >= self.limits.per_ip {
+            return Err(StreamLimitError::Ip);
+        }
+        if streams.open.iter().filter(|stream| stream.uuid == uuid).count() >= self.limits.per_user {
+            match self.limits.user_policy {
+                UserLimitPolicy::Reject => return Err(StreamLimitError::User),
+                UserLimitPolicy::ReplaceOldest => {
+                    // Streams are stored in the order in which they where opened, the first one wins a tie
+                    let oldest = streams.open.iter()
+                        .enumerate()
+                        .filter(|(_, stream)| stream.uuid == uuid)
+                        .min_by_key(|(_, stream)| stream.last_seen)
+                        .map(|(index, _)| index)
+                        .unwrap();
+                    streams.open.remove(oldest).close.notify_one();
+                },
+            }
+        }
+        let id = streams.next_id;
+        streams.next_id += 1;
+        streams.heartbeats.insert(uuid, Heartbeat { game_code, at: Instant::now() });
+        let close = Arc::new(Notify::new());
+        streams.open.push(OpenStream {
+            id,
+            uuid,
+            ip_addr,
+            game_code,
+            last_seen: Instant::now(),
+            close: close.clone(),
+        });
+        Ok(StreamSlot {
+            tracker: self,
+            id,
+            close,
+        })
+    }
+
+    /// Returns the number of open streams of the user.
+    #[cfg(test)]
+    pub fn streams_of_user(&self, uuid: Uuid) -> usize {
+        self.streams.lock().unwrap().open.iter().filter(|stream| stream.uuid == uuid).count()
+    }
+
+    /// Returns the time at which something was last send on any stream of the user.
+    #[cfg(test)]
+    pub fn last_seen(&self, uuid: Uuid) -> Option<Instant> {
+        self.streams.lock().unwrap().open.iter().filter(|stream| stream.uuid == uuid).map(|stream| stream.last_seen).max()
+    }
+
+    /// Returns the users whose streams have not send anything for longer than `timeout` and forgets them.
+    ///
+    /// The streams of these users have died without disconnecting the user, so they have to be disconnected.
+    /// Each user is returned only once, a new stream of the user records a heartbeat again.
+    pub fn dead_streams(&self, timeout: Duration) -> Vec<UserAuth> {
+        let now = Instant::now();
+        let mut dead = Vec::new();
+        self.streams.lock().unwrap().heartbeats.retain(|uuid, heartbeat| {
+            if now.saturating_duration_since(heartbeat.at) <= timeout {
+                return true;
+            }
+            dead.push(UserAuth { uuid: *uuid, game_code: heartbeat.game_code });
+            false
+        });
+        dead
+    }
+
+    fn touch(&self, id: u64) {
+        let mut streams = self.streams.lock().unwrap();
+        let now = Instant::now();
+        let (uuid, game_code) = match streams.open.iter_mut().find(|stream| stream.id == id) {
+            Some(stream) => {
+                stream.last_seen = now;
+                (stream.uuid, stream.game_code)
+            },
+            None => return,
+        };
+        streams.heartbeats.insert(uuid, Heartbeat { game_code, at: now });
+    }
+
+    fn close(&self, id: u64) {
+        self.streams.lock().unwrap().open.retain(|stream| stream.id != id);
+    }
+}
+
+/// Marks a sse stream as open as long as it exists.
+///
+/// Dropping the slot frees it again, this happens on every path on which the stream ends.
+pub struct StreamSlot<'a> {
+    tracker: &'a ConnectionTracker,
+    id: u64,
+    close: Arc<Notify>,
+}
+
+impl StreamSlot<'_> {
+    /// Completes when the stream has been replaced by a newer stream of the same user and should be closed.
+    pub async fn replaced(&self) {
+        self.close.notified().await
+    }
+
+    /// Remembers that something was send on the stream just now, this is also the heartbeat of the user.
+    pub fn touch(&self) {
+        self.tracker.touch(self.id);
+    }
+}
+
+impl Drop for StreamSlot<'_> {
+    fn drop(&mut self) {
+        self.tracker.close(self.id);
+    }
+}
+
+/// Fairing that starts a task which disconnects the users whose sse stream has died without being closed.
+///
+/// When the client is gone rocket drops the stream, or the stream gets stuck while sending, without reaching the code
+/// that disconnects the user. Open streams send at least a keep-alive comment every [keep_alive](struct.ConnectionTracker.html#method.keep_alive)
+/// and [touch](struct.StreamSlot.html#method.touch) their slot after everything they have send. Every interval the task disconnects the users whose
+/// streams have not send anything for [DEAD_STREAM_INTERVALS]() intervals, see [ConnectionTracker::dead_streams]().
+pub struct DisconnectDeadStreams;
+
+#[rocket::async_trait]
+impl Fairing for DisconnectDeadStreams {
+    fn info(&self) -> Info {
+        Info {
+            name: "Disconnect dead sse streams",
+            kind: Kind::Liftoff,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let (game_manager, event, connections) = match (rocket.state::<ShardedGameManager>(), rocket.state::<EventBus>(), rocket.state::<ConnectionTracker>()) {
+            (Some(game_manager), Some(event), Some(connections)) => (game_manager.clone(), event.clone(), connections.clone()),
+            _ => return,
+        };
+        let keep_alive = connections.keep_alive();
+        let mut shutdown = rocket.shutdown();
+        let mut check = interval(keep_alive);
+        check.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        // The first tick completes immediately
+        check.tick().await;
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = check.tick() => (),
+                    _ = &mut shutdown => break,
+                }
+                for user_auth in connections.dead_streams(keep_alive * DEAD_STREAM_INTERVALS) {
+                    info!("Stream of user {} has died, disconnecting", user_auth.uuid);
+                    game_manager.connection_lost(user_auth, &event, GAME_INSTANCE_TIMEOUT);
+                }
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, net::{IpAddr, Ipv4Addr}, thread};
+
+    use rocket::tokio::time::{timeout, Duration};
+    use uuid::Uuid;
+
+    use crate::game::game_instance::GameCode;
+
+    use super::{ConnectionTracker, StreamLimitError, StreamLimits, UserLimitPolicy};
+
+    fn game_code(c: char) -> GameCode {
+        GameCode::new([c; 8]).unwrap()
+    }
+
+    #[test]
+    fn test_limits() {
+        let tracker = ConnectionTracker::new(StreamLimits { per_user: 2, per_ip: 3, per_game_factor: 1, user_policy: UserLimitPolicy::Reject });
+        let uuid = Uuid::new_v4();
+        let ip = Some(IpAddr::V4(Ipv4Addr::LOCALHOST));
+        let first = tracker.open(uuid, ip, game_code('A'), 6).unwrap();
+        let _second = tracker.open(uuid, ip, game_code('A'), 6).unwrap();
+        assert_eq!(Some(StreamLimitError::User), tracker.open(uuid, ip, game_code('A'), 6).err());
+        let _third = tracker.open(Uuid::new_v4(), ip, game_code('B'), 6).unwrap();
+        assert_eq!(Some(StreamLimitError::Ip), tracker.open(Uuid::new_v4(), ip, game_code('B'), 6).err());
+        assert_eq!(Some(StreamLimitError::Game), tracker.open(Uuid::new_v4(), None, game_code('A'), 2).err());
+        // reconnecting after a stream was closed works
+        drop(first);
+        assert!(tracker.open(uuid, ip, game_code('A'), 6).is_ok());
+        assert_eq!(1, tracker.streams_of_user(uuid));
+    }
+
+    #[test]
+    fn test_dead_streams() {
+        let tracker = ConnectionTracker::new(StreamLimits::default());
+        let timeout = Duration::from_millis(50);
+        let dead = || -> HashSet<Uuid> { tracker.dead_streams(timeout).into_iter().map(|user_auth| user_auth.uuid).collect() };
+        let uuids: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+        let slots: Vec<_> = uuids.iter().map(|uuid| tracker.open(*uuid, None, game_code('A'), 6).unwrap()).collect();
+        assert!(dead().is_empty());
+        thread::sleep(timeout + Duration::from_millis(10));
+        slots[0].touch();
+        // streams that were dropped without disconnecting the user are still noticed
+        drop(slots);
+        assert_eq!(HashSet::from([uuids[1], uuids[2]]), dead());
+        // each user is only reported once, a new stream records a heartbeat again
+        assert!(dead().is_empty());
+        let _slot = tracker.open(uuids[1], None, game_code('A'), 6).unwrap();
+        thread::sleep(timeout + Duration::from_millis(10));
+        assert_eq!(HashSet::from([uuids[0], uuids[1]]), dead());
+    }
+
+    #[rocket::async_test]
+    async fn test_replace_oldest() {
+        let tracker = ConnectionTracker::new(StreamLimits { per_user: 1, user_policy: UserLimitPolicy::ReplaceOldest, ..StreamLimits::default() });
+        let uuid = Uuid::new_v4();
+        let old = tracker.open(uuid, None, game_code('A'), 6).unwrap();
+        let new = tracker.open(uuid, None, game_code('A'), 6).unwrap();
+        assert_eq!(1, tracker.streams_of_user(uuid));
+        assert!(timeout(Duration::from_secs(1), old.replaced()).await.is_ok());
+        assert!(timeout(Duration::from_millis(10), new.replaced()).await.is_err());
+        drop(old);
+        assert_eq!(1, tracker.streams_of_user(uuid));
+        drop(new);
+        assert_eq!(0, tracker.streams_of_user(uuid));
+    }
+}
@@ -0,0 +1,447 @@
+use rocket::{
+    http::Status,
+    response::{self, Responder},
+    serde::json::{self, Json},
+    Request,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    authentication::{FromRequestError, GameCodeError},
+    connections::StreamLimitError,
+    game::{game_instance::{AnnotateError, BuyStockError, ChooseSurvivorError, EndGameError, FoundChainError, MergerDecisionError, PlayTileError, StartGameError, board::PlaceTileError, turns::TurnError}, UserRegistrationError},
+    request_data::{FieldError, TurnStatus},
+};
+
+/// Error that is returned by request handlers when a request could not be processed.
+///
+/// Each variant maps to a single http status. The `code` is a stable machine readable string
+/// that the client can use to decide what to show, `detail` can contain additional information
+/// that is only meant to be read by humans.
+///
+/// Domain errors (like [UserRegistrationError](../game/enum.UserRegistrationError.html)) are converted
+/// into an `ApiError` by using the `From` implementations below, this way handlers can return
+/// `Result<Json<T>, ApiError>` and use `?`.
+///
+/// # Response
+/// The response body is always json formatted like this:
+///
+/// `{"error": "name_taken"}` or `{"error": "auth_missing", "detail": "The user_id header is missing"}`
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ApiError {
+    /// The request contains invalid data.
+    #[error("bad request: {code}")]
+    BadRequest { code: &'static str, detail: Option<String> },
+    /// The request is not allowed for the requesting user.
+    #[error("forbidden: {code}")]
+    Forbidden { code: &'static str, detail: Option<String> },
+    /// The requested resource does not exist.
+    #[error("not found: {code}")]
+    NotFound { code: &'static str, detail: Option<String> },
+    /// The request conflicts with the current state of the game.
+    #[error("conflict: {code}")]
+    Conflict { code: &'static str, detail: Option<String> },
+    /// The request is well formed but can not be processed, for example because a version is not supported.
+    #[error("unprocessable entity: {code}")]
+    UnprocessableEntity { code: &'static str, detail: Option<String> },
+    /// The client has opened too many connections or send too many requests.
+    #[error("too many requests: {code}")]
+    TooManyRequests { code: &'static str, detail: Option<String> },
+    /// The server can currently not process the request, for example because it is in maintenance mode.
+    #[error("service unavailable: {code}")]
+    ServiceUnavailable { code: &'static str, detail: Option<String> },
+    /// One or more fields of the request are invalid, send as `422 Unprocessable Entity` with the code `invalid_fields`.
+    ///
+    /// All invalid fields are listed in the response so that the client can show them at once.
+    #[error("invalid fields: {fields:?}")]
+    InvalidFields { fields: Vec<FieldError>, detail: Option<String> },
+    /// The user tried to act while another player has to act, send as `409 Conflict`.
+    ///
+    /// The turn as it actually is gets listed in the response so that the client can catch up without another request.
+    #[error("turn conflict: {code}")]
+    TurnConflict { code: &'static str, turn: Box<TurnStatus>, detail: Option<String> },
+}
+
+impl ApiError {
+    /// Constructs a new [ApiError::BadRequest]() without detail.
+    pub fn bad_request(code: &'static str) -> Self {
+        Self::BadRequest { code, detail: None }
+    }
+
+    /// Constructs a new [ApiError::Forbidden]() without detail.
+    pub fn forbidden(code: &'static str) -> Self {
+        Self::Forbidden { code, detail: None }
+    }
+
+    /// Constructs a new [ApiError::NotFound]() without detail.
+    pub fn not_found(code: &'static str) -> Self {
+        Self::NotFound { code, detail: None }
+    }
+
+    /// Constructs a new [ApiError::Conflict]() without detail.
+    pub fn conflict(code: &'static str) -> Self {
+        Self::Conflict { code, detail: None }
+    }
+
+    /// Constructs a new [ApiError::UnprocessableEntity]() without detail.
+    pub fn unprocessable_entity(code: &'static str) -> Self {
+        Self::UnprocessableEntity { code, detail: None }
+    }
+
+    /// Constructs a new [ApiError::TooManyRequests]() without detail.
+    pub fn too_many_requests(code: &'static str) -> Self {
+        Self::TooManyRequests { code, detail: None }
+    }
+
+    /// Constructs a new [ApiError::ServiceUnavailable]() without detail.
+    pub fn service_unavailable(code: &'static str) -> Self {
+        Self::ServiceUnavailable { code, detail: None }
+    }
+
+    /// Constructs a new [ApiError::InvalidFields]() without detail.
+    pub fn invalid_fields(fields: Vec<FieldError>) -> Self {
+        Self::InvalidFields { fields, detail: None }
+    }
+
+    /// Constructs a new [ApiError::TurnConflict]() without detail.
+    pub fn turn_conflict(code: &'static str, turn: Box<TurnStatus>) -> Self {
+        Self::TurnConflict { code, turn, detail: None }
+    }
+
+    /// Adds a detail message to this error.
+    pub fn with_detail(mut self, message: impl Into<String>) -> Self {
+        match &mut self {
+            Self::BadRequest { detail, .. }
+            | Self::Forbidden { detail, .. }
+            | Self::NotFound { detail, .. }
+            | Self::Conflict { detail, .. }
+            | Self::UnprocessableEntity { detail, .. }
+            | Self::TooManyRequests { detail, .. }
+            | Self::ServiceUnavailable { detail, .. }
+            | Self::InvalidFields { detail, .. }
+            | Self::TurnConflict { detail, .. } => *detail = Some(message.into()),
+        }
+        self
+    }
+
+    /// Returns the http status that is used when this error is send to the client.
+    pub fn status(&self) -> Status {
+        match self {
+            Self::BadRequest { .. } => Status::BadRequest,
+            Self::Forbidden { .. } => Status::Forbidden,
+            Self::NotFound { .. } => Status::NotFound,
+            Self::Conflict { .. } => Status::Conflict,
+            Self::UnprocessableEntity { .. } => Status::UnprocessableEntity,
+            Self::TooManyRequests { .. } => Status::TooManyRequests,
+            Self::ServiceUnavailable { .. } => Status::ServiceUnavailable,
+            Self::InvalidFields { .. } => Status::UnprocessableEntity,
+            Self::TurnConflict { .. } => Status::Conflict,
+        }
+    }
+
+    /// Returns the machine readable error code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::BadRequest { code, .. }
+            | Self::Forbidden { code, .. }
+            | Self::NotFound { code, .. }
+            | Self::Conflict { code, .. }
+            | Self::UnprocessableEntity { code, .. }
+            | Self::TooManyRequests { code, .. }
+            | Self::ServiceUnavailable { code, .. }
+            | Self::TurnConflict { code, .. } => code,
+            Self::InvalidFields { .. } => "invalid_fields",
+        }
+    }
+
+    /// Returns the detail message, if set.
+    pub fn detail(&self) -> Option<&str> {
+        match self {
+            Self::BadRequest { detail, .. }
+            | Self::Forbidden { detail, .. }
+            | Self::NotFound { detail, .. }
+            | Self::Conflict { detail, .. }
+            | Self::UnprocessableEntity { detail, .. }
+            | Self::TooManyRequests { detail, .. }
+            | Self::ServiceUnavailable { detail, .. }
+            | Self::InvalidFields { detail, .. }
+            | Self::TurnConflict { detail, .. } => detail.as_deref(),
+        }
+    }
+
+    /// Returns the json body that is send to the client.
+    pub fn body(&self) -> ApiErrorBody {
+        ApiErrorBody {
+            error: String::from(self.code()),
+            detail: self.detail().map(String::from),
+            fields: match self {
+                Self::InvalidFields { fields, .. } => fields.clone(),
+                _ => Vec::new(),
+            },
+            turn: match self {
+                Self::TurnConflict { turn, .. } => Some(turn.as_ref().clone()),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl<'r> Responder<'r, 'static> for ApiError {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let status = self.status();
+        response::Response::build_from(Json(self.body()).respond_to(request)?)
+            .status(status)
+            .ok()
+    }
+}
+
+/// The json body of an [ApiError]().
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ApiErrorBody {
+    /// The machine readable error code
+    pub error: String,
+    /// Additional information on the error
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub detail: Option<String>,
+    /// The invalid fields of the request, only send for the code `invalid_fields`
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub fields: Vec<FieldError>,
+    /// Whose turn it actually is, only send for the codes `not_your_turn` and `not_your_decision`
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub turn: Option<TurnStatus>,
+}
+
+impl From<UserRegistrationError> for ApiError {
+    fn from(err: UserRegistrationError) -> Self {
+        match err {
+            UserRegistrationError::NameTaken => ApiError::forbidden("name_taken"),
+            UserRegistrationError::GameDoesNotExist => ApiError::forbidden("game_not_found"),
+            UserRegistrationError::GameAlreadyStarted => ApiError::forbidden("game_already_started"),
+            UserRegistrationError::GameFull => ApiError::forbidden("game_full"),
+            UserRegistrationError::LobbyLocked => ApiError::forbidden("lobby_locked"),
+            UserRegistrationError::RecoveryExpired => ApiError::forbidden("recovery_expired"),
+            UserRegistrationError::InviteRequired => ApiError::forbidden("invite_required"),
+            UserRegistrationError::InviteInvalid => ApiError::forbidden("invite_invalid"),
+        }
+    }
+}
+
+impl From<StartGameError> for ApiError {
+    fn from(err: StartGameError) -> Self {
+        match err {
+            StartGameError::AlreadyStarted => ApiError::conflict("game_already_started"),
+            StartGameError::NotEnoughPlayers => ApiError::conflict("not_enough_players"),
+        }
+    }
+}
+
+impl From<PlayTileError> for ApiError {
+    fn from(err: PlayTileError) -> Self {
+        let code = match err {
+            PlayTileError::Turn(err) => return err.into(),
+            PlayTileError::ChainChoicePending => return ApiError::conflict("choose_chain_first"),
+            PlayTileError::AlreadyPlaced => return ApiError::conflict("tile_already_placed"),
+            PlayTileError::NotInHand => "tile_not_in_hand",
+            PlayTileError::Board(PlaceTileError::Occupied(_)) => "position_occupied",
+            PlayTileError::Board(PlaceTileError::NoChainAvailable(_)) => "no_chain_available",
+            PlayTileError::Board(PlaceTileError::SafeChainsMerge(_)) => "safe_chains_merge",
+        };
+        ApiError::unprocessable_entity(code).with_detail(err.to_string())
+    }
+}
+
+impl From<TurnError> for ApiError {
+    fn from(err: TurnError) -> Self {
+        match err {
+            TurnError::NotStarted => ApiError::conflict("game_not_started"),
+            TurnError::Finished => ApiError::conflict("game_finished"),
+            TurnError::NotYourTurn(turn) => ApiError::turn_conflict("not_your_turn", turn),
+            TurnError::StaleToken => ApiError::conflict("stale_turn_token"),
+        }
+    }
+}
+
+impl From<FoundChainError> for ApiError {
+    fn from(err: FoundChainError) -> Self {
+        match err {
+            FoundChainError::Turn(err) => err.into(),
+            FoundChainError::NothingToFound => ApiError::conflict("no_chain_to_found"),
+            FoundChainError::ChainOnBoard(_) => ApiError::unprocessable_entity("chain_already_founded").with_detail(err.to_string()),
+        }
+    }
+}
+
+impl From<ChooseSurvivorError> for ApiError {
+    fn from(err: ChooseSurvivorError) -> Self {
+        match err {
+            ChooseSurvivorError::Turn(err) => err.into(),
+            ChooseSurvivorError::NothingToChoose => ApiError::conflict("no_survivor_to_choose"),
+            ChooseSurvivorError::NotLargest(_) => ApiError::unprocessable_entity("not_largest_chain").with_detail(err.to_string()),
+        }
+    }
+}
+
+impl From<BuyStockError> for ApiError {
+    fn from(err: BuyStockError) -> Self {
+        let code = match err {
+            BuyStockError::Turn(err) => return err.into(),
+            BuyStockError::WrongPhase => return ApiError::conflict("not_buy_phase"),
+            BuyStockError::TooManyShares => "too_many_shares",
+            BuyStockError::ChainNotFounded(_) => "chain_not_founded",
+            BuyStockError::NotEnoughShares(_) => "not_enough_shares",
+            BuyStockError::NotEnoughMoney { .. } => "not_enough_money",
+        };
+        ApiError::unprocessable_entity(code).with_detail(err.to_string())
+    }
+}
+
+impl From<MergerDecisionError> for ApiError {
+    fn from(err: MergerDecisionError) -> Self {
+        let code = match err {
+            MergerDecisionError::NothingToDecide => return ApiError::conflict("no_decision_pending"),
+            MergerDecisionError::NotYourDecision(turn) => return ApiError::turn_conflict("not_your_decision", turn),
+            MergerDecisionError::SharesMismatch { .. } => "shares_mismatch",
+            MergerDecisionError::OddTrade => "odd_trade",
+            MergerDecisionError::NotEnoughShares(_) => "not_enough_shares",
+        };
+        ApiError::unprocessable_entity(code).with_detail(err.to_string())
+    }
+}
+
+impl From<EndGameError> for ApiError {
+    fn from(err: EndGameError) -> Self {
+        match err {
+            EndGameError::Turn(err) => err.into(),
+            EndGameError::DecisionPending => ApiError::conflict("decision_pending"),
+            EndGameError::ConditionsNotMet => ApiError::conflict("end_conditions_not_met").with_detail(err.to_string()),
+        }
+    }
+}
+
+impl From<AnnotateError> for ApiError {
+    fn from(err: AnnotateError) -> Self {
+        match err {
+            AnnotateError::NotGameMaster => ApiError::forbidden("not_game_master"),
+            AnnotateError::NotFinished => ApiError::conflict("game_not_finished"),
+            AnnotateError::InvalidFields(fields) => ApiError::invalid_fields(fields),
+        }
+    }
+}
+
+impl From<StreamLimitError> for ApiError {
+    fn from(err: StreamLimitError) -> Self {
+        let code = match err {
+            StreamLimitError::User => "too_many_streams_user",
+            StreamLimitError::Ip => "too_many_streams_ip",
+            StreamLimitError::Game => "too_many_streams_game",
+        };
+        ApiError::too_many_requests(code).with_detail(err.to_string())
+    }
+}
+
+impl From<json::Error<'_>> for ApiError {
+    fn from(err: json::Error<'_>) -> Self {
+        let detail = match err {
+            json::Error::Io(err) => err.to_string(),
+            json::Error::Parse(_, err) => err.to_string(),
+        };
+        ApiError::bad_request("invalid_request").with_detail(detail)
+    }
+}
+
+impl From<FromRequestError> for ApiError {
+    fn from(err: FromRequestError) -> Self {
+        match err {
+            FromRequestError::Missing(detail) => ApiError::forbidden("auth_missing").with_detail(detail),
+            FromRequestError::Invalid(detail) => ApiError::forbidden("auth_invalid").with_detail(detail),
+        }
+    }
+}
+
+impl From<GameCodeError> for ApiError {
+    fn from(err: GameCodeError) -> Self {
+        match err {
+            GameCodeError::Missing => ApiError::forbidden("game_code_missing"),
+            GameCodeError::ParseError => ApiError::forbidden("game_code_invalid"),
+            GameCodeError::NotFound => ApiError::forbidden("game_not_found"),
+            GameCodeError::TooManyGuesses => ApiError::too_many_requests("too_many_guesses"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::{get, http::Status, local::blocking::Client, routes};
+
+    use crate::{
+        authentication::{FromRequestError, GameCodeError},
+        game::UserRegistrationError,
+    };
+
+    use super::{ApiError, ApiErrorBody};
+
+    #[get("/error/<index>")]
+    fn error(index: usize) -> Result<(), ApiError> {
+        let errors = [
+            ApiError::forbidden("forbidden"),
+            ApiError::not_found("missing"),
+            ApiError::conflict("conflict").with_detail("some detail"),
+            ApiError::bad_request("invalid"),
+        ];
+        Err(errors.into_iter().nth(index).unwrap())
+    }
+
+    fn client() -> Client {
+        Client::tracked(rocket::build().mount("/", routes![error])).unwrap()
+    }
+
+    #[test]
+    fn test_responder_status_and_body() {
+        let client = client();
+        let expected = [
+            (Status::Forbidden, r#"{"error":"forbidden"}"#),
+            (Status::NotFound, r#"{"error":"missing"}"#),
+            (Status::Conflict, r#"{"error":"conflict","detail":"some detail"}"#),
+            (Status::BadRequest, r#"{"error":"invalid"}"#),
+        ];
+        for (index, (status, body)) in expected.iter().enumerate() {
+            let response = client.get(format!("/error/{}", index)).dispatch();
+            assert_eq!(*status, response.status());
+            assert_eq!(Some(rocket::http::ContentType::JSON), response.content_type());
+            assert_eq!(*body, response.into_string().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_user_registration_error_conversion() {
+        let name_taken = ApiError::from(UserRegistrationError::NameTaken);
+        assert_eq!(Status::Forbidden, name_taken.status());
+        assert_eq!(ApiErrorBody { error: String::from("name_taken"), detail: None, fields: Vec::new(), turn: None }, name_taken.body());
+        let not_found = ApiError::from(UserRegistrationError::GameDoesNotExist);
+        assert_eq!(Status::Forbidden, not_found.status());
+        assert_eq!("game_not_found", not_found.code());
+        let started = ApiError::from(UserRegistrationError::GameAlreadyStarted);
+        assert_eq!(Status::Forbidden, started.status());
+        assert_eq!("game_already_started", started.code());
+        assert_eq!("game_full", ApiError::from(UserRegistrationError::GameFull).code());
+        assert_eq!("lobby_locked", ApiError::from(UserRegistrationError::LobbyLocked).code());
+        assert_eq!("recovery_expired", ApiError::from(UserRegistrationError::RecoveryExpired).code());
+        assert_eq!("invite_required", ApiError::from(UserRegistrationError::InviteRequired).code());
+        assert_eq!("invite_invalid", ApiError::from(UserRegistrationError::InviteInvalid).code());
+    }
+
+    #[test]
+    fn test_request_guard_error_conversion() {
+        let missing = ApiError::from(FromRequestError::Missing(String::from("header missing")));
+        assert_eq!(Status::Forbidden, missing.status());
+        assert_eq!("auth_missing", missing.code());
+        assert_eq!(Some("header missing"), missing.detail());
+        assert_eq!("auth_invalid", ApiError::from(FromRequestError::Invalid(String::new())).code());
+        assert_eq!("game_code_missing", ApiError::from(GameCodeError::Missing).code());
+        assert_eq!("game_code_invalid", ApiError::from(GameCodeError::ParseError).code());
+        let not_found = ApiError::from(GameCodeError::NotFound);
+        assert_eq!(Status::Forbidden, not_found.status());
+        assert_eq!("game_not_found", not_found.code());
+    }
+}
@@ -0,0 +1,649 @@
+use std::{collections::{HashMap, VecDeque}, sync::{Arc, Mutex, OnceLock}, time::Duration};
+
+use rocket::{log::private::{error, info}, tokio::{self, runtime::Handle, sync::broadcast::{channel, Receiver, Sender}}};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{Serialize, Serializer};
+use uuid::Uuid;
+
+use crate::{game::game_instance::GameCode, request_data::{BufferWarning, EventData, GameBuffer, GameEvent}};
+
+/// Events that contain a complete snapshot of some state, when several of them are published in quick succession
+/// only the newest one has to be send.
+pub const COALESCIBLE_EVENTS: &[&str] = &["PlayerList"];
+
+/// The time for which coalescible events are held back when it is not set in the configuration.
+pub const DEFAULT_COALESCE_WINDOW: Duration = Duration::from_millis(300);
+
+/// The number of events a stream can fall behind the [GameChannel]() of its game before it misses events.
+pub const GAME_CHANNEL_CAPACITY: usize = 128;
+
+/// The number of events a [GameChannel]() keeps for streams that reconnect with `Last-Event-ID`.
+pub const EVENT_HISTORY_LEN: usize = 256;
+
+/// The game master receives `BufferWarning` once a bounded buffer of the game holds this many percent of its capacity,
+/// see [GameChannel::watch_buffer]().
+pub const BUFFER_WARNING_PERCENT: usize = 80;
+
+/// An event as it is send on a [GameChannel](), it is cheap to clone.
+///
+/// The event is serialized once when it is delivered, all streams send the same json instead of serializing
+/// the event again for each subscriber. The [msgpack](#method.msgpack) form is created once when the first stream needs it. The game and the recipient are kept typed, so that the streams can filter
+/// the events without allocating.
+#[derive(Debug, Clone)]
+pub struct PublishedEvent {
+    /// The sequence number of the event in its game, send as the sse id
+    id: u64,
+    game_code: GameCode,
+    /// The player to which the event is directed, `None` when it is send to all players of the game
+    recipient: Option<Uuid>,
+    data: Arc<EventData>,
+    json: Arc<str>,
+    msgpack: Arc<OnceLock<String>>,
+}
+
+impl PublishedEvent {
+    /// Serializes `data`, the `recipient` has to be the user for which `data` was created.
+    pub fn new(id: u64, game_code: GameCode, recipient: Option<Uuid>, data: EventData) -> Self {
+        let json = rocket::serde::json::to_string(&data).unwrap_or_default();
+        Self { id, game_code, recipient, data: Arc::new(data), json: Arc::from(json), msgpack: Arc::default() }
+    }
+
+    /// # Returns
+    /// The sequence number of the event in its game, it increases by one with each event that is send on the [GameChannel]()
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Checks if the event has to be send to the stream of the user with `uuid` in the game.
+    pub fn is_for(&self, game_code: GameCode, uuid: Uuid) -> bool {
+        self.game_code == game_code && self.recipient.is_none_or(|recipient| recipient == uuid)
+    }
+
+    /// # Returns
+    /// The player to which the event is directed, `None` when it is send to all players of the game
+    pub fn recipient(&self) -> Option<Uuid> {
+        self.recipient
+    }
+
+    /// # Returns
+    /// The event that is send
+    pub fn event(&self) -> &GameEvent {
+        self.data.event()
+    }
+
+    /// # Returns
+    /// The serialized [EventData](../request_data/struct.EventData.html), the same as `Event::json` would send.
+    pub fn json(&self) -> &str {
+        &self.json
+    }
+
+    /// # Returns
+    /// The [EventData](../request_data/struct.EventData.html) as base64 encoded msgpack, see [to_msgpack]().
+    pub fn msgpack(&self) -> &str {
+        self.msgpack.get_or_init(|| to_msgpack(&self.data))
+    }
+}
+
+/// Serializes the [compact](../request_data/struct.EventData.html#method.compact) form of `data` as msgpack and encodes it with base64,
+/// so that it can be send as text in the data field of a sse event.
+///
+/// Structs are written as maps with their field names and values keep the form they have in json (uuids as strings),
+/// so that a decoded event looks like the json form without the legacy `data` pair.
+pub fn to_msgpack(data: &EventData) -> String {
+    let mut buf = Vec::new();
+    let mut serializer = rmp_serde::Serializer::new(&mut buf).with_struct_map().with_human_readable();
+    if let Err(err) = data.compact().serialize(&mut serializer) {
+        error!("Unable to serialize event {} as msgpack: {}", data.name(), err);
+    }
+    BASE64.encode(buf)
+}
+
+/// Decodes an event that was encoded with [to_msgpack]() into its json form, like `decode_event` of the wasm module does.
+#[cfg(test)]
+pub fn from_msgpack(encoded: &str) -> Option<rocket::serde::json::Value> {
+    let bytes = BASE64.decode(encoded).ok()?;
+    let mut deserializer = rmp_serde::Deserializer::new(&bytes[..]).with_human_readable();
+    serde::Deserialize::deserialize(&mut deserializer).ok()
+}
+
+/// Serializes the contained [EventData](../request_data/struct.EventData.html).
+impl Serialize for PublishedEvent {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        self.data.serialize(serializer)
+    }
+}
+
+/// The channel on which the events of a single game are send to the sse streams of the game.
+///
+/// Each [GameInstance](../game/game_instance/struct.GameInstance.html) owns the channel of its game, so streams only
+/// receive the events of their own game and a busy game can not make the streams of other games fall behind.
+/// The channel is closed when the game was deleted and all [EventBatch]()es of the game were published.
+///
+/// The last [EVENT_HISTORY_LEN]() events are kept, so that a stream that lost its connection can [resume](#method.resume)
+/// with the events it missed. The history and the other bounded buffers of the game are watched with [watch_buffer](#method.watch_buffer).
+#[derive(Debug, Clone)]
+pub struct GameChannel {
+    game_code: GameCode,
+    sender: Sender<PublishedEvent>,
+    history: Arc<Mutex<EventHistory>>,
+}
+
+/// The events that were send last on a [GameChannel](), the oldest event first.
+#[derive(Debug)]
+struct EventHistory {
+    events: VecDeque<PublishedEvent>,
+    /// The id of the next event, ids start at `1`
+    next_id: u64,
+    /// The user that receives `BufferWarning`, see [GameChannel::set_game_master]()
+    game_master: Option<Uuid>,
+    /// The buffers for which `BufferWarning` was already send
+    warned: Vec<GameBuffer>,
+}
+
+impl GameChannel {
+    /// Creates the channel of the game, each subscriber can fall behind by [GAME_CHANNEL_CAPACITY]() events.
+    pub fn new(game_code: GameCode) -> Self {
+        Self {
+            game_code,
+            sender: channel(GAME_CHANNEL_CAPACITY).0,
+            history: Arc::new(Mutex::new(EventHistory { events: VecDeque::new(), next_id: 1, game_master: None, warned: Vec::new() })),
+        }
+    }
+
+    /// Returns a new receiver for all events of the game that are send after this call.
+    pub fn subscribe(&self) -> Receiver<PublishedEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Returns a new receiver like [subscribe](#method.subscribe) together with the events that were send after the event with `last_id`.
+    ///
+    /// # Returns
+    /// `None` instead of the missed events when some of them were already removed from the history or when no event
+    /// with `last_id` was send yet, the client then has to fetch the state of the game again.
+    pub fn resume(&self, last_id: u64) -> (Receiver<PublishedEvent>, Option<Vec<PublishedEvent>>) {
+        let history = self.history.lock().unwrap();
+        // Subscribed while the history is locked, so each event is either missed or received
+        let receiver = self.sender.subscribe();
+        let oldest = history.events.front().map(PublishedEvent::id).unwrap_or(history.next_id);
+        if last_id >= history.next_id || last_id + 1 < oldest {
+            return (receiver, None);
+        }
+        let missed = history.events.iter().filter(|event| event.id > last_id).cloned().collect();
+        (receiver, Some(missed))
+    }
+
+    /// Returns the number of subscribed receivers.
+    #[cfg(test)]
+    pub fn receiver_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+
+    /// Sets the user that receives the event `BufferWarning`, the game instance calls this whenever the game master changes.
+    pub fn set_game_master(&self, uuid: Uuid) {
+        self.history.lock().unwrap().game_master = Some(uuid);
+    }
+
+    /// Sends the event `BufferWarning` to the game master the first time that `buffer` holds at least
+    /// [BUFFER_WARNING_PERCENT]() of its `capacity`, from then on old entries are about to be removed.
+    ///
+    /// The event history of the channel is watched on its own, the owners of the other buffers call this after each entry they add.
+    /// The warning is send right away instead of through the [EventBus](), it does not belong to the action that filled the buffer.
+    pub fn watch_buffer(&self, buffer: GameBuffer, used: usize, capacity: usize) {
+        let mut history = self.history.lock().unwrap();
+        self.warn(&mut history, BufferWarning { buffer, capacity, used });
+    }
+
+    /// Sends `warning` to the game master unless it was already send for the buffer or the buffer is below the watermark.
+    /// 
+    /// The warning is only marked as send when a game master is known.
+    fn warn(&self, history: &mut EventHistory, warning: BufferWarning) {
+        if warning.used * 100 < warning.capacity * BUFFER_WARNING_PERCENT || history.warned.contains(&warning.buffer) {
+            return;
+        }
+        let game_master = match history.game_master {
+            Some(game_master) => game_master,
+            None => return,
+        };
+        history.warned.push(warning.buffer);
+        info!("Game {}: the {:?} holds {} of {} entries", self.game_code, warning.buffer, warning.used, warning.capacity);
+        match EventData::new(Some(game_master), self.game_code, GameEvent::BufferWarning(warning)) {
+            Ok(data) => self.push(history, QueuedEvent { recipient: Some(game_master), data }),
+            Err(err) => error!("Event was not send to game {}: {}", self.game_code, err),
+        }
+    }
+
+    /// Serializes the event, adds it to the history and sends it to all subscribed streams of the game.
+    fn deliver(&self, event: QueuedEvent) {
+        let mut history = self.history.lock().unwrap();
+        self.push(&mut history, event);
+        let used = history.events.len();
+        self.warn(&mut history, BufferWarning { buffer: GameBuffer::EventHistory, capacity: EVENT_HISTORY_LEN, used });
+    }
+
+    fn push(&self, history: &mut EventHistory, event: QueuedEvent) {
+        let published = PublishedEvent::new(history.next_id, self.game_code, event.recipient, event.data);
+        history.next_id += 1;
+        if history.events.len() >= EVENT_HISTORY_LEN {
+            history.events.pop_front();
+        }
+        history.events.push_back(published.clone());
+        // Sending only fails when no stream is subscribed, in that case nobody needs the event
+        let _e = self.sender.send(published);
+    }
+}
+
+/// An event that was added to an [EventBatch]() but is not serialized yet.
+#[derive(Debug)]
+struct QueuedEvent {
+    recipient: Option<Uuid>,
+    data: EventData,
+}
+
+/// A coalescible event that has not been send yet.
+struct PendingEvent {
+    /// Used by the timer to check that the event was not already flushed and replaced by a newer timer
+    id: u64,
+    channel: GameChannel,
+    event: QueuedEvent,
+}
+
+#[derive(Default)]
+struct BusState {
+    next_id: u64,
+    /// The held back coalescible events
+    pending: HashMap<GameCode, PendingEvent>,
+}
+
+/// Publishes the events of all games on the [GameChannel]() of their game, managed by rocket.
+///
+/// Events named in [COALESCIBLE_EVENTS]() are held back for a short window, when another one is published for the same game
+/// in that time it replaces the held back event. This way the clients only get a single `PlayerList` when many players
+/// join at the same time. All other events flush the held back event of their game first, so the order of the events is kept.
+///
+/// The window can be set in milliseconds with `event_coalesce_window_ms` in the rocket configuration, `0` disables coalescing.
+///
+/// Clones share the held back events.
+#[derive(Clone)]
+pub struct EventBus {
+    window: Duration,
+    state: Arc<Mutex<BusState>>,
+}
+
+impl EventBus {
+    /// Creates a new bus that holds back coalescible events for `window`.
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            state: Arc::new(Mutex::new(BusState::default())),
+        }
+    }
+
+    /// Sends `event` on `channel` or holds it back when it is coalescible.
+    fn send(&self, channel: &GameChannel, event: QueuedEvent) {
+        let game_code = channel.game_code;
+        let mut state = self.state.lock().unwrap();
+        // Without a runtime no timer can be started to send the event later
+        let runtime = Handle::try_current().ok();
+        let coalesce = !self.window.is_zero() && runtime.is_some() && COALESCIBLE_EVENTS.contains(&event.data.name());
+        if !coalesce {
+            if let Some(held_back) = state.pending.remove(&game_code) {
+                held_back.channel.deliver(held_back.event);
+            }
+            channel.deliver(event);
+            return;
+        }
+        if let Some(held_back) = state.pending.get_mut(&game_code) {
+            held_back.event = event;
+            return;
+        }
+        let id = state.next_id;
+        state.next_id += 1;
+        state.pending.insert(game_code, PendingEvent { id, channel: channel.clone(), event });
+        let (window, shared) = (self.window, self.state.clone());
+        runtime.unwrap().spawn(async move {
+            tokio::time::sleep(window).await;
+            let mut state = shared.lock().unwrap();
+            if state.pending.get(&game_code).is_some_and(|held_back| held_back.id == id) {
+                let held_back = state.pending.remove(&game_code).unwrap();
+                held_back.channel.deliver(held_back.event);
+            }
+        });
+    }
+}
+
+/// Collects the events that are caused by a single action in a game so that they can be send together.
+///
+/// Game logic that changes the state of a game does not send events directly. Instead it adds all events
+/// that result from the action to an `EventBatch` and returns the batch. The request handler publishes the
+/// batch with [publish](#method.publish) on the [EventBus]() once all locks are released, the events are then send on the
+/// [GameChannel]() of the game.
+///
+/// This way either all events of an action are send, in the order in which they were added, or none
+/// when the action fails.
+#[derive(Debug)]
+#[must_use = "the events are only send when the batch is published"]
+pub struct EventBatch {
+    /// The channel of the game to which all events in this batch belong
+    channel: GameChannel,
+    events: Vec<QueuedEvent>,
+}
+
+impl EventBatch {
+    /// Creates a new empty batch for the game of `channel`.
+    pub fn new(channel: &GameChannel) -> Self {
+        Self {
+            channel: channel.clone(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Adds an event that is send to all players of the game.
+    pub fn push(&mut self, event: GameEvent) {
+        self.push_to(None, event);
+    }
+
+    /// Adds an event that is only send to the player with `uuid`, when `uuid` is `None` the event is send to all players.
+    /// 
+    /// Events that are not valid (see [EventData::new](../request_data/struct.EventData.html#method.new)) are a bug in the server,
+    /// they are logged and left out of the batch.
+    pub fn push_to(&mut self, uuid: Option<Uuid>, event: GameEvent) {
+        match EventData::new(uuid, self.channel.game_code, event) {
+            Ok(data) => self.events.push(QueuedEvent { recipient: uuid, data }),
+            Err(err) => error!("Event was not send to game {}: {}", self.channel.game_code, err),
+        }
+    }
+
+    /// Checks if the batch contains no events.
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Returns the name and the data of each event in this batch.
+    #[cfg(test)]
+    pub fn contents(&self) -> Vec<(&str, Option<&str>)> {
+        self.events.iter().map(|event| (event.data.name(), event.data.payload())).collect()
+    }
+
+    /// Appends all events of `other` to this batch.
+    pub fn append(&mut self, mut other: EventBatch) {
+        self.events.append(&mut other.events);
+    }
+
+    /// Sends all events of this batch in order.
+    ///
+    /// This should only be called after the locks on the game manager and the game instance are released.
+    ///
+    /// # Returns
+    /// The number of events that were published, coalescible events might be send later, see [EventBus]().
+    pub fn publish(self, bus: &EventBus) -> usize {
+        let len = self.events.len();
+        for event in self.events {
+            bus.send(&self.channel, event);
+        }
+        len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use rocket::{serde::json::to_value, tokio::{self, sync::broadcast::{error::TryRecvError, Receiver}}};
+
+    use uuid::Uuid;
+
+    use crate::{game::{base_game::{Vacancy, VacancyReason}, game_instance::{GameCode, PlayerListEntry}}, request_data::{EventData, GameBuffer, GameEvent, MAX_EVENT_DATA_LEN}};
+
+    use super::{from_msgpack, EventBatch, EventBus, GameChannel, PublishedEvent, BUFFER_WARNING_PERCENT, EVENT_HISTORY_LEN};
+
+    const WINDOW: Duration = Duration::from_millis(50);
+
+    /// Returns the names and data of all events that where received.
+    fn received(receiver: &mut Receiver<PublishedEvent>) -> Vec<(String, Option<String>)> {
+        let mut events = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            events.push(rocket::serde::json::from_value(to_value(event).unwrap()["data"].clone()).unwrap());
+        }
+        events
+    }
+
+    fn publish(bus: &EventBus, channel: &GameChannel, event: GameEvent) {
+        let mut batch = EventBatch::new(channel);
+        batch.push(event);
+        batch.publish(bus);
+    }
+
+    /// Returns a `PlayerList` event with the players `1` to `len`.
+    fn player_list(len: u32) -> GameEvent {
+        let players = (1..=len)
+            .map(|player_id| PlayerListEntry { player_id, seat: player_id as u8, name: player_id.to_string(), connected: true, game_master: player_id == 1, vacancy: None })
+            .collect();
+        GameEvent::PlayerList(players)
+    }
+
+    fn message(text: &str) -> GameEvent {
+        GameEvent::LobbyMessage(String::from(text))
+    }
+
+    #[test]
+    fn test_publish_in_order() {
+        let channel = GameChannel::new(GameCode::new(['A'; 8]).unwrap());
+        let bus = EventBus::new(WINDOW);
+        let mut receiver = channel.subscribe();
+        let mut batch = EventBatch::new(&channel);
+        batch.push(GameEvent::LobbyLocked);
+        let mut other = EventBatch::new(&channel);
+        other.push(GameEvent::SecurityAlert(String::from("data")));
+        batch.append(other);
+        batch.push(GameEvent::LobbyUnlocked);
+        // events with too much data are left out
+        batch.push(message(&"a".repeat(MAX_EVENT_DATA_LEN + 1)));
+        assert_eq!(3, batch.publish(&bus));
+        for name in ["LobbyLocked", "SecurityAlert", "LobbyUnlocked"] {
+            assert_eq!(name, to_value(receiver.try_recv().unwrap()).unwrap()["data"][0]);
+        }
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_msgpack_is_smaller() {
+        // the player list of a full lobby is the largest snapshot that is send
+        let names = ["Alice", "Bartholomew", "Zoë", "Dave", "Eve", "Frank"];
+        let players = names.iter().zip(1..)
+            .map(|(name, player_id)| PlayerListEntry {
+                player_id,
+                seat: player_id as u8,
+                name: String::from(*name),
+                connected: player_id % 3 != 0,
+                game_master: player_id == 1,
+                vacancy: (player_id % 3 == 0).then_some(Vacancy { reason: VacancyReason::ConnectionLost, since: 1700000000 }),
+            })
+            .collect();
+        let data = EventData::new(None, GameCode::new(['A'; 8]).unwrap(), GameEvent::PlayerList(players)).unwrap();
+        let event = PublishedEvent::new(1, GameCode::new(['A'; 8]).unwrap(), None, data);
+        // at least 25% smaller, even with the base64 overhead
+        assert!(event.msgpack().len() * 4 <= event.json().len() * 3, "{} bytes msgpack, {} bytes json", event.msgpack().len(), event.json().len());
+        let mut expected = to_value(&event).unwrap();
+        expected.as_object_mut().unwrap().remove("data");
+        assert_eq!(Some(expected), from_msgpack(event.msgpack()));
+    }
+
+    #[test]
+    fn test_published_events_are_serialized_once() {
+        let game_code = GameCode::new(['A'; 8]).unwrap();
+        let other_game = GameCode::new(['B'; 8]).unwrap();
+        let channel = GameChannel::new(game_code);
+        let (player, other_player) = (Uuid::new_v4(), Uuid::new_v4());
+        let bus = EventBus::new(Duration::ZERO);
+        let mut receiver = channel.subscribe();
+        let mut batch = EventBatch::new(&channel);
+        batch.push(message("\"quoted\" \u{e4}\n"));
+        batch.push_to(Some(player), GameEvent::Kicked);
+        batch.publish(&bus);
+
+        let expected = [
+            EventData::new(None, game_code, message("\"quoted\" \u{e4}\n")).unwrap(),
+            EventData::new(Some(player), game_code, GameEvent::Kicked).unwrap(),
+        ];
+        for expected in expected {
+            let published = receiver.try_recv().unwrap();
+            // the frame is the same that was send when each stream serialized the event itself
+            assert_eq!(rocket::serde::json::to_string(&expected).unwrap(), published.json());
+            assert_eq!(to_value(&expected).unwrap(), to_value(&published).unwrap());
+        }
+        let broadcast = PublishedEvent::new(1, game_code, None, EventData::new(None, game_code, GameEvent::LobbyLocked).unwrap());
+        assert!(broadcast.is_for(game_code, player));
+        assert!(!broadcast.is_for(other_game, player));
+        let targeted = PublishedEvent::new(2, game_code, Some(player), EventData::new(Some(player), game_code, GameEvent::Kicked).unwrap());
+        assert!(targeted.is_for(game_code, player));
+        assert!(!targeted.is_for(game_code, other_player));
+    }
+
+    #[rocket::async_test]
+    async fn test_coalesce_player_lists() {
+        let channel = GameChannel::new(GameCode::new(['A'; 8]).unwrap());
+        let other_game = GameChannel::new(GameCode::new(['B'; 8]).unwrap());
+        let bus = EventBus::new(WINDOW);
+        let (mut receiver, mut other_receiver) = (channel.subscribe(), other_game.subscribe());
+        for players in 1..=3 {
+            publish(&bus, &channel, player_list(players));
+        }
+        // events of other games do not flush the held back event
+        publish(&bus, &other_game, GameEvent::LobbyLocked);
+        assert_eq!(vec![(String::from("LobbyLocked"), None)], received(&mut other_receiver));
+        assert!(received(&mut receiver).is_empty());
+        tokio::time::sleep(WINDOW * 3).await;
+        assert_eq!(vec![(String::from("PlayerList"), player_list(3).legacy_payload())], received(&mut receiver));
+        assert!(received(&mut other_receiver).is_empty());
+    }
+
+    #[rocket::async_test]
+    async fn test_other_events_flush_player_list() {
+        let channel = GameChannel::new(GameCode::new(['A'; 8]).unwrap());
+        let bus = EventBus::new(WINDOW);
+        let mut receiver = channel.subscribe();
+        publish(&bus, &channel, player_list(1));
+        publish(&bus, &channel, GameEvent::LobbyLocked);
+        let names: Vec<String> = received(&mut receiver).into_iter().map(|(name, _)| name).collect();
+        assert_eq!(vec!["PlayerList", "LobbyLocked"], names);
+        tokio::time::sleep(WINDOW * 3).await;
+        assert!(received(&mut receiver).is_empty());
+    }
+
+    #[rocket::async_test]
+    async fn test_zero_window_disables_coalescing() {
+        let channel = GameChannel::new(GameCode::new(['A'; 8]).unwrap());
+        let bus = EventBus::new(Duration::ZERO);
+        let mut receiver = channel.subscribe();
+        publish(&bus, &channel, player_list(1));
+        publish(&bus, &channel, player_list(2));
+        assert_eq!(2, received(&mut receiver).len());
+    }
+
+    #[test]
+    fn test_games_only_receive_their_own_events() {
+        let games: Vec<GameChannel> = (0..50)
+            .map(|i| GameCode::new([char::from(b'0' + i / 10), char::from(b'0' + i % 10), 'B', 'B', 'B', 'B', 'B', 'B']).unwrap())
+            .map(GameChannel::new)
+            .collect();
+        let bus = EventBus::new(Duration::ZERO);
+        let mut receivers: Vec<_> = games.iter().map(GameChannel::subscribe).collect();
+        for _ in 0..1000 {
+            publish(&bus, &games[0], GameEvent::LobbyUnlocked);
+            assert_eq!(1, received(&mut receivers[0]).len());
+        }
+        publish(&bus, &games[7], message("7"));
+        assert_eq!(vec![(String::from("LobbyMessage"), Some(String::from("7")))], received(&mut receivers[7]));
+        assert!(receivers.iter_mut().all(|receiver| received(receiver).is_empty()));
+    }
+
+    #[test]
+    fn test_channel_is_closed_with_the_game() {
+        let channel = GameChannel::new(GameCode::new(['A'; 8]).unwrap());
+        let mut receiver = channel.subscribe();
+        let batch = EventBatch::new(&channel);
+        drop(channel);
+        assert_eq!(Err(TryRecvError::Empty), receiver.try_recv().map(|_| ()));
+        // the batches of the game keep the channel open until they are published
+        batch.publish(&EventBus::new(Duration::ZERO));
+        assert_eq!(Err(TryRecvError::Closed), receiver.try_recv().map(|_| ()));
+    }
+
+    #[test]
+    fn test_resume() {
+        let channel = GameChannel::new(GameCode::new(['A'; 8]).unwrap());
+        let bus = EventBus::new(Duration::ZERO);
+        // no event was send yet
+        assert!(channel.resume(0).1.unwrap().is_empty());
+        assert!(channel.resume(1).1.is_none());
+        let mut receiver = channel.subscribe();
+        for i in 0..5 {
+            publish(&bus, &channel, message(&i.to_string()));
+        }
+        let ids: Vec<u64> = (0..5).map(|_| receiver.try_recv().unwrap().id()).collect();
+        assert_eq!(vec![1, 2, 3, 4, 5], ids);
+        let (mut resumed, missed) = channel.resume(2);
+        let missed: Vec<&str> = missed.iter().flatten().map(|event| event.data.payload().unwrap()).collect();
+        assert_eq!(vec!["2", "3", "4"], missed);
+        assert!(channel.resume(5).1.unwrap().is_empty());
+        assert!(channel.resume(6).1.is_none());
+        publish(&bus, &channel, GameEvent::LobbyLocked);
+        assert_eq!(6, resumed.try_recv().unwrap().id());
+    }
+
+    #[test]
+    fn test_resume_after_eviction() {
+        let channel = GameChannel::new(GameCode::new(['A'; 8]).unwrap());
+        let bus = EventBus::new(Duration::ZERO);
+        for _ in 0..EVENT_HISTORY_LEN + 2 {
+            publish(&bus, &channel, GameEvent::LobbyUnlocked);
+        }
+        // the events 1 and 2 were removed
+        assert!(channel.resume(1).1.is_none());
+        assert_eq!(EVENT_HISTORY_LEN, channel.resume(2).1.unwrap().len());
+        assert_eq!(Some(EVENT_HISTORY_LEN as u64 + 2), channel.resume(3).1.unwrap().last().map(PublishedEvent::id));
+    }
+
+    #[test]
+    fn test_buffer_warning() {
+        let channel = GameChannel::new(GameCode::new(['A'; 8]).unwrap());
+        let bus = EventBus::new(Duration::ZERO);
+        let mut receiver = channel.subscribe();
+        let game_master = Uuid::new_v4();
+        let watermark = EVENT_HISTORY_LEN * BUFFER_WARNING_PERCENT / 100;
+        // the receiver falls behind, the events it missed are skipped
+        let warnings = |receiver: &mut Receiver<PublishedEvent>| -> Vec<PublishedEvent> {
+            let mut warnings = Vec::new();
+            loop {
+                match receiver.try_recv() {
+                    Ok(event) if event.data.name() == "BufferWarning" => warnings.push(event),
+                    Ok(_) | Err(TryRecvError::Lagged(_)) => (),
+                    Err(_) => return warnings,
+                }
+            }
+        };
+        // without a game master nobody is warned, the warning is send once one is known
+        for _ in 0..watermark + 1 {
+            publish(&bus, &channel, GameEvent::LobbyUnlocked);
+        }
+        assert!(warnings(&mut receiver).is_empty());
+        channel.set_game_master(game_master);
+        publish(&bus, &channel, GameEvent::LobbyUnlocked);
+        let warned = warnings(&mut receiver);
+        assert_eq!(1, warned.len());
+        assert_eq!(Some(game_master), warned[0].recipient());
+        assert_eq!(format!(r#"{{"buffer":"event_history","capacity":{},"used":{}}}"#, EVENT_HISTORY_LEN, watermark + 2), warned[0].data.payload().unwrap());
+        // each buffer is only warned about once
+        for _ in 0..EVENT_HISTORY_LEN {
+            publish(&bus, &channel, GameEvent::LobbyUnlocked);
+        }
+        assert!(warnings(&mut receiver).is_empty());
+
+        // other buffers are watched on their own
+        channel.watch_buffer(GameBuffer::GameLog, 7, 10);
+        assert!(warnings(&mut receiver).is_empty());
+        channel.watch_buffer(GameBuffer::GameLog, 8, 10);
+        channel.watch_buffer(GameBuffer::GameLog, 9, 10);
+        assert_eq!(1, warnings(&mut receiver).len());
+    }
+}
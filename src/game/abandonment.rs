@@ -0,0 +1,109 @@
+use std::{sync::atomic::{AtomicU64, Ordering}, time::Duration};
+
+use serde::{Deserialize, Serialize};
+
+use super::GAME_INSTANCE_TIMEOUT;
+
+/// The upper bounds of the buckets of [AbandonmentStats](), the last bucket contains all longer gaps.
+pub const RECOVERY_BUCKETS: [Duration; 5] = [
+    Duration::from_secs(5),
+    Duration::from_secs(15),
+    Duration::from_secs(30),
+    Duration::from_secs(60),
+    Duration::from_secs(120),
+];
+
+/// Counts what happens to games after all players have disconnected, to check if [GAME_INSTANCE_TIMEOUT]() is long enough.
+///
+/// A game is recovered when a player connects again before it is deleted, the time the game was abandoned is recorded
+/// in a histogram. Games that are deleted because no player came back are only counted.
+///
+/// The shards of a [ShardedGameManager](../shards/struct.ShardedGameManager.html) share the same stats.
+#[derive(Debug, Default)]
+pub struct AbandonmentStats {
+    /// The number of recovered games per bucket of [RECOVERY_BUCKETS](), the last entry counts the longer gaps
+    recovered: [AtomicU64; RECOVERY_BUCKETS.len() + 1],
+    deleted: AtomicU64,
+}
+
+impl AbandonmentStats {
+    /// Records that a player connected to the game after it was abandoned for `gap`.
+    pub fn record_recovery(&self, gap: Duration) {
+        let bucket = RECOVERY_BUCKETS.iter().position(|bound| gap < *bound).unwrap_or(RECOVERY_BUCKETS.len());
+        self.recovered[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records that an abandoned game was deleted.
+    pub fn record_deletion(&self) {
+        self.deleted.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the counters, see [AbandonmentReport]().
+    pub fn report(&self) -> AbandonmentReport {
+        let buckets: Vec<RecoveryBucket> = self.recovered.iter().enumerate()
+            .map(|(index, count)| RecoveryBucket {
+                below_secs: RECOVERY_BUCKETS.get(index).map(Duration::as_secs),
+                count: count.load(Ordering::Relaxed),
+            })
+            .collect();
+        let recovered: u64 = buckets.iter().map(|bucket| bucket.count).sum();
+        let deleted = self.deleted.load(Ordering::Relaxed);
+        AbandonmentReport {
+            timeout_secs: GAME_INSTANCE_TIMEOUT.as_secs(),
+            buckets,
+            recovered,
+            deleted,
+            recovery_ratio: match recovered + deleted {
+                0 => None,
+                total => Some(recovered as f64 / total as f64),
+            },
+        }
+    }
+}
+
+/// The counters of [AbandonmentStats]() as they are included in the [ServerStatus](../../request_data/struct.ServerStatus.html).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AbandonmentReport {
+    /// The time after which abandoned games are deleted
+    pub timeout_secs: u64,
+    /// How long recovered games were abandoned
+    pub buckets: Vec<RecoveryBucket>,
+    pub recovered: u64,
+    pub deleted: u64,
+    /// The share of abandoned games that were recovered, `None` until a game was recovered or deleted
+    pub recovery_ratio: Option<f64>,
+}
+
+/// A bucket of the histogram in the [AbandonmentReport]().
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecoveryBucket {
+    /// The games of this bucket were abandoned for less than this time, `None` for the last bucket
+    pub below_secs: Option<u64>,
+    pub count: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::AbandonmentStats;
+
+    #[test]
+    fn test_buckets_and_ratio() {
+        let stats = AbandonmentStats::default();
+        assert_eq!(None, stats.report().recovery_ratio);
+        for secs in [0, 4, 5, 29, 60, 119, 120, 3600] {
+            stats.record_recovery(Duration::from_secs(secs));
+        }
+        stats.record_recovery(Duration::from_millis(4999));
+        for _ in 0..3 {
+            stats.record_deletion();
+        }
+        let report = stats.report();
+        let buckets: Vec<(Option<u64>, u64)> = report.buckets.iter().map(|bucket| (bucket.below_secs, bucket.count)).collect();
+        assert_eq!(vec![(Some(5), 3), (Some(15), 1), (Some(30), 1), (Some(60), 0), (Some(120), 2), (None, 2)], buckets);
+        assert_eq!(9, report.recovered);
+        assert_eq!(3, report.deleted);
+        assert_eq!(Some(0.75), report.recovery_ratio);
+    }
+}
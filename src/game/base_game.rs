@@ -1,6 +1,22 @@
+use std::{collections::BTreeMap, time::{SystemTime, UNIX_EPOCH}};
+
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
-use super::User;
+use super::{game_instance::{board::{HotelChain, Position}, seats::SeatAssignment}, User};
+
+/// The number of tiles each player holds
+pub const HAND_SIZE: usize = 6;
+
+/// The number of shares of each chain, the shares that no player holds are in the bank
+pub const SHARES_PER_CHAIN: u32 = 25;
+
+/// The money each player has when the game starts
+pub const STARTING_MONEY: u32 = 6000;
+
+/// The largest number of shares a player can buy in one turn
+pub const MAX_SHARES_PER_TURN: u32 = 3;
 
 /// Player in the game.
 /// 
@@ -8,19 +24,175 @@ use super::User;
 pub struct Player {
     /// The [User](../struct.User.html) that is associated to this player.
     pub user: User,
+    /// The seat and the public id of this player, see [seat](#method.seat).
+    seat: SeatAssignment,
     /// Signals that this player is the game master and can start the game.
     game_master: bool,
+    /// Why the seat of this player is vacant, `None` while the player is connected or has not connected yet.
+    vacancy: Option<Vacancy>,
+    /// The tiles this player holds, dealt when the game starts
+    hand: Vec<Tile>,
+    /// The number of shares this player holds of each chain, chains without shares are not listed
+    shares: BTreeMap<HotelChain, u32>,
+    /// The money of this player, set to [STARTING_MONEY]() when the game starts
+    money: u32,
+    /// What this player did in the game, see [stats](#method.stats)
+    stats: PlayerStats,
+}
+
+/// A tile that can be placed on the [Position](../game_instance/board/struct.Position.html) with the same name, for example `5E`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Tile(Position);
+
+impl Tile {
+    /// Creates the tile for `column` (1 to 12) and `row` (`A` to `I`), `None` when the position is not on the board.
+    // Tiles are only created from positions by the tile bag or parsed from requests for now
+    #[allow(dead_code)]
+    pub fn new(column: u8, row: char) -> Option<Self> {
+        Position::new(column, row).map(Self)
+    }
+
+    /// Returns the position on which this tile is placed.
+    pub fn position(&self) -> Position {
+        self.0
+    }
+}
+
+impl From<Position> for Tile {
+    fn from(position: Position) -> Self {
+        Self(position)
+    }
+}
+
+/// The tiles that were not drawn yet, there is exactly one tile for each position of the board.
+#[derive(Debug, Clone)]
+pub struct TileBag {
+    tiles: Vec<Tile>,
+    /// Drawn tiles that can never be placed, they are put aside and not drawn again
+    discarded: Vec<Tile>,
+}
+
+impl TileBag {
+    /// Creates a bag with all tiles in random order.
+    /// 
+    /// # Params
+    /// `rng` the rng of the game, see [GameRng](../game_instance/rng/struct.GameRng.html)
+    pub fn shuffled(rng: &mut impl Rng) -> Self {
+        let mut tiles: Vec<Tile> = Position::all().map(Tile).collect();
+        tiles.shuffle(rng);
+        Self { tiles, discarded: Vec::new() }
+    }
+
+    /// Takes the next tile out of the bag.
+    /// 
+    /// # Returns
+    /// `None` when the bag is empty.
+    pub fn draw(&mut self) -> Option<Tile> {
+        self.tiles.pop()
+    }
+
+    /// Returns the number of tiles that are left in the bag.
+    pub fn remaining(&self) -> usize {
+        self.tiles.len()
+    }
+
+    /// Takes `tile` out of the bag, tests use this to arrange hands and the board.
+    /// 
+    /// # Returns
+    /// `false` when the tile is not in the bag.
+    #[cfg(test)]
+    pub fn take(&mut self, tile: Tile) -> bool {
+        match self.tiles.iter().position(|held| *held == tile) {
+            Some(index) => {
+                self.tiles.remove(index);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Puts `tile` back into the bag, it is drawn after all other tiles.
+    pub fn put_back(&mut self, tile: Tile) {
+        self.tiles.insert(0, tile);
+    }
+
+    /// Puts a drawn tile aside because it can never be placed, see [Board::is_dead](../game_instance/board/struct.Board.html#method.is_dead).
+    pub fn discard(&mut self, tile: Tile) {
+        self.discarded.push(tile);
+    }
+
+    /// Returns the tiles that are left in the bag followed by the discarded tiles.
+    #[cfg(debug_assertions)]
+    pub fn tiles(&self) -> impl Iterator<Item = Tile> + '_ {
+        self.tiles.iter().chain(&self.discarded).copied()
+    }
+}
+
+/// Why the seat of a player became vacant, see [Vacancy]().
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum VacancyReason {
+    /// The player left with [leave_game](../../paths/lobby_api/fn.leave_game.html)
+    Left,
+    /// The sse stream of the player was closed without leaving, the player might come back
+    ConnectionLost,
+}
+
+/// A seat whose player is no longer connected.
+/// 
+/// The other players use this to decide if it is worth waiting for the player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Vacancy {
+    pub reason: VacancyReason,
+    /// Unix seconds at which the seat became vacant
+    pub since: u64,
+}
+
+/// What a player did in the game, returned by [game_stats](../../paths/game_api/fn.game_stats.html) once the game is finished.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerStats {
+    pub tiles_placed: u32,
+    pub chains_founded: u32,
+    pub stocks_bought: u32,
+    /// The shares of defunct chains that were sold in mergers, the sale at the end of the game is not counted
+    pub stocks_sold: u32,
+    /// The shares of defunct chains that were traded in mergers, two of them for each share of the survivor
+    pub stocks_traded: u32,
+    /// The majority and minority bonuses of the mergers and the end of the game
+    pub bonuses: u32,
+    /// The money plus the value of the shares at the end of each turn of the player, one entry per round
+    pub net_worth: Vec<u32>,
 }
 
 impl Player {
-    /// Creates a new player
-    pub fn new(user: User) -> Self {
+    /// Creates a new player that sits on `seat`
+    pub fn new(user: User, seat: SeatAssignment) -> Self {
         Self {
             user,
+            seat,
             game_master: false,
+            vacancy: None,
+            hand: Vec::new(),
+            shares: BTreeMap::new(),
+            money: 0,
+            stats: PlayerStats::default(),
         }
     }
 
+    /// Returns the public id of this player.
+    /// 
+    /// Unlike the uuid this id is send to all players, clients use it to identify players because it never changes
+    /// while the name is only used for displaying.
+    pub fn id(&self) -> u32 {
+        self.seat.public_id
+    }
+
+    /// Returns the seat of this player, it is kept until the player leaves the game.
+    pub fn seat(&self) -> SeatAssignment {
+        self.seat
+    }
+
     pub fn username(&self) -> String {
         self.user.name()
     }
@@ -45,4 +217,120 @@ impl Player {
     pub fn is_game_master(&self) -> bool {
         self.game_master
     }
+
+    /// Returns why the seat of this player is vacant.
+    pub fn vacancy(&self) -> Option<Vacancy> {
+        self.vacancy
+    }
+
+    /// Marks the seat of this player as vacant, the time of the first vacancy is kept.
+    pub fn vacate(&mut self, reason: VacancyReason) {
+        let since = match self.vacancy {
+            Some(vacancy) => vacancy.since,
+            None => SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or_default(),
+        };
+        self.vacancy = Some(Vacancy { reason, since });
+    }
+
+    /// Returns the tiles this player holds.
+    pub fn hand(&self) -> &[Tile] {
+        &self.hand
+    }
+
+    /// Adds `tile` to the hand of this player.
+    pub fn add_tile(&mut self, tile: Tile) {
+        self.hand.push(tile);
+    }
+
+    /// Returns the number of shares of `chain` this player holds.
+    pub fn shares(&self, chain: HotelChain) -> u32 {
+        self.shares.get(&chain).copied().unwrap_or_default()
+    }
+
+    /// Gives `count` shares of `chain` to this player, the caller has to make sure that the bank holds them.
+    pub fn add_shares(&mut self, chain: HotelChain, count: u32) {
+        *self.shares.entry(chain).or_default() += count;
+    }
+
+    /// Returns the shares this player holds, chains without shares are not listed.
+    pub fn portfolio(&self) -> &BTreeMap<HotelChain, u32> {
+        &self.shares
+    }
+
+    /// Returns the money of this player.
+    pub fn money(&self) -> u32 {
+        self.money
+    }
+
+    /// Sets the money of this player.
+    pub fn set_money(&mut self, money: u32) {
+        self.money = money;
+    }
+
+    /// Returns what this player did in the game.
+    pub fn stats(&self) -> &PlayerStats {
+        &self.stats
+    }
+
+    /// Returns what this player did in the game, the game logic updates it with every action.
+    pub fn stats_mut(&mut self) -> &mut PlayerStats {
+        &mut self.stats
+    }
+
+    /// Takes `count` shares of `chain` from this player, the caller has to make sure that the player holds them.
+    pub fn remove_shares(&mut self, chain: HotelChain, count: u32) {
+        if let Some(shares) = self.shares.get_mut(&chain) {
+            *shares -= count;
+            if *shares == 0 {
+                self.shares.remove(&chain);
+            }
+        }
+    }
+
+    /// Removes `tile` from the hand of this player.
+    /// 
+    /// # Returns
+    /// `false` when the player does not hold `tile`.
+    pub fn remove_tile(&mut self, tile: Tile) -> bool {
+        match self.hand.iter().position(|held| *held == tile) {
+            Some(index) => {
+                self.hand.remove(index);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Clears the vacancy because the player is connected again.
+    /// 
+    /// # Returns
+    /// `true` when the seat was vacant.
+    pub fn reoccupy(&mut self) -> bool {
+        self.vacancy.take().is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::game::game_instance::rng::GameRng;
+
+    use super::{Tile, TileBag, HAND_SIZE};
+
+    #[test]
+    fn test_tile_bag() {
+        let mut bag = TileBag::shuffled(&mut GameRng::from_seed(3));
+        assert_eq!(108, bag.remaining());
+        let hands: Vec<Vec<Tile>> = (0..6).map(|_| (0..HAND_SIZE).map(|_| bag.draw().unwrap()).collect()).collect();
+        assert_eq!(72, bag.remaining());
+        let mut tiles: Vec<Tile> = hands.into_iter().flatten().collect();
+        tiles.extend(std::iter::from_fn(|| bag.draw()));
+        assert_eq!(None, bag.draw());
+        assert_eq!(0, bag.remaining());
+        assert_eq!(108, tiles.iter().collect::<HashSet<_>>().len());
+        // the same seed shuffles the same way
+        let mut other = TileBag::shuffled(&mut GameRng::from_seed(3));
+        assert_eq!(tiles[0], other.draw().unwrap());
+    }
 }
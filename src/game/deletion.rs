@@ -0,0 +1,123 @@
+use std::{
+    collections::HashMap,
+    sync::{mpsc::{self, Sender}, Arc, Condvar, Mutex, RwLock},
+    thread,
+    time::Duration,
+};
+
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    log::private::{info, warn},
+    Orbit, Rocket,
+};
+use uuid::Uuid;
+
+use crate::utils::{get_gm_read_guard, get_gm_write_guard};
+
+use super::{game_instance::GameCode, shards::{shard_index, user_index, ShardedGameManager}, GameManager};
+
+/// How long the shutdown waits for the queued deletions, see [DrainDeletions]()
+pub const SHUTDOWN_DRAIN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The shards of a [ShardedGameManager](../shards/struct.ShardedGameManager.html), shared with the deletion worker.
+pub(super) type Shards = Arc<[RwLock<GameManager>]>;
+
+/// The user index of a [ShardedGameManager](../shards/struct.ShardedGameManager.html), shared with the deletion worker.
+pub(super) type UserIndex = Arc<[RwLock<HashMap<Uuid, GameCode>>]>;
+
+/// Deletes the games that are [marked for deletion](../struct.GameManager.html#method.mark_for_deletion) on a separate thread.
+///
+/// Deleting a game frees the game code and the ids of all its users in the game manager and in the user index.
+/// This is done here so that the request that triggered the deletion (for example the last player leaving) does not wait for it.
+/// Cleanup that is added later, like writing a replay file, belongs into the worker as well so that it runs without
+/// holding a lock that requests need.
+///
+/// Each [ShardedGameManager](../shards/struct.ShardedGameManager.html) has its own queue, the worker thread ends when the manager
+/// and all its clones are dropped.
+#[derive(Clone)]
+pub struct DeletionQueue {
+    sender: Sender<GameCode>,
+    /// The number of games that were queued but not yet deleted
+    pending: Arc<(Mutex<usize>, Condvar)>,
+}
+
+impl DeletionQueue {
+    /// Creates the queue and starts the worker thread.
+    pub(super) fn new(shards: Shards, users: UserIndex) -> Self {
+        let (sender, receiver) = mpsc::channel::<GameCode>();
+        let pending = Arc::new((Mutex::new(0_usize), Condvar::new()));
+        let worker_pending = Arc::clone(&pending);
+        thread::Builder::new()
+            .name(String::from("game deletion"))
+            .spawn(move || {
+                // Ends when the sender is dropped and all queued games are deleted
+                for game_code in receiver {
+                    delete(&shards, &users, game_code);
+                    let (count, done) = &*worker_pending;
+                    *count.lock().unwrap() -= 1;
+                    done.notify_all();
+                }
+            })
+            .expect("the game deletion thread could not be started");
+        Self { sender, pending }
+    }
+
+    /// Queues a game that is marked for deletion.
+    pub fn push(&self, game_code: GameCode) {
+        let (count, done) = &*self.pending;
+        *count.lock().unwrap() += 1;
+        if self.sender.send(game_code).is_err() {
+            warn!("Game {} could not be queued for deletion, the deletion thread has stopped", game_code);
+            *count.lock().unwrap() -= 1;
+            done.notify_all();
+        }
+    }
+
+    /// Waits until all queued games are deleted or `timeout` has passed.
+    ///
+    /// # Returns
+    /// `true` when all queued games are deleted.
+    pub fn wait(&self, timeout: Duration) -> bool {
+        let (count, done) = &*self.pending;
+        let (count, _) = done.wait_timeout_while(count.lock().unwrap(), timeout, |count| *count > 0).unwrap();
+        *count == 0
+    }
+}
+
+/// Removes the users of the game from the index and deletes the game afterwards.
+///
+/// The game code is freed last, so a new game with the same code can not be created before the old game is gone completely.
+fn delete(shards: &Shards, users: &UserIndex, game_code: GameCode) {
+    let shard = &shards[shard_index(&game_code, shards.len())];
+    let uuids = match get_gm_read_guard(shard, "delete_game: users").pending_deletion_uuids(game_code) {
+        Some(uuids) => uuids,
+        None => return,
+    };
+    for uuid in &uuids {
+        users[user_index(*uuid, users.len())].write().unwrap().remove(uuid);
+    }
+    if get_gm_write_guard(shard, "delete_game").delete_game(&game_code) {
+        info!("Game {} and its {} users were removed", game_code, uuids.len());
+    }
+}
+
+/// Fairing that waits on shutdown until the queued deletions are done, at most for [SHUTDOWN_DRAIN_TIMEOUT]().
+pub struct DrainDeletions;
+
+#[rocket::async_trait]
+impl Fairing for DrainDeletions {
+    fn info(&self) -> Info {
+        Info {
+            name: "Drain game deletions",
+            kind: Kind::Shutdown,
+        }
+    }
+
+    async fn on_shutdown(&self, rocket: &Rocket<Orbit>) {
+        if let Some(game_manager) = rocket.state::<ShardedGameManager>() {
+            if !game_manager.wait_for_deletions(SHUTDOWN_DRAIN_TIMEOUT) {
+                warn!("Not all games could be deleted before the shutdown");
+            }
+        }
+    }
+}
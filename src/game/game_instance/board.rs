@@ -0,0 +1,686 @@
+use std::fmt::{self, Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// The number of columns of the board, numbered from 1 to 12
+pub const COLUMNS: u8 = 12;
+
+/// The number of rows of the board, named from `A` to `I`
+pub const ROWS: u8 = 9;
+
+/// A chain with at least this many tiles is safe, it can no longer be taken over in a merger
+pub const SAFE_CHAIN_SIZE: usize = 11;
+
+/// The game can be ended as soon as one chain has this many tiles
+pub const END_CHAIN_SIZE: usize = 41;
+
+/// A field of the board, written like `1A` (top left) or `12I` (bottom right).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Position {
+    /// Starts at 0 for row `A`, the rows are compared first so positions are ordered row by row
+    row: u8,
+    /// Starts at 1
+    column: u8,
+}
+
+impl Position {
+    /// Creates the position in `column` (1 to [COLUMNS]()) and `row` (`A` to `I`).
+    ///
+    /// # Returns
+    /// `None` when the position is not on the board.
+    pub fn new(column: u8, row: char) -> Option<Self> {
+        let row = u8::try_from(row).ok()?.checked_sub(b'A')?;
+        if column == 0 || column > COLUMNS || row >= ROWS {
+            return None;
+        }
+        Some(Self { row, column })
+    }
+
+    /// Parses a position like `1A` or `12I`.
+    ///
+    /// The column consists of ASCII digits without a sign or leading zeros, so each position has exactly one spelling.
+    ///
+    /// # Returns
+    /// `None` when `input` is not a position on the board.
+    pub fn parse(input: &str) -> Option<Self> {
+        let row = input.chars().last()?;
+        let column = &input[..input.len() - row.len_utf8()];
+        if column.starts_with('0') || !column.bytes().all(|byte| byte.is_ascii_digit()) {
+            return None;
+        }
+        Self::new(column.parse().ok()?, row)
+    }
+
+    /// Returns the positions that share an edge with this position, diagonal positions are not neighbors.
+    ///
+    /// Positions on the edge of the board have 3 neighbors, positions in the corners 2.
+    pub fn neighbors(&self) -> Vec<Position> {
+        let (row, column) = (self.row as i8, self.column as i8);
+        [(row - 1, column), (row, column - 1), (row, column + 1), (row + 1, column)]
+            .into_iter()
+            .filter(|(row, column)| (0..ROWS as i8).contains(row) && (1..=COLUMNS as i8).contains(column))
+            .map(|(row, column)| Self { row: row as u8, column: column as u8 })
+            .collect()
+    }
+
+    /// Returns all positions of the board row by row, starting with `1A`.
+    pub fn all() -> impl Iterator<Item = Position> {
+        (0..ROWS).flat_map(|row| (1..=COLUMNS).map(move |column| Self { row, column }))
+    }
+
+    fn index(&self) -> usize {
+        self.row as usize * COLUMNS as usize + self.column as usize - 1
+    }
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.column, char::from(b'A' + self.row))
+    }
+}
+
+impl TryFrom<String> for Position {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::parse(&value).ok_or_else(|| format!("{} is not a position on the board", value))
+    }
+}
+
+impl From<Position> for String {
+    fn from(position: Position) -> Self {
+        position.to_string()
+    }
+}
+
+/// The hotel chains that can be founded on the board.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HotelChain {
+    Tower,
+    Luxor,
+    American,
+    Worldwide,
+    Festival,
+    Imperial,
+    Continental,
+}
+
+impl HotelChain {
+    /// All chains, a new chain can only be founded while one of them is not on the board
+    pub const ALL: [HotelChain; 7] = [Self::Tower, Self::Luxor, Self::American, Self::Worldwide, Self::Festival, Self::Imperial, Self::Continental];
+
+    /// Returns the price tier of this chain, the shares of more expensive chains cost more at the same size.
+    pub fn tier(&self) -> PriceTier {
+        match self {
+            Self::Tower | Self::Luxor => PriceTier::Cheap,
+            Self::American | Self::Worldwide | Self::Festival => PriceTier::Medium,
+            Self::Imperial | Self::Continental => PriceTier::Expensive,
+        }
+    }
+
+    /// Returns the price of one share of this chain when the chain has `size` tiles, following the price table of the game.
+    ///
+    /// # Returns
+    /// `None` when `size` is less than 2, a chain with less tiles does not exist.
+    pub fn price(&self, size: usize) -> Option<u32> {
+        let base = match size {
+            0 | 1 => return None,
+            2..=5 => size as u32 * 100,
+            6..=10 => 600,
+            11..=20 => 700,
+            21..=30 => 800,
+            31..=40 => 900,
+            _ => 1000,
+        };
+        let surcharge = match self.tier() {
+            PriceTier::Cheap => 0,
+            PriceTier::Medium => 100,
+            PriceTier::Expensive => 200,
+        };
+        Some(base + surcharge)
+    }
+}
+
+impl Display for HotelChain {
+    /// Writes the name of the chain like it is serialized, for example `tower`
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Tower => "tower",
+            Self::Luxor => "luxor",
+            Self::American => "american",
+            Self::Worldwide => "worldwide",
+            Self::Festival => "festival",
+            Self::Imperial => "imperial",
+            Self::Continental => "continental",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// The price tiers of the chains, see [HotelChain::tier]().
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceTier {
+    /// Tower and Luxor
+    Cheap,
+    /// American, Worldwide and Festival, 100 more than the cheap tier
+    Medium,
+    /// Imperial and Continental, 200 more than the cheap tier
+    Expensive,
+}
+
+/// The state of a chain on the board, see [Board::chains]().
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainState {
+    pub chain: HotelChain,
+    pub tier: PriceTier,
+    /// `true` while the chain has tiles on the board
+    pub founded: bool,
+    /// The number of tiles that belong to the chain
+    pub size: usize,
+    /// The chain has at least [SAFE_CHAIN_SIZE]() tiles
+    pub safe: bool,
+    /// The price of one share, `None` while the chain is not founded
+    pub price: Option<u32>,
+}
+
+/// The reasons why a tile can not be placed, see [Board::place_tile]().
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PlaceTileError {
+    #[error("a tile was already placed on {0}")]
+    Occupied(Position),
+    /// The tile would found a new chain but all chains are already on the board
+    #[error("the tile on {0} would found an eighth chain")]
+    NoChainAvailable(Position),
+    /// The tile would merge two or more chains that have at least [SAFE_CHAIN_SIZE]() tiles
+    #[error("the tile on {0} would merge safe chains")]
+    SafeChainsMerge(Position),
+}
+
+/// The invariants of the board that are broken, see [Board::check_invariants]().
+#[cfg(debug_assertions)]
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum BoardInvariantError {
+    /// The tile belongs to `chain` but touches a tile of `other`, the chains should have been merged
+    #[error("the tile on {position} belongs to {chain} but touches {other}")]
+    ChainsTouch { position: Position, chain: HotelChain, other: HotelChain },
+    /// The tile does not belong to a chain but touches exactly one chain, it should have joined the chain
+    #[error("the tile on {position} touches {chain} but does not belong to it")]
+    NotJoined { position: Position, chain: HotelChain },
+    #[error("{0} has less than 2 tiles")]
+    ChainTooSmall(HotelChain),
+    #[error("the tiles of {0} are not connected")]
+    ChainSplit(HotelChain),
+}
+
+/// The board of a game, it stores on which positions a tile was placed and to which hotel chain the tiles belong.
+///
+/// The board only records the tiles, deciding what happens when a tile is placed (founding a chain, growing a
+/// chain or merging chains) is up to the game logic, which uses [placed_neighbors](#method.placed_neighbors)
+/// and [adjacent_chains](#method.adjacent_chains) to detect these cases.
+#[derive(Debug, Clone)]
+pub struct Board {
+    /// One entry for each position in the order of [Position::index](), `None` when no tile was placed.
+    /// The inner option is the chain to which the tile belongs.
+    tiles: Vec<Option<Option<HotelChain>>>,
+}
+
+impl Default for Board {
+    fn default() -> Self {
+        Self { tiles: vec![None; COLUMNS as usize * ROWS as usize] }
+    }
+}
+
+impl Board {
+    /// Places a tile on `position` when the rules allow it.
+    ///
+    /// When the tile is next to exactly one chain the chain grows, the new tile and all tiles without a chain that are
+    /// connected through it join the chain. Otherwise the tile does not belong to a chain, when it
+    /// [founds_chain](#method.founds_chain) the game logic has to call [found_chain](#method.found_chain) and
+    /// when it is next to several chains the game logic has to call [merge_chains](#method.merge_chains).
+    ///
+    /// # Returns
+    /// - The tiles that were placed or changed their chain, row by row.
+    /// - `Err(PlaceTileError)` when the tile can not be placed, the board is not changed in this case.
+    pub fn place_tile(&mut self, position: Position) -> Result<Vec<PlacedTile>, PlaceTileError> {
+        self.check_placement(position)?;
+        self.tiles[position.index()] = Some(None);
+        match self.adjacent_chains(position)[..] {
+            [chain] => Ok(self.join_chain(position, chain)),
+            _ => Ok(vec![PlacedTile { position, chain: None }]),
+        }
+    }
+
+    /// Checks if the tile on `position` founds a new chain, this is the case when it does not belong to a chain
+    /// and is connected to other tiles that do not belong to a chain either.
+    pub fn founds_chain(&self, position: Position) -> bool {
+        self.is_occupied(position) && self.chain_at(position).is_none()
+            && !self.placed_neighbors(position).is_empty() && self.adjacent_chains(position).is_empty()
+    }
+
+    /// Founds `chain` with the tile on `position` and all tiles without a chain that are connected to it.
+    ///
+    /// # Returns
+    /// The tiles that joined the chain, row by row.
+    pub fn found_chain(&mut self, position: Position, chain: HotelChain) -> Vec<PlacedTile> {
+        self.join_chain(position, chain)
+    }
+
+    /// Merges the chains that are connected through the tile on `position` into `survivor`, the tiles without a chain
+    /// that are connected join `survivor` as well. The other chains are no longer on the board and can be founded again.
+    ///
+    /// # Returns
+    /// The tiles that changed their chain, row by row.
+    pub fn merge_chains(&mut self, position: Position, survivor: HotelChain) -> Vec<PlacedTile> {
+        let mut visited = vec![position];
+        let mut unvisited = vec![position];
+        let mut changed = Vec::new();
+        while let Some(current) = unvisited.pop() {
+            if self.chain_at(current) != Some(survivor) {
+                self.set_chain(current, survivor);
+                changed.push(current);
+            }
+            for neighbor in self.placed_neighbors(current) {
+                if !visited.contains(&neighbor) {
+                    visited.push(neighbor);
+                    unvisited.push(neighbor);
+                }
+            }
+        }
+        changed.sort();
+        changed.into_iter().map(|position| PlacedTile { position, chain: Some(survivor) }).collect()
+    }
+
+    /// Sets the chain of the tile on `position` and of all tiles without a chain that are connected through it to `chain`.
+    fn join_chain(&mut self, position: Position, chain: HotelChain) -> Vec<PlacedTile> {
+        let mut changed = vec![position];
+        let mut unvisited = vec![position];
+        while let Some(current) = unvisited.pop() {
+            self.set_chain(current, chain);
+            for neighbor in self.placed_neighbors(current) {
+                if self.chain_at(neighbor).is_none() && !changed.contains(&neighbor) {
+                    changed.push(neighbor);
+                    unvisited.push(neighbor);
+                }
+            }
+        }
+        changed.sort();
+        changed.into_iter().map(|position| PlacedTile { position, chain: Some(chain) }).collect()
+    }
+
+    /// Checks if a tile can be placed on `position`.
+    ///
+    /// # Returns
+    /// `Err(PlaceTileError)` when
+    /// - a tile was already placed on `position`
+    /// - the tile would found a new chain but all [HotelChain::ALL]() are on the board
+    /// - the tile would merge two or more chains that are safe, see [SAFE_CHAIN_SIZE]()
+    pub fn check_placement(&self, position: Position) -> Result<(), PlaceTileError> {
+        if self.is_occupied(position) {
+            return Err(PlaceTileError::Occupied(position));
+        }
+        let chains = self.adjacent_chains(position);
+        if chains.is_empty() && !self.placed_neighbors(position).is_empty() && self.chains_on_board().len() == HotelChain::ALL.len() {
+            return Err(PlaceTileError::NoChainAvailable(position));
+        }
+        if chains.iter().filter(|chain| self.is_safe(**chain)).count() >= 2 {
+            return Err(PlaceTileError::SafeChainsMerge(position));
+        }
+        Ok(())
+    }
+
+    /// Checks if a tile for `position` can never be placed because it would merge two safe chains.
+    ///
+    /// Chains only grow, so a chain that is safe stays safe for the rest of the game.
+    pub fn is_dead(&self, position: Position) -> bool {
+        matches!(self.check_placement(position), Err(PlaceTileError::SafeChainsMerge(_)))
+    }
+
+    /// Checks if a tile was placed on `position`.
+    pub fn is_occupied(&self, position: Position) -> bool {
+        self.tiles[position.index()].is_some()
+    }
+
+    /// Returns the chain to which the tile on `position` belongs, `None` when no tile was placed or the tile does not belong to a chain.
+    pub fn chain_at(&self, position: Position) -> Option<HotelChain> {
+        self.tiles[position.index()].flatten()
+    }
+
+    /// Returns the [neighbors](struct.Position.html#method.neighbors) of `position` on which a tile was placed.
+    pub fn placed_neighbors(&self, position: Position) -> Vec<Position> {
+        position.neighbors().into_iter().filter(|neighbor| self.is_occupied(*neighbor)).collect()
+    }
+
+    /// Returns the different chains of the neighbors of `position`, ordered and without duplicates.
+    ///
+    /// When a tile is placed on `position` one chain means that the chain grows and several chains mean a merger.
+    /// No chain but [placed_neighbors](#method.placed_neighbors) means that a new chain is founded.
+    pub fn adjacent_chains(&self, position: Position) -> Vec<HotelChain> {
+        let mut chains: Vec<HotelChain> = position.neighbors().into_iter().filter_map(|neighbor| self.chain_at(neighbor)).collect();
+        chains.sort();
+        chains.dedup();
+        chains
+    }
+
+    /// Sets the chain of the tile on `position`, used when a chain is founded, grows or takes over another chain.
+    ///
+    /// Positions without a tile are not changed.
+    pub fn set_chain(&mut self, position: Position, chain: HotelChain) {
+        if let Some(tile) = &mut self.tiles[position.index()] {
+            *tile = Some(chain);
+        }
+    }
+
+    /// Returns the number of tiles that belong to `chain`, `0` when the chain is not on the board.
+    pub fn chain_size(&self, chain: HotelChain) -> usize {
+        self.tiles.iter().filter(|tile| **tile == Some(Some(chain))).count()
+    }
+
+    /// Returns the chains that have at least one tile on the board, ordered and without duplicates.
+    pub fn chains_on_board(&self) -> Vec<HotelChain> {
+        HotelChain::ALL.into_iter().filter(|chain| self.chain_size(*chain) > 0).collect()
+    }
+
+    /// Checks if `chain` has at least [SAFE_CHAIN_SIZE]() tiles, a safe chain can no longer be taken over.
+    pub fn is_safe(&self, chain: HotelChain) -> bool {
+        self.chain_size(chain) >= SAFE_CHAIN_SIZE
+    }
+
+    /// Checks if the game can be ended: one chain has at least [END_CHAIN_SIZE]() tiles or all chains on the board are safe.
+    pub fn can_end_game(&self) -> bool {
+        let chains = self.chains_on_board();
+        chains.iter().any(|chain| self.chain_size(*chain) >= END_CHAIN_SIZE) || (!chains.is_empty() && chains.iter().all(|chain| self.is_safe(*chain)))
+    }
+
+    /// Returns the current price of one share of `chain`, `None` when the chain is not on the board.
+    pub fn chain_price(&self, chain: HotelChain) -> Option<u32> {
+        chain.price(self.chain_size(chain))
+    }
+
+    /// Returns the state of all chains in the order of [HotelChain::ALL](), including the chains that are not founded.
+    ///
+    /// The board is the only place where the chains are recorded, so the state is always derived from the tiles.
+    pub fn chains(&self) -> Vec<ChainState> {
+        HotelChain::ALL.into_iter().map(|chain| {
+            let size = self.chain_size(chain);
+            ChainState { chain, tier: chain.tier(), founded: size > 0, size, safe: self.is_safe(chain), price: self.chain_price(chain) }
+        }).collect()
+    }
+}
+
+impl Board {
+    /// Returns the placed tiles, so that the board can be send to the clients.
+    pub fn snapshot(&self) -> BoardSnapshot {
+        let tiles = Position::all()
+            .filter_map(|position| self.tiles[position.index()].map(|chain| PlacedTile { position, chain }))
+            .collect();
+        BoardSnapshot { columns: COLUMNS, rows: ROWS, tiles }
+    }
+}
+
+#[cfg(debug_assertions)]
+impl Board {
+    /// Checks that the tiles and chains on the board are what the rules can produce, the game logic calls this after
+    /// every change in debug builds.
+    ///
+    /// The chain of a tile is stored on the tile itself, so the chains never share a tile. The checks cover the rest:
+    /// - tiles of different chains do not touch
+    /// - a tile without a chain does not touch exactly one chain, a tile that touches several chains waits for
+    ///   [merge_chains](#method.merge_chains)
+    /// - every chain on the board has at least 2 tiles and all its tiles are connected
+    ///
+    /// # Returns
+    /// `Err(BoardInvariantError)` naming the first tile or chain that breaks an invariant.
+    pub fn check_invariants(&self) -> Result<(), BoardInvariantError> {
+        for position in Position::all().filter(|position| self.is_occupied(*position)) {
+            let chains = self.adjacent_chains(position);
+            match self.chain_at(position) {
+                Some(chain) => {
+                    if let Some(other) = chains.into_iter().find(|other| *other != chain) {
+                        return Err(BoardInvariantError::ChainsTouch { position, chain, other });
+                    }
+                },
+                None => {
+                    if let [chain] = chains[..] {
+                        return Err(BoardInvariantError::NotJoined { position, chain });
+                    }
+                },
+            }
+        }
+        for chain in self.chains_on_board() {
+            let size = self.chain_size(chain);
+            if size < 2 {
+                return Err(BoardInvariantError::ChainTooSmall(chain));
+            }
+            let first = match Position::all().find(|position| self.chain_at(*position) == Some(chain)) {
+                Some(first) => first,
+                None => continue,
+            };
+            let mut connected = vec![first];
+            let mut unvisited = vec![first];
+            while let Some(current) = unvisited.pop() {
+                for neighbor in self.placed_neighbors(current) {
+                    if self.chain_at(neighbor) == Some(chain) && !connected.contains(&neighbor) {
+                        connected.push(neighbor);
+                        unvisited.push(neighbor);
+                    }
+                }
+            }
+            if connected.len() != size {
+                return Err(BoardInvariantError::ChainSplit(chain));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The board as it is send to the clients, see [Board::snapshot]().
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BoardSnapshot {
+    pub columns: u8,
+    pub rows: u8,
+    /// The placed tiles, row by row
+    pub tiles: Vec<PlacedTile>,
+}
+
+/// A tile on the board, see [BoardSnapshot]().
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlacedTile {
+    pub position: Position,
+    /// The chain to which the tile belongs, `None` for tiles that do not belong to a chain
+    pub chain: Option<HotelChain>,
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::serde::json::to_string;
+
+    use super::{Board, BoardInvariantError, HotelChain, PlaceTileError, PlacedTile, Position, PriceTier, SAFE_CHAIN_SIZE};
+
+    fn position(input: &str) -> Position {
+        Position::parse(input).unwrap()
+    }
+
+    fn neighbors(input: &str) -> Vec<String> {
+        let mut neighbors: Vec<String> = position(input).neighbors().iter().map(Position::to_string).collect();
+        neighbors.sort();
+        neighbors
+    }
+
+    #[test]
+    fn test_parse_position() {
+        assert_eq!(Position::new(1, 'A'), Position::parse("1A"));
+        assert_eq!("12I", position("12I").to_string());
+        for invalid in ["", "A", "0A", "13A", "1J", "1a", "-1A", "A1", "1AA", "1\u{c4}", "+1A", "01A", "012A", "00A", " 1A", "1 A"] {
+            assert_eq!(None, Position::parse(invalid), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_neighbors() {
+        // corners
+        assert_eq!(vec!["1B", "2A"], neighbors("1A"));
+        assert_eq!(vec!["11A", "12B"], neighbors("12A"));
+        assert_eq!(vec!["1H", "2I"], neighbors("1I"));
+        assert_eq!(vec!["11I", "12H"], neighbors("12I"));
+        // edges
+        assert_eq!(vec!["4A", "5B", "6A"], neighbors("5A"));
+        assert_eq!(vec!["1D", "1F", "2E"], neighbors("1E"));
+        assert_eq!(vec!["11E", "12D", "12F"], neighbors("12E"));
+        assert_eq!(vec!["4I", "5H", "6I"], neighbors("5I"));
+        // inside
+        assert_eq!(vec!["4E", "5D", "5F", "6E"], neighbors("5E"));
+    }
+
+    #[test]
+    fn test_place_tiles() {
+        let mut board = Board::default();
+        assert_eq!(Ok(vec![PlacedTile { position: position("5E"), chain: None }]), board.place_tile(position("5E")));
+        assert_eq!(Err(PlaceTileError::Occupied(position("5E"))), board.place_tile(position("5E")));
+        assert_eq!(vec![position("5E")], board.placed_neighbors(position("5F")));
+        assert!(board.adjacent_chains(position("5F")).is_empty());
+
+        board.place_tile(position("5G")).unwrap();
+        board.place_tile(position("4F")).unwrap();
+        board.set_chain(position("5E"), HotelChain::Luxor);
+        board.set_chain(position("5G"), HotelChain::Tower);
+        board.set_chain(position("4F"), HotelChain::Tower);
+        board.set_chain(position("1A"), HotelChain::Tower);
+        assert_eq!(None, board.chain_at(position("1A")));
+        assert_eq!(Some(HotelChain::Luxor), board.chain_at(position("5E")));
+        assert_eq!(vec![HotelChain::Tower, HotelChain::Luxor], board.adjacent_chains(position("5F")));
+
+        let snapshot = to_string(&board.snapshot()).unwrap();
+        assert_eq!(r#"{"columns":12,"rows":9,"tiles":[{"position":"5E","chain":"luxor"},{"position":"4F","chain":"tower"},{"position":"5G","chain":"tower"}]}"#, snapshot);
+    }
+
+    #[test]
+    fn test_prices() {
+        let prices = |chain: HotelChain| [0, 1, 2, 5, 6, 10, 11, 20, 21, 30, 31, 40, 41, 108].map(|size| chain.price(size));
+        let cheap = [None, None, Some(200), Some(500), Some(600), Some(600), Some(700), Some(700), Some(800), Some(800), Some(900), Some(900), Some(1000), Some(1000)];
+        assert_eq!(cheap, prices(HotelChain::Tower));
+        assert_eq!(cheap, prices(HotelChain::Luxor));
+        let expensive = cheap.map(|price| price.map(|price| price + 200));
+        assert_eq!(expensive, prices(HotelChain::Imperial));
+        assert_eq!(expensive, prices(HotelChain::Continental));
+        assert_eq!(Some(400), HotelChain::Festival.price(3));
+        assert_eq!(3, HotelChain::ALL.iter().filter(|chain| chain.tier() == PriceTier::Medium).count());
+    }
+
+    #[test]
+    fn test_chain_grows() {
+        let mut board = Board::default();
+        for tile in ["4E", "6E", "7E", "6G"] {
+            board.place_tile(position(tile)).unwrap();
+        }
+        board.set_chain(position("4E"), HotelChain::Tower);
+        // the connected tiles without a chain join, 6G is not connected
+        let changed = board.place_tile(position("5E")).unwrap();
+        let expected: Vec<PlacedTile> = ["5E", "6E", "7E"].iter().map(|tile| PlacedTile { position: position(tile), chain: Some(HotelChain::Tower) }).collect();
+        assert_eq!(expected, changed);
+        assert_eq!(4, board.chain_size(HotelChain::Tower));
+        assert_eq!(None, board.chain_at(position("6G")));
+        assert!(!board.founds_chain(position("6G")));
+        assert_eq!(vec![HotelChain::Tower], board.chains_on_board());
+        let tower = &board.chains()[0];
+        assert_eq!((HotelChain::Tower, true, 4, false, Some(400)), (tower.chain, tower.founded, tower.size, tower.safe, tower.price));
+        assert!(board.chains()[1..].iter().all(|chain| !chain.founded && chain.price.is_none()));
+    }
+
+    #[test]
+    fn test_found_chain() {
+        let mut board = Board::default();
+        for tile in ["3C", "3E", "4D"] {
+            board.place_tile(position(tile)).unwrap();
+            assert!(!board.founds_chain(position(tile)));
+        }
+        assert_eq!(vec![PlacedTile { position: position("3D"), chain: None }], board.place_tile(position("3D")).unwrap());
+        assert!(board.founds_chain(position("3D")));
+        let founded = board.found_chain(position("3D"), HotelChain::Imperial);
+        assert_eq!(vec!["3C", "3D", "4D", "3E"], founded.iter().map(|tile| tile.position.to_string()).collect::<Vec<_>>());
+        assert_eq!(4, board.chain_size(HotelChain::Imperial));
+        assert!(!board.founds_chain(position("3D")));
+    }
+
+    #[test]
+    fn test_merge_chains() {
+        let mut board = Board::default();
+        for (tiles, chain) in [(["1A", "2A", "3A"], HotelChain::Tower), (["5A", "6A", "7A"], HotelChain::Luxor)] {
+            for tile in tiles {
+                board.place_tile(position(tile)).unwrap();
+            }
+            board.found_chain(position(tiles[0]), chain);
+        }
+        board.place_tile(position("4B")).unwrap();
+        assert_eq!(vec![PlacedTile { position: position("4A"), chain: None }], board.place_tile(position("4A")).unwrap());
+        assert_eq!(vec![HotelChain::Tower, HotelChain::Luxor], board.adjacent_chains(position("4A")));
+        let changed: Vec<String> = board.merge_chains(position("4A"), HotelChain::Tower).iter().map(|tile| tile.position.to_string()).collect();
+        assert_eq!(vec!["4A", "5A", "6A", "7A", "4B"], changed);
+        assert_eq!(8, board.chain_size(HotelChain::Tower));
+        assert_eq!(vec![HotelChain::Tower], board.chains_on_board());
+    }
+
+    #[test]
+    fn test_check_invariants() {
+        let mut board = Board::default();
+        for tile in ["1A", "2A", "4A", "5A", "3C"] {
+            board.place_tile(position(tile)).unwrap();
+        }
+        board.found_chain(position("1A"), HotelChain::Tower);
+        board.found_chain(position("4A"), HotelChain::Luxor);
+        assert_eq!(Ok(()), board.check_invariants());
+        // a tile that touches two chains waits for the merger
+        board.place_tile(position("3A")).unwrap();
+        assert_eq!(Ok(()), board.check_invariants());
+
+        let mut broken = board.clone();
+        broken.set_chain(position("3A"), HotelChain::Tower);
+        let error = BoardInvariantError::ChainsTouch { position: position("3A"), chain: HotelChain::Tower, other: HotelChain::Luxor };
+        assert_eq!(Err(error), broken.check_invariants());
+        let mut broken = board.clone();
+        broken.set_chain(position("3C"), HotelChain::Imperial);
+        assert_eq!(Err(BoardInvariantError::ChainTooSmall(HotelChain::Imperial)), broken.check_invariants());
+        let mut broken = board.clone();
+        broken.place_tile(position("8E")).unwrap();
+        broken.place_tile(position("8F")).unwrap();
+        broken.set_chain(position("8E"), HotelChain::Tower);
+        assert_eq!(Err(BoardInvariantError::NotJoined { position: position("8F"), chain: HotelChain::Tower }), broken.check_invariants());
+        broken.set_chain(position("8F"), HotelChain::Tower);
+        assert_eq!(Err(BoardInvariantError::ChainSplit(HotelChain::Tower)), broken.check_invariants());
+    }
+
+    #[test]
+    fn test_placement_rules() {
+        let mut board = Board::default();
+        for (tile, chain) in ["1A", "3A", "5A", "7A", "9A", "11A", "1C"].into_iter().zip(HotelChain::ALL) {
+            board.place_tile(position(tile)).unwrap();
+            board.set_chain(position(tile), chain);
+        }
+        board.place_tile(position("5E")).unwrap();
+        // a tile that does not touch other tiles can always be placed
+        assert!(board.check_placement(position("12I")).is_ok());
+        assert_eq!(Err(PlaceTileError::NoChainAvailable(position("5F"))), board.place_tile(position("5F")));
+        assert!(!board.is_occupied(position("5F")));
+
+        // two chains that grow to a safe size
+        let mut board = Board::default();
+        for (row, chain) in [('A', HotelChain::Tower), ('C', HotelChain::Luxor)] {
+            board.place_tile(Position::new(1, row).unwrap()).unwrap();
+            board.set_chain(Position::new(1, row).unwrap(), chain);
+            for column in 2..SAFE_CHAIN_SIZE as u8 {
+                board.place_tile(Position::new(column, row).unwrap()).unwrap();
+            }
+        }
+        board.place_tile(position("11A")).unwrap();
+        assert_eq!(SAFE_CHAIN_SIZE, board.chain_size(HotelChain::Tower));
+        assert!(board.is_safe(HotelChain::Tower));
+        assert_eq!(Some(700), board.chain_price(HotelChain::Tower));
+        // one safe chain can still take over a smaller chain
+        assert!(board.check_placement(position("1B")).is_ok());
+        board.place_tile(position("11C")).unwrap();
+        assert_eq!(Err(PlaceTileError::SafeChainsMerge(position("1B"))), board.place_tile(position("1B")));
+    }
+}
@@ -0,0 +1,169 @@
+use std::{collections::VecDeque, time::{SystemTime, UNIX_EPOCH}};
+
+use serde::Serialize;
+
+use crate::{events::GameChannel, request_data::{DisposalDecision, GameBuffer, StockPurchase}};
+
+use super::board::{HotelChain, Position};
+
+/// The number of entries that are kept in the log, older entries are removed
+pub const GAME_LOG_LEN: usize = 10_000;
+
+/// The maximum number of entries that are returned by [GameLog::since](struct.GameLog.html#method.since)
+pub const GAME_LOG_PAGE_LEN: usize = 500;
+
+/// What happened in a [LogEntry]().
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum LogAction {
+    Joined,
+    /// The player left the lobby or was kicked
+    Left,
+    /// The tiles in the hand of a player that left the running game went back into the bag
+    HandReturned { tiles: usize },
+    GameStarted,
+    TilePlaced { position: Position },
+    ChainFounded { chain: HotelChain },
+    StockPurchased { purchases: Vec<StockPurchase> },
+    /// The defunct chain was taken over by the survivor, the bonuses are not repeated here
+    MergerResolved { survivor: HotelChain, defunct: HotelChain },
+    MergerDecision { defunct: HotelChain, #[serde(flatten)] decision: DisposalDecision },
+    /// The turn passed to the acting player
+    TurnChanged,
+    GameEnded,
+}
+
+/// A single action in a game.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LogEntry {
+    /// Increases by one with every entry and is not reused when old entries are removed
+    pub index: usize,
+    /// Unix seconds of the time at which the action happened
+    pub timestamp: u64,
+    /// The public id of the player that acted, `None` for actions of the game itself
+    pub player_id: Option<u32>,
+    #[serde(flatten)]
+    pub action: LogAction,
+}
+
+/// A page of the [GameLog](), see [since](struct.GameLog.html#method.since).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct GameLogPage {
+    /// At most [GAME_LOG_PAGE_LEN]() entries, the oldest entry first
+    pub entries: Vec<LogEntry>,
+    /// The index to pass as `since` to fetch the next page, or the next entry once it is recorded
+    pub next_cursor: usize,
+    /// If more entries than the ones of this page are already recorded
+    pub has_more: bool,
+    /// If entries after the requested index were already removed and are missing in this page
+    pub gap: bool,
+}
+
+/// Everything that happened in a single game, can be viewed by all players.
+///
+/// Clients that lost their sse stream can fetch the entries they missed with [since](#method.since).
+#[derive(Debug)]
+pub struct GameLog {
+    entries: VecDeque<LogEntry>,
+    /// The index of the next entry
+    next_index: usize,
+    /// The channel of the game, the game master is warned on it when the log is almost full
+    channel: GameChannel,
+}
+
+impl GameLog {
+    /// Creates an empty log for the game of `channel`.
+    pub fn new(channel: GameChannel) -> Self {
+        Self { entries: VecDeque::new(), next_index: 0, channel }
+    }
+
+    /// Adds a new entry to the log, the oldest entry is removed when the log holds [GAME_LOG_LEN]() entries.
+    /// 
+    /// The game master is warned before that, see [GameChannel::watch_buffer](../../../events/struct.GameChannel.html#method.watch_buffer).
+    pub fn record(&mut self, player_id: Option<u32>, action: LogAction) {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or_default();
+        if self.entries.len() >= GAME_LOG_LEN {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(LogEntry { index: self.next_index, timestamp, player_id, action });
+        self.next_index += 1;
+        self.channel.watch_buffer(GameBuffer::GameLog, self.entries.len(), GAME_LOG_LEN);
+    }
+
+    /// Returns up to [GAME_LOG_PAGE_LEN]() entries with an index of at least `index`, the oldest entry first.
+    ///
+    /// Entries that were already removed are missing, [gap](struct.GameLogPage.html#structfield.gap) is set then.
+    pub fn since(&self, index: usize) -> GameLogPage {
+        let first = self.next_index - self.entries.len();
+        let start = index.clamp(first, self.next_index);
+        let entries: Vec<LogEntry> = self.entries.iter().skip(start - first).take(GAME_LOG_PAGE_LEN).cloned().collect();
+        let next_cursor = start + entries.len();
+        GameLogPage { entries, next_cursor, has_more: next_cursor < self.next_index, gap: index < first }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::{events::{GameChannel, BUFFER_WARNING_PERCENT}, game::game_instance::{board::HotelChain, GameCode}, request_data::{BufferWarning, GameBuffer, GameEvent}};
+
+    use super::{GameLog, LogAction, GAME_LOG_LEN, GAME_LOG_PAGE_LEN};
+
+    fn log() -> GameLog {
+        GameLog::new(GameChannel::new(GameCode::new(['A'; 8]).unwrap()))
+    }
+
+    #[test]
+    fn test_since() {
+        let mut log = log();
+        log.record(Some(1), LogAction::Joined);
+        log.record(Some(2), LogAction::Joined);
+        log.record(Some(1), LogAction::ChainFounded { chain: HotelChain::Imperial });
+        assert_eq!(3, log.since(0).entries.len());
+        let page = log.since(2);
+        assert_eq!(vec![(2, Some(1), LogAction::ChainFounded { chain: HotelChain::Imperial })], page.entries.into_iter().map(|entry| (entry.index, entry.player_id, entry.action)).collect::<Vec<_>>());
+        assert_eq!((3, false, false), (page.next_cursor, page.has_more, page.gap));
+        assert!(log.since(3).entries.is_empty());
+        let page = log.since(100);
+        assert!(page.entries.is_empty());
+        assert_eq!(3, page.next_cursor);
+    }
+
+    #[test]
+    fn test_since_pages() {
+        let mut log = log();
+        for _ in 0..GAME_LOG_PAGE_LEN + 10 {
+            log.record(None, LogAction::TurnChanged);
+        }
+        let page = log.since(0);
+        assert_eq!(GAME_LOG_PAGE_LEN, page.entries.len());
+        assert_eq!((GAME_LOG_PAGE_LEN, true, false), (page.next_cursor, page.has_more, page.gap));
+        let page = log.since(page.next_cursor);
+        assert_eq!(10, page.entries.len());
+        assert_eq!(GAME_LOG_PAGE_LEN, page.entries[0].index);
+        assert_eq!((GAME_LOG_PAGE_LEN + 10, false, false), (page.next_cursor, page.has_more, page.gap));
+    }
+
+    #[test]
+    fn test_log_is_bounded() {
+        let mut log = log();
+        let game_master = Uuid::new_v4();
+        log.channel.set_game_master(game_master);
+        let mut receiver = log.channel.subscribe();
+        for _ in 0..GAME_LOG_LEN + 5 {
+            log.record(None, LogAction::TurnChanged);
+        }
+        // the game master is warned once before the first entry is removed
+        let warnings: Vec<GameEvent> = std::iter::from_fn(|| receiver.try_recv().ok()).map(|event| event.event().clone()).collect();
+        let used = GAME_LOG_LEN * BUFFER_WARNING_PERCENT / 100;
+        assert!(matches!(warnings[..], [GameEvent::BufferWarning(BufferWarning { buffer: GameBuffer::GameLog, capacity: GAME_LOG_LEN, used: warned })] if warned == used));
+        let page = log.since(0);
+        assert!(page.gap);
+        assert_eq!(GAME_LOG_PAGE_LEN, page.entries.len());
+        assert_eq!(5, page.entries[0].index);
+        assert_eq!(GAME_LOG_PAGE_LEN + 5, page.next_cursor);
+        assert!(!log.since(5).gap);
+        assert_eq!(GAME_LOG_LEN + 4, log.since(GAME_LOG_LEN + 4).entries[0].index);
+    }
+}
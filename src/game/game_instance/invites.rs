@@ -0,0 +1,144 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// The largest number of invites that can be active in a single game at the same time
+pub const MAX_ACTIVE_INVITES: usize = 20;
+
+/// The longest time for which an invite can be valid
+pub const MAX_INVITE_DURATION: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// An invite that allows new players to join a lobby in which [require_invite](../struct.LobbySettings.html#method.require_invite) is set.
+///
+/// Invites are minted by the game master and shared as link `/lobby/<game_code>?invite=<id>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invite {
+    /// The token that has to be presented when joining
+    id: Uuid,
+    /// How often the invite can still be used
+    uses_left: u32,
+    /// The invite can no longer be used after this time
+    expires_at: Instant,
+}
+
+impl Invite {
+    /// Checks if the invite can still be used.
+    fn is_valid(&self, now: Instant) -> bool {
+        self.uses_left > 0 && now < self.expires_at
+    }
+
+    /// Returns the information about this invite that is send to the game master.
+    pub fn info(&self) -> InviteInfo {
+        InviteInfo {
+            id: self.id,
+            uses_left: self.uses_left,
+            expires_in_secs: self.expires_at.saturating_duration_since(Instant::now()).as_secs(),
+        }
+    }
+}
+
+/// An [Invite]() as it is send to the game master.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct InviteInfo {
+    pub id: Uuid,
+    pub uses_left: u32,
+    pub expires_in_secs: u64,
+}
+
+/// All invites of a game.
+#[derive(Debug, Default)]
+pub struct Invites {
+    invites: Vec<Invite>,
+}
+
+impl Invites {
+    /// Creates a new invite that can be used `uses` times during the next `valid_for`.
+    ///
+    /// # Returns
+    /// - `Some(&Invite)` the new invite.
+    /// - `None` when [MAX_ACTIVE_INVITES]() invites are already active.
+    pub fn create(&mut self, uses: u32, valid_for: Duration) -> Option<&Invite> {
+        let now = Instant::now();
+        self.invites.retain(|invite| invite.is_valid(now));
+        if self.invites.len() >= MAX_ACTIVE_INVITES {
+            return None;
+        }
+        self.invites.push(Invite {
+            id: Uuid::new_v4(),
+            uses_left: uses,
+            expires_at: now + valid_for.min(MAX_INVITE_DURATION),
+        });
+        self.invites.last()
+    }
+
+    /// Uses the invite with `id` once.
+    ///
+    /// # Returns
+    /// `false` when the invite does not exist, is expired or has no uses left.
+    pub fn consume(&mut self, id: Uuid) -> bool {
+        let now = Instant::now();
+        match self.invites.iter_mut().find(|invite| invite.id == id && invite.is_valid(now)) {
+            Some(invite) => {
+                invite.uses_left -= 1;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Revokes the invite with `id` so that it can no longer be used.
+    ///
+    /// # Returns
+    /// `false` when the invite does not exist.
+    pub fn revoke(&mut self, id: Uuid) -> bool {
+        let len = self.invites.len();
+        self.invites.retain(|invite| invite.id != id);
+        self.invites.len() != len
+    }
+
+    /// Returns all invites that can still be used.
+    pub fn active(&self) -> Vec<&Invite> {
+        let now = Instant::now();
+        self.invites.iter().filter(|invite| invite.is_valid(now)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use uuid::Uuid;
+
+    use super::{Invites, MAX_ACTIVE_INVITES};
+
+    #[test]
+    fn test_invite_uses_and_expiry() {
+        let mut invites = Invites::default();
+        let twice = invites.create(2, Duration::from_secs(60)).unwrap().info().id;
+        assert!(invites.consume(twice));
+        assert_eq!(1, invites.active()[0].info().uses_left);
+        assert!(invites.consume(twice));
+        assert!(!invites.consume(twice));
+        assert!(!invites.consume(Uuid::new_v4()));
+        let expired = invites.create(1, Duration::ZERO).unwrap().info().id;
+        assert!(!invites.consume(expired));
+        assert!(invites.active().is_empty());
+        let revoked = invites.create(1, Duration::from_secs(60)).unwrap().info().id;
+        assert!(invites.revoke(revoked));
+        assert!(!invites.revoke(revoked));
+        assert!(!invites.consume(revoked));
+    }
+
+    #[test]
+    fn test_active_invite_limit() {
+        let mut invites = Invites::default();
+        for _ in 0..MAX_ACTIVE_INVITES {
+            assert!(invites.create(1, Duration::from_secs(60)).is_some());
+        }
+        assert!(invites.create(1, Duration::from_secs(60)).is_none());
+        let id = invites.active()[0].info().id;
+        assert!(invites.revoke(id));
+        assert!(invites.create(1, Duration::from_secs(60)).is_some());
+    }
+}
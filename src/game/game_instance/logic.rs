@@ -1 +1,1061 @@
+use rocket::log::private::info;
+use uuid::Uuid;
 
+use crate::{events::EventBatch, game::base_game::{Tile, HAND_SIZE, MAX_SHARES_PER_TURN}, request_data::{DisposalDecision, FieldError, GameAnnotation, GameEvent, MergerDecision, MergerResolved, Payout, PendingDisposal, Portfolio, Standing, StockPurchase, StockPurchased, TilePlacement, TilesDrawn, TurnEnded}, utils::strip_markup};
+
+use super::{board::{HotelChain, PlacedTile, Position}, game_log::LogAction, turns::{TurnError, TurnManager, TurnPhase}, AnnotateError, BuyStockError, ChooseSurvivorError, EndGameError, FoundChainError, GameInstance, GameState, MergerDecisionError, PlayTileError, MAX_NOTES_LENGTH, MAX_NOTE_TITLE_LENGTH};
+
+/// The majority bonus of a defunct chain is this many times the price of one share
+const MAJORITY_BONUS_FACTOR: u32 = 10;
+
+/// The minority bonus of a defunct chain is this many times the price of one share
+const MINORITY_BONUS_FACTOR: u32 = 5;
+
+impl GameInstance {
+    /// Places `tile` from the hand of the player with `uuid` on the board.
+    /// 
+    /// When the tile founds a new chain the player has to choose the chain with [found_chain](#method.found_chain).
+    /// When the tile merges chains the largest chain takes over the others, see [merge](#method.merge). If several
+    /// chains have the largest size the player has to choose the survivor with [choose_survivor](#method.choose_survivor).
+    /// After a merger the shareholders of the defunct chains decide what happens to their shares with [merger_decision](#method.merger_decision).
+    /// Afterwards the player can buy shares with [buy_stock](#method.buy_stock), while no chain is on the board the
+    /// turn passes to the next player right away.
+    /// 
+    /// # Returns
+    /// - A batch containing the event `TilePlaced` with the position of the tile, followed by `MergerResolved` for each
+    ///   defunct chain and the events of [end_turn](#method.end_turn) when the turn ended.
+    /// - The tiles that were placed or changed their chain and the chains the player can choose from, see [TilePlacement](../../request_data/struct.TilePlacement.html).
+    /// - `Err(PlayTileError)` when the tile can not be placed, the game is not changed in this case.
+    pub fn place_tile(&mut self, uuid: Uuid, tile: Tile) -> Result<(EventBatch, TilePlacement), PlayTileError> {
+        let index = self.check_turn(uuid)?;
+        match self.turns.phase() {
+            TurnPhase::PlaceTile => (),
+            TurnPhase::FoundChain(_) | TurnPhase::ChooseSurvivor(_) => return Err(PlayTileError::ChainChoicePending),
+            TurnPhase::MergerDisposal | TurnPhase::BuyStock => return Err(PlayTileError::AlreadyPlaced),
+        }
+        if !self.players[index].hand().contains(&tile) {
+            return Err(PlayTileError::NotInHand);
+        }
+        let position = tile.position();
+        let tiles = self.board.place_tile(position)?;
+        self.players[index].remove_tile(tile);
+        self.players[index].stats_mut().tiles_placed += 1;
+        self.debug_check_invariants();
+        self.game_log.record(Some(self.players[index].id()), LogAction::TilePlaced { position });
+        let mut events = EventBatch::new(&self.channel);
+        events.push(GameEvent::TilePlaced(position));
+        let mut placement = TilePlacement { tiles, choose_chain: None, choose_survivor: None, buy_stock: false };
+        if self.board.founds_chain(position) {
+            self.turns.set_phase(TurnPhase::FoundChain(position));
+            let chains_on_board = self.board.chains_on_board();
+            placement.choose_chain = Some(HotelChain::ALL.into_iter().filter(|chain| !chains_on_board.contains(chain)).collect());
+            return Ok((events, placement));
+        }
+        if self.board.adjacent_chains(position).len() >= 2 {
+            let largest = self.largest_adjacent_chains(position);
+            if largest.len() > 1 {
+                self.turns.set_phase(TurnPhase::ChooseSurvivor(position));
+                placement.choose_survivor = Some(largest);
+                return Ok((events, placement));
+            }
+            let (merger, tiles) = self.merge(position, largest[0]);
+            events.append(merger);
+            placement.tiles = tiles;
+            placement.buy_stock = self.turns.phase() == TurnPhase::BuyStock;
+            return Ok((events, placement));
+        }
+        if self.board.chains_on_board().is_empty() {
+            events.append(self.end_turn());
+            return Ok((events, placement));
+        }
+        self.turns.set_phase(TurnPhase::BuyStock);
+        placement.buy_stock = true;
+        Ok((events, placement))
+    }
+
+    /// Chooses which of the largest chains survives the merger that the player with `uuid` has started with their last tile,
+    /// then the merger is resolved like in [place_tile](#method.place_tile).
+    /// 
+    /// # Returns
+    /// - A batch containing the event `MergerResolved` for each defunct chain, followed by `AwaitingDisposal` when shareholders have to decide.
+    /// - The tiles that changed their chain.
+    /// - `Err(ChooseSurvivorError)` when the chain can not survive, the game is not changed in this case.
+    pub fn choose_survivor(&mut self, uuid: Uuid, survivor: HotelChain) -> Result<(EventBatch, Vec<PlacedTile>), ChooseSurvivorError> {
+        self.check_turn(uuid)?;
+        let position = match self.turns.phase() {
+            TurnPhase::ChooseSurvivor(position) => position,
+            _ => return Err(ChooseSurvivorError::NothingToChoose),
+        };
+        if !self.largest_adjacent_chains(position).contains(&survivor) {
+            return Err(ChooseSurvivorError::NotLargest(survivor));
+        }
+        Ok(self.merge(position, survivor))
+    }
+
+    /// Decides what the player with `uuid` does with their shares of a defunct chain after a merger, see [PendingDisposal](../../request_data/struct.PendingDisposal.html).
+    /// 
+    /// Sold shares are paid with the price of the defunct chain before the merger, traded shares are exchanged two for one
+    /// against shares of the survivor and kept shares stay with the player. When all shareholders have decided
+    /// the player whose turn it is can buy shares.
+    /// 
+    /// # Returns
+    /// - A batch containing the event `MergerDecision` followed by `AwaitingDisposal` for the next shareholder.
+    /// - The money and the shares of the player after the decision.
+    /// - `Err(MergerDecisionError)` when the decision is not valid, the game is not changed in this case.
+    pub fn merger_decision(&mut self, uuid: Uuid, decision: &DisposalDecision) -> Result<(EventBatch, Portfolio), MergerDecisionError> {
+        let pending = *self.disposals.front().ok_or(MergerDecisionError::NothingToDecide)?;
+        let index = match self.players.iter().position(|player| player.uuid() == uuid && player.id() == pending.player_id) {
+            Some(index) => index,
+            None => return Err(MergerDecisionError::NotYourDecision(Box::new(self.turn_status().unwrap_or_default()))),
+        };
+        let held = self.players[index].shares(pending.defunct);
+        // Each count is checked on its own first, so the sum can not overflow
+        if [decision.sell, decision.trade, decision.keep].into_iter().any(|count| count > held) || decision.sell + decision.trade + decision.keep != held {
+            return Err(MergerDecisionError::SharesMismatch { held });
+        }
+        if !decision.trade.is_multiple_of(2) {
+            return Err(MergerDecisionError::OddTrade);
+        }
+        if self.bank_shares(pending.survivor) < decision.trade / 2 {
+            return Err(MergerDecisionError::NotEnoughShares(pending.survivor));
+        }
+        self.disposals.pop_front();
+        self.return_shares(index, pending.defunct, decision.sell + decision.trade);
+        self.give_shares(index, pending.survivor, decision.trade / 2);
+        let player = &mut self.players[index];
+        player.set_money(player.money() + decision.sell * pending.price);
+        player.stats_mut().stocks_sold += decision.sell;
+        player.stats_mut().stocks_traded += decision.trade;
+        let portfolio = Portfolio { money: player.money(), shares: player.portfolio().clone() };
+        self.debug_check_invariants();
+        self.game_log.record(Some(pending.player_id), LogAction::MergerDecision { defunct: pending.defunct, decision: *decision });
+        let mut events = EventBatch::new(&self.channel);
+        let data = MergerDecision { player_id: pending.player_id, defunct: pending.defunct, decision: *decision, remaining: self.disposals.len() };
+        events.push(GameEvent::MergerDecision(data));
+        events.append(self.next_disposal());
+        Ok((events, portfolio))
+    }
+
+    /// Asks the next shareholder to decide about their shares, when all have decided the player whose turn it is can buy shares.
+    /// 
+    /// # Returns
+    /// A batch containing the event `AwaitingDisposal` when a shareholder still has to decide.
+    fn next_disposal(&mut self) -> EventBatch {
+        let mut events = EventBatch::new(&self.channel);
+        match self.disposals.front() {
+            Some(pending) => {
+                self.turns.set_phase(TurnPhase::MergerDisposal);
+                events.push(GameEvent::AwaitingDisposal(*pending));
+            },
+            None => self.turns.set_phase(TurnPhase::BuyStock),
+        }
+        events
+    }
+
+    /// Takes the player with `uuid` and the public `player_id` out of the turn order and out of the pending merger decisions.
+    /// 
+    /// When it was their turn the merger they started ends without the outstanding decisions, the other shareholders
+    /// keep their shares of the defunct chains, and the next player starts their turn from the beginning. When they
+    /// had to decide next the following shareholder is asked, or the current player can buy shares.
+    /// 
+    /// # Returns
+    /// A batch containing the event `TurnChanged` when it was the turn of the player, or `AwaitingDisposal` for the next shareholder.
+    pub(super) fn leave_turns(&mut self, uuid: Uuid, player_id: u32) -> EventBatch {
+        let mut events = EventBatch::new(&self.channel);
+        let deciding = self.disposals.front().map(|pending| pending.player_id);
+        if !self.turns.remove(uuid) {
+            self.disposals.retain(|pending| pending.player_id != player_id);
+            if self.turns.phase() == TurnPhase::MergerDisposal && deciding == Some(player_id) {
+                events.append(self.next_disposal());
+            }
+            return events;
+        }
+        self.disposals.clear();
+        self.turns.set_phase(TurnPhase::PlaceTile);
+        let next = self.turns.current().and_then(|uuid| self.players.iter().find(|player| player.uuid() == uuid));
+        if let Some(player_id) = next.map(|player| player.id()) {
+            events.push(GameEvent::TurnChanged(player_id));
+            self.game_log.record(Some(player_id), LogAction::TurnChanged);
+        }
+        events
+    }
+
+    /// Returns the chains next to `position` that have the largest size.
+    fn largest_adjacent_chains(&self, position: Position) -> Vec<HotelChain> {
+        let chains = self.board.adjacent_chains(position);
+        let largest = chains.iter().map(|chain| self.board.chain_size(*chain)).max().unwrap_or_default();
+        chains.into_iter().filter(|chain| self.board.chain_size(*chain) == largest).collect()
+    }
+
+    /// Merges the chains next to `position` into `survivor`, the merger was started by the player whose turn it is.
+    /// 
+    /// The shareholders of each defunct chain receive the bonuses for the price of the chain before the merger,
+    /// see [shareholder_bonuses](fn.shareholder_bonuses.html). The defunct chains are taken over from the largest to the smallest.
+    /// Afterwards the shareholders decide what happens to their shares chain by chain, in turn order starting with the
+    /// merging player, see [merger_decision](#method.merger_decision).
+    /// 
+    /// # Returns
+    /// - A batch containing the event `MergerResolved` for each defunct chain, followed by `AwaitingDisposal` when shareholders have to decide.
+    /// - The tiles that changed their chain.
+    fn merge(&mut self, position: Position, survivor: HotelChain) -> (EventBatch, Vec<PlacedTile>) {
+        let mut defunct: Vec<HotelChain> = self.board.adjacent_chains(position).into_iter().filter(|chain| *chain != survivor).collect();
+        // sort_by_key is stable, chains of the same size keep their order
+        defunct.sort_by_key(|chain| std::cmp::Reverse(self.board.chain_size(*chain)));
+        let mut events = EventBatch::new(&self.channel);
+        for chain in defunct {
+            let size = self.board.chain_size(chain);
+            let price = self.board.chain_price(chain).unwrap_or_default();
+            let holdings: Vec<(u32, u32)> = self.players.iter().map(|player| (player.id(), player.shares(chain))).collect();
+            let payouts = shareholder_bonuses(&holdings, price);
+            for payout in &payouts {
+                if let Some(player) = self.players.iter_mut().find(|player| player.id() == payout.player_id) {
+                    player.set_money(player.money() + payout.amount);
+                    player.stats_mut().bonuses += payout.amount;
+                }
+            }
+            info!("Game {}: {} took over {} ({} tiles)", self.game_code, survivor, chain, size);
+            let merging = self.current_player().map(|player| player.id());
+            self.game_log.record(merging, LogAction::MergerResolved { survivor, defunct: chain });
+            let resolved = MergerResolved { survivor, defunct: chain, defunct_size: size, payouts };
+            events.push(GameEvent::MergerResolved(resolved));
+            for uuid in self.turns.order_from_current() {
+                let player = match self.players.iter().find(|player| player.uuid() == uuid) {
+                    Some(player) => player,
+                    None => continue,
+                };
+                let shares = player.shares(chain);
+                if shares > 0 {
+                    self.disposals.push_back(PendingDisposal { player_id: player.id(), defunct: chain, survivor, shares, price });
+                }
+            }
+        }
+        events.append(self.next_disposal());
+        let tiles = self.board.merge_chains(position, survivor);
+        self.debug_check_invariants();
+        (events, tiles)
+    }
+
+    /// Founds `chain` with the tile that the player with `uuid` has placed before, the player receives one free share
+    /// of the chain when the bank still holds one. The player can then buy shares with [buy_stock](#method.buy_stock).
+    /// 
+    /// # Returns
+    /// - A batch containing the event `ChainFounded` with the name of the chain.
+    /// - The tiles that joined the chain.
+    /// - `Err(FoundChainError)` when the chain can not be founded, the game is not changed in this case.
+    pub fn found_chain(&mut self, uuid: Uuid, chain: HotelChain) -> Result<(EventBatch, Vec<PlacedTile>), FoundChainError> {
+        let index = self.check_turn(uuid)?;
+        let position = match self.turns.phase() {
+            TurnPhase::FoundChain(position) => position,
+            _ => return Err(FoundChainError::NothingToFound),
+        };
+        if self.board.chain_size(chain) > 0 {
+            return Err(FoundChainError::ChainOnBoard(chain));
+        }
+        self.turns.set_phase(TurnPhase::BuyStock);
+        let tiles = self.board.found_chain(position, chain);
+        self.give_shares(index, chain, 1);
+        self.players[index].stats_mut().chains_founded += 1;
+        self.debug_check_invariants();
+        self.game_log.record(Some(self.players[index].id()), LogAction::ChainFounded { chain });
+        let mut events = EventBatch::new(&self.channel);
+        events.push(GameEvent::ChainFounded(chain));
+        Ok((events, tiles))
+    }
+
+    /// Buys shares for the player with `uuid` at the current prices and passes the turn to the next player.
+    /// 
+    /// At most [MAX_SHARES_PER_TURN](../base_game/constant.MAX_SHARES_PER_TURN.html) shares can be bought, `purchases`
+    /// can list the same chain several times and can be empty when the player does not want to buy shares.
+    /// 
+    /// # Returns
+    /// - A batch containing the event `StockPurchased` with the bought shares when shares were bought, followed by the events of [end_turn](#method.end_turn).
+    /// - The shares and the money of the player after the purchase.
+    /// - `Err(BuyStockError)` when the shares can not be bought, the game is not changed in this case.
+    pub fn buy_stock(&mut self, uuid: Uuid, purchases: &[StockPurchase]) -> Result<(EventBatch, Portfolio), BuyStockError> {
+        let index = self.check_turn(uuid)?;
+        if self.turns.phase() != TurnPhase::BuyStock {
+            return Err(BuyStockError::WrongPhase);
+        }
+        let total = purchases.iter().try_fold(0_u32, |total, purchase| total.checked_add(purchase.quantity));
+        if total.is_none_or(|total| total > MAX_SHARES_PER_TURN) {
+            return Err(BuyStockError::TooManyShares);
+        }
+        let mut cost = 0;
+        for chain in HotelChain::ALL {
+            let quantity: u32 = purchases.iter().filter(|purchase| purchase.chain == chain).map(|purchase| purchase.quantity).sum();
+            if quantity == 0 {
+                continue;
+            }
+            let price = self.board.chain_price(chain).ok_or(BuyStockError::ChainNotFounded(chain))?;
+            if self.bank_shares(chain) < quantity {
+                return Err(BuyStockError::NotEnoughShares(chain));
+            }
+            cost += price * quantity;
+        }
+        let player = &mut self.players[index];
+        if cost > player.money() {
+            return Err(BuyStockError::NotEnoughMoney { cost, shortfall: cost - player.money() });
+        }
+        player.set_money(player.money() - cost);
+        for purchase in purchases.iter().filter(|purchase| purchase.quantity > 0) {
+            self.give_shares(index, purchase.chain, purchase.quantity);
+        }
+        // the total was checked above, so the sum can not overflow
+        self.players[index].stats_mut().stocks_bought += purchases.iter().map(|purchase| purchase.quantity).sum::<u32>();
+        self.debug_check_invariants();
+        let player = &self.players[index];
+        let portfolio = Portfolio { money: player.money(), shares: player.portfolio().clone() };
+        let mut events = EventBatch::new(&self.channel);
+        let purchases: Vec<StockPurchase> = purchases.iter().filter(|purchase| purchase.quantity > 0).cloned().collect();
+        if !purchases.is_empty() {
+            self.game_log.record(Some(player.id()), LogAction::StockPurchased { purchases: purchases.clone() });
+            let purchased = StockPurchased { player_id: player.id(), purchases };
+            events.push(GameEvent::StockPurchased(purchased));
+        }
+        events.append(self.end_turn());
+        Ok((events, portfolio))
+    }
+
+    /// Ends the game in the turn of the player with `uuid`, see [Board::can_end_game](board/struct.Board.html#method.can_end_game).
+    /// 
+    /// The shareholders of every chain on the board receive the bonuses (see [shareholder_bonuses](fn.shareholder_bonuses.html))
+    /// and sell all their shares at the current prices, then the players are ranked by their money.
+    /// 
+    /// # Returns
+    /// - A batch containing the event `GameEnded` with the standings.
+    /// - The final standings, see [Standing](../../request_data/struct.Standing.html).
+    /// - `Err(EndGameError)` when the game can not be ended, the game is not changed in this case.
+    pub fn end_game(&mut self, uuid: Uuid) -> Result<(EventBatch, Vec<Standing>), EndGameError> {
+        let index = self.check_turn(uuid)?;
+        if !matches!(self.turns.phase(), TurnPhase::PlaceTile | TurnPhase::BuyStock) {
+            return Err(EndGameError::DecisionPending);
+        }
+        if !self.board.can_end_game() {
+            return Err(EndGameError::ConditionsNotMet);
+        }
+        for chain in self.board.chains_on_board() {
+            let price = self.board.chain_price(chain).unwrap_or_default();
+            let holdings: Vec<(u32, u32)> = self.players.iter().map(|player| (player.id(), player.shares(chain))).collect();
+            for payout in shareholder_bonuses(&holdings, price) {
+                if let Some(player) = self.players.iter_mut().find(|player| player.id() == payout.player_id) {
+                    player.set_money(player.money() + payout.amount);
+                    player.stats_mut().bonuses += payout.amount;
+                }
+            }
+            for index in 0..self.players.len() {
+                let shares = self.players[index].shares(chain);
+                self.return_shares(index, chain, shares);
+                let player = &mut self.players[index];
+                player.set_money(player.money() + shares * price);
+            }
+        }
+        let standings = rank(self.players.iter().map(|player| Standing { place: 0, player_id: player.id(), name: player.username(), money: player.money() }).collect());
+        info!("Game {} has ended", self.game_code);
+        self.game_log.record(Some(self.players[index].id()), LogAction::GameEnded);
+        self.game_state = GameState::Finished(standings.clone());
+        self.turns = TurnManager::default();
+        Ok((self.game_ended_events(None), standings))
+    }
+
+    /// Sets the notes of the finished game, notes that where set before are replaced.
+    /// 
+    /// Markup is removed from the title and the notes and surrounding whitespace from the title, the title has to
+    /// contain 1 to [MAX_NOTE_TITLE_LENGTH](constant.MAX_NOTE_TITLE_LENGTH.html) characters afterwards and the notes
+    /// at most [MAX_NOTES_LENGTH](constant.MAX_NOTES_LENGTH.html) characters.
+    /// 
+    /// # Returns
+    /// - A batch containing the event `GameAnnotated` with the new notes.
+    /// - The new notes, the player with `uuid` is recorded as editor.
+    /// - `Err(AnnotateError)` when the user is not the game master, the game is not finished or the notes are invalid,
+    ///   the notes are not changed in this case.
+    pub fn annotate(&mut self, uuid: Uuid, title: &str, notes: &str) -> Result<(EventBatch, GameAnnotation), AnnotateError> {
+        let editor = match self.players.iter().find(|player| player.uuid() == uuid && player.is_game_master()) {
+            Some(player) => player.id(),
+            None => return Err(AnnotateError::NotGameMaster),
+        };
+        if !matches!(self.game_state, GameState::Finished(_)) {
+            return Err(AnnotateError::NotFinished);
+        }
+        let title = strip_markup(title).trim().to_string();
+        let notes = strip_markup(notes);
+        let mut invalid = Vec::new();
+        if !(1..=MAX_NOTE_TITLE_LENGTH).contains(&title.chars().count()) {
+            invalid.push(FieldError::new("title", "invalid_length", 1..=MAX_NOTE_TITLE_LENGTH));
+        }
+        if notes.chars().count() > MAX_NOTES_LENGTH {
+            invalid.push(FieldError::new("notes", "invalid_length", 0..=MAX_NOTES_LENGTH));
+        }
+        if !invalid.is_empty() {
+            return Err(AnnotateError::InvalidFields(invalid));
+        }
+        let annotation = GameAnnotation { title, notes, editor };
+        self.annotation = Some(annotation.clone());
+        let mut events = EventBatch::new(&self.channel);
+        events.push(GameEvent::GameAnnotated(annotation.clone()));
+        Ok((events, annotation))
+    }
+
+    /// Checks that it is the turn of the player with `uuid`.
+    /// 
+    /// # Returns
+    /// - The index of the player in `players`.
+    /// - `Err(TurnError)` naming whose turn it actually is.
+    fn check_turn(&self, uuid: Uuid) -> Result<usize, TurnError> {
+        if matches!(self.game_state, GameState::Finished(_)) {
+            return Err(TurnError::Finished);
+        }
+        let status = self.turn_status().ok_or(TurnError::NotStarted)?;
+        if !self.turns.is_turn_of(uuid) {
+            return Err(TurnError::NotYourTurn(Box::new(status)));
+        }
+        self.players.iter().position(|player| player.uuid() == uuid).ok_or(TurnError::NotStarted)
+    }
+
+    /// Ends the turn of the current player: they draw tiles until they hold [HAND_SIZE](../base_game/constant.HAND_SIZE.html)
+    /// again, see [refill_hand](#method.refill_hand), and their net worth is added to their stats, then the turn passes
+    /// to the next player in the order of the [TurnManager](turns/struct.TurnManager.html).
+    /// 
+    /// # Returns
+    /// A batch containing the event `TilesDrawn` for the current player, `TurnEnded` for all players and `TurnChanged` with the id of the next player.
+    fn end_turn(&mut self) -> EventBatch {
+        let mut events = EventBatch::new(&self.channel);
+        let current = self.turns.current().and_then(|uuid| self.players.iter().position(|player| player.uuid() == uuid));
+        if let Some(index) = current {
+            let drawn = self.refill_hand(index);
+            let net_worth = self.net_worth(index);
+            self.players[index].stats_mut().net_worth.push(net_worth);
+            let player = &self.players[index];
+            let ended = TurnEnded { player_id: player.id(), discarded: drawn.discarded.clone() };
+            events.push_to(Some(player.uuid()), GameEvent::TilesDrawn(drawn));
+            events.push(GameEvent::TurnEnded(ended));
+        }
+        let next = self.turns.advance().and_then(|uuid| self.players.iter().find(|player| player.uuid() == uuid));
+        if let Some(player_id) = next.map(|player| player.id()) {
+            events.push(GameEvent::TurnChanged(player_id));
+            self.game_log.record(Some(player_id), LogAction::TurnChanged);
+        }
+        events
+    }
+
+    /// Returns the money of the player at `index` plus the value of their shares at the current prices.
+    fn net_worth(&self, index: usize) -> u32 {
+        let player = &self.players[index];
+        let shares: u32 = player.portfolio().iter().map(|(chain, count)| count * self.board.chain_price(*chain).unwrap_or_default()).sum();
+        player.money() + shares
+    }
+
+    /// Draws tiles for the player at `index` until they hold [HAND_SIZE](../base_game/constant.HAND_SIZE.html) tiles.
+    /// 
+    /// Tiles that can never be placed (see [Board::is_dead](board/struct.Board.html#method.is_dead)) are discarded and
+    /// replaced, this includes dead tiles that the player held before. When the bag runs empty the player holds less tiles.
+    fn refill_hand(&mut self, index: usize) -> TilesDrawn {
+        let mut drawn = TilesDrawn { drawn: Vec::new(), discarded: Vec::new() };
+        let (player, board) = (&mut self.players[index], &self.board);
+        let bag = match self.tile_bag.as_mut() {
+            Some(bag) => bag,
+            None => return drawn,
+        };
+        loop {
+            while player.hand().len() < HAND_SIZE {
+                match bag.draw() {
+                    Some(tile) => {
+                        player.add_tile(tile);
+                        drawn.drawn.push(tile);
+                    },
+                    None => break,
+                }
+            }
+            let dead: Vec<Tile> = player.hand().iter().copied().filter(|tile| board.is_dead(tile.position())).collect();
+            if dead.is_empty() {
+                break;
+            }
+            for tile in dead {
+                player.remove_tile(tile);
+                bag.discard(tile);
+                drawn.discarded.push(tile);
+            }
+        }
+        self.debug_check_invariants();
+        drawn
+    }
+}
+
+/// Orders `standings` by money and assigns the places, players with the same money share a place and the following place is skipped.
+/// Players with the same money keep their order.
+fn rank(mut standings: Vec<Standing>) -> Vec<Standing> {
+    standings.sort_by_key(|standing| std::cmp::Reverse(standing.money));
+    for index in 0..standings.len() {
+        standings[index].place = match index {
+            0 => 1,
+            _ if standings[index].money == standings[index - 1].money => standings[index - 1].place,
+            _ => index + 1,
+        };
+    }
+    standings
+}
+
+/// Returns the bonuses that the shareholders of a defunct chain receive when the price of one share is `price`.
+/// 
+/// The largest shareholder receives the majority bonus and the second largest the minority bonus. Players that hold the
+/// same number of shares split the bonuses, rounded up to the next 100:
+/// - when several players are the largest shareholders they split both bonuses and no minority bonus is paid
+/// - when several players are the second largest shareholders they split the minority bonus
+/// - a sole shareholder receives both bonuses
+/// 
+/// # Params
+/// `holdings` the public id and the number of shares of each player, players without shares are ignored
+/// 
+/// # Returns
+/// The payouts in the order of `holdings`, empty when no player holds shares.
+pub fn shareholder_bonuses(holdings: &[(u32, u32)], price: u32) -> Vec<Payout> {
+    let majority = price * MAJORITY_BONUS_FACTOR;
+    let minority = price * MINORITY_BONUS_FACTOR;
+    let split = |bonus: u32, players: usize| bonus.div_ceil(players as u32 * 100) * 100;
+    let most = holdings.iter().map(|(_, shares)| *shares).max().unwrap_or_default();
+    if most == 0 {
+        return Vec::new();
+    }
+    let largest = holdings.iter().filter(|(_, shares)| *shares == most).count();
+    let second = holdings.iter().map(|(_, shares)| *shares).filter(|shares| *shares > 0 && *shares < most).max();
+    let second_count = holdings.iter().filter(|(_, shares)| Some(*shares) == second).count();
+    holdings.iter().filter_map(|(player_id, shares)| {
+        let amount = if *shares == most {
+            match (largest, second) {
+                (1, Some(_)) => majority,
+                (1, None) => majority + minority,
+                _ => split(majority + minority, largest),
+            }
+        } else if largest == 1 && Some(*shares) == second {
+            split(minority, second_count)
+        } else {
+            return None;
+        };
+        Some(Payout { player_id: *player_id, amount })
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::{authentication::Urid, game::{base_game::{Tile, HAND_SIZE, SHARES_PER_CHAIN}, game_instance::{board::{BoardInvariantError, HotelChain, PlaceTileError, Position}, game_log::LogAction, turns::{TurnError, TurnManager, TurnPhase}, AnnotateError, BuyStockError, ChooseSurvivorError, EndGameError, FoundChainError, GameCode, GameInstance, GameState, InvariantError, MergerDecisionError, PlayTileError, MAX_NOTES_LENGTH, MAX_NOTE_TITLE_LENGTH}, User}, request_data::{DisposalDecision, GameAnnotation, MergerDecision, MergerResolved, Payout, PendingDisposal, Standing, StockPurchase, TilesDrawn, TurnEnded, TurnStatus}};
+
+    use super::{rank, shareholder_bonuses};
+
+    /// Returns a started game with two players and their uuids in seat order.
+    fn started_game() -> (GameInstance, Vec<Uuid>) {
+        let game_code = GameCode::new(['A'; 8]).unwrap();
+        let mut game = GameInstance::with_seed(game_code, 5);
+        let uuids: Vec<Uuid> = (0..2).map(|_| Uuid::new_v4()).collect();
+        for (i, uuid) in uuids.iter().enumerate() {
+            assert!(game.add_user(User::new(format!("player {}", i), *uuid, Urid::from_uuid(Uuid::new_v4()), game_code)));
+            game.user_connected(*uuid);
+        }
+        let (_events, _removed) = game.start().unwrap();
+        // the order is drawn at random, the tests expect the players to take their turns in seat order
+        game.turns = TurnManager::new(uuids.clone());
+        (game, uuids)
+    }
+
+    fn not_your_turn(game: &GameInstance) -> TurnError {
+        TurnError::NotYourTurn(Box::new(game.turn_status().unwrap()))
+    }
+
+    fn position(input: &str) -> Position {
+        Position::parse(input).unwrap()
+    }
+
+    fn purchase(chain: HotelChain, quantity: u32) -> StockPurchase {
+        StockPurchase { chain, quantity }
+    }
+
+    /// Takes `tile` out of the bag or the hand that holds it, so that the test can put it somewhere else without
+    /// breaking the invariants of the game.
+    fn take_tile(game: &mut GameInstance, tile: Tile) {
+        game.tile_bag.as_mut().unwrap().take(tile);
+        for player in &mut game.players {
+            player.remove_tile(tile);
+        }
+    }
+
+    /// Moves `tile` into the hand of the player at `index`, the hand can hold one tile too many until a tile is placed.
+    fn give_tile(game: &mut GameInstance, index: usize, tile: Tile) {
+        take_tile(game, tile);
+        game.players[index].add_tile(tile);
+    }
+
+    /// Moves the tile for `input` onto the board.
+    fn place(game: &mut GameInstance, input: &str) {
+        take_tile(game, Tile::from(position(input)));
+        game.board.place_tile(position(input)).unwrap();
+    }
+
+    /// Checks that the shares of each chain in the overview of every player add up to 25.
+    fn assert_shares_add_up(game: &GameInstance, uuids: &[Uuid]) {
+        for uuid in uuids {
+            let stocks = game.stocks(*uuid).unwrap();
+            for chain in HotelChain::ALL {
+                let held: u32 = stocks.players.iter().map(|player| player.shares.get(&chain).copied().unwrap_or_default()).sum();
+                assert_eq!(SHARES_PER_CHAIN, held + stocks.bank[&chain], "{}", chain);
+            }
+        }
+    }
+
+    #[test]
+    fn test_found_chain() {
+        let (mut game, uuids) = started_game();
+        place(&mut game, "3C");
+        give_tile(&mut game, 0, Tile::new(3, 'D').unwrap());
+
+        let (_events, placement) = game.place_tile(uuids[0], Tile::new(3, 'D').unwrap()).unwrap();
+        assert_eq!(Some(HotelChain::ALL.to_vec()), placement.choose_chain);
+        assert!(!placement.buy_stock);
+        // the turn does not end before the chain was chosen
+        assert_eq!(uuids[0], game.current_player().unwrap().uuid());
+        let tile = game.players[0].hand()[0];
+        assert_eq!(Err(PlayTileError::ChainChoicePending), game.place_tile(uuids[0], tile).map(|_| ()));
+        assert_eq!(Err(FoundChainError::Turn(not_your_turn(&game))), game.found_chain(uuids[1], HotelChain::Tower).map(|_| ()));
+
+        let (_events, tiles) = game.found_chain(uuids[0], HotelChain::Tower).unwrap();
+        assert_eq!(vec![position("3C"), position("3D")], tiles.iter().map(|tile| tile.position).collect::<Vec<_>>());
+        assert_eq!(1, game.players[0].shares(HotelChain::Tower));
+        assert_eq!(24, game.bank_shares(HotelChain::Tower));
+        assert_eq!(Err(FoundChainError::NothingToFound), game.found_chain(uuids[0], HotelChain::Luxor).map(|_| ()));
+        assert_shares_add_up(&game, &uuids);
+        let (_events, _portfolio) = game.buy_stock(uuids[0], &[]).unwrap();
+        assert_eq!(uuids[1], game.current_player().unwrap().uuid());
+        assert_eq!(Err(FoundChainError::NothingToFound), game.found_chain(uuids[1], HotelChain::Luxor).map(|_| ()));
+
+        // chains on the board can not be founded again
+        place(&mut game, "8G");
+        give_tile(&mut game, 1, Tile::new(9, 'G').unwrap());
+        let (_events, placement) = game.place_tile(uuids[1], Tile::new(9, 'G').unwrap()).unwrap();
+        assert_eq!(Some(HotelChain::ALL[1..].to_vec()), placement.choose_chain);
+        assert_eq!(Err(FoundChainError::ChainOnBoard(HotelChain::Tower)), game.found_chain(uuids[1], HotelChain::Tower).map(|_| ()));
+        assert!(game.found_chain(uuids[1], HotelChain::Continental).is_ok());
+        assert_eq!(0, game.players[1].shares(HotelChain::Tower));
+        // the free share of the founder is not counted as bought
+        let stats = game.players[0].stats();
+        assert_eq!((1, 1, 0), (stats.tiles_placed, stats.chains_founded, stats.stocks_bought));
+    }
+
+    #[test]
+    fn test_buy_stock() {
+        let (mut game, uuids) = started_game();
+        for (tiles, chain) in [(["3C", "3D"], HotelChain::Tower), (["8G", "9G"], HotelChain::Imperial)] {
+            for tile in tiles {
+                place(&mut game, tile);
+            }
+            game.board.found_chain(position(tiles[0]), chain);
+        }
+        assert_eq!(Err(BuyStockError::WrongPhase), game.buy_stock(uuids[0], &[]).map(|_| ()));
+        // the tile grows tower, then shares can be bought
+        give_tile(&mut game, 0, Tile::new(3, 'E').unwrap());
+        let (_events, placement) = game.place_tile(uuids[0], Tile::new(3, 'E').unwrap()).unwrap();
+        assert!(placement.buy_stock);
+        assert_eq!(Err(PlayTileError::AlreadyPlaced), game.place_tile(uuids[0], game.players[0].hand()[0]).map(|_| ()));
+        assert_eq!(Err(BuyStockError::Turn(not_your_turn(&game))), game.buy_stock(uuids[1], &[]).map(|_| ()));
+
+        let buy = |game: &mut GameInstance, purchases: &[StockPurchase]| game.buy_stock(uuids[0], purchases).map(|_| ());
+        assert_eq!(Err(BuyStockError::TooManyShares), buy(&mut game, &[purchase(HotelChain::Tower, 2), purchase(HotelChain::Imperial, 2)]));
+        // quantities that would wrap around to an allowed total
+        assert_eq!(Err(BuyStockError::TooManyShares), buy(&mut game, &[purchase(HotelChain::Tower, u32::MAX), purchase(HotelChain::Tower, 2)]));
+        assert_eq!(Err(BuyStockError::ChainNotFounded(HotelChain::Luxor)), buy(&mut game, &[purchase(HotelChain::Luxor, 1)]));
+        game.give_shares(1, HotelChain::Tower, 24);
+        assert_eq!(Err(BuyStockError::NotEnoughShares(HotelChain::Tower)), buy(&mut game, &[purchase(HotelChain::Tower, 1), purchase(HotelChain::Tower, 1)]));
+        game.players[0].set_money(700);
+        assert_eq!(Err(BuyStockError::NotEnoughMoney { cost: 1100, shortfall: 400 }), buy(&mut game, &[purchase(HotelChain::Tower, 1), purchase(HotelChain::Imperial, 2)]));
+        assert_eq!(6000, game.players[1].money());
+
+        // tower has 3 tiles (300), imperial 2 tiles (400), empty purchases are not added to the portfolio
+        game.players[0].set_money(6000);
+        let (_events, portfolio) = game.buy_stock(uuids[0], &[purchase(HotelChain::Tower, 1), purchase(HotelChain::Imperial, 2), purchase(HotelChain::Luxor, 0)]).unwrap();
+        assert_eq!(4900, portfolio.money);
+        assert_eq!(vec![(HotelChain::Tower, 1), (HotelChain::Imperial, 2)], portfolio.shares.into_iter().collect::<Vec<_>>());
+        assert_eq!(0, game.bank_shares(HotelChain::Tower));
+        assert_eq!(uuids[1], game.current_player().unwrap().uuid());
+
+        let stocks = game.stocks(uuids[1]).unwrap();
+        assert_eq!((6000, Some(&24)), (stocks.own.money, stocks.own.shares.get(&HotelChain::Tower)));
+        assert_eq!(Some(&2), stocks.players[0].shares.get(&HotelChain::Imperial));
+        assert_eq!(23, stocks.bank[&HotelChain::Imperial]);
+        assert_shares_add_up(&game, &uuids);
+        assert!(game.stocks(Uuid::new_v4()).is_none());
+        // the net worth includes the bought shares at the current prices
+        let stats = game.players[0].stats();
+        assert_eq!((1, 3, vec![4900 + 300 + 2 * 400]), (stats.tiles_placed, stats.stocks_bought, stats.net_worth.clone()));
+        assert!(game.players[1].stats().net_worth.is_empty());
+    }
+
+    #[test]
+    fn test_bank_shares() {
+        let (mut game, uuids) = started_game();
+        game.give_shares(0, HotelChain::Tower, 20);
+        // the bank only has 5 shares left
+        game.give_shares(1, HotelChain::Tower, 10);
+        assert_eq!((20, 5, 0), (game.players[0].shares(HotelChain::Tower), game.players[1].shares(HotelChain::Tower), game.bank_shares(HotelChain::Tower)));
+        game.return_shares(1, HotelChain::Tower, 10);
+        assert_eq!((0, 5), (game.players[1].shares(HotelChain::Tower), game.bank_shares(HotelChain::Tower)));
+        assert_shares_add_up(&game, &uuids);
+
+        // the shares of a player that leaves go back to the bank
+        let player_id = game.players[0].id();
+        assert!(game.remove_player(player_id).is_some());
+        assert_eq!(SHARES_PER_CHAIN, game.bank_shares(HotelChain::Tower));
+        assert_shares_add_up(&game, &uuids[1..]);
+    }
+
+    /// Places the tiles on the board and founds `chain` with them.
+    fn found(game: &mut GameInstance, tiles: &[&str], chain: HotelChain) {
+        for tile in tiles {
+            place(game, tile);
+        }
+        game.board.found_chain(position(tiles[0]), chain);
+    }
+
+    #[test]
+    fn test_shareholder_bonuses() {
+        let payouts = |holdings: &[(u32, u32)], price: u32| -> Vec<(u32, u32)> {
+            shareholder_bonuses(holdings, price).into_iter().map(|payout| (payout.player_id, payout.amount)).collect()
+        };
+        // majority 3000, minority 1500
+        assert_eq!(vec![(1, 3000), (2, 1500)], payouts(&[(1, 4), (2, 2), (3, 0)], 300));
+        // a sole shareholder takes both bonuses
+        assert_eq!(vec![(2, 4500)], payouts(&[(1, 0), (2, 3)], 300));
+        // a two-way tie for the majority splits both bonuses rounded up, the next shareholder gets nothing
+        assert_eq!(vec![(1, 2300), (2, 2300)], payouts(&[(1, 2), (2, 2), (3, 1)], 300));
+        assert_eq!(vec![(1, 2000), (2, 2000), (3, 2000)], payouts(&[(1, 5), (2, 5), (3, 5)], 400));
+        // a tie for the minority splits the minority bonus rounded up
+        assert_eq!(vec![(1, 3000), (2, 800), (3, 800)], payouts(&[(1, 4), (2, 2), (3, 2)], 300));
+        assert!(payouts(&[(1, 0), (2, 0)], 300).is_empty());
+    }
+
+    #[test]
+    fn test_merger() {
+        let (mut game, uuids) = started_game();
+        found(&mut game, &["1A", "2A", "3A"], HotelChain::Tower);
+        found(&mut game, &["5A", "6A"], HotelChain::Luxor);
+        game.give_shares(0, HotelChain::Luxor, 2);
+        game.give_shares(1, HotelChain::Luxor, 1);
+        give_tile(&mut game, 0, Tile::new(4, 'A').unwrap());
+
+        let (events, placement) = game.place_tile(uuids[0], Tile::new(4, 'A').unwrap()).unwrap();
+        // the shareholders of luxor have to decide first
+        assert!(!placement.buy_stock);
+        assert_eq!(vec!["4A", "5A", "6A"], placement.tiles.iter().map(|tile| tile.position.to_string()).collect::<Vec<_>>());
+        assert!(placement.tiles.iter().all(|tile| tile.chain == Some(HotelChain::Tower)));
+        // luxor with 2 tiles costs 200, so the bonuses are 2000 and 1000
+        let (_, data) = events.contents()[1];
+        let resolved: MergerResolved = rocket::serde::json::from_str(data.unwrap()).unwrap();
+        let payouts = vec![Payout { player_id: game.players[0].id(), amount: 2000 }, Payout { player_id: game.players[1].id(), amount: 1000 }];
+        assert_eq!(MergerResolved { survivor: HotelChain::Tower, defunct: HotelChain::Luxor, defunct_size: 2, payouts }, resolved);
+        assert_eq!((8000, 7000), (game.players[0].money(), game.players[1].money()));
+        assert_eq!((6, 0), (game.board.chain_size(HotelChain::Tower), game.board.chain_size(HotelChain::Luxor)));
+        assert_eq!(Err(ChooseSurvivorError::NothingToChoose), game.choose_survivor(uuids[0], HotelChain::Tower).map(|_| ()));
+    }
+
+    #[test]
+    fn test_merger_decision() {
+        let (mut game, uuids) = started_game();
+        found(&mut game, &["1A", "2A", "3A"], HotelChain::Tower);
+        found(&mut game, &["5A", "6A"], HotelChain::Luxor);
+        game.give_shares(0, HotelChain::Luxor, 1);
+        game.give_shares(1, HotelChain::Luxor, 5);
+        game.give_shares(0, HotelChain::Tower, 24);
+        give_tile(&mut game, 0, Tile::new(4, 'A').unwrap());
+        let decision = |sell, trade, keep| DisposalDecision { sell, trade, keep };
+        let ids = (game.players[0].id(), game.players[1].id());
+
+        let (events, _placement) = game.place_tile(uuids[0], Tile::new(4, 'A').unwrap()).unwrap();
+        // the merging player decides first even though they hold less shares
+        let (name, data) = events.contents()[2];
+        assert_eq!("AwaitingDisposal", name);
+        let pending: PendingDisposal = rocket::serde::json::from_str(data.unwrap()).unwrap();
+        assert_eq!(PendingDisposal { player_id: ids.0, defunct: HotelChain::Luxor, survivor: HotelChain::Tower, shares: 1, price: 200 }, pending);
+        assert_eq!(Err(PlayTileError::AlreadyPlaced), game.place_tile(uuids[0], game.players[0].hand()[0]).map(|_| ()));
+        assert_eq!(Err(BuyStockError::WrongPhase), game.buy_stock(uuids[0], &[]).map(|_| ()));
+        let turn = TurnStatus { player_id: ids.0, phase: TurnPhase::MergerDisposal, disposal: Some(pending), turn_token: game.turns.token() };
+        assert_eq!(Some(turn.clone()), game.turn_status());
+        assert_eq!(Err(MergerDecisionError::NotYourDecision(Box::new(turn))), game.merger_decision(uuids[1], &decision(5, 0, 0)).map(|_| ()));
+        assert_eq!(Err(MergerDecisionError::SharesMismatch { held: 1 }), game.merger_decision(uuids[0], &decision(1, 0, 1)).map(|_| ()));
+        // counts that would wrap around to the held shares
+        assert_eq!(Err(MergerDecisionError::SharesMismatch { held: 1 }), game.merger_decision(uuids[0], &decision(u32::MAX, 2, 0)).map(|_| ()));
+        assert_eq!(Err(MergerDecisionError::SharesMismatch { held: 1 }), game.merger_decision(uuids[0], &decision(u32::MAX, u32::MAX, u32::MAX)).map(|_| ()));
+
+        let (events, portfolio) = game.merger_decision(uuids[0], &decision(1, 0, 0)).unwrap();
+        assert_eq!(6000 + 1000 + 200, portfolio.money);
+        assert_eq!(0, game.players[0].shares(HotelChain::Luxor));
+        let (_, data) = events.contents()[0];
+        let made: MergerDecision = rocket::serde::json::from_str(data.unwrap()).unwrap();
+        assert_eq!(MergerDecision { player_id: ids.0, defunct: HotelChain::Luxor, decision: decision(1, 0, 0), remaining: 1 }, made);
+        assert_eq!("AwaitingDisposal", events.contents()[1].0);
+
+        assert_eq!(Err(MergerDecisionError::OddTrade), game.merger_decision(uuids[1], &decision(0, 3, 2)).map(|_| ()));
+        // the bank only holds one share of tower
+        assert_eq!(Err(MergerDecisionError::NotEnoughShares(HotelChain::Tower)), game.merger_decision(uuids[1], &decision(1, 4, 0)).map(|_| ()));
+        let (events, portfolio) = game.merger_decision(uuids[1], &decision(1, 2, 2)).unwrap();
+        assert_eq!(6000 + 2000 + 200, portfolio.money);
+        assert_eq!((2, 1), (game.players[1].shares(HotelChain::Luxor), game.players[1].shares(HotelChain::Tower)));
+        assert_eq!(1, events.contents().len());
+        assert_shares_add_up(&game, &uuids);
+        assert_eq!(Err(MergerDecisionError::NothingToDecide), game.merger_decision(uuids[1], &decision(2, 0, 0)).map(|_| ()));
+        let actions: Vec<LogAction> = game.game_log(0).entries.into_iter().map(|entry| entry.action).skip_while(|action| *action != LogAction::TilePlaced { position: position("4A") }).collect();
+        assert_eq!(vec![
+            LogAction::TilePlaced { position: position("4A") },
+            LogAction::MergerResolved { survivor: HotelChain::Tower, defunct: HotelChain::Luxor },
+            LogAction::MergerDecision { defunct: HotelChain::Luxor, decision: decision(1, 0, 0) },
+            LogAction::MergerDecision { defunct: HotelChain::Luxor, decision: decision(1, 2, 2) },
+        ], actions);
+        let (_events, _portfolio) = game.buy_stock(uuids[0], &[]).unwrap();
+        let stats: Vec<(u32, u32, u32)> = game.players.iter().map(|player| (player.stats().stocks_sold, player.stats().stocks_traded, player.stats().bonuses)).collect();
+        assert_eq!(vec![(1, 0, 1000), (1, 2, 2000)], stats);
+    }
+
+    #[test]
+    fn test_shareholder_leaves_during_merger() {
+        let (mut game, uuids) = started_game();
+        found(&mut game, &["1A", "2A", "3A"], HotelChain::Tower);
+        found(&mut game, &["5A", "6A"], HotelChain::Luxor);
+        game.give_shares(0, HotelChain::Luxor, 1);
+        game.give_shares(1, HotelChain::Luxor, 5);
+        give_tile(&mut game, 0, Tile::new(4, 'A').unwrap());
+        let (_events, _placement) = game.place_tile(uuids[0], Tile::new(4, 'A').unwrap()).unwrap();
+        let (_events, _portfolio) = game.merger_decision(uuids[0], &DisposalDecision { sell: 1, trade: 0, keep: 0 }).unwrap();
+        let remaining = game.tile_bag.as_ref().unwrap().remaining();
+
+        // the current player can buy shares once the last shareholder that had to decide is gone
+        let (player_id, held) = (game.players[1].id(), game.players[1].hand().len());
+        let (_user, events) = game.remove_player(player_id).unwrap();
+        assert!(events.contents().is_empty());
+        assert!(game.disposals.is_empty());
+        assert_eq!((uuids[0], TurnPhase::BuyStock), (game.current_player().unwrap().uuid(), game.turns.phase()));
+        assert_eq!(remaining + held, game.tile_bag.as_ref().unwrap().remaining());
+        let actions: Vec<LogAction> = game.game_log(0).entries.into_iter().rev().take(2).map(|entry| entry.action).collect();
+        assert_eq!(vec![LogAction::HandReturned { tiles: held }, LogAction::Left], actions);
+    }
+
+    #[test]
+    fn test_merging_player_leaves() {
+        let (mut game, uuids) = started_game();
+        found(&mut game, &["1A", "2A", "3A"], HotelChain::Tower);
+        found(&mut game, &["5A", "6A"], HotelChain::Luxor);
+        game.give_shares(0, HotelChain::Luxor, 1);
+        game.give_shares(1, HotelChain::Luxor, 5);
+        give_tile(&mut game, 0, Tile::new(4, 'A').unwrap());
+        let (_events, _placement) = game.place_tile(uuids[0], Tile::new(4, 'A').unwrap()).unwrap();
+        assert_eq!(2, game.disposals.len());
+
+        // the merger ends with the merging player, the next player starts with placing a tile
+        let ids = (game.players[0].id(), game.players[1].id());
+        let (_user, events) = game.remove_player(ids.0).unwrap();
+        assert_eq!(vec![("TurnChanged", Some(ids.1.to_string()))], events.contents().iter().map(|(name, data)| (*name, data.map(String::from))).collect::<Vec<_>>());
+        assert!(game.disposals.is_empty());
+        assert_eq!((uuids[1], TurnPhase::PlaceTile), (game.current_player().unwrap().uuid(), game.turns.phase()));
+        // the other shareholder keeps the shares of the defunct chain
+        assert_eq!(5, game.players[0].shares(HotelChain::Luxor));
+        assert_eq!(Err(MergerDecisionError::NothingToDecide), game.merger_decision(uuids[1], &DisposalDecision { sell: 5, trade: 0, keep: 0 }).map(|_| ()));
+        let last = game.game_log(0).entries.pop().unwrap();
+        assert_eq!((Some(ids.1), LogAction::TurnChanged), (last.player_id, last.action));
+        let tile = game.players[0].hand()[0];
+        assert!(game.place_tile(uuids[1], tile).is_ok());
+    }
+
+    #[test]
+    fn test_choose_survivor() {
+        let (mut game, uuids) = started_game();
+        found(&mut game, &["1A", "2A"], HotelChain::Tower);
+        found(&mut game, &["4A", "5A"], HotelChain::Luxor);
+        found(&mut game, &["3C", "3D"], HotelChain::Imperial);
+        game.give_shares(1, HotelChain::Tower, 1);
+        game.give_shares(1, HotelChain::Luxor, 1);
+        give_tile(&mut game, 0, Tile::new(3, 'A').unwrap());
+
+        let (events, placement) = game.place_tile(uuids[0], Tile::new(3, 'A').unwrap()).unwrap();
+        assert_eq!(Some(vec![HotelChain::Tower, HotelChain::Luxor]), placement.choose_survivor);
+        assert!(!placement.buy_stock);
+        assert_eq!(vec!["TilePlaced"], events.contents().iter().map(|(name, _)| *name).collect::<Vec<_>>());
+        assert_eq!(Err(PlayTileError::ChainChoicePending), game.place_tile(uuids[0], game.players[0].hand()[0]).map(|_| ()));
+        assert_eq!(Err(ChooseSurvivorError::Turn(not_your_turn(&game))), game.choose_survivor(uuids[1], HotelChain::Luxor).map(|_| ()));
+        assert_eq!(Err(ChooseSurvivorError::NotLargest(HotelChain::Imperial)), game.choose_survivor(uuids[0], HotelChain::Imperial).map(|_| ()));
+
+        let (events, tiles) = game.choose_survivor(uuids[0], HotelChain::Luxor).unwrap();
+        assert_eq!(vec!["1A", "2A", "3A"], tiles.iter().map(|tile| tile.position.to_string()).collect::<Vec<_>>());
+        assert_eq!(vec!["MergerResolved", "AwaitingDisposal"], events.contents().iter().map(|(name, _)| *name).collect::<Vec<_>>());
+        // the sole shareholder of tower receives both bonuses for 2 tiles
+        assert_eq!(6000 + 3000, game.players[1].money());
+        assert_eq!(vec![HotelChain::Luxor, HotelChain::Imperial], game.board.chains_on_board());
+        // tower can be founded again
+        assert_eq!(None, game.board.chain_price(HotelChain::Tower));
+        let keep = DisposalDecision { sell: 0, trade: 0, keep: 1 };
+        let (_events, _portfolio) = game.merger_decision(uuids[1], &keep).unwrap();
+        let (_events, _portfolio) = game.buy_stock(uuids[0], &[]).unwrap();
+    }
+
+    #[test]
+    fn test_dead_tiles() {
+        let (mut game, uuids) = started_game();
+        let tiles = |row: char| (1..=11).map(move |column| format!("{}{}", column, row));
+        let (tower, luxor): (Vec<String>, Vec<String>) = (tiles('A').collect(), tiles('C').collect());
+        found(&mut game, &tower.iter().map(String::as_str).collect::<Vec<_>>(), HotelChain::Tower);
+        found(&mut game, &luxor.iter().map(String::as_str).collect::<Vec<_>>(), HotelChain::Luxor);
+        // 5B would merge the two safe chains, 12B is next to neither of them
+        let (dead, alive) = (Tile::new(5, 'B').unwrap(), Tile::new(12, 'B').unwrap());
+        assert!(game.board.is_dead(dead.position()));
+        assert!(!game.board.is_dead(alive.position()));
+        // the player holds the two tiles instead of two others
+        for _ in 0..2 {
+            let tile = game.players[0].hand()[0];
+            take_tile(&mut game, tile);
+            game.tile_bag.as_mut().unwrap().put_back(tile);
+        }
+        give_tile(&mut game, 0, dead);
+        give_tile(&mut game, 0, alive);
+        assert_eq!(Err(PlayTileError::Board(PlaceTileError::SafeChainsMerge(dead.position()))), game.place_tile(uuids[0], dead).map(|_| ()));
+        // the player has placed another tile, it goes back into the bag to keep the tiles complete
+        let played = game.players[0].hand().iter().copied().find(|tile| *tile != dead && *tile != alive).unwrap();
+        take_tile(&mut game, played);
+        game.tile_bag.as_mut().unwrap().put_back(played);
+        game.turns.set_phase(TurnPhase::BuyStock);
+        let held = game.players[0].hand().len();
+
+        let (events, _portfolio) = game.buy_stock(uuids[0], &[]).unwrap();
+        let hand = game.players[0].hand();
+        assert_eq!(HAND_SIZE, hand.len());
+        assert!(hand.contains(&alive));
+        assert!(hand.iter().all(|tile| !game.board.is_dead(tile.position())));
+        let contents = events.contents();
+        assert_eq!(vec!["TilesDrawn", "TurnEnded", "TurnChanged"], contents.iter().map(|(name, _)| *name).collect::<Vec<_>>());
+        let drawn: TilesDrawn = rocket::serde::json::from_str(contents[0].1.unwrap()).unwrap();
+        assert!(drawn.discarded.contains(&dead));
+        let ended: TurnEnded = rocket::serde::json::from_str(contents[1].1.unwrap()).unwrap();
+        assert_eq!(TurnEnded { player_id: game.players[0].id(), discarded: drawn.discarded.clone() }, ended);
+        assert_eq!(HAND_SIZE, held - drawn.discarded.len() + drawn.drawn.len());
+    }
+
+    #[test]
+    fn test_refill_with_empty_bag() {
+        let (mut game, uuids) = started_game();
+        // the tiles that are left are put aside
+        let bag = game.tile_bag.as_mut().unwrap();
+        while let Some(tile) = bag.draw() {
+            bag.discard(tile);
+        }
+        let tile = game.players[0].hand()[0];
+        let (events, _placement) = game.place_tile(uuids[0], tile).unwrap();
+        assert_eq!(HAND_SIZE - 1, game.players[0].hand().len());
+        let (_, drawn) = events.contents()[1];
+        assert_eq!(Some(r#"{"drawn":[],"discarded":[]}"#), drawn);
+        assert!(game.turns.is_turn_of(uuids[1]));
+    }
+
+    #[test]
+    fn test_invariants() {
+        let (mut game, _uuids) = started_game();
+        assert_eq!(Ok(()), game.check_game_invariants());
+        let tile = game.players[1].hand()[0];
+        game.players[0].add_tile(tile);
+        assert_eq!(Err(InvariantError::TileCount { tile: tile.position(), count: 2 }), game.check_game_invariants());
+        take_tile(&mut game, tile);
+        assert_eq!(Err(InvariantError::TileCount { tile: tile.position(), count: 0 }), game.check_game_invariants());
+        game.tile_bag.as_mut().unwrap().put_back(tile);
+
+        let player_id = game.players[0].id();
+        give_tile(&mut game, 0, Tile::new(12, 'I').unwrap());
+        assert_eq!(Err(InvariantError::HandTooLarge { player_id, size: HAND_SIZE + 1 }), game.check_game_invariants());
+        let tile = game.players[0].hand()[0];
+        take_tile(&mut game, tile);
+        game.tile_bag.as_mut().unwrap().put_back(tile);
+
+        game.players[0].add_shares(HotelChain::Festival, 2);
+        assert_eq!(Err(InvariantError::SharesMismatch { chain: HotelChain::Festival, total: SHARES_PER_CHAIN + 2 }), game.check_game_invariants());
+        game.players[0].remove_shares(HotelChain::Festival, 2);
+
+        place(&mut game, "5E");
+        game.board.set_chain(position("5E"), HotelChain::Luxor);
+        assert_eq!(Err(InvariantError::Board(BoardInvariantError::ChainTooSmall(HotelChain::Luxor))), game.check_game_invariants());
+    }
+
+    #[test]
+    #[should_panic(expected = "invariant violated: there are 24 shares of tower")]
+    fn test_invariants_are_checked() {
+        let (mut game, uuids) = started_game();
+        game.bank.insert(HotelChain::Tower, 24);
+        let tile = game.players[0].hand()[0];
+        let _ = game.place_tile(uuids[0], tile);
+    }
+
+    #[test]
+    fn test_rank() {
+        let standing = |player_id: u32, money: u32| Standing { place: 0, player_id, name: format!("player {}", player_id), money };
+        let places = |standings: Vec<Standing>| rank(standings).into_iter().map(|standing| (standing.place, standing.player_id)).collect::<Vec<_>>();
+        assert_eq!(vec![(1, 2), (2, 3), (3, 1)], places(vec![standing(1, 100), standing(2, 300), standing(3, 200)]));
+        // a shared place skips the next one
+        assert_eq!(vec![(1, 1), (1, 3), (3, 2), (4, 4)], places(vec![standing(1, 500), standing(2, 400), standing(3, 500), standing(4, 0)]));
+        assert!(rank(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn test_end_game() {
+        let (mut game, uuids) = started_game();
+        found(&mut game, &["1A", "2A"], HotelChain::Tower);
+        assert_eq!(Err(EndGameError::ConditionsNotMet), game.end_game(uuids[0]).map(|_| ()));
+        let tiles: Vec<String> = (1..=11).map(|column| format!("{}C", column)).collect();
+        found(&mut game, &tiles.iter().map(String::as_str).collect::<Vec<_>>(), HotelChain::Luxor);
+        // tower is not safe yet
+        assert_eq!(Err(EndGameError::ConditionsNotMet), game.end_game(uuids[0]).map(|_| ()));
+        for column in 3..=11 {
+            place(&mut game, &format!("{}A", column));
+        }
+        assert!(game.board.can_end_game());
+        assert_eq!(Err(EndGameError::Turn(not_your_turn(&game))), game.end_game(uuids[1]).map(|_| ()));
+        game.turns.set_phase(TurnPhase::FoundChain(position("1E")));
+        assert_eq!(Err(EndGameError::DecisionPending), game.end_game(uuids[0]).map(|_| ()));
+        game.turns.set_phase(TurnPhase::PlaceTile);
+
+        game.give_shares(0, HotelChain::Tower, 3);
+        game.give_shares(1, HotelChain::Tower, 3);
+        game.give_shares(1, HotelChain::Luxor, 2);
+        assert!(game.game_stats().is_none());
+        let (events, standings) = game.end_game(uuids[0]).unwrap();
+        // tower and luxor cost 700 with 11 tiles, the tie for tower splits 10500 and luxor pays 10500 to its sole shareholder
+        let (first, second) = (6000 + 5300 + 3 * 700, 6000 + 5300 + 3 * 700 + 10500 + 2 * 700);
+        let ids = (game.players[0].id(), game.players[1].id());
+        assert_eq!(vec![(1, ids.1, second), (2, ids.0, first)], standings.iter().map(|standing| (standing.place, standing.player_id, standing.money)).collect::<Vec<_>>());
+        assert!(game.players.iter().all(|player| player.portfolio().is_empty()));
+        assert_eq!(vec!["GameEnded"], events.contents().iter().map(|(name, _)| *name).collect::<Vec<_>>());
+        assert_eq!(Some(standings.as_slice()), game.results());
+        assert!(game.turn_status().is_none());
+        assert_eq!(Err(PlayTileError::Turn(TurnError::Finished)), game.place_tile(uuids[0], game.players[0].hand()[0]).map(|_| ()));
+        assert_eq!(Err(EndGameError::Turn(TurnError::Finished)), game.end_game(uuids[0]).map(|_| ()));
+        assert_eq!(1, game.game_ended_events(Some(uuids[1])).contents().len());
+        let last = game.game_log(0).entries.pop().unwrap();
+        assert_eq!((Some(ids.0), LogAction::GameEnded), (last.player_id, last.action));
+        // the final bonuses are counted, the final sale is not
+        let stats: Vec<(u32, u32, u32)> = game.game_stats().unwrap().into_iter().map(|entry| (entry.player_id, entry.stats.bonuses, entry.stats.stocks_sold)).collect();
+        assert_eq!(vec![(ids.0, 5300, 0), (ids.1, 5300 + 10500, 0)], stats);
+    }
+
+    #[test]
+    fn test_annotate() {
+        let (mut game, uuids) = started_game();
+        assert!(game.set_game_master(uuids[0]));
+        assert_eq!(Err(AnnotateError::NotFinished), game.annotate(uuids[0], "Close game", "").map(|_| ()));
+        game.game_state = GameState::Finished(Vec::new());
+        assert_eq!(Err(AnnotateError::NotGameMaster), game.annotate(uuids[1], "Close game", "").map(|_| ()));
+        let err = game.annotate(uuids[0], " <b></b> ", &"a".repeat(MAX_NOTES_LENGTH + 1)).unwrap_err();
+        let fields = match err {
+            AnnotateError::InvalidFields(fields) => fields,
+            err => panic!("unexpected error {}", err),
+        };
+        assert_eq!(vec!["title", "notes"], fields.iter().map(|field| field.field.as_str()).collect::<Vec<_>>());
+        assert!(game.annotation.is_none());
+
+        let (events, annotation) = game.annotate(uuids[0], " <i>Close</i> game ", "Won by <b>100</b>").unwrap();
+        let editor = game.players[0].id();
+        assert_eq!(GameAnnotation { title: String::from("Close game"), notes: String::from("Won by 100"), editor }, annotation);
+        assert_eq!(vec!["GameAnnotated"], events.contents().iter().map(|(name, _)| *name).collect::<Vec<_>>());
+        // the notes are replaced as a whole
+        let (_, annotation) = game.annotate(uuids[0], &"t".repeat(MAX_NOTE_TITLE_LENGTH), "").unwrap();
+        assert_eq!(Some(annotation), game.annotation);
+    }
+}
@@ -1,19 +1,60 @@
-use std::{net::IpAddr, collections::HashSet};
+use std::{collections::{BTreeMap, VecDeque}, fmt::{self, Display, Formatter}, time::{Duration, Instant}};
 
-use rocket::form::name;
+use rand::seq::SliceRandom;
 use uuid::Uuid;
 
-use crate::{authentication::UserRecovery, request_data::UserRegistration};
+use rocket::log::private::info;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
-use super::{base_game::Player, User};
+use crate::{authentication::{UserRecovery, Urid}, events::{EventBatch, GameChannel}, rules::parse_game_code, request_data::{FieldError, GameAnnotation, GameEvent, Hand, PendingDisposal, PlayerShares, PlayerStatsEntry, Portfolio, Standing, StockOverview, TurnStatus, UserRegistration, LobbyAdminRequest, LobbyAdminResult, KickOutcome, KickResult}};
+
+use self::{board::{Board, HotelChain, PlaceTileError}, game_log::{GameLog, GameLogPage, LogAction}, rng::GameRng, invites::Invites, seats::SeatPool, security_log::SecurityLog, turns::{TurnError, TurnManager}, waitlist::Waitlist};
+
+use super::{base_game::{Player, TileBag, Vacancy, VacancyReason, HAND_SIZE, MAX_SHARES_PER_TURN, SHARES_PER_CHAIN, STARTING_MONEY}, User, UserRegistrationError};
 
 /// Functions related to the games logic
 ///
 /// All these function will be called from within a [GameInstance](../struct.GameInstance.html)
 mod logic;
 
-/// All characters that can be used to generate a game code
-pub const GAME_CODE_CHARSET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWZ";
+/// The random number generator that is used for all random decisions inside a game
+pub mod rng;
+
+/// Invites that allow new players to join a lobby that requires an invite
+pub mod invites;
+
+/// Rejected join requests that the game master can review
+pub mod security_log;
+
+/// Users that wait for a seat in a full lobby
+pub mod waitlist;
+
+/// The seats of the players
+pub mod seats;
+
+/// The board on which the tiles are placed
+pub mod board;
+
+/// The order in which the players take their turns
+pub mod turns;
+
+/// Everything that happened in a game, see [GameLog](game_log/struct.GameLog.html)
+pub mod game_log;
+
+/// The smallest number of players with which a game can be played
+pub const MIN_PLAYERS: usize = 2;
+
+/// The largest number of players with which a game can be played
+pub const MAX_PLAYERS: usize = 6;
+
+/// The longest title of the notes of a finished game, in characters
+pub const MAX_NOTE_TITLE_LENGTH: usize = 80;
+
+/// The longest notes of a finished game, in characters
+pub const MAX_NOTES_LENGTH: usize = 1000;
+
+pub use crate::rules::GAME_CODE_CHARSET;
 
 /// Representation of a game
 pub struct GameInstance {
@@ -23,19 +64,101 @@ pub struct GameInstance {
     game_code: GameCode,
     /// The current state of the game
     game_state: GameState,
+    /// The tiles that were placed and the hotel chains they belong to
+    board: Board,
+    /// The tiles that were not drawn yet, `None` until the game is started
+    tile_bag: Option<TileBag>,
+    /// The order of the turns and the step of the turn of the [current_player](#method.current_player), empty until the game is started
+    turns: TurnManager,
+    /// The shares of each chain that no player holds, see [bank_shares](#method.bank_shares)
+    bank: BTreeMap<HotelChain, u32>,
+    /// The shareholders that still have to decide what happens to their shares of a defunct chain, see [merger_decision](#method.merger_decision)
+    disposals: VecDeque<PendingDisposal>,
+    /// The rng that is used for all random decisions in this game
+    rng: GameRng,
+    /// The settings that the game master has set in the lobby
+    settings: LobbySettings,
+    /// When the lobby is locked no new players can join, players that are already part of the game can still reconnect.
+    locked: bool,
+    /// Incremented each time a player joins or reconnects.
+    /// 
+    /// [disconnect_user](../fn.disconnect_user.html) remembers the generation when the game became abandoned
+    /// and only deletes the game when it did not change while waiting.
+    generation: u64,
+    /// The time since when no player is connected to the game.
+    abandoned_since: Option<Instant>,
+    /// The invites that the game master has created
+    invites: Invites,
+    /// Join requests for this game that where rejected
+    security_log: SecurityLog,
+    /// The actions of the players, see [game_log](#method.game_log)
+    game_log: GameLog,
+    /// The notes of the game master once the game is finished, see [annotate](#method.annotate)
+    annotation: Option<GameAnnotation>,
+    /// Users that wait for a seat, see [LobbySettings::enable_waitlist]()
+    waitlist: Waitlist,
+    /// The seats of the players, see [SeatAssignment](seats/struct.SeatAssignment.html)
+    seats: SeatPool,
+    /// The time of the last action of the game master, see [LobbySettings::master_idle_rotate_secs]()
+    master_active_at: Instant,
+    /// Incremented each time the game master acts or is replaced.
+    /// 
+    /// [rotate_idle_game_master](../fn.rotate_idle_game_master.html) remembers the generation when the game master
+    /// has idled for too long and only rotates the game master when it did not change in the meantime.
+    master_generation: u64,
+    /// The channel on which the events of this game are send, dropped with the game
+    channel: GameChannel,
 }
 
 impl GameInstance {
 
-    /// Creates a new game instance
+    /// Creates a new game instance with a random seed
     pub fn new(game_code: GameCode) -> Self {
+        Self::with_rng(game_code, GameRng::new())
+    }
+
+    /// Creates a new game instance whose random decisions are derived from `seed`.
+    /// 
+    /// Two games that are created with the same seed and in which the same actions are made play out identically.
+    pub fn with_seed(game_code: GameCode, seed: u64) -> Self {
+        Self::with_rng(game_code, GameRng::from_seed(seed))
+    }
+
+    fn with_rng(game_code: GameCode, rng: GameRng) -> Self {
+        let channel = GameChannel::new(game_code);
         Self {
             players: Vec::new(),
             game_code,
             game_state: GameState::Lobby,
+            board: Board::default(),
+            tile_bag: None,
+            turns: TurnManager::default(),
+            bank: HotelChain::ALL.into_iter().map(|chain| (chain, SHARES_PER_CHAIN)).collect(),
+            disposals: VecDeque::new(),
+            rng,
+            settings: LobbySettings::default(),
+            locked: false,
+            generation: 0,
+            abandoned_since: None,
+            invites: Invites::default(),
+            security_log: SecurityLog::default(),
+            game_log: GameLog::new(channel.clone()),
+            annotation: None,
+            waitlist: Waitlist::default(),
+            seats: SeatPool::default(),
+            master_active_at: Instant::now(),
+            master_generation: 0,
+            channel,
         }
     }
 
+    /// Returns the seed of the rng of this game.
+    /// 
+    /// The seed can be used to reproduce this game with [with_seed](#method.with_seed).
+    pub fn seed(&self) -> u64 {
+        self.rng.seed()
+    }
+
     /// Creates a new player that is associated to the user and adds them to the game.
     /// 
     /// # Params
@@ -44,15 +167,49 @@ impl GameInstance {
     /// # Returns
     /// `true` when the player was added.
     /// 
-    /// `false` when the player was not added because the game has already started.
+    /// `false` when the player was not added because the game has already started or is full.
     pub fn add_user(&mut self, user: User) -> bool {
-        match self.game_state {
-            GameState::Lobby => {
-                self.players.push(Player::new(user));
-                true
-            },
-            _ => false,
+        if !matches!(self.game_state, GameState::Lobby) {
+            return false;
+        }
+        let seat = match self.seats.assign(self.settings.max_players) {
+            Some(seat) => seat,
+            None => return false,
+        };
+        // players are kept in seat order
+        let index = self.players.partition_point(|player| player.seat().seat < seat.seat);
+        self.players.insert(index, Player::new(user, seat));
+        self.game_log.record(Some(seat.public_id), LogAction::Joined);
+        self.generation += 1;
+        true
+    }
+
+    /// Removes the player with the public `player_id` from the game.
+    /// 
+    /// The shares of the player go back to the bank and the tiles in their hand back into the bag, see
+    /// [leave_turns](#method.leave_turns) for what happens to the turn.
+    /// 
+    /// # Returns
+    /// - The [User](../struct.User.html) of the removed player or `None` when no player has this id.
+    /// - The events of [leave_turns](#method.leave_turns).
+    pub fn remove_player(&mut self, player_id: u32) -> Option<(User, EventBatch)> {
+        let index = self.players.iter().position(|player| player.id() == player_id)?;
+        self.generation += 1;
+        let player = self.players.remove(index);
+        for (chain, count) in player.portfolio() {
+            *self.bank.entry(*chain).or_default() += count;
+        }
+        self.seats.release(player.seat());
+        self.game_log.record(Some(player_id), LogAction::Left);
+        if let Some(bag) = self.tile_bag.as_mut().filter(|_| !player.hand().is_empty()) {
+            for tile in player.hand() {
+                bag.put_back(*tile);
+            }
+            self.game_log.record(Some(player_id), LogAction::HandReturned { tiles: player.hand().len() });
         }
+        let events = self.leave_turns(player.uuid(), player_id);
+        self.debug_check_invariants();
+        Some((player.user, events))
     }
 
     /// Sets the game master of the game.
@@ -72,11 +229,13 @@ impl GameInstance {
         match self.player_by_uuid_mut(uuid) {
             Some(new_gm) => {
                 new_gm.make_game_master();
+                self.channel.set_game_master(uuid);
                 for player in &mut self.players {
                     if player.is_game_master() && player.uuid() != uuid {
                         player.revoke_game_master();
                     }
                 }
+                self.game_master_active();
                 true
             },
             None => false,
@@ -84,6 +243,7 @@ impl GameInstance {
     }
 
     /// Returns a vector containing all players
+    #[cfg(test)]
     pub fn players(&self) -> &Vec<Player> {
         &self.players
     }
@@ -93,24 +253,14 @@ impl GameInstance {
         &self.game_code
     }
 
-    /// Returns the player by uuid if found
-    pub fn player_by_uuid(&self, id: Uuid) -> Option<&Player> {
-        for player in &self.players {
-            if player.uuid() == id {
-                return Some(player);
-            }
-        }
-        None
+    /// Returns the channel on which the events of this game are send, streams subscribe to it and batches are created for it.
+    pub fn channel(&self) -> &GameChannel {
+        &self.channel
     }
 
     /// Returns the player by id mutable if found
     pub fn player_by_uuid_mut(&mut self, uuid: Uuid) -> Option<&mut Player> {
-        for player in &mut self.players {
-            if player.uuid() == uuid {
-                return Some(player);
-            }
-        }
-        None
+        self.players.iter_mut().find(|player| player.uuid() == uuid)
     }
 
     /// Checks if a player with the name already exists
@@ -135,25 +285,37 @@ impl GameInstance {
 
     /// Validates the UserRecovery.
     /// 
+    /// The urid is compared against the urid that is stored for the user, the age of the urid
+    /// is also taken from the stored urid because the cookie can not be trusted.
+    /// 
     /// # Returns
-    /// - `true` user recovery is valid
-    /// - `false` user recovery is invalid
-    pub fn validate_urid(&self, ur: UserRecovery) -> bool {
-        for player in &self.players {
-            let user = &player.user;
-            if user.urid.value() == ur.urid.value() {
-                if ur.name.is_some() {
-                    if &user.username == ur.name.as_ref().unwrap() {
-                        return true;
-                    } else {
-                        continue;
-                    }
+    /// - `Ok(())` user recovery is valid
+    /// - `Err(UserRegistrationError::NameTaken)` user recovery is invalid
+    /// - `Err(UserRegistrationError::RecoveryExpired)` the urid matches but is older than [URID_MAX_AGE](../../authentication/constant.URID_MAX_AGE.html)
+    pub fn validate_urid(&self, ur: &UserRecovery) -> Result<(), UserRegistrationError> {
+        for user in self.users() {
+            if user.urid == ur.urid && ur.name.as_ref() == Some(&user.username) {
+                if user.urid.is_expired() {
+                    info!("Recovery for {} in game {} refused: urid expired, issued {}s ago", user.username, self.game_code, user.urid.age().as_secs());
+                    return Err(UserRegistrationError::RecoveryExpired);
                 }
+                return Ok(());
             }
         }
-        false
+        info!("Recovery for {:?} in game {} failed: urid unknown, cookie issued {}s ago", ur.name, self.game_code, ur.urid.age().as_secs());
+        Err(UserRegistrationError::NameTaken)
     }
     
+    /// Checks if `urid` belongs to a player or a waiting user of this game.
+    pub fn has_urid(&self, urid: &Urid) -> bool {
+        self.users().any(|user| user.urid == *urid)
+    }
+
+    /// Returns the users of all players and of all users on the waitlist.
+    pub fn users(&self) -> impl Iterator<Item = &User> {
+        self.players.iter().map(|player| &player.user).chain(self.waitlist.users())
+    }
+
     /// Updates the user entry to reflect that the user is connected.
     /// 
     /// Returns `false` when the user is not assigned to this game.
@@ -161,10 +323,74 @@ impl GameInstance {
         for player in &mut self.players {
             if player.uuid() == uuid {
                 player.user.set_connected(true);
+                player.reoccupy();
+                let game_master = player.is_game_master();
+                self.generation += 1;
+                self.abandoned_since = None;
+                if game_master {
+                    self.game_master_active();
+                }
                 return true;
             }
         }
-        false
+        // Waiting users follow the lobby but do not keep the game alive
+        match self.waitlist.user_mut(uuid) {
+            Some(user) => {
+                user.set_connected(true);
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Updates the user entry to reflect that the user is no longer connected, the seat of a player is marked as vacant for `reason`.
+    /// 
+    /// # Returns
+    /// `true` when the user was connected before.
+    /// 
+    /// `false` when the user is not assigned to this game or was already marked as disconnected.
+    pub fn user_disconnected(&mut self, uuid: Uuid, reason: VacancyReason) -> bool {
+        let user = match self.players.iter_mut().find(|player| player.uuid() == uuid) {
+            Some(player) => {
+                // The client closes the sse stream right before it sends the leave request,
+                // so leaving replaces a lost connection
+                if player.user.connected() || reason == VacancyReason::Left {
+                    player.vacate(reason);
+                }
+                Some(&mut player.user)
+            },
+            None => self.waitlist.user_mut(uuid),
+        };
+        match user {
+            Some(user) if user.connected() => {
+                user.set_connected(false);
+                true
+            },
+            _ => false,
+        }
+    }
+
+    /// Remembers that the game is abandoned, when no player is connected anymore.
+    /// 
+    /// # Returns
+    /// - `Some(generation)` when the game is abandoned, the game may only be deleted if the [generation](#method.generation) is still the same later.
+    /// - `None` when at least one player is still connected.
+    pub fn mark_abandoned(&mut self) -> Option<u64> {
+        if !self.abandoned() {
+            return None;
+        }
+        self.abandoned_since.get_or_insert_with(Instant::now);
+        Some(self.generation)
+    }
+
+    /// Returns the current generation of this game, see [mark_abandoned](#method.mark_abandoned).
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Returns how long no player has been connected to this game.
+    pub fn abandoned_for(&self) -> Option<Duration> {
+        self.abandoned_since.map(|since| since.elapsed())
     }
 
     /// Checks if players are still connected to this game
@@ -173,14 +399,495 @@ impl GameInstance {
     /// `true` when no player is connected to the game
     /// 
     /// `false` when at least one player is still connected to the game
-    pub fn abandoned(&mut self) -> bool {
-        let mut player_connected = false;
-        for player in self.players.iter_mut() {
-            if player.user.connected {
-                player_connected = true;
+    pub fn abandoned(&self) -> bool {
+        !self.players.iter().any(|player| player.user.connected)
+    }
+
+    /// Returns the number of players that are currently connected to this game.
+    pub fn connected_players(&self) -> usize {
+        self.players.iter().filter(|player| player.user.connected()).count()
+    }
+
+    /// Checks if the maximum number of players has joined the game.
+    pub fn is_full(&self) -> bool {
+        self.seats.taken() >= self.settings.max_players
+    }
+
+    /// Returns the users that wait for a seat.
+    pub fn waitlist(&self) -> &Waitlist {
+        &self.waitlist
+    }
+
+    /// Adds the user to the waitlist, see [LobbySettings::enable_waitlist]().
+    /// 
+    /// # Returns
+    /// - `Ok(position)` the position of the user on the waitlist, the first user has the position 1.
+    /// - `Err(UserRegistrationError::GameFull)` when the waitlist is disabled or full.
+    pub fn join_waitlist(&mut self, user: User) -> Result<usize, UserRegistrationError> {
+        if !self.settings.enable_waitlist {
+            return Err(UserRegistrationError::GameFull);
+        }
+        self.waitlist.push(user).ok_or(UserRegistrationError::GameFull)
+    }
+
+    /// Moves users from the waitlist into the free seats, the user that waits the longest first.
+    /// 
+    /// # Returns
+    /// A batch containing the targeted event `Promoted` with the [UserRegistration]() for each promoted user,
+    /// the new `PlayerList` and `LobbyStatus` are added when at least one user was promoted.
+    pub fn promote_waitlisted(&mut self) -> EventBatch {
+        let mut events = EventBatch::new(&self.channel);
+        let mut promoted = false;
+        while !self.is_full() {
+            let user = match self.waitlist.pop_front() {
+                Some(user) => user,
+                None => break,
+            };
+            info!("{} was promoted from the waitlist of game {}", user.name(), self.game_code);
+            events.push_to(Some(user.uuid()), GameEvent::Promoted(UserRegistration::from_user(&user)));
+            self.add_user(user);
+            promoted = true;
+        }
+        if promoted {
+            events.append(self.player_list_events());
+            events.append(self.lobby_status_events());
+        }
+        events
+    }
+
+    /// Removes all users from the waitlist.
+    /// 
+    /// # Returns
+    /// - A batch containing the targeted event `WaitlistClosed` for each removed user.
+    /// - The removed users, they still have to be unregistered from the [GameManager](../struct.GameManager.html).
+    pub fn close_waitlist(&mut self) -> (EventBatch, Vec<User>) {
+        let mut events = EventBatch::new(&self.channel);
+        let users = self.waitlist.clear();
+        for user in &users {
+            events.push_to(Some(user.uuid()), GameEvent::WaitlistClosed);
+        }
+        (events, users)
+    }
+
+    /// Starts the game, new players can no longer join and waiting users lose their place.
+    /// 
+    /// The turn order is drawn at random with the rng of the game.
+    /// 
+    /// # Returns
+    /// - A batch containing the events `GameStarted` and `TurnChanged` for all players followed by the targeted event `WaitlistClosed` for each waiting user.
+    /// - The users that were removed from the waitlist, they still have to be unregistered from the [GameManager](../struct.GameManager.html).
+    /// - `Err(StartGameError)` when the game can not be started, the game is not changed in this case.
+    pub fn start(&mut self) -> Result<(EventBatch, Vec<User>), StartGameError> {
+        if !matches!(self.game_state, GameState::Lobby) {
+            return Err(StartGameError::AlreadyStarted);
+        }
+        if !self.lobby_status().can_start {
+            return Err(StartGameError::NotEnoughPlayers);
+        }
+        self.game_state = GameState::Running;
+        self.deal_tiles();
+        for player in &mut self.players {
+            player.set_money(STARTING_MONEY);
+        }
+        let mut order: Vec<Uuid> = self.players.iter().map(|player| player.uuid()).collect();
+        order.shuffle(&mut self.rng);
+        self.turns = TurnManager::new(order);
+        info!("Game {} was started with {} players", self.game_code, self.players.len());
+        let mut events = EventBatch::new(&self.channel);
+        events.push(GameEvent::GameStarted);
+        self.game_log.record(None, LogAction::GameStarted);
+        if let Some(player_id) = self.current_player().map(|player| player.id()) {
+            events.push(GameEvent::TurnChanged(player_id));
+            self.game_log.record(Some(player_id), LogAction::TurnChanged);
+        }
+        let (closed, users) = self.close_waitlist();
+        events.append(closed);
+        Ok((events, users))
+    }
+
+    /// Fills a new [TileBag](../base_game/struct.TileBag.html) and deals [HAND_SIZE](../base_game/constant.HAND_SIZE.html) tiles to every player in seat order.
+    fn deal_tiles(&mut self) {
+        let mut bag = TileBag::shuffled(&mut self.rng);
+        for player in &mut self.players {
+            for tile in std::iter::from_fn(|| bag.draw()).take(HAND_SIZE) {
+                player.add_tile(tile);
+            }
+        }
+        self.tile_bag = Some(bag);
+    }
+
+    /// Returns the player whose turn it is, `None` while the game is in the lobby.
+    /// 
+    /// Players take their turns in the order of the [TurnManager](turns/struct.TurnManager.html), when the player whose
+    /// turn it is was removed the turn passes to the next player.
+    pub fn current_player(&self) -> Option<&Player> {
+        let uuid = self.turns.current()?;
+        self.players.iter().find(|player| player.uuid() == uuid)
+    }
+
+    /// Returns a page of the [GameLog](game_log/struct.GameLog.html) with the entries with an index of at least `since`, the oldest entry first.
+    pub fn game_log(&self, since: usize) -> GameLogPage {
+        self.game_log.since(since)
+    }
+
+    /// Returns the final standings, `None` until the game is finished.
+    pub fn results(&self) -> Option<&[Standing]> {
+        match &self.game_state {
+            GameState::Finished(standings) => Some(standings),
+            _ => None,
+        }
+    }
+
+    /// Returns what each player did in the game in seat order, `None` until the game is finished.
+    pub fn game_stats(&self) -> Option<Vec<PlayerStatsEntry>> {
+        self.results()?;
+        Some(self.players.iter().map(|player| PlayerStatsEntry { player_id: player.id(), stats: player.stats().clone() }).collect())
+    }
+
+    /// Returns the event `GameEnded` with the final standings for `recipient`, the batch is empty until the game is finished.
+    /// 
+    /// Players that connect after the end receive it with their other connection events.
+    pub fn game_ended_events(&self, recipient: Option<Uuid>) -> EventBatch {
+        let mut events = EventBatch::new(&self.channel);
+        if let Some(standings) = self.results() {
+            events.push_to(recipient, GameEvent::GameEnded(standings.to_vec()));
+        }
+        events
+    }
+
+    /// Returns whose turn it is and what they have to do next, `None` while the game is in the lobby.
+    pub fn turn_status(&self) -> Option<TurnStatus> {
+        Some(TurnStatus {
+            player_id: self.current_player()?.id(),
+            phase: self.turns.phase(),
+            disposal: self.disposals.front().copied(),
+            turn_token: self.turns.token(),
+        })
+    }
+
+    /// Checks that `token` is the token of the current step of the turn, see [TurnManager::token](turns/struct.TurnManager.html#method.token).
+    /// 
+    /// Gameplay requests are checked with this before they are applied while the game is locked for writing,
+    /// so a retried request is rejected without changing the game.
+    pub fn check_turn_token(&self, token: Uuid) -> Result<(), TurnError> {
+        match self.game_state {
+            GameState::Lobby => Err(TurnError::NotStarted),
+            GameState::Finished(_) => Err(TurnError::Finished),
+            GameState::Running if token != self.turns.token() => Err(TurnError::StaleToken),
+            GameState::Running => Ok(()),
+        }
+    }
+
+    /// Returns the number of shares of `chain` that no player holds.
+    pub fn bank_shares(&self, chain: HotelChain) -> u32 {
+        self.bank.get(&chain).copied().unwrap_or_default()
+    }
+
+    /// Moves `count` shares of `chain` from the bank to the player at `index`.
+    /// 
+    /// The bank never gives out more shares than it holds, so the player receives fewer shares when the bank runs out.
+    fn give_shares(&mut self, index: usize, chain: HotelChain, count: u32) {
+        let bank = self.bank.entry(chain).or_default();
+        let count = count.min(*bank);
+        *bank -= count;
+        self.players[index].add_shares(chain, count);
+    }
+
+    /// Moves `count` shares of `chain` from the player at `index` back to the bank, at most the shares the player holds are moved.
+    fn return_shares(&mut self, index: usize, chain: HotelChain, count: u32) {
+        let count = count.min(self.players[index].shares(chain));
+        self.players[index].remove_shares(chain, count);
+        *self.bank.entry(chain).or_default() += count;
+    }
+
+    /// Checks the invariants of a started game:
+    /// - every tile is exactly once in the tile bag (drawn or discarded), in a hand or on the board
+    /// - the [Board::check_invariants](board/struct.Board.html#method.check_invariants) hold
+    /// - the players and the bank hold [SHARES_PER_CHAIN](../base_game/constant.SHARES_PER_CHAIN.html) shares of each chain together
+    /// - no player holds more than [HAND_SIZE](../base_game/constant.HAND_SIZE.html) tiles
+    /// 
+    /// The tiles are only checked once the game has started.
+    /// 
+    /// # Returns
+    /// `Err(InvariantError)` naming the first tile, chain or player that breaks an invariant.
+    #[cfg(debug_assertions)]
+    pub fn check_game_invariants(&self) -> Result<(), InvariantError> {
+        if let Some(bag) = &self.tile_bag {
+            let mut counts: BTreeMap<board::Position, usize> = board::Position::all().map(|position| (position, 0)).collect();
+            let hands = self.players.iter().flat_map(|player| player.hand().iter().copied());
+            for tile in bag.tiles().chain(hands) {
+                *counts.entry(tile.position()).or_default() += 1;
+            }
+            for (position, count) in &mut counts {
+                if self.board.is_occupied(*position) {
+                    *count += 1;
+                }
+            }
+            if let Some((tile, count)) = counts.into_iter().find(|(_, count)| *count != 1) {
+                return Err(InvariantError::TileCount { tile, count });
+            }
+        }
+        self.board.check_invariants()?;
+        for chain in HotelChain::ALL {
+            let total = self.bank_shares(chain) + self.players.iter().map(|player| player.shares(chain)).sum::<u32>();
+            if total != SHARES_PER_CHAIN {
+                return Err(InvariantError::SharesMismatch { chain, total });
+            }
+        }
+        match self.players.iter().find(|player| player.hand().len() > HAND_SIZE) {
+            Some(player) => Err(InvariantError::HandTooLarge { player_id: player.id(), size: player.hand().len() }),
+            None => Ok(()),
+        }
+    }
+
+    /// Logs and panics when [check_game_invariants](#method.check_game_invariants) fails, does nothing in release builds.
+    fn debug_check_invariants(&self) {
+        #[cfg(debug_assertions)]
+        if let Err(err) = self.check_game_invariants() {
+            rocket::log::private::error!("Game {}: invariant violated: {}", self.game_code, err);
+            panic!("Game {}: invariant violated: {}", self.game_code, err);
+        }
+    }
+
+    /// Checks if the player with `uuid` is the game master of this game.
+    pub fn is_game_master(&self, uuid: Uuid) -> bool {
+        self.players.iter().any(|player| player.uuid() == uuid && player.is_game_master())
+    }
+
+    /// Returns the uuid of the game master.
+    pub fn game_master(&self) -> Option<Uuid> {
+        self.players.iter().find(|player| player.is_game_master()).map(|player| player.uuid())
+    }
+
+    /// Remembers that the game master has acted, this restarts the time after which the game master is rotated.
+    pub fn game_master_active(&mut self) {
+        self.master_active_at = Instant::now();
+        self.master_generation += 1;
+    }
+
+    /// Checks if the game master has idled for longer than [LobbySettings::master_idle_rotate_secs]() at `now`.
+    /// 
+    /// The game master is only rotated while the game is in the lobby, enough players to start are connected
+    /// and the game is not waiting to be deleted.
+    /// 
+    /// # Returns
+    /// - `Some(generation)` when the game master should be rotated, the rotation may only be applied if the generation is still the same later.
+    /// - `None` when the game master should not be rotated.
+    pub fn idle_master_rotation(&self, now: Instant) -> Option<u64> {
+        let idle_for = Duration::from_secs(self.settings.master_idle_rotate_secs);
+        if idle_for.is_zero() || !matches!(self.game_state, GameState::Lobby) || self.abandoned_since.is_some() {
+            return None;
+        }
+        if self.connected_players() < self.settings.min_players || now.saturating_duration_since(self.master_active_at) < idle_for {
+            return None;
+        }
+        Some(self.master_generation)
+    }
+
+    /// Makes the next connected player in seat order the game master, see [idle_master_rotation](#method.idle_master_rotation).
+    /// 
+    /// # Returns
+    /// - `Some(EventBatch)` containing the events `NewGameMaster` and `PlayerList` when the game master was rotated.
+    /// - `None` when the game master has acted since `generation` was read or no other player is connected.
+    pub fn rotate_idle_game_master(&mut self, generation: u64) -> Option<EventBatch> {
+        if generation != self.master_generation {
+            return None;
+        }
+        let current = self.players.iter().position(|player| player.is_game_master())?;
+        let next = (1..self.players.len())
+            .map(|offset| &self.players[(current + offset) % self.players.len()])
+            .find(|player| player.user.connected())?;
+        let (uuid, player_id) = (next.uuid(), next.id());
+        self.set_game_master(uuid);
+        info!("Game master of game {} idled for too long, player {} is the new game master", self.game_code, player_id);
+        let mut events = EventBatch::new(&self.channel);
+        let data = NewGameMaster { player_id, reason: GameMasterChangeReason::IdleRotation };
+        events.push(GameEvent::NewGameMaster(data));
+        events.append(self.player_list_events());
+        Some(events)
+    }
+
+    /// Returns the log of rejected join requests.
+    pub fn security_log(&self) -> &SecurityLog {
+        &self.security_log
+    }
+
+    /// Returns the log of rejected join requests.
+    pub fn security_log_mut(&mut self) -> &mut SecurityLog {
+        &mut self.security_log
+    }
+
+    /// Checks if the lobby is locked.
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Locks or unlocks the lobby.
+    /// 
+    /// While the lobby is locked new players can not join.
+    pub fn set_locked(&mut self, locked: bool) {
+        self.locked = locked;
+    }
+
+    /// Returns the invites of this game.
+    pub fn invites(&self) -> &Invites {
+        &self.invites
+    }
+
+    /// Returns the invites of this game.
+    pub fn invites_mut(&mut self) -> &mut Invites {
+        &mut self.invites
+    }
+
+    /// Returns the lobby settings of this game.
+    pub fn settings(&self) -> &LobbySettings {
+        &self.settings
+    }
+
+    /// Sets after how many seconds an idle game master is replaced, see [LobbySettings::master_idle_rotate_secs]().
+    pub fn set_master_idle_rotate_secs(&mut self, secs: u64) {
+        self.settings.master_idle_rotate_secs = secs;
+    }
+
+    /// Replaces all lobby settings at once.
+    /// 
+    /// # Returns
+    /// - `Ok(())` when the settings where replaced.
+    /// - `Err(Vec<FieldError>)` containing all invalid fields, see [LobbySettings::validate](). The settings are not changed in that case.
+    pub fn set_settings(&mut self, settings: LobbySettings) -> Result<(), Vec<FieldError>> {
+        settings.validate(self.players.len())?;
+        self.settings = settings;
+        Ok(())
+    }
+
+    /// Returns the current player counts of the lobby, see [LobbyStatus]().
+    pub fn lobby_status(&self) -> LobbyStatus {
+        let current_players = self.connected_players();
+        LobbyStatus {
+            current_players,
+            min_players: self.settings.min_players,
+            max_players: self.settings.max_players,
+            can_start: (self.settings.min_players..=self.settings.max_players).contains(&current_players),
+        }
+    }
+
+    /// Returns all players of the game in seat order, see [PlayerListEntry]().
+    pub fn player_list(&self) -> Vec<PlayerListEntry> {
+        self.players.iter().map(|player| PlayerListEntry {
+            player_id: player.id(),
+            seat: player.seat().seat,
+            name: player.username(),
+            connected: player.user.connected(),
+            game_master: player.is_game_master(),
+            vacancy: player.vacancy(),
+        }).collect()
+    }
+
+    /// Returns a batch containing the `PlayerList` event with the current [player_list](#method.player_list).
+    /// 
+    /// The event is send when players join, connect or leave. Several `PlayerList` events that are send in quick succession
+    /// are merged by the [EventBus](../../events/struct.EventBus.html).
+    pub fn player_list_events(&self) -> EventBatch {
+        let mut events = EventBatch::new(&self.channel);
+        events.push(GameEvent::PlayerList(self.player_list()));
+        events
+    }
+
+    /// Returns what the server renders into the lobby page, see [LobbyPage]().
+    /// 
+    /// `urid` is the recovery id of the visitor, when it belongs to a player of this game the page offers to rejoin as that player.
+    pub fn lobby_page(&self, urid: Option<&Urid>) -> LobbyPage {
+        LobbyPage {
+            game_code: self.game_code,
+            players: self.player_list(),
+            status: self.lobby_status(),
+            require_invite: self.settings.require_invite(),
+            locked: self.is_locked(),
+            rejoin_as: urid
+                .and_then(|urid| self.players.iter().find(|player| player.user.urid == *urid))
+                .map(|player| player.username()),
+        }
+    }
+
+    /// Returns why the seat of the player with `uuid` is vacant.
+    pub fn vacancy(&self, uuid: Uuid) -> Option<Vacancy> {
+        self.players.iter().find(|player| player.uuid() == uuid)?.vacancy()
+    }
+
+    /// Returns a batch containing the event `SeatVacancyChanged` for the player with `uuid`.
+    /// 
+    /// The data contains the `player_id` and the `vacancy` of the player, the vacancy is `null` when the player is connected again.
+    /// The batch is empty when the user is not a player of this game.
+    pub fn seat_vacancy_events(&self, uuid: Uuid) -> EventBatch {
+        let mut events = EventBatch::new(&self.channel);
+        if let Some(player) = self.players.iter().find(|player| player.uuid() == uuid) {
+            let change = SeatVacancyChange { player_id: player.id(), vacancy: player.vacancy() };
+            events.push(GameEvent::SeatVacancyChanged(change));
+        }
+        events
+    }
+
+    /// Returns a batch containing the `LobbyStatus` event with the current [LobbyStatus]().
+    pub fn lobby_status_events(&self) -> EventBatch {
+        let mut events = EventBatch::new(&self.channel);
+        events.push(GameEvent::LobbyStatus(self.lobby_status()));
+        events
+    }
+
+    /// Returns the event `LobbySettings` containing the complete [LobbySettings]() of the game.
+    pub fn lobby_settings_events(&self) -> EventBatch {
+        let mut events = EventBatch::new(&self.channel);
+        events.push(GameEvent::LobbySettings(self.settings.clone()));
+        events
+    }
+
+    /// Applies the operations of the game master `game_master` in the order lock, kicks, message.
+    /// 
+    /// The game master can not kick themselves, that kick is skipped without aborting the other operations.
+    /// 
+    /// # Returns
+    /// - The outcome of each operation.
+    /// - The events for all players in the order in which the operations where applied,
+    ///   the kicked players receive the targeted event `Kicked`.
+    /// - The users of the kicked players, they still have to be unregistered from the [GameManager](../struct.GameManager.html).
+    pub fn lobby_admin(&mut self, game_master: Uuid, request: &LobbyAdminRequest) -> (LobbyAdminResult, EventBatch, Vec<User>) {
+        let mut result = LobbyAdminResult::default();
+        let mut events = EventBatch::new(&self.channel);
+        if let Some(locked) = request.lock {
+            self.set_locked(locked);
+            result.locked = Some(locked);
+            events.push(if locked { GameEvent::LobbyLocked } else { GameEvent::LobbyUnlocked });
+        }
+        let mut kicked = Vec::new();
+        for &player_id in &request.kick {
+            let is_self = self.players.iter().any(|player| player.id() == player_id && player.uuid() == game_master);
+            let outcome = if is_self {
+                KickOutcome::Skipped
+            } else if let Some((user, left)) = self.remove_player(player_id) {
+                info!("Player {} was kicked from game {}", user.name(), self.game_code);
+                events.append(left);
+                events.push_to(Some(user.uuid()), GameEvent::Kicked);
+                kicked.push(user);
+                KickOutcome::Kicked
+            } else {
+                KickOutcome::NotFound
+            };
+            result.kicks.push(KickResult { player_id, outcome });
+        }
+        if !kicked.is_empty() {
+            let promoted = self.promote_waitlisted();
+            if promoted.is_empty() {
+                events.append(self.player_list_events());
+                events.append(self.lobby_status_events());
+            } else {
+                events.append(promoted);
             }
         }
-        !player_connected
+        if let Some(message) = &request.message {
+            events.push(GameEvent::LobbyMessage(message.clone()));
+            result.message_sent = true;
+        }
+        (result, events, kicked)
     }
 
     /// Returns the current game state
@@ -188,30 +895,375 @@ impl GameInstance {
         &self.game_state
     }
 
-    /// Returns a HashSet containing all uuids of the players that are assigned to this game instance.
-    pub fn player_uuids(&self) -> HashSet<Uuid> {
-        let mut set = HashSet::new();
-        for player in &self.players {
-            set.insert(player.uuid());
-        }
-        set
+    /// Returns the board of this game.
+    pub fn board(&self) -> &Board {
+        &self.board
+    }
+
+    /// Returns the tiles of the player with `uuid` and the number of tiles left in the bag, `None` when the user is not a player of this game.
+    pub fn hand(&self, uuid: Uuid) -> Option<Hand> {
+        let player = self.players.iter().find(|player| player.uuid() == uuid)?;
+        Some(Hand {
+            tiles: player.hand().to_vec(),
+            tiles_remaining: self.tile_bag.as_ref().map(TileBag::remaining).unwrap_or_default(),
+        })
+    }
+
+    /// Returns the shares of all players and the bank together with the money of the player with `uuid`,
+    /// `None` when the user is not a player of this game.
+    pub fn stocks(&self, uuid: Uuid) -> Option<StockOverview> {
+        let player = self.players.iter().find(|player| player.uuid() == uuid)?;
+        Some(StockOverview {
+            own: Portfolio { money: player.money(), shares: player.portfolio().clone() },
+            players: self.players.iter().map(|player| PlayerShares { player_id: player.id(), shares: player.portfolio().clone() }).collect(),
+            bank: HotelChain::ALL.into_iter().map(|chain| (chain, self.bank_shares(chain))).collect(),
+        })
     }
 
     /// Returns the user registration for the user with `name` if that user exists.
+    /// 
+    /// The registration of a waiting user contains their [waitlist_position](../../request_data/struct.UserRegistration.html#method.waitlist_position).
     pub fn user_registration(&self, name: &str) -> Option<UserRegistration> {
         for player in &self.players {
             if player.user.name() == name {
                 return Some(UserRegistration::from_user(&player.user));
             }
         }
-        None
+        let user = self.waitlist.user_by_name(name)?;
+        Some(UserRegistration::from_user(user).waitlisted(self.waitlist.position(user.uuid())?))
+    }
+}
+
+/// The settings of a game that can be changed by the game master while the game is in the lobby.
+/// 
+/// Serialized this is the settings document that [patch_lobby_settings](../../paths/lobby_api/fn.patch_lobby_settings.html)
+/// merges a patch into, missing fields are set to their default value.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LobbySettings {
+    /// The number of players that are required to start the game
+    min_players: usize,
+    /// The number of players that can join the game at most
+    max_players: usize,
+    /// New players can only join with an invite
+    require_invite: bool,
+    /// The game master is replaced when they do not act for this many seconds, see [master_idle_rotate_secs](#method.master_idle_rotate_secs)
+    master_idle_rotate_secs: u64,
+    /// Users that join a full lobby wait for a seat, see [enable_waitlist](#method.enable_waitlist)
+    enable_waitlist: bool,
+}
+
+impl LobbySettings {
+    /// Creates new lobby settings, the settings are validated when they are applied with [GameInstance::set_settings](struct.GameInstance.html#method.set_settings).
+    pub fn new(min_players: usize, max_players: usize, require_invite: bool) -> Self {
+        Self {
+            min_players,
+            max_players,
+            require_invite,
+            master_idle_rotate_secs: 0,
+            enable_waitlist: false,
+        }
+    }
+
+    /// Checks all settings for a game in which `joined_players` players have already joined.
+    /// 
+    /// The player limits have to be within [MIN_PLAYERS]() and [MAX_PLAYERS](), `max_players` can not be smaller than the number
+    /// of joined players and `min_players` can not be greater than `max_players`.
+    /// 
+    /// # Returns
+    /// `Err(Vec<FieldError>)` containing every invalid field, not only the first one.
+    pub fn validate(&self, joined_players: usize) -> Result<(), Vec<FieldError>> {
+        let mut errors = Vec::new();
+        let max_players = MIN_PLAYERS.max(joined_players)..=MAX_PLAYERS;
+        if !max_players.contains(&self.max_players) {
+            errors.push(FieldError::new("max_players", "out_of_range", max_players));
+        }
+        if !(MIN_PLAYERS..=MAX_PLAYERS).contains(&self.min_players) {
+            errors.push(FieldError::new("min_players", "out_of_range", MIN_PLAYERS..=MAX_PLAYERS));
+        } else if self.min_players > self.max_players {
+            errors.push(FieldError::new("min_players", "greater_than_max_players", MIN_PLAYERS..=self.max_players.max(MIN_PLAYERS)));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Sets the value of [master_idle_rotate_secs](#method.master_idle_rotate_secs).
+    pub fn with_master_idle_rotate_secs(mut self, secs: u64) -> Self {
+        self.master_idle_rotate_secs = secs;
+        self
+    }
+
+    /// Sets the value of [enable_waitlist](#method.enable_waitlist).
+    pub fn with_enable_waitlist(mut self, enable_waitlist: bool) -> Self {
+        self.enable_waitlist = enable_waitlist;
+        self
+    }
+
+    /// Returns the number of players that are required to start the game.
+    pub fn min_players(&self) -> usize {
+        self.min_players
+    }
+
+    /// Returns the number of players that can join the game at most.
+    pub fn max_players(&self) -> usize {
+        self.max_players
+    }
+
+    /// Checks if new players need an [Invite](invites/struct.Invite.html) to join the game.
+    /// 
+    /// Players that are already part of the game can always rejoin.
+    pub fn require_invite(&self) -> bool {
+        self.require_invite
+    }
+
+    /// Returns after how many seconds without an action of the game master the next connected player becomes the game master,
+    /// `0` disables the rotation.
+    /// 
+    /// This keeps lobbies of strangers from stalling when the game master does not start the game.
+    pub fn master_idle_rotate_secs(&self) -> u64 {
+        self.master_idle_rotate_secs
+    }
+
+    /// Checks if users that join a full lobby are put on the [Waitlist](waitlist/struct.Waitlist.html) instead of being rejected.
+    /// 
+    /// Waiting users take the seats that become free when players are kicked or the maximum number of players is raised.
+    pub fn enable_waitlist(&self) -> bool {
+        self.enable_waitlist
+    }
+}
+
+impl Default for LobbySettings {
+    fn default() -> Self {
+        Self {
+            min_players: MIN_PLAYERS,
+            max_players: MAX_PLAYERS,
+            require_invite: false,
+            master_idle_rotate_secs: 0,
+            enable_waitlist: false,
+        }
     }
 }
 
+/// A player as it is shown in the player list of the clients.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PlayerListEntry {
+    /// Identifies the player, see [Player::id](../base_game/struct.Player.html#method.id)
+    pub player_id: u32,
+    /// The seat of the player starting at 1, see [SeatAssignment](seats/struct.SeatAssignment.html)
+    pub seat: u8,
+    /// Only used for displaying
+    pub name: String,
+    /// `false` when the player has not yet opened the sse stream or has left the game
+    pub connected: bool,
+    /// `true` for the game master, clients show a badge for this player
+    pub game_master: bool,
+    /// Why the seat of the player is vacant, missing while the player is connected or has not connected yet
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vacancy: Option<Vacancy>,
+}
+
+/// The data of the event `SeatVacancyChanged`, see [GameInstance::seat_vacancy_events]().
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SeatVacancyChange {
+    pub player_id: u32,
+    /// `None` when the player is connected again
+    pub vacancy: Option<Vacancy>,
+}
+
+/// Why the game master was changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameMasterChangeReason {
+    /// The game master did not act for [LobbySettings::master_idle_rotate_secs]()
+    IdleRotation,
+}
+
+/// The data of the `NewGameMaster` event.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NewGameMaster {
+    /// The [id](../base_game/struct.Player.html#method.id) of the new game master
+    pub player_id: u32,
+    pub reason: GameMasterChangeReason,
+}
+
+/// The player counts of a lobby.
+/// 
+/// Send to all players with the `LobbyStatus` event whenever a player joins or leaves the lobby or the settings change.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LobbyStatus {
+    /// The number of players that are currently connected
+    pub current_players: usize,
+    /// The number of players that is required to start the game
+    pub min_players: usize,
+    /// The number of players that can join the game at most
+    pub max_players: usize,
+    /// `true` when the game can be started with the current players
+    pub can_start: bool,
+}
+
+/// The state of a lobby that is rendered into the lobby page, so the page shows the lobby even when the scripts can not be loaded.
+/// 
+/// See [lobby_join](../../paths/pages/fn.lobby_join.html).
+pub struct LobbyPage {
+    pub game_code: GameCode,
+    pub players: Vec<PlayerListEntry>,
+    pub status: LobbyStatus,
+    pub require_invite: bool,
+    pub locked: bool,
+    /// The name of the player that visits the page, `None` when the visitor is not a player of the game
+    pub rejoin_as: Option<String>,
+}
+
 /// The different states a game can be in
 pub enum GameState {
     /// Signals that this game is still in the lobby and players can join
     Lobby,
+    /// The game master has started the game, see [GameInstance::start](struct.GameInstance.html#method.start)
+    Running,
+    /// A player has ended the game, see [GameInstance::end_game](struct.GameInstance.html#method.end_game).
+    /// Contains the final standings ordered by place.
+    Finished(Vec<Standing>),
+}
+
+/// The reasons why a game can not be started, see [GameInstance::start]().
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum StartGameError {
+    #[error("the game has already started")]
+    AlreadyStarted,
+    /// Less than [min_players](struct.LobbySettings.html#structfield.min_players) players are connected
+    #[error("not enough players are connected")]
+    NotEnoughPlayers,
+}
+
+/// The reasons why a player can not place a tile, see [GameInstance::place_tile]().
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PlayTileError {
+    /// The game is not started or the user is not the [current_player](struct.GameInstance.html#method.current_player)
+    #[error(transparent)]
+    Turn(#[from] TurnError),
+    #[error("the player does not hold this tile")]
+    NotInHand,
+    /// The tile that was placed before founds a chain or merges chains of the same size, the player has to choose
+    /// the chain with [GameInstance::found_chain]() or [GameInstance::choose_survivor]() first
+    #[error("the player has to choose a chain first")]
+    ChainChoicePending,
+    /// The player has already placed a tile in this turn and can now buy shares
+    #[error("the player has already placed a tile in this turn")]
+    AlreadyPlaced,
+    /// The board rules do not allow the tile, see [Board::check_placement](board/struct.Board.html#method.check_placement)
+    #[error(transparent)]
+    Board(#[from] PlaceTileError),
+}
+
+/// The reasons why shares can not be bought, see [GameInstance::buy_stock]().
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum BuyStockError {
+    /// The game is not started or the user is not the [current_player](struct.GameInstance.html#method.current_player)
+    #[error(transparent)]
+    Turn(#[from] TurnError),
+    /// The player has to place a tile or found a chain first
+    #[error("shares can only be bought after a tile was placed")]
+    WrongPhase,
+    #[error("at most {} shares can be bought in one turn", MAX_SHARES_PER_TURN)]
+    TooManyShares,
+    #[error("{0} is not on the board")]
+    ChainNotFounded(HotelChain),
+    /// The bank holds less shares of the chain than requested
+    #[error("the bank does not hold enough shares of {0}")]
+    NotEnoughShares(HotelChain),
+    /// The shares cost more than the player has, contains the missing amount
+    #[error("{shortfall} more is needed to pay {cost}")]
+    NotEnoughMoney { cost: u32, shortfall: u32 },
+}
+
+/// The reasons why a chain can not be founded, see [GameInstance::found_chain]().
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum FoundChainError {
+    /// The game is not started or the user is not the [current_player](struct.GameInstance.html#method.current_player)
+    #[error(transparent)]
+    Turn(#[from] TurnError),
+    #[error("no tile was placed that founds a chain")]
+    NothingToFound,
+    #[error("{0:?} is already on the board")]
+    ChainOnBoard(HotelChain),
+}
+
+/// The reasons why the survivor of a merger can not be chosen, see [GameInstance::choose_survivor]().
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ChooseSurvivorError {
+    /// The game is not started or the user is not the [current_player](struct.GameInstance.html#method.current_player)
+    #[error(transparent)]
+    Turn(#[from] TurnError),
+    #[error("no tile was placed that merges chains of the same size")]
+    NothingToChoose,
+    /// Only one of the largest chains that are merged can survive
+    #[error("{0} is not one of the largest chains of the merger")]
+    NotLargest(HotelChain),
+}
+
+/// The reasons why a player can not end the game, see [GameInstance::end_game]().
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum EndGameError {
+    /// The game is not running or the user is not the [current_player](struct.GameInstance.html#method.current_player)
+    #[error(transparent)]
+    Turn(#[from] TurnError),
+    /// A chain has to be founded, a survivor chosen or shares of a defunct chain disposed first
+    #[error("the current turn has a decision pending")]
+    DecisionPending,
+    /// No chain has [END_CHAIN_SIZE](board/constant.END_CHAIN_SIZE.html) tiles and not all chains on the board are safe
+    #[error("no chain has {} tiles and not all chains are safe", board::END_CHAIN_SIZE)]
+    ConditionsNotMet,
+}
+
+/// The reasons why the notes of a game can not be changed, see [GameInstance::annotate]().
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum AnnotateError {
+    #[error("only the game master can change the notes")]
+    NotGameMaster,
+    /// Notes can only be written once the game is finished
+    #[error("the game is not finished")]
+    NotFinished,
+    /// The title or the notes are too long or the title is empty
+    #[error("the title or the notes are invalid")]
+    InvalidFields(Vec<FieldError>),
+}
+
+/// The reasons why a decision about the shares of a defunct chain is rejected, see [GameInstance::merger_decision]().
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum MergerDecisionError {
+    #[error("no shareholder has to decide about their shares")]
+    NothingToDecide,
+    /// Another shareholder has to decide first, contains the turn as it actually is
+    #[error("it is not the decision of this player")]
+    NotYourDecision(Box<TurnStatus>),
+    /// The shares that are sold, traded and kept do not add up to the shares the player holds
+    #[error("the decision has to cover exactly the {held} shares the player holds")]
+    SharesMismatch { held: u32 },
+    /// Shares can only be traded two for one
+    #[error("an odd number of shares can not be traded")]
+    OddTrade,
+    /// The bank holds less shares of the survivor than the trade requires
+    #[error("the bank does not hold enough shares of {0}")]
+    NotEnoughShares(HotelChain),
+}
+
+/// The invariants of a game that are broken, see [GameInstance::check_game_invariants]().
+#[cfg(debug_assertions)]
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum InvariantError {
+    /// The tile is not exactly once in the tile bag, the hands and on the board together
+    #[error("tile {tile} exists {count} times")]
+    TileCount { tile: board::Position, count: usize },
+    #[error(transparent)]
+    Board(#[from] board::BoardInvariantError),
+    /// The players and the bank do not hold [SHARES_PER_CHAIN](../base_game/constant.SHARES_PER_CHAIN.html) shares of the chain together
+    #[error("there are {total} shares of {chain}")]
+    SharesMismatch { chain: HotelChain, total: u32 },
+    #[error("player {player_id} holds {size} tiles")]
+    HandTooLarge { player_id: u32, size: usize },
 }
 
 /// Unique 9 character code that identifies a game
@@ -246,52 +1298,24 @@ impl GameCode {
 
     /// Construct a new game code from string
     /// 
-    /// Input should be a in the format like the result of [GameCode::to_string()](#method.to_string).
+    /// Input should be a in the format like the result of [GameCode::to_string()](#method.to_string),
+    /// see [parse_game_code](../../rules/fn.parse_game_code.html).
     /// 
     /// # Returns
     /// `Some(Self)` when the string was valid and the game code was constructed
     /// `None` when the string could not be constructed into a game code
     pub fn from_string(string: &str) -> Option<Self> {
-        let mut game_code: [char; 8] = ['a','a','a','a','a','a','a','a'];
-        if string.len() > 9 {
-            return None;
-        }
-        let mut second_half = false;
-        for (index, char) in string.chars().enumerate() {
-            let charset: Vec<char> = GAME_CODE_CHARSET.iter().map(|s| *s as char).collect();
-            if index != 4 {
-                if charset.contains(&char) {
-                    if second_half {
-                        game_code[index-1] = char;
-                    } else {
-                        game_code[index] = char;
-                    }
-                } else {
-                    return None;
-                }
-            } else {
-                if char != '-' {
-                    return None;
-                }
-                second_half = true;
-            }
-        } 
-        Some(Self {
-            game_code
-        })
+        parse_game_code(string).map(|game_code| Self { game_code })
     }
 }
 
-impl ToString for GameCode {
-    /// Converts the given value to `String`.
+impl Display for GameCode {
+    /// Formats the game code.
     ///
     /// An example output of this function might be: `A23B-9FRT`
-    fn to_string(&self) -> String {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         let s: String = self.game_code.iter().collect();
         let parts = s.split_at(4);
-        let mut print = String::from(parts.0);
-        print.push('-');
-        print.push_str(parts.1);
-        print
+        write!(f, "{}-{}", parts.0, parts.1)
     }
 }
@@ -0,0 +1,83 @@
+use rand::{Error, RngCore, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+/// Random number generator that is owned by a single [GameInstance](../struct.GameInstance.html).
+///
+/// Every random decision that is made inside a game (for example shuffling the tile bag or the turn order)
+/// has to use this rng so that a game can be reproduced from its seed.
+///
+/// Randomness that is not part of a game (game codes, uuids, urids) is still generated by the [GameManager](../../struct.GameManager.html)
+/// with the global rng.
+#[derive(Debug, Clone)]
+pub struct GameRng {
+    /// The seed from which this rng was created.
+    seed: u64,
+    rng: ChaCha8Rng,
+}
+
+impl Default for GameRng {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GameRng {
+    /// Creates a new rng with a random seed.
+    pub fn new() -> Self {
+        Self::from_seed(rand::random())
+    }
+
+    /// Creates a new rng from `seed`.
+    ///
+    /// Two rngs that are created from the same seed produce the same values.
+    pub fn from_seed(seed: u64) -> Self {
+        Self {
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        }
+    }
+
+    /// Returns the seed from which this rng was created.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+}
+
+impl RngCore for GameRng {
+    fn next_u32(&mut self) -> u32 {
+        self.rng.next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.rng.next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.rng.fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), Error> {
+        self.rng.try_fill_bytes(dest)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{seq::SliceRandom, Rng};
+
+    use super::GameRng;
+
+    #[test]
+    fn test_same_seed_same_values() {
+        let mut first = GameRng::from_seed(42);
+        let mut second = GameRng::from_seed(42);
+        let mut first_values: Vec<u32> = (0..100).collect();
+        let mut second_values = first_values.clone();
+        first_values.shuffle(&mut first);
+        second_values.shuffle(&mut second);
+        assert_eq!(first_values, second_values);
+        assert_eq!(first.gen::<u64>(), second.gen::<u64>());
+        assert_eq!(42, first.seed());
+        assert_ne!(GameRng::from_seed(42).gen::<u64>(), GameRng::from_seed(43).gen::<u64>());
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::BTreeSet;
+
+use serde::{Deserialize, Serialize};
+
+/// The seat of a player, issued by the [SeatPool]() when the player joins.
+///
+/// The assignment never changes while the player is part of the game, recovering a lost connection keeps it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeatAssignment {
+    /// The place at the table starting at 1, players are listed in seat order
+    pub seat: u8,
+    /// See [Player::id](../../base_game/struct.Player.html#method.id)
+    pub public_id: u32,
+}
+
+/// Hands out the [SeatAssignment]()s of a game.
+///
+/// This is the only place that decides which seats and ids are reused:
+/// - a new player takes the lowest free seat, seats of players that left the lobby are free again.
+/// - public ids are never reused, so that clients do not mistake a new player for one that left.
+#[derive(Debug, Default)]
+pub struct SeatPool {
+    taken: BTreeSet<u8>,
+    /// The public id of the player that joined last, ids start at 1
+    last_public_id: u32,
+}
+
+impl SeatPool {
+    /// Assigns the lowest free seat and a new public id.
+    ///
+    /// # Returns
+    /// `None` when `capacity` seats are already taken.
+    pub fn assign(&mut self, capacity: usize) -> Option<SeatAssignment> {
+        if self.taken.len() >= capacity {
+            return None;
+        }
+        let seat = (1..=u8::MAX).find(|seat| !self.taken.contains(seat))?;
+        self.taken.insert(seat);
+        self.last_public_id += 1;
+        Some(SeatAssignment { seat, public_id: self.last_public_id })
+    }
+
+    /// Frees the seat of `assignment`, the public id stays used.
+    pub fn release(&mut self, assignment: SeatAssignment) {
+        self.taken.remove(&assignment.seat);
+    }
+
+    /// Returns the number of seats that are taken.
+    pub fn taken(&self) -> usize {
+        self.taken.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SeatAssignment, SeatPool};
+
+    #[test]
+    fn test_assign_and_release() {
+        let mut pool = SeatPool::default();
+        let seats: Vec<SeatAssignment> = (0..3).map(|_| pool.assign(3).unwrap()).collect();
+        assert_eq!(vec![(1, 1), (2, 2), (3, 3)], seats.iter().map(|seat| (seat.seat, seat.public_id)).collect::<Vec<_>>());
+        assert_eq!(None, pool.assign(3));
+        // a larger capacity offers the next seat
+        assert_eq!(Some(SeatAssignment { seat: 4, public_id: 4 }), pool.assign(4));
+
+        // the seat is reused, the public id is not
+        pool.release(seats[1]);
+        assert_eq!(3, pool.taken());
+        assert_eq!(Some(SeatAssignment { seat: 2, public_id: 5 }), pool.assign(4));
+        pool.release(seats[0]);
+        pool.release(seats[2]);
+        assert_eq!(Some(SeatAssignment { seat: 1, public_id: 6 }), pool.assign(4));
+        assert_eq!(Some(SeatAssignment { seat: 3, public_id: 7 }), pool.assign(4));
+    }
+}
@@ -0,0 +1,129 @@
+use std::{collections::VecDeque, net::IpAddr, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+
+use serde::Serialize;
+
+/// The number of entries that are kept in the log, older entries are removed
+pub const SECURITY_LOG_LEN: usize = 100;
+
+/// The game master is alerted when more than this number of rejections happen within [ALERT_WINDOW]()
+pub const ALERT_THRESHOLD: usize = 5;
+
+/// See [ALERT_THRESHOLD]()
+pub const ALERT_WINDOW: Duration = Duration::from_secs(60);
+
+/// The reason why a join request was rejected.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecurityEventKind {
+    /// Someone tried to join with the name of a connected player without the matching recovery cookie
+    RecoveryFailed,
+    /// The recovery cookie matched but was too old
+    RecoveryExpired,
+    /// Someone tried to join while the lobby was locked
+    LobbyLocked,
+    /// Someone tried to join without a valid invite
+    InviteRejected,
+}
+
+/// A single rejected join request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SecurityEntry {
+    pub kind: SecurityEventKind,
+    /// The name with which the user tried to join
+    pub username: String,
+    /// Unix seconds of the time at which the request was rejected
+    pub timestamp: u64,
+    /// The network from which the request was send (`/24` for ipv4 and `/48` for ipv6),
+    /// the full address is not stored for privacy reasons.
+    pub network: Option<String>,
+}
+
+/// Rejected join requests of a single game, can be viewed by the game master.
+#[derive(Debug, Default)]
+pub struct SecurityLog {
+    entries: VecDeque<SecurityEntry>,
+    /// The times of the rejections within the last [ALERT_WINDOW]()
+    recent: VecDeque<Instant>,
+    /// The time at which the game master was last alerted
+    last_alert: Option<Instant>,
+}
+
+impl SecurityLog {
+    /// Adds a new entry to the log.
+    ///
+    /// # Returns
+    /// `true` when the game master should be alerted, this happens at most once per [ALERT_WINDOW]().
+    pub fn record(&mut self, kind: SecurityEventKind, username: String, ip_addr: Option<IpAddr>) -> bool {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or_default();
+        if self.entries.len() >= SECURITY_LOG_LEN {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(SecurityEntry {
+            kind,
+            username,
+            timestamp,
+            network: ip_addr.map(network),
+        });
+        let now = Instant::now();
+        while self.recent.front().is_some_and(|time| now.duration_since(*time) >= ALERT_WINDOW) {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(now);
+        let alerted_recently = self.last_alert.is_some_and(|time| now.duration_since(time) < ALERT_WINDOW);
+        if self.recent.len() > ALERT_THRESHOLD && !alerted_recently {
+            self.last_alert = Some(now);
+            return true;
+        }
+        false
+    }
+
+    /// Returns all entries, the oldest entry first.
+    pub fn entries(&self) -> Vec<SecurityEntry> {
+        self.entries.iter().cloned().collect()
+    }
+}
+
+/// Returns the coarse network of `ip_addr`.
+fn network(ip_addr: IpAddr) -> String {
+    match ip_addr {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            format!("{}.{}.{}.0/24", a, b, c)
+        },
+        IpAddr::V6(ip) => {
+            let segments = ip.segments();
+            format!("{:x}:{:x}:{:x}::/48", segments[0], segments[1], segments[2])
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    use super::{SecurityEventKind, SecurityLog, ALERT_THRESHOLD, SECURITY_LOG_LEN};
+
+    #[test]
+    fn test_alert_once_per_window() {
+        let mut log = SecurityLog::default();
+        let ip = Some(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7)));
+        let alerts = (0..ALERT_THRESHOLD * 3)
+            .filter(|_| log.record(SecurityEventKind::RecoveryFailed, String::from("Bob"), ip))
+            .count();
+        assert_eq!(1, alerts);
+        assert_eq!(Some(String::from("203.0.113.0/24")), log.entries()[0].network);
+    }
+
+    #[test]
+    fn test_log_is_bounded() {
+        let mut log = SecurityLog::default();
+        let ip = Some(IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 1, 2, 3, 4, 5, 6)));
+        for i in 0..SECURITY_LOG_LEN + 1 {
+            log.record(SecurityEventKind::LobbyLocked, format!("player {}", i), ip);
+        }
+        let entries = log.entries();
+        assert_eq!(SECURITY_LOG_LEN, entries.len());
+        assert_eq!("player 1", entries[0].username);
+        assert_eq!(Some(String::from("2001:db8:1::/48")), entries[0].network);
+    }
+}
@@ -0,0 +1,176 @@
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::request_data::TurnStatus;
+
+use super::board::Position;
+
+/// The steps of a turn, the turn passes to the next player after the last step.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "phase", content = "position", rename_all = "snake_case")]
+pub enum TurnPhase {
+    /// The player has to place a tile with [GameInstance::place_tile](../struct.GameInstance.html#method.place_tile)
+    #[default]
+    PlaceTile,
+    /// The tile on the position founds a chain, the player has to choose it with [GameInstance::found_chain](../struct.GameInstance.html#method.found_chain)
+    FoundChain(Position),
+    /// The tile on the position merges chains of the same size, the player has to choose the survivor with [GameInstance::choose_survivor](../struct.GameInstance.html#method.choose_survivor)
+    ChooseSurvivor(Position),
+    /// The shareholders of the defunct chains decide what happens to their shares with [GameInstance::merger_decision](../struct.GameInstance.html#method.merger_decision)
+    MergerDisposal,
+    /// The player can buy shares with [GameInstance::buy_stock](../struct.GameInstance.html#method.buy_stock), skipped while no chain is on the board
+    BuyStock,
+}
+
+/// Decides whose turn it is and which step of the turn is next.
+///
+/// The order is fixed when the game is started, players that leave are taken out of it.
+/// While the game is in the lobby the order is empty and it is nobody's turn.
+#[derive(Debug)]
+pub struct TurnManager {
+    /// The players in the order in which they take their turns
+    order: Vec<Uuid>,
+    /// The index in `order` of the player whose turn it is
+    current: usize,
+    phase: TurnPhase,
+    /// Replaced with every step of the turn, see [token](#method.token)
+    token: Uuid,
+}
+
+impl Default for TurnManager {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl TurnManager {
+    /// Creates a new turn manager in which the first player of `order` takes the first turn.
+    pub fn new(order: Vec<Uuid>) -> Self {
+        Self { order, current: 0, phase: TurnPhase::PlaceTile, token: Uuid::new_v4() }
+    }
+
+    /// Returns the player whose turn it is, `None` while the game is in the lobby.
+    pub fn current(&self) -> Option<Uuid> {
+        self.order.get(self.current).copied()
+    }
+
+    /// Returns the players in the order in which they take their turns, starting with the player whose turn it is.
+    pub fn order_from_current(&self) -> impl Iterator<Item = Uuid> + '_ {
+        self.order.iter().cycle().skip(self.current).take(self.order.len()).copied()
+    }
+
+    /// Returns the step of the turn that is next.
+    pub fn phase(&self) -> TurnPhase {
+        self.phase
+    }
+
+    /// Sets the step of the turn that is next, this replaces the [token](#method.token).
+    pub fn set_phase(&mut self, phase: TurnPhase) {
+        self.phase = phase;
+        self.token = Uuid::new_v4();
+    }
+
+    /// Returns the token of the current step of the turn.
+    ///
+    /// Gameplay requests have to send the token, a request that was already applied or that was made for an earlier
+    /// step carries an old token and is rejected, so that retried requests can not act twice.
+    pub fn token(&self) -> Uuid {
+        self.token
+    }
+
+    /// Checks if it is the turn of the player with `uuid`.
+    pub fn is_turn_of(&self, uuid: Uuid) -> bool {
+        self.current() == Some(uuid)
+    }
+
+    /// Passes the turn to the next player, who starts with placing a tile.
+    ///
+    /// # Returns
+    /// The player whose turn it is now.
+    pub fn advance(&mut self) -> Option<Uuid> {
+        if !self.order.is_empty() {
+            self.current = (self.current + 1) % self.order.len();
+        }
+        self.set_phase(TurnPhase::PlaceTile);
+        self.current()
+    }
+
+    /// Takes the player with `uuid` out of the order, when it was their turn the next player takes over.
+    ///
+    /// # Returns
+    /// `true` when it was the turn of the removed player, the phase is not changed.
+    pub fn remove(&mut self, uuid: Uuid) -> bool {
+        let index = match self.order.iter().position(|other| *other == uuid) {
+            Some(index) => index,
+            None => return false,
+        };
+        let was_current = index == self.current;
+        self.order.remove(index);
+        if index < self.current {
+            self.current -= 1;
+        }
+        if self.current >= self.order.len() {
+            self.current = 0;
+        }
+        was_current
+    }
+}
+
+/// The reasons why a player can not act in the current turn, shared by all game actions.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TurnError {
+    #[error("the game has not started yet")]
+    NotStarted,
+    #[error("the game is over")]
+    Finished,
+    /// The user is not the player whose turn it is, contains the turn as it actually is
+    #[error("it is not the turn of this player")]
+    NotYourTurn(Box<TurnStatus>),
+    /// The turn token of the request is not the token of the current step, see [TurnManager::token]()
+    #[error("the turn token is no longer valid")]
+    StaleToken,
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::{TurnManager, TurnPhase};
+
+    #[test]
+    fn test_advance() {
+        let uuids: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+        let mut turns = TurnManager::new(uuids.clone());
+        assert!(turns.is_turn_of(uuids[0]));
+        turns.set_phase(TurnPhase::BuyStock);
+        let token = turns.token();
+        assert_eq!(Some(uuids[1]), turns.advance());
+        assert_eq!(TurnPhase::PlaceTile, turns.phase());
+        assert_ne!(token, turns.token());
+        assert_eq!(vec![uuids[1], uuids[2], uuids[0]], turns.order_from_current().collect::<Vec<_>>());
+        assert_eq!(Some(uuids[2]), turns.advance());
+        assert_eq!(Some(uuids[0]), turns.advance());
+        assert_eq!(None, TurnManager::default().advance());
+    }
+
+    #[test]
+    fn test_remove() {
+        let uuids: Vec<Uuid> = (0..4).map(|_| Uuid::new_v4()).collect();
+        let mut turns = TurnManager::new(uuids.clone());
+        turns.advance();
+        turns.advance();
+        // removing a player before the current one keeps the turn
+        assert!(!turns.remove(uuids[0]));
+        assert_eq!(Some(uuids[2]), turns.current());
+        // the next player takes over
+        assert!(turns.remove(uuids[2]));
+        assert_eq!(Some(uuids[3]), turns.current());
+        // the turn wraps around when the last player is removed
+        assert!(turns.remove(uuids[3]));
+        assert_eq!(Some(uuids[1]), turns.current());
+        assert!(!turns.remove(Uuid::new_v4()));
+        assert!(turns.remove(uuids[1]));
+        assert_eq!(None, turns.current());
+    }
+}
@@ -0,0 +1,71 @@
+use uuid::Uuid;
+
+use crate::game::User;
+
+/// The largest number of users that can wait for a seat in a single game
+pub const MAX_WAITING_USERS: usize = 10;
+
+/// Users that tried to join a full lobby in which [enable_waitlist](../struct.LobbySettings.html#method.enable_waitlist) is set.
+///
+/// Waiting users are registered like players and can open the sse stream to follow the lobby,
+/// but they are not part of the player list. When a seat becomes free the first waiting user is promoted to a player.
+#[derive(Default)]
+pub struct Waitlist {
+    users: Vec<User>,
+}
+
+impl Waitlist {
+    /// Adds the user to the end of the waitlist.
+    ///
+    /// # Returns
+    /// - `Some(position)` the position of the user, the first user has the position 1.
+    /// - `None` when [MAX_WAITING_USERS]() are already waiting.
+    pub fn push(&mut self, user: User) -> Option<usize> {
+        if self.is_full() {
+            return None;
+        }
+        self.users.push(user);
+        Some(self.users.len())
+    }
+
+    /// Removes the first user from the waitlist, all other users move up by one position.
+    pub fn pop_front(&mut self) -> Option<User> {
+        if self.users.is_empty() {
+            return None;
+        }
+        Some(self.users.remove(0))
+    }
+
+    /// Removes all users from the waitlist.
+    pub fn clear(&mut self) -> Vec<User> {
+        self.users.drain(..).collect()
+    }
+
+    /// Returns the position of the user with `uuid`, the first user has the position 1.
+    pub fn position(&self, uuid: Uuid) -> Option<usize> {
+        self.users.iter().position(|user| user.uuid() == uuid).map(|index| index + 1)
+    }
+
+    /// Returns the waiting user with the name `name`.
+    pub fn user_by_name(&self, name: &str) -> Option<&User> {
+        self.users.iter().find(|user| user.username == name)
+    }
+
+    /// Returns the waiting user with `uuid` mutable.
+    pub fn user_mut(&mut self, uuid: Uuid) -> Option<&mut User> {
+        self.users.iter_mut().find(|user| user.uuid() == uuid)
+    }
+
+    /// Returns all waiting users, the first user first.
+    pub fn users(&self) -> &[User] {
+        &self.users
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.users.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.users.len() >= MAX_WAITING_USERS
+    }
+}
@@ -1,12 +1,13 @@
-use std::{net::IpAddr, sync::{RwLock, RwLockReadGuard, RwLockWriteGuard}, collections::{HashMap, HashSet}, time::Duration, thread};
+use std::{net::IpAddr, sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard}, collections::{HashMap, HashSet}, time::{Duration, Instant}};
 
 use rand::{thread_rng, Rng};
-use rocket::{State, tokio::sync::broadcast::Sender, log::private::info, Responder, serde::json::Json};
+use rocket::log::private::{debug, info};
+use thiserror::Error;
 use uuid::Uuid;
 
-use crate::{request_data::{UserRegistration, EventData}, authentication::{UserAuth, UserRecovery, Urid, Urids}, paths::utils::get_gm_write_guard};
+use crate::{request_data::{GameEvent, PlayersInGame, UserRegistration}, events::EventBatch, authentication::{UserAuth, UserRecovery, Urid, Urids}, utils::get_gm_read_guard};
 
-use self::{game_instance::{GameInstance, GameCode, GAME_CODE_CHARSET, GameState}};
+use self::{abandonment::AbandonmentStats, base_game::VacancyReason, game_instance::{GameInstance, GameCode, GAME_CODE_CHARSET, GameState, security_log::{SecurityEventKind, ALERT_THRESHOLD}}};
 
 /// Contains all base components that are required to run a game
 pub mod base_game;
@@ -14,15 +15,25 @@ pub mod base_game;
 /// Contains the struct that represents a single game
 pub mod game_instance;
 
+/// Splits the games over multiple [GameManager]()s to reduce lock contention
+pub mod shards;
+
+/// Deletes games outside of the request that triggered the deletion
+pub mod deletion;
+
+/// Counts how many abandoned games are recovered and how long they were abandoned
+pub mod abandonment;
+
 /// This is the time a game instance is kept alive when no more players are connected
 /// 
 /// When this time runs out the `GameInstance` and `User`s that where assigned to that instance will be deleted from the `GameManager`.
 //const GAME_INSTANCE_TIMEOUT: Duration = Duration::from_secs(60);
-const GAME_INSTANCE_TIMEOUT: Duration = Duration::from_secs(20);
+pub const GAME_INSTANCE_TIMEOUT: Duration = Duration::from_secs(20);
 
 /// Used to manage all currently running games.
 ///
-/// One `GameManager` instance is managed by rocket and given to each request handler.
+/// Rocket manages a [ShardedGameManager](shards/struct.ShardedGameManager.html) that contains one or more `GameManager`s,
+/// request handlers lock the `GameManager` that is responsible for the game of the request.
 pub struct GameManager {
     /// Contains all games that are currently running.
     /// 
@@ -37,6 +48,19 @@ pub struct GameManager {
     urids: Urids,
     /// Stores all game codes that are already in use
     used_game_codes: HashSet<GameCode>,
+    /// Games that are marked for deletion, see [mark_for_deletion](#method.mark_for_deletion).
+    /// 
+    /// These games are treated as if they no longer exist, but their game code and the ids of their users stay in use
+    /// until the game is removed with [delete_game](#method.delete_game).
+    pending_deletion: HashSet<GameCode>,
+    /// What happens to the games of this manager after they were abandoned
+    abandonment: Arc<AbandonmentStats>,
+}
+
+impl Default for GameManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl GameManager {
@@ -46,36 +70,47 @@ impl GameManager {
             used_uuids: HashMap::new(),
             urids: Urids::new(),
             used_game_codes: HashSet::new(),
+            pending_deletion: HashSet::new(),
+            abandonment: Arc::default(),
         }
     }    
 
-    /// Some debug functionality, should be deleted from final version
-    pub fn debug(&mut self) -> GameCode {
-        //let code = *game.game_code();
-        //let mut game = GameInstance::new(self);
-        //self.used_game_codes.push(*game.game_code());
-        //game.players.push(Player {name: String::from("Louis"), id: 1});
-        //game.players.push(Player {name: String::from("Markus"), id: 1});
-        //game.players.push(Player {name: String::from("David"), id: 1});
-        //self.games.push(game);
-        //code
-        self.generate_game_code()
+    /// Records the abandoned games in `stats`, this way several managers can share the same stats.
+    pub fn with_abandonment_stats(mut self, stats: Arc<AbandonmentStats>) -> Self {
+        self.abandonment = stats;
+        self
+    }
+
+    /// Returns what happened to the games of this manager after they were abandoned.
+    #[cfg(test)]
+    pub fn abandonment_stats(&self) -> &AbandonmentStats {
+        &self.abandonment
     }
 
     /// Creates a new game.
     /// 
+    /// New games are usually created with [ShardedGameManager::create_game](shards/struct.ShardedGameManager.html#method.create_game) which picks the game code.
+    /// 
     /// # Params
+    /// `code` the game code of the new game
     /// `username` the username of the user that creates the game
     /// `ip_addr` the ip address of the user that creates the game. See [User]() for reason why `ip_address` is required.
     /// 
     /// # Returns
     /// `Some(UserRegistration)` when the game was created
-    /// `None` when the game was not created
-    pub fn create_game(&mut self, username: String, ip_addr: Option<IpAddr>) -> Option<UserRegistration> {
-        let code = self.generate_game_code();
-        let mut game = GameInstance::new(code);
+    /// `None` when the game was not created because `code` is already used by another game
+    pub fn create_game(&mut self, code: GameCode, username: String, ip_addr: Option<IpAddr>, seed: Option<u64>) -> Option<UserRegistration> {
+        if self.used_game_codes.contains(&code) {
+            return None;
+        }
+        let mut game = match seed {
+            Some(seed) => GameInstance::with_seed(code, seed),
+            None => GameInstance::new(code),
+        };
+        info!("Created game {} with seed {}", code, game.seed());
         let uuid = self.generate_uuid();
         let urid = self.urids.register(ip_addr);
+        self.urids.remember_name(urid, username.clone());
         let user = User::new(username, uuid, urid, code);
         game.add_user(user);
         game.set_game_master(uuid);
@@ -89,6 +124,9 @@ impl GameManager {
     /// 
     /// This will also delete all users and players assigned to the game.
     /// The `GameCode` under wich the game is registered is also freed.
+    /// 
+    /// Games are usually not deleted directly, they are [marked for deletion](#method.mark_for_deletion) and then deleted
+    /// by the [DeletionQueue](deletion/struct.DeletionQueue.html) outside of the request that triggered the deletion.
     /// # Returns
     /// `true` when the game was deleted
     /// `false` when the game was not found
@@ -97,13 +135,15 @@ impl GameManager {
             return false;
         }
         
-        // Free uuids and urids
+        // Free uuids and urids of the players and the waiting users
         let mut urids_to_remove = HashSet::new();
-        for player in self.game_by_code_read(*game_code).unwrap().players() {
-            urids_to_remove.insert(player.user.urid.clone());
+        let mut uuids = Vec::new();
+        // game_by_code_read can not be used because the game is usually marked for deletion
+        for user in self.games[game_code].read().unwrap().users() {
+            urids_to_remove.insert(user.urid);
+            uuids.push(user.uuid);
         }
         self.urids.unregister_all(&urids_to_remove);
-        let uuids = self.game_by_code_read(*game_code).unwrap().player_uuids();
         for uuid in uuids {
             self.used_uuids.remove(&uuid);
         }
@@ -111,6 +151,56 @@ impl GameManager {
         self.used_game_codes.remove(game_code);
         // Remove game instance
         self.games.remove(game_code);
+        self.pending_deletion.remove(game_code);
+        true
+    }
+
+    /// Marks the game for deletion, from now on the game is treated as if it no longer exists.
+    /// 
+    /// The game has to be deleted afterwards with [delete_game](#method.delete_game), until then its game code can not be reused.
+    /// 
+    /// # Returns
+    /// `true` when the game was marked, `false` when the game does not exist or is already marked.
+    pub fn mark_for_deletion(&mut self, game_code: GameCode) -> bool {
+        self.games.contains_key(&game_code) && self.pending_deletion.insert(game_code)
+    }
+
+    /// Checks if the game is marked for deletion but was not yet deleted.
+    pub fn is_pending_deletion(&self, game_code: &GameCode) -> bool {
+        self.pending_deletion.contains(game_code)
+    }
+
+    /// Returns the uuids of all users of a game that is marked for deletion.
+    /// 
+    /// # Returns
+    /// `None` when the game is not marked for deletion.
+    pub fn pending_deletion_uuids(&self, game_code: GameCode) -> Option<Vec<Uuid>> {
+        if !self.is_pending_deletion(&game_code) {
+            return None;
+        }
+        self.games.get(&game_code).map(|game| game.read().unwrap().users().map(|user| user.uuid).collect())
+    }
+
+    /// Frees the uuids and urids of users that where removed from their game, see [GameInstance::remove_player](game_instance/struct.GameInstance.html#method.remove_player).
+    pub fn forget_users(&mut self, users: &[User]) {
+        self.urids.unregister_all(&users.iter().map(|user| user.urid).collect());
+        for user in users {
+            self.used_uuids.remove(&user.uuid);
+        }
+    }
+
+    /// Marks the game for deletion when it is still abandoned and its generation is still `generation`, see [mark_for_deletion](#method.mark_for_deletion).
+    /// 
+    /// # Returns
+    /// `true` when the game was marked for deletion.
+    pub fn delete_game_if_abandoned(&mut self, game_code: GameCode, generation: u64) -> bool {
+        let abandoned_for = match self.game_by_code_read(game_code) {
+            Some(game) if game.generation() == generation && game.abandoned() => game.abandoned_for().unwrap_or_default(),
+            _ => return false,
+        };
+        self.mark_for_deletion(game_code);
+        self.abandonment.record_deletion();
+        info!("Game instance with code {} is deleted because all players left {}s ago.", game_code, abandoned_for.as_secs());
         true
     }
 
@@ -118,41 +208,91 @@ impl GameManager {
     /// 
     /// This will fail when the game does not exist, the game was already started or when a player with that name was already registered.
     /// 
+    /// When the game is full and the lobby has [enable_waitlist](game_instance/struct.LobbySettings.html#method.enable_waitlist) set,
+    /// the user is put on the waitlist instead, the registration then contains the
+    /// [waitlist_position](../request_data/struct.UserRegistration.html#method.waitlist_position).
+    /// Waiting users that join again are treated like players that join again.
+    /// 
     /// # Params
     /// - `username` the username of the user that should be added to the game
     /// - `ur` used to recover the user session when the user has lost connection.
+    ///   The recovery is ignored when its urid does not belong to a player of this game, for example when the cookie is left over from an older game.
+    /// - `invite` the invite token, new players need a valid invite when the lobby [requires it](game_instance/struct.LobbySettings.html#method.require_invite).
+    ///   The invite is used up when the player is added, players that are already part of the game do not need an invite.
     /// 
     /// # Returns
-    /// - `Ok(UserRegistration)` when the user was added to the game.
+    /// - `Ok((UserRegistration, EventBatch))` when the user was added to the game, the batch contains the events that have to be published.
     /// - `Err(UserRegistrationError)` when the player was not added to the game, contains the reason why the player was not added.
-    pub fn add_player_to_game(&mut self, event: &State<Sender<EventData>>, game_code: GameCode, username: String, ur: Option<UserRecovery>, ip_addr: Option<IpAddr>) -> Result<UserRegistration, UserRegistrationError> {//TODO Move function to GameInstance
+    /// 
+    /// The username is remembered for the urid of the registration, see [name_by_urid](#method.name_by_urid).
+    pub fn add_player_to_game(&mut self, game_code: GameCode, username: String, ur: Option<UserRecovery>, ip_addr: Option<IpAddr>, invite: Option<Uuid>) -> Result<(UserRegistration, EventBatch), UserRegistrationError> {
+        let result = self.join_game(game_code, username.clone(), ur, ip_addr, invite);
+        if let Ok((registration, _)) = &result {
+            self.urids.remember_name(registration.urid, username);
+        }
+        result
+    }
+
+    /// Adds the player to the game, see [add_player_to_game](#method.add_player_to_game).
+    fn join_game(&mut self, game_code: GameCode, username: String, ur: Option<UserRecovery>, ip_addr: Option<IpAddr>, invite: Option<Uuid>) -> Result<(UserRegistration, EventBatch), UserRegistrationError> {//TODO Move function to GameInstance
         let uuid = self.generate_uuid();
         let urid = self.urids.register(ip_addr);
-        match self.games.get(&game_code) {
+        // The fields are borrowed separately from the game below, so game_by_code can not be used here
+        let game = self.games.get(&game_code).filter(|_| !self.pending_deletion.contains(&game_code));
+        let events = match game {
             Some(game) => {
                 let mut game_write = game.write().unwrap();
-                if !game_write.does_player_exist(&username) {
-                    match game_write.game_state() {
-                        GameState::Lobby => {
-                            game_write.add_user(User::new(username.clone(), uuid, urid, game_code));
-                        }
-                        _ => return Err(UserRegistrationError::GameAlreadyStarted(())),
+                let mut events = EventBatch::new(game_write.channel());
+                let ur = ur.filter(|ur| {
+                    let applicable = game_write.has_urid(&ur.urid);
+                    if !applicable {
+                        debug!("Ignoring recovery cookie for game {}, the urid does not belong to this game", game_code);
                     }
-                } else {
-                    if game_write.is_player_connected(&username) {
-                        if ur.is_some() && game_write.validate_urid(ur.unwrap()) {
-                            return Ok(game_write.user_registration(&username).unwrap());
-                        } else {
-                            return Err(UserRegistrationError::NameTaken(Json(String::from("name_taken"))));
+                    applicable
+                });
+                let waiting = game_write.waitlist().user_by_name(&username).map(|user| user.connected());
+                if !game_write.does_player_exist(&username) && waiting.is_none() {
+                    if !matches!(game_write.game_state(), GameState::Lobby) {
+                        return Err(UserRegistrationError::GameAlreadyStarted);
+                    }
+                    if game_write.is_locked() {
+                        return Err(UserRegistrationError::LobbyLocked);
+                    }
+                    // New users queue behind the users that are already waiting
+                    let waitlisted = game_write.is_full() || !game_write.waitlist().is_empty();
+                    if waitlisted && (!game_write.settings().enable_waitlist() || game_write.waitlist().is_full()) {
+                        return Err(UserRegistrationError::GameFull);
+                    }
+                    if game_write.settings().require_invite() {
+                        // The invite is consumed while the game is locked so that it can not be used more often than allowed
+                        match invite {
+                            Some(invite) if game_write.invites_mut().consume(invite) => (),
+                            Some(_) => return Err(UserRegistrationError::InviteInvalid),
+                            None => return Err(UserRegistrationError::InviteRequired),
                         }
-                    } else {
-                        let _e = event.send(EventData::new(None, game_code, (String::from("AddPlayer"), Some(username.clone()))));
-                        return Ok(game_write.user_registration(&username).unwrap());
                     }
+                    let user = User::new(username.clone(), uuid, urid, game_code);
+                    if waitlisted {
+                        let position = game_write.join_waitlist(user)?;
+                        info!("{} is waiting for a seat in game {} at position {}", username, game_code, position);
+                        self.used_uuids.insert(uuid, game_code);
+                        return Ok((UserRegistration::new(uuid, urid, game_code).waitlisted(position), events));
+                    }
+                    game_write.add_user(user);
+                    events.append(game_write.player_list_events());
+                    events
+                } else if game_write.is_player_connected(&username) || waiting == Some(true) {
+                    return match ur {
+                        Some(ur) => game_write.validate_urid(&ur).map(|_| (game_write.user_registration(&username).unwrap(), events)),
+                        None => Err(UserRegistrationError::NameTaken),
+                    };
+                } else {
+                    events.append(game_write.player_list_events());
+                    return Ok((game_write.user_registration(&username).unwrap(), events));
                 }
             },
-            None => return Err(UserRegistrationError::GameDoesNotExist(())),
-        }
+            None => return Err(UserRegistrationError::GameDoesNotExist),
+        };
         self.used_uuids.insert(uuid, game_code);
         //if ur.is_some() {
         //    self.urids.add_urid(urid, ur.unwrap().ip_addr);
@@ -160,8 +300,83 @@ impl GameManager {
         //    self.urids.add_urid(urid, None);
         //}
         //self.used_urids.insert(urid);
-        let _e = event.send(EventData::new(None, game_code, (String::from("AddPlayer"), Some(username))));
-        Ok(UserRegistration::new(uuid, urid, game_code))
+        Ok((UserRegistration::new(uuid, urid, game_code), events))
+    }
+
+    /// Adds a rejected join request to the [SecurityLog](game_instance/security_log/struct.SecurityLog.html) of the game.
+    /// 
+    /// Only rejections that could be an attempt to take over a seat or to join without permission are logged.
+    /// 
+    /// # Returns
+    /// - `Some(EventBatch)` containing a `SecurityAlert` for the game master when too many join requests where rejected recently.
+    /// - `None` when nothing has to be send.
+    pub fn record_rejection(&self, game_code: GameCode, err: &UserRegistrationError, username: String, ip_addr: Option<IpAddr>) -> Option<EventBatch> {
+        let kind = match err {
+            UserRegistrationError::NameTaken => SecurityEventKind::RecoveryFailed,
+            UserRegistrationError::RecoveryExpired => SecurityEventKind::RecoveryExpired,
+            UserRegistrationError::LobbyLocked => SecurityEventKind::LobbyLocked,
+            UserRegistrationError::InviteRequired | UserRegistrationError::InviteInvalid => SecurityEventKind::InviteRejected,
+            _ => return None,
+        };
+        let mut game = self.game_by_code_write(game_code)?;
+        if !game.security_log_mut().record(kind, username, ip_addr) {
+            return None;
+        }
+        let mut events = EventBatch::new(game.channel());
+        events.push_to(Some(game.game_master()?), GameEvent::SecurityAlert(format!("More than {} join requests where rejected within the last minute", ALERT_THRESHOLD)));
+        Some(events)
+    }
+
+    /// Marks the user as connected to their game.
+    /// 
+    /// # Returns
+    /// - `Some(EventBatch)` containing the new player list and [LobbyStatus](game_instance/struct.LobbyStatus.html) that have to be send to all players,
+    ///   followed by the event `SeatVacancyChanged` when the seat of the player was vacant.
+    /// - `None` when the game of the user does not exist.
+    pub fn user_connected(&self, user_auth: UserAuth) -> Option<EventBatch> {
+        let mut game = self.game_by_user_auth_write(user_auth)?;
+        let vacant = game.vacancy(user_auth.uuid).is_some();
+        let abandoned_for = game.abandoned_for();
+        game.user_connected(user_auth.uuid);
+        // waiting users do not recover the game
+        if let (Some(gap), None) = (abandoned_for, game.abandoned_for()) {
+            self.abandonment.record_recovery(gap);
+        }
+        let mut events = game.player_list_events();
+        events.append(game.lobby_status_events());
+        if vacant {
+            events.append(game.seat_vacancy_events(user_auth.uuid));
+        }
+        events.append(game.game_ended_events(Some(user_auth.uuid)));
+        Some(events)
+    }
+
+    /// Returns the events that have to be send to all players after the user was [disconnected](fn.disconnect_user.html):
+    /// the `PlayerList` and `LobbyStatus` events followed by the event `SeatVacancyChanged` of the player.
+    /// 
+    /// # Returns
+    /// `None` when the game does not exist.
+    pub fn user_disconnected_events(&self, user_auth: UserAuth) -> Option<EventBatch> {
+        let mut events = self.lobby_status_events(user_auth.game_code)?;
+        events.append(self.game_by_code_read(user_auth.game_code)?.seat_vacancy_events(user_auth.uuid));
+        Some(events)
+    }
+
+    /// Returns the `PlayerList` and `LobbyStatus` events with the current players and [LobbyStatus](game_instance/struct.LobbyStatus.html) of the game.
+    /// 
+    /// # Returns
+    /// `None` when the game does not exist.
+    pub fn lobby_status_events(&self, game_code: GameCode) -> Option<EventBatch> {
+        self.game_by_code_read(game_code).map(|game| {
+            let mut events = game.player_list_events();
+            events.append(game.lobby_status_events());
+            events
+        })
+    }
+
+    /// Returns the game codes of all games that currently exist.
+    pub fn game_codes(&self) -> Vec<GameCode> {
+        self.games.keys().filter(|game_code| !self.is_pending_deletion(game_code)).copied().collect()
     }
 
     /// Returns reference to [GameInstance](game_instance/struct.GameInstance.html) wrapped inside an [RwLock]() where the [User](struct.User.html) with `uuid` is assigned to when found.
@@ -172,51 +387,37 @@ impl GameManager {
     pub fn game_by_uuid(&self, uuid: Uuid) -> Option<&RwLock<GameInstance>> {
         if self.used_uuids.contains_key(&uuid) {
             let code = self.used_uuids.get(&uuid).unwrap();
-            self.games.get(code)
+            self.game_by_code(*code)
         } else {
             None
         }
     }
     
     /// Returns [RwLockReadGuard]() for the [GameInstance]() where the `uuid` is assigned to.
-    pub fn game_by_uuid_read(&self, uuid: Uuid) -> Option<RwLockReadGuard<GameInstance>> {
-        match self.game_by_uuid(uuid) {
-            Some(game) => Some(game.read().unwrap()),
-            None => None,
-        }
-    }
-
-    /// Returns [RwLockWriteGuard]() for the [GameInstance]() where the `uuid` is assigned to.    
-    pub fn game_by_uuid_write(&self, uuid: Uuid) -> Option<RwLockWriteGuard<GameInstance>> {
-        match self.game_by_uuid(uuid) {
-            Some(game) => Some(game.write().unwrap()),
-            None => None,
-        }
+    pub fn game_by_uuid_read(&self, uuid: Uuid) -> Option<RwLockReadGuard<'_, GameInstance>> {
+        self.game_by_uuid(uuid).map(|game| game.read().unwrap())
     }
 
     /// Returns reference to [GameInstance](game_instance/struct.GameInstance.html) wrapped inside an [RwLock]() when a [GameInstance]() for this code exists.
     /// 
     /// # Returns
     /// - `Some(&RwLock<GameInstance>)` when the game with the game code exists.
-    /// - `None` the game does not exist.
+    /// - `None` the game does not exist or is marked for deletion.
     pub fn game_by_code(&self, game_code: GameCode) -> Option<&RwLock<GameInstance>> {
+        if self.is_pending_deletion(&game_code) {
+            return None;
+        }
         self.games.get(&game_code)
     }
 
     /// Returns [RwLockReadGuard]() for the [GameInstance]() with the specified `game_code`.
-    pub fn game_by_code_read(&self, game_code: GameCode) -> Option<RwLockReadGuard<GameInstance>> {
-        match self.game_by_code(game_code) {
-            Some(game) => Some(game.read().unwrap()),
-            None => None,
-        }
+    pub fn game_by_code_read(&self, game_code: GameCode) -> Option<RwLockReadGuard<'_, GameInstance>> {
+        self.game_by_code(game_code).map(|game| game.read().unwrap())
     }
 
     /// Returns [RwLockWriteGuard]() for the [GameInstance]() with the specified `game_code`.    
-    pub fn game_by_code_write(&self, game_code: GameCode) -> Option<RwLockWriteGuard<GameInstance>> {
-        match self.game_by_code(game_code) {
-            Some(game) => Some(game.write().unwrap()),
-            None => None,
-        }
+    pub fn game_by_code_write(&self, game_code: GameCode) -> Option<RwLockWriteGuard<'_, GameInstance>> {
+        self.game_by_code(game_code).map(|game| game.write().unwrap())
     }
 
     /// Returns the game a user is assigned to by using the `user_auth`, wrapped in an [RwLock]().
@@ -224,70 +425,36 @@ impl GameManager {
         self.game_by_code(user_auth.game_code)
     }
 
-    
-    /// Returns [RwLockReadGuard]() for the [GameInstance]() where the `user_auth` is assigned to.
-    pub fn game_by_user_auth_read(&self, user_auth: UserAuth) -> Option<RwLockReadGuard<GameInstance>> {
-        match self.game_by_user_auth(user_auth) {
-            Some(game) => Some(game.read().unwrap()),
-            None => None,
-        }
+    /// Returns [RwLockWriteGuard]() for the [GameInstance]() where the `user_auth` is assigned to.    
+    pub fn game_by_user_auth_write(&self, user_auth: UserAuth) -> Option<RwLockWriteGuard<'_, GameInstance>> {
+        self.game_by_user_auth(user_auth).map(|game| game.write().unwrap())
     }
 
-    /// Returns [RwLockWriteGuard]() for the [GameInstance]() where the `user_auth` is assigned to.    
-    pub fn game_by_user_auth_write(&self, user_auth: UserAuth) -> Option<RwLockWriteGuard<GameInstance>> {
-        match self.game_by_user_auth(user_auth) {
-            Some(game) => Some(game.write().unwrap()),
-            None => None,
-        }
+    /// Returns the name that was last used with `urid` in this game manager, see [Urids::remember_name](../authentication/struct.Urids.html#method.remember_name).
+    pub fn name_by_urid(&self, urid: &Urid) -> Option<String> {
+        self.urids.name(urid).cloned()
+    }
+
+    /// Returns the code of the game in which a player has the `urid`.
+    pub fn game_by_urid(&self, urid: &Urid) -> Option<GameCode> {
+        self.games.iter()
+            .filter(|(game_code, _)| !self.is_pending_deletion(game_code))
+            .find(|(_, game)| game.read().unwrap().has_urid(urid))
+            .map(|(game_code, _)| *game_code)
     }
 
-    /// Checks if a game with the game code exists
+    /// Checks if a game with the game code exists, games that are marked for deletion do not exist.
     pub fn does_game_exist(&self, game_code: &GameCode) -> bool {
-        self.used_game_codes.contains(game_code)
+        self.used_game_codes.contains(game_code) && !self.is_pending_deletion(game_code)
     }
 
-    /// Returns the names of the players that are currently joined in the selected game
+    /// Returns the players of the selected game.
     /// 
     /// # Returns
-    /// `Some(Vec<String>)` when the game exists. Vector of string contains the currently joined players.
+    /// `Some(PlayersInGame)` when the game exists. Contains all players, as json only the names of the connected players are send.
     /// `None` the game does not exist
-    pub fn players_in_game(&self, game_code: GameCode) -> Option<Vec<String>> {// TODO Move to GameInstance
-        match self.game_by_code_read(game_code) {
-            Some(game) => {
-                let mut player_names = Vec::new();
-                for player in game.players() {
-                    if player.user.connected() {
-                        player_names.push(String::from(player.username()))
-                    }
-                }
-                Some(player_names)
-            },
-            None => None,
-        }
-    }
-
-    /// Generates a new game code that is not yet used by another game
-    /// 
-    /// This does not add the generated game code to the used_game_codes vector.
-    fn generate_game_code(&self) -> GameCode {
-        let mut rng = thread_rng();
-        loop {
-            let code: String = (0..8)
-                .map(|_| {
-                    let idx = rng.gen_range(0..GAME_CODE_CHARSET.len());
-                    GAME_CODE_CHARSET[idx] as char
-                })
-                .collect();
-            let chars: Vec<char> = code.chars().collect();
-            let code: [char; 8] = [
-                chars[0], chars[1], chars[2], chars[3], chars[4], chars[5], chars[6], chars[7],
-            ];
-            let game_code = GameCode::new(code).unwrap();
-            if self.used_game_codes.contains(&game_code) {
-               continue; 
-            }
-            return GameCode::new(code).unwrap()
-        }
+    pub fn players_in_game(&self, game_code: GameCode) -> Option<PlayersInGame> {
+        self.game_by_code_read(game_code).map(|game| PlayersInGame(game.player_list()))
     }
 
     /// Generates a unique user id that is not yet registered in the `used_uuids` vector.
@@ -303,68 +470,117 @@ impl GameManager {
 
 }
 
+/// Generates a random game code, it is not checked if the code is already in use.
+pub fn random_game_code() -> GameCode {
+    let mut rng = thread_rng();
+    let code: String = (0..8)
+        .map(|_| {
+            let idx = rng.gen_range(0..GAME_CODE_CHARSET.len());
+            GAME_CODE_CHARSET[idx] as char
+        })
+        .collect();
+    let chars: Vec<char> = code.chars().collect();
+    let code: [char; 8] = [
+        chars[0], chars[1], chars[2], chars[3], chars[4], chars[5], chars[6], chars[7],
+    ];
+    GameCode::new(code).unwrap()
+}
 
-/// Disconnects the user from the [GameInstance](game_instance/struct.GameInstance.html) and performs cleanup actions if necessary.
+/// Disconnects the user from the [GameInstance](game_instance/struct.GameInstance.html) and checks if the game is abandoned afterwards.
 /// 
-/// This updates the value [User.connected](struct.User.html#structfield.connected) for that user to false.
+/// This updates the value [User.connected](struct.User.html#structfield.connected) for that user to false and marks the seat
+/// of the player as vacant for `reason`, see [Vacancy](base_game/struct.Vacancy.html).
 /// 
 /// It is then checked if the [GameInstance](game_instance/struct.GameInstance.html) is abandoned (no more players are marked as connected).
-/// If the [GameInstance](game_instance/struct.GameInstance.html) is abandoned, a timer with [GAME_INSTANCE_TIMEOUT](constant.GAME_INSTANCE_TIMEOUT.html) duration is started.
-/// 
-/// When this timer runs out it is checked again if the [GameInstance](game_instance/struct.GameInstance.html) is abandoned.
+/// If the [GameInstance](game_instance/struct.GameInstance.html) is abandoned, the current [generation](game_instance/struct.GameInstance.html#method.generation)
+/// of the game is returned. The game may only be deleted with [delete_game_if_abandoned](struct.GameManager.html#method.delete_game_if_abandoned)
+/// when no player has joined or reconnected in the meantime (the generation is unchanged).
+/// [ShardedGameManager::disconnect_user](shards/struct.ShardedGameManager.html#method.disconnect_user) takes care of that,
+/// it waits for the delay in a task so that the caller does not block.
 /// 
-/// If the [GameInstance](game_instance/struct.GameInstance.html) is still abandoned it will be deleted from the server and the [GameCode](game_instance/struct.GameCode.html) is made available again.
-/// 
-/// Because this thread will be sleeping for some time an `RwLock<GameManager>` is provided to not block access to the [GameManager](struct.GameManager.html) wile sleeping.
-/// 
-/// When `no_sleep` is set and no more players are connected the game will be deleted directly.
-pub fn disconnect_user(game_manager: &RwLock<GameManager>, user_auth: UserAuth, no_sleep: bool) -> UserDisconnectedStatus {
-    // Not optimal in terms of runtime when the number of players grows, can be optimized
-    {
-        let game_manager = get_gm_write_guard(game_manager, "disconnect_user: phase 1");
-        let mut game = game_manager.game_by_code_write(user_auth.game_code).unwrap();
-        // 1. Update connection status to false
-        game.player_by_uuid_mut(user_auth.uuid).unwrap().user.set_connected(false);
-        // 2. Check if game is abandoned
-        if !game.abandoned() {
-            return UserDisconnectedStatus::GameAlive;
-        }
+/// Calling this function for a user that is already disconnected does nothing except replacing the reason with [VacancyReason::Left](base_game/enum.VacancyReason.html),
+/// so it is safe to call it
+/// from multiple places (for example when the sse stream closes and from [leave_game](../paths/lobby_api/fn.leave_game.html)) at the same time.
+pub fn disconnect_user(game_manager: &RwLock<GameManager>, user_auth: UserAuth, reason: VacancyReason) -> UserDisconnectedStatus {
+    let game_manager = get_gm_read_guard(game_manager, "disconnect_user");
+    let mut game = match game_manager.game_by_code_write(user_auth.game_code) {
+        Some(game) => game,
+        None => return UserDisconnectedStatus::GameDeleted,
+    };
+    // 1. Update connection status to false
+    if !game.user_disconnected(user_auth.uuid, reason) {
+        return UserDisconnectedStatus::AlreadyDisconnected;
     }
-    if !no_sleep {
-        // 3. Wait for some time to check if the game keeps being abandoned
-        thread::sleep(GAME_INSTANCE_TIMEOUT);
-    }
-    {
-        // 4. Check again if game is abandoned
-        let mut game_manager = get_gm_write_guard(game_manager, "disconnect_user: phase 2");
-        if !game_manager.game_by_code_write(user_auth.game_code).unwrap().abandoned() {
-            return UserDisconnectedStatus::GameAlive;
-        }
-        // 5. Delete game
-        game_manager.delete_game(&user_auth.game_code);
-        info!("Game instance with code {} was deleted because all players left.", user_auth.game_code.to_string());
-        UserDisconnectedStatus::GameDeleted
+    // 2. Check if game is abandoned
+    match game.mark_abandoned() {
+        Some(generation) => UserDisconnectedStatus::GameAbandoned(generation),
+        None => UserDisconnectedStatus::GameAlive,
     }
 }
 
+/// Makes the next connected player the game master when the game master of the game has idled for too long,
+/// see [GameInstance::idle_master_rotation](game_instance/struct.GameInstance.html#method.idle_master_rotation).
+/// 
+/// Like in [disconnect_user]() the generation is read first and the rotation is only applied when the game master
+/// did not act in the meantime, so an action of the game master always cancels a pending rotation.
+/// 
+/// # Returns
+/// The events that have to be published when the game master was rotated.
+pub fn rotate_idle_game_master(game_manager: &RwLock<GameManager>, game_code: GameCode) -> Option<EventBatch> {
+    let game_manager = get_gm_read_guard(game_manager, "rotate_idle_game_master");
+    let generation = game_manager.game_by_code_read(game_code)?.idle_master_rotation(Instant::now())?;
+    let events = game_manager.game_by_code_write(game_code)?.rotate_idle_game_master(generation);
+    events
+}
+
 /// The different ways a user registration can fail.
-#[derive(Responder)]
+///
+/// See [ApiError](../error/enum.ApiError.html) for how these errors are send to the client.
+#[derive(Debug, Error, PartialEq, Eq)]
 pub enum UserRegistrationError {
-    #[response(status = 403, content_type = "json")]
-    NameTaken(Json<String>),
-    #[response(status = 403)]
-    GameDoesNotExist(()),
-    #[response(status = 403)]
-    GameAlreadyStarted(()),
+    /// A connected player with the requested name is already part of the game.
+    #[error("name is already taken")]
+    NameTaken,
+    /// No game exists for the game code.
+    #[error("game does not exist")]
+    GameDoesNotExist,
+    /// The game has already been started, new players can no longer join.
+    #[error("game has already started")]
+    GameAlreadyStarted,
+    /// The game master has locked the lobby, only players that are already part of the game can join.
+    #[error("lobby is locked")]
+    LobbyLocked,
+    /// The maximum number of players has already joined the game.
+    #[error("game is full")]
+    GameFull,
+    /// The urid that was used to recover the session is too old.
+    #[error("recovery id has expired")]
+    RecoveryExpired,
+    /// The lobby requires an invite but none was presented.
+    #[error("an invite is required to join")]
+    InviteRequired,
+    /// The presented invite does not exist, is expired or was already used up.
+    #[error("invite is invalid")]
+    InviteInvalid,
 }
 
-/// The different ways [user_disconnected]() can return.
-#[derive(Debug)]
+/// The different ways [disconnect_user]() can return.
+#[derive(Debug, PartialEq, Eq)]
 pub enum UserDisconnectedStatus {
     /// Indicates that at least one player is still connected to the game.
     GameAlive,
+    /// Indicates that no player is connected anymore, contains the generation of the game that has to be unchanged when the game is deleted.
+    /// 
+    /// Only returned by [disconnect_user](), [ShardedGameManager::disconnect_user](shards/struct.ShardedGameManager.html#method.disconnect_user)
+    /// deletes the game or schedules the deletion instead.
+    GameAbandoned(u64),
+    /// Indicates that no player is connected anymore and the game is deleted after the delay,
+    /// unless a player reconnects or joins in the meantime.
+    DeletionScheduled,
     /// Indicates that the game was deleted because no players where connected anymore.
     GameDeleted,
+    /// Indicates that the user was already marked as disconnected, nothing was changed.
+    AlreadyDisconnected,
 }
 
 /// User that is playing in a game.
@@ -398,8 +614,8 @@ impl User {
     pub fn new(username: String, uuid: Uuid, urid: Urid, game_code: GameCode) -> Self {
         Self {
             username,
-            uuid: uuid,
-            urid: urid,
+            uuid,
+            urid,
             game_code,
             connected: false,
         }
@@ -438,10 +654,543 @@ impl User {
 
 #[cfg(test)]
 mod tests {
-    use super::GameCode;
+    use std::{collections::HashSet, sync::{Barrier, RwLock}, thread, time::{Duration, Instant}};
+
+    use uuid::Uuid;
+
+    use crate::{authentication::{Urid, UserAuth}, events::{EventBatch, EventBus, DEFAULT_COALESCE_WINDOW}, request_data::{FieldError, LobbyAdminRequest, UserRegistration}};
+
+    use super::{base_game::{Tile, Vacancy, VacancyReason}, disconnect_user, game_instance::{turns::{TurnError, TurnPhase}, waitlist::MAX_WAITING_USERS, LobbySettings, PlayTileError, MAX_PLAYERS, MIN_PLAYERS}, random_game_code, GameCode, GameInstance, GameManager, User, UserDisconnectedStatus, UserRegistrationError};
+
+    fn user_auth(registration: UserRegistration) -> UserAuth {
+        let registration = rocket::serde::json::to_value(registration).unwrap();
+        UserAuth {
+            uuid: Uuid::parse_str(registration["uuid"].as_str().unwrap()).unwrap(),
+            game_code: GameCode::from_string(registration["game_code"].as_str().unwrap()).unwrap(),
+        }
+    }
+
+    /// Creates a game with a connected game master.
+    fn connected_game(game_manager: &RwLock<GameManager>) -> UserAuth {
+        let mut game_manager = game_manager.write().unwrap();
+        let auth = user_auth(game_manager.create_game(random_game_code(), String::from("a"), None, None).unwrap());
+        let _events = game_manager.user_connected(auth);
+        auth
+    }
 
     #[test]
     fn test_game_code_from_string() {
         assert_eq!("ABCD-1234", GameCode::from_string("ABCD-1234").unwrap().to_string());
     }
+
+    #[test]
+    fn test_create_game_with_seed() {
+        let mut game_manager = GameManager::new();
+        game_manager.create_game(random_game_code(), String::from("a"), None, Some(7)).unwrap();
+        game_manager.create_game(random_game_code(), String::from("b"), None, Some(7)).unwrap();
+        let seeds: Vec<u64> = game_manager.games.values().map(|game| game.read().unwrap().seed()).collect();
+        assert_eq!(vec![7, 7], seeds);
+    }
+
+    #[test]
+    fn test_lobby_status_thresholds() {
+        let game_code = GameCode::new(['A'; 8]).unwrap();
+        let mut game = GameInstance::new(game_code);
+        let set_min_players = |game: &mut GameInstance, min_players| game.set_settings(LobbySettings::new(min_players, MAX_PLAYERS, false)).is_ok();
+        assert!(!set_min_players(&mut game, 1));
+        assert!(!set_min_players(&mut game, 7));
+        assert!(set_min_players(&mut game, 6));
+        assert!(set_min_players(&mut game, 2));
+        for i in 0..6 {
+            let uuid = Uuid::new_v4();
+            assert!(game.add_user(User::new(format!("player {}", i), uuid, Urid::from_uuid(Uuid::new_v4()), game_code)));
+            game.user_connected(uuid);
+            let status = game.lobby_status();
+            assert_eq!(i + 1, status.current_players);
+            assert_eq!(i >= 1, status.can_start);
+        }
+        assert!(game.is_full());
+        assert!(!game.add_user(User::new(String::from("too many"), Uuid::new_v4(), Urid::from_uuid(Uuid::new_v4()), game_code)));
+        assert!(set_min_players(&mut game, 6));
+        assert!(game.lobby_status().can_start);
+    }
+
+    #[test]
+    fn test_deal_tiles_on_start() {
+        let game_code = GameCode::new(['A'; 8]).unwrap();
+        let mut game = GameInstance::with_seed(game_code, 5);
+        let uuids: Vec<Uuid> = (0..MAX_PLAYERS).map(|_| Uuid::new_v4()).collect();
+        for (i, uuid) in uuids.iter().enumerate() {
+            assert!(game.add_user(User::new(format!("player {}", i), *uuid, Urid::from_uuid(Uuid::new_v4()), game_code)));
+            game.user_connected(*uuid);
+        }
+        assert!(game.hand(uuids[0]).unwrap().tiles.is_empty());
+        let (_events, _removed) = game.start().unwrap();
+        let mut dealt = HashSet::new();
+        for uuid in &uuids {
+            let hand = game.hand(*uuid).unwrap();
+            assert_eq!(6, hand.tiles.len());
+            assert_eq!(72, hand.tiles_remaining);
+            dealt.extend(hand.tiles);
+        }
+        assert_eq!(36, dealt.len());
+        assert!(game.hand(Uuid::new_v4()).is_none());
+    }
+
+    #[test]
+    fn test_turn_order() {
+        let game_code = GameCode::new(['A'; 8]).unwrap();
+        let mut game = GameInstance::with_seed(game_code, 5);
+        let uuids: Vec<Uuid> = (0..3).map(|_| Uuid::new_v4()).collect();
+        for (i, uuid) in uuids.iter().enumerate() {
+            assert!(game.add_user(User::new(format!("player {}", i), *uuid, Urid::from_uuid(Uuid::new_v4()), game_code)));
+            game.user_connected(*uuid);
+        }
+        let tile = |game: &GameInstance, uuid: Uuid| game.hand(uuid).unwrap().tiles[0];
+        assert_eq!(Err(PlayTileError::Turn(TurnError::NotStarted)), game.place_tile(uuids[0], Tile::new(1, 'A').unwrap()).map(|_| ()));
+        assert!(game.current_player().is_none());
+        assert!(game.turn_status().is_none());
+        let (events, _removed) = game.start().unwrap();
+        let first = game.current_player().unwrap().id();
+        assert_eq!(("TurnChanged", Some(first.to_string().as_str())), events.contents()[1]);
+
+        // the order is drawn once and then repeats
+        let mut order = Vec::new();
+        for _ in 0..4 {
+            let uuid = game.current_player().unwrap().uuid();
+            order.push(uuid);
+            let turn = game.turn_status().unwrap();
+            assert_eq!((game.current_player().unwrap().id(), TurnPhase::PlaceTile), (turn.player_id, turn.phase));
+            let other = uuids.iter().find(|other| **other != uuid).unwrap();
+            assert_eq!(Err(PlayTileError::Turn(TurnError::NotYourTurn(Box::new(turn)))), game.place_tile(*other, tile(&game, *other)).map(|_| ()));
+            let (_events, placement) = game.place_tile(uuid, tile(&game, uuid)).unwrap();
+            if let Some(chains) = &placement.choose_chain {
+                let (_events, _tiles) = game.found_chain(uuid, chains[0]).unwrap();
+            }
+            if let Some(chains) = &placement.choose_survivor {
+                let (_events, _tiles) = game.choose_survivor(uuid, chains[0]).unwrap();
+            }
+            if placement.buy_stock || placement.choose_chain.is_some() || placement.choose_survivor.is_some() {
+                let (_events, _portfolio) = game.buy_stock(uuid, &[]).unwrap();
+            }
+        }
+        assert_eq!(3, order[..3].iter().collect::<HashSet<_>>().len());
+        assert_eq!(order[0], order[3]);
+        // every player draws a new tile at the end of their turn
+        let hand = game.hand(order[0]).unwrap();
+        assert_eq!((6, 108 - 3 * 6 - 4), (hand.tiles.len(), hand.tiles_remaining));
+        assert_eq!(4, game.board().snapshot().tiles.len());
+        let not_held = tile(&game, order[2]);
+        assert_eq!(Err(PlayTileError::NotInHand), game.place_tile(order[1], not_held).map(|_| ()));
+
+        // the turn passes to the next player when the current player is removed
+        let player_id = game.current_player().unwrap().id();
+        let (_user, _events) = game.remove_player(player_id).unwrap();
+        assert_eq!(order[2], game.current_player().unwrap().uuid());
+    }
+
+    #[test]
+    fn test_settings_validation() {
+        let valid = LobbySettings::new(3, 5, false);
+        assert_eq!(Ok(()), valid.validate(5));
+        let field = |errors: Vec<FieldError>| errors.into_iter().map(|error| (error.field, error.code, error.min, error.max)).collect::<Vec<_>>();
+        let fields = field(LobbySettings::new(1, 9, false).validate(0).unwrap_err());
+        assert_eq!(vec![
+            (String::from("max_players"), String::from("out_of_range"), Some(2), Some(6)),
+            (String::from("min_players"), String::from("out_of_range"), Some(2), Some(6)),
+        ], fields);
+        // min_players has to fit below max_players
+        let fields = field(LobbySettings::new(5, 4, false).validate(0).unwrap_err());
+        assert_eq!(vec![(String::from("min_players"), String::from("greater_than_max_players"), Some(2), Some(4))], fields);
+        // max_players can not drop below the joined players
+        let fields = field(LobbySettings::new(2, 3, false).validate(4).unwrap_err());
+        assert_eq!(vec![(String::from("max_players"), String::from("out_of_range"), Some(4), Some(6))], fields);
+    }
+
+    #[test]
+    fn test_lobby_status_event_on_connect() {
+        let mut game_manager = GameManager::new();
+        let bus = EventBus::new(DEFAULT_COALESCE_WINDOW);
+        let registration = rocket::serde::json::to_value(game_manager.create_game(random_game_code(), String::from("a"), None, None).unwrap()).unwrap();
+        let uuid = Uuid::parse_str(registration["uuid"].as_str().unwrap()).unwrap();
+        let game_code = GameCode::from_string(registration["game_code"].as_str().unwrap()).unwrap();
+        let mut receiver = game_manager.game_by_code_read(game_code).unwrap().channel().subscribe();
+        assert_eq!(2, game_manager.user_connected(UserAuth { uuid, game_code }).unwrap().publish(&bus));
+        let event = rocket::serde::json::to_value(receiver.try_recv().unwrap()).unwrap();
+        assert_eq!("PlayerList", event["data"][0]);
+        assert_eq!(r#"[{"player_id":1,"seat":1,"name":"a","connected":true,"game_master":true}]"#, event["data"][1]);
+        let event = rocket::serde::json::to_value(receiver.try_recv().unwrap()).unwrap();
+        assert_eq!("LobbyStatus", event["data"][0]);
+        assert_eq!(r#"{"current_players":1,"min_players":2,"max_players":6,"can_start":false}"#, event["data"][1]);
+    }
+
+    #[test]
+    fn test_add_player_events() {
+        let mut game_manager = GameManager::new();
+        let bus = EventBus::new(DEFAULT_COALESCE_WINDOW);
+        let registration = rocket::serde::json::to_value(game_manager.create_game(random_game_code(), String::from("a"), None, None).unwrap()).unwrap();
+        let game_code = GameCode::from_string(registration["game_code"].as_str().unwrap()).unwrap();
+        let mut receiver = game_manager.game_by_code_read(game_code).unwrap().channel().subscribe();
+        let (_, events) = game_manager.add_player_to_game(game_code, String::from("b"), None, None, None).unwrap();
+        // nothing is send before the batch is published
+        assert!(receiver.try_recv().is_err());
+        // without a runtime the player list is send directly
+        assert_eq!(1, events.publish(&bus));
+        let event = rocket::serde::json::to_value(receiver.try_recv().unwrap()).unwrap();
+        assert_eq!("PlayerList", event["data"][0]);
+        assert_eq!(r#"[{"player_id":1,"seat":1,"name":"a","connected":false,"game_master":true},{"player_id":2,"seat":2,"name":"b","connected":false,"game_master":false}]"#, event["data"][1]);
+        let unknown = GameCode::new(['0'; 8]).unwrap();
+        assert!(game_manager.add_player_to_game(unknown, String::from("c"), None, None, None).is_err());
+    }
+
+    #[test]
+    fn test_player_ids_are_stable() {
+        let mut game_manager = GameManager::new();
+        let game_master = user_auth(game_manager.create_game(random_game_code(), String::from("a"), None, None).unwrap());
+        let game_code = game_master.game_code;
+        let b = user_auth(game_manager.add_player_to_game(game_code, String::from("b"), None, None, None).unwrap().0);
+        let _joined = game_manager.add_player_to_game(game_code, String::from("c"), None, None, None).unwrap();
+        let _events = game_manager.user_connected(b);
+        game_manager.game_by_code_write(game_code).unwrap().user_disconnected(b.uuid, VacancyReason::ConnectionLost);
+        // rejoining keeps the id
+        let rejoined = user_auth(game_manager.add_player_to_game(game_code, String::from("b"), None, None, None).unwrap().0);
+        assert_eq!(b.uuid, rejoined.uuid);
+        let seats = |game_manager: &GameManager| -> Vec<(u8, u32, String)> {
+            game_manager.game_by_code_read(game_code).unwrap().player_list().into_iter().map(|entry| (entry.seat, entry.player_id, entry.name)).collect()
+        };
+        assert_eq!(vec![(1, 1, String::from("a")), (2, 2, String::from("b")), (3, 3, String::from("c"))], seats(&game_manager));
+
+        // a new player takes the free seat but gets a new id
+        assert!(game_manager.game_by_code_write(game_code).unwrap().remove_player(2).is_some());
+        let _joined = game_manager.add_player_to_game(game_code, String::from("d"), None, None, None).unwrap();
+        assert_eq!(vec![(1, 1, String::from("a")), (2, 4, String::from("d")), (3, 3, String::from("c"))], seats(&game_manager));
+    }
+
+    #[test]
+    fn test_abandonment_stats() {
+        let game_manager = RwLock::new(GameManager::new());
+        let auth = connected_game(&game_manager);
+        let report = || game_manager.read().unwrap().abandonment_stats().report();
+        // connecting for the first time does not recover the game
+        assert_eq!(0, report().recovered);
+        let generation = {
+            let game_manager = game_manager.read().unwrap();
+            let mut game = game_manager.game_by_code_write(auth.game_code).unwrap();
+            assert!(game.user_disconnected(auth.uuid, VacancyReason::ConnectionLost));
+            game.mark_abandoned().unwrap()
+        };
+        let _events = game_manager.read().unwrap().user_connected(auth);
+        assert_eq!(1, report().buckets[0].count);
+        let _events = game_manager.read().unwrap().user_connected(auth);
+        assert_eq!(1, report().recovered);
+
+        assert!(!game_manager.write().unwrap().delete_game_if_abandoned(auth.game_code, generation));
+        assert_eq!(0, report().deleted);
+        let status = disconnect_user(&game_manager, auth, VacancyReason::Left);
+        let generation = game_manager.read().unwrap().game_by_code_read(auth.game_code).unwrap().generation();
+        assert_eq!(UserDisconnectedStatus::GameAbandoned(generation), status);
+        assert!(game_manager.write().unwrap().delete_game_if_abandoned(auth.game_code, generation));
+        let report = report();
+        assert_eq!((1, 1, Some(0.5)), (report.recovered, report.deleted, report.recovery_ratio));
+    }
+
+    #[test]
+    fn test_seat_vacancy() {
+        let game_manager = RwLock::new(GameManager::new());
+        let auth = connected_game(&game_manager);
+        let other = {
+            let mut game_manager = game_manager.write().unwrap();
+            let other = user_auth(game_manager.add_player_to_game(auth.game_code, String::from("b"), None, None, None).unwrap().0);
+            let _events = game_manager.user_connected(other);
+            other
+        };
+        let vacancy = |user: UserAuth| game_manager.read().unwrap().game_by_code_read(user.game_code).unwrap().vacancy(user.uuid);
+        assert_eq!(None, vacancy(other));
+
+        assert_eq!(UserDisconnectedStatus::GameAlive, disconnect_user(&game_manager, other, VacancyReason::ConnectionLost));
+        let lost = vacancy(other).unwrap();
+        assert_eq!(VacancyReason::ConnectionLost, lost.reason);
+        let events = game_manager.read().unwrap().user_disconnected_events(other).unwrap();
+        assert_eq!(3, events.publish(&EventBus::new(DEFAULT_COALESCE_WINDOW)));
+        // leaving after the stream was closed replaces the reason but keeps the time
+        assert_eq!(UserDisconnectedStatus::AlreadyDisconnected, disconnect_user(&game_manager, other, VacancyReason::Left));
+        assert_eq!(Some(Vacancy { reason: VacancyReason::Left, since: lost.since }), vacancy(other));
+        // a lost connection does not hide that the player left
+        disconnect_user(&game_manager, other, VacancyReason::ConnectionLost);
+        assert_eq!(VacancyReason::Left, vacancy(other).unwrap().reason);
+
+        // reconnecting clears the vacancy and tells the other players
+        let bus = EventBus::new(DEFAULT_COALESCE_WINDOW);
+        let mut receiver = game_manager.read().unwrap().game_by_code_read(other.game_code).unwrap().channel().subscribe();
+        assert_eq!(3, game_manager.read().unwrap().user_connected(other).unwrap().publish(&bus));
+        assert_eq!(None, vacancy(other));
+        let change = std::iter::from_fn(|| receiver.try_recv().ok())
+            .map(|event| rocket::serde::json::to_value(event).unwrap())
+            .find(|event| event["data"][0] == "SeatVacancyChanged")
+            .unwrap();
+        assert_eq!(r#"{"player_id":2,"vacancy":null}"#, change["data"][1]);
+        // without a vacancy no event is send
+        assert_eq!(2, game_manager.read().unwrap().user_connected(auth).unwrap().publish(&bus));
+    }
+
+    #[test]
+    fn test_disconnect_user_twice() {
+        let game_manager = RwLock::new(GameManager::new());
+        let auth = connected_game(&game_manager);
+        let other = {
+            let mut game_manager = game_manager.write().unwrap();
+            let other = user_auth(game_manager.add_player_to_game(auth.game_code, String::from("b"), None, None, None).unwrap().0);
+            let _events = game_manager.user_connected(other);
+            other
+        };
+        let statuses: Vec<UserDisconnectedStatus> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..2).map(|_| scope.spawn(|| disconnect_user(&game_manager, auth, VacancyReason::ConnectionLost))).collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+        assert!(statuses.contains(&UserDisconnectedStatus::GameAlive));
+        assert!(statuses.contains(&UserDisconnectedStatus::AlreadyDisconnected));
+        assert!(game_manager.read().unwrap().game_by_code(auth.game_code).is_some());
+        // the last player leaving abandons the game exactly once
+        let statuses: Vec<UserDisconnectedStatus> = thread::scope(|scope| {
+            let handles: Vec<_> = (0..2).map(|_| scope.spawn(|| disconnect_user(&game_manager, other, VacancyReason::Left))).collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+        assert_eq!(1, statuses.iter().filter(|status| matches!(status, UserDisconnectedStatus::GameAbandoned(_))).count());
+        assert!(statuses.contains(&UserDisconnectedStatus::AlreadyDisconnected));
+    }
+
+    /// Disconnects the only connected player of the game.
+    ///
+    /// # Returns
+    /// The generation with which the game can be deleted.
+    fn abandon(game_manager: &RwLock<GameManager>, auth: UserAuth) -> u64 {
+        match disconnect_user(game_manager, auth, VacancyReason::ConnectionLost) {
+            UserDisconnectedStatus::GameAbandoned(generation) => generation,
+            status => panic!("the game was not abandoned: {:?}", status),
+        }
+    }
+
+    #[test]
+    fn test_reconnect_while_abandoned() {
+        let game_manager = RwLock::new(GameManager::new());
+        let auth = connected_game(&game_manager);
+        let generation = abandon(&game_manager, auth);
+        let _events = game_manager.read().unwrap().user_connected(auth);
+        assert!(!game_manager.write().unwrap().delete_game_if_abandoned(auth.game_code, generation));
+        assert!(game_manager.read().unwrap().game_by_code(auth.game_code).is_some());
+    }
+
+    #[test]
+    fn test_join_while_abandoned() {
+        let game_manager = RwLock::new(GameManager::new());
+        let auth = connected_game(&game_manager);
+        let generation = abandon(&game_manager, auth);
+        // the new player has not opened a stream yet but the game should still be kept
+        let _joined = game_manager.write().unwrap().add_player_to_game(auth.game_code, String::from("b"), None, None, None).unwrap();
+        assert!(!game_manager.write().unwrap().delete_game_if_abandoned(auth.game_code, generation));
+        assert!(game_manager.read().unwrap().game_by_code(auth.game_code).is_some());
+    }
+
+    #[test]
+    fn test_single_use_invite_race() {
+        let game_manager = RwLock::new(GameManager::new());
+        let auth = connected_game(&game_manager);
+        let invite = {
+            let game_manager = game_manager.read().unwrap();
+            let mut game = game_manager.game_by_code_write(auth.game_code).unwrap();
+            game.set_settings(LobbySettings::new(MIN_PLAYERS, MAX_PLAYERS, true)).unwrap();
+            game.invites_mut().create(1, Duration::from_secs(60)).unwrap().info().id
+        };
+        let results: Vec<_> = thread::scope(|scope| {
+            let handles: Vec<_> = ["b", "c"].into_iter()
+                .map(|name| {
+                    let game_manager = &game_manager;
+                    scope.spawn(move || game_manager.write().unwrap().add_player_to_game(auth.game_code, String::from(name), None, None, Some(invite)).map(|_| ()))
+                })
+                .collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+        assert_eq!(1, results.iter().filter(|result| result.is_ok()).count());
+        assert!(results.contains(&Err(UserRegistrationError::InviteInvalid)));
+    }
+
+    #[test]
+    fn test_join_during_lobby_admin_lands_after_lock() {
+        let game_manager = RwLock::new(GameManager::new());
+        let auth = connected_game(&game_manager);
+        let request = LobbyAdminRequest { lock: Some(true), ..LobbyAdminRequest::default() };
+        let barrier = Barrier::new(2);
+        let joined = thread::scope(|scope| {
+            let mut locked_manager = game_manager.write().unwrap();
+            let join = scope.spawn(|| {
+                barrier.wait();
+                game_manager.write().unwrap().add_player_to_game(auth.game_code, String::from("b"), None, None, None).map(|_| ())
+            });
+            barrier.wait();
+            // the join is now waiting for the lock of the game manager
+            thread::sleep(Duration::from_millis(50));
+            let (_, _events, kicked) = locked_manager.game_by_code_write(auth.game_code).unwrap().lobby_admin(auth.uuid, &request);
+            locked_manager.forget_users(&kicked);
+            drop(locked_manager);
+            join.join().unwrap()
+        });
+        assert_eq!(Err(UserRegistrationError::LobbyLocked), joined);
+    }
+
+    /// Creates a game for two players with an enabled waitlist, "b" has taken the second seat.
+    fn full_game_with_waitlist(game_manager: &RwLock<GameManager>) -> UserAuth {
+        let auth = connected_game(game_manager);
+        let mut game_manager = game_manager.write().unwrap();
+        {
+            let mut game = game_manager.game_by_code_write(auth.game_code).unwrap();
+            game.set_settings(LobbySettings::new(2, 2, false).with_enable_waitlist(true)).unwrap();
+        }
+        let _joined = game_manager.add_player_to_game(auth.game_code, String::from("b"), None, None, None).unwrap();
+        auth
+    }
+
+    fn kick(game_manager: &mut GameManager, auth: UserAuth, player_id: u32) -> EventBatch {
+        let request = LobbyAdminRequest { kick: vec![player_id], ..LobbyAdminRequest::default() };
+        let (_, events, kicked) = game_manager.game_by_code_write(auth.game_code).unwrap().lobby_admin(auth.uuid, &request);
+        game_manager.forget_users(&kicked);
+        events
+    }
+
+    #[test]
+    fn test_waitlist_positions_and_cap() {
+        let game_manager = RwLock::new(GameManager::new());
+        let auth = full_game_with_waitlist(&game_manager);
+        let mut game_manager = game_manager.write().unwrap();
+        for i in 1..=MAX_WAITING_USERS {
+            let (registration, _) = game_manager.add_player_to_game(auth.game_code, format!("w{}", i), None, None, None).unwrap();
+            assert_eq!(Some(i), registration.waitlist_position());
+        }
+        let joined = game_manager.add_player_to_game(auth.game_code, String::from("late"), None, None, None).map(|_| ());
+        assert_eq!(Err(UserRegistrationError::GameFull), joined);
+        // waiting users that join again keep their position
+        let (registration, _) = game_manager.add_player_to_game(auth.game_code, String::from("w2"), None, None, None).unwrap();
+        assert_eq!(Some(2), registration.waitlist_position());
+        assert_eq!(2, game_manager.game_by_code_read(auth.game_code).unwrap().players().len());
+    }
+
+    #[test]
+    fn test_waitlist_promotion_order() {
+        let game_manager = RwLock::new(GameManager::new());
+        let auth = full_game_with_waitlist(&game_manager);
+        let waiting: Vec<UserAuth> = ["w1", "w2"].iter()
+            .map(|name| user_auth(game_manager.write().unwrap().add_player_to_game(auth.game_code, String::from(*name), None, None, None).unwrap().0))
+            .collect();
+        let bus = EventBus::new(Duration::ZERO);
+        let mut receiver = game_manager.read().unwrap().game_by_code_read(auth.game_code).unwrap().channel().subscribe();
+        kick(&mut game_manager.write().unwrap(), auth, 2).publish(&bus);
+        let names: Vec<String> = std::iter::from_fn(|| receiver.try_recv().ok())
+            .map(|event| rocket::serde::json::to_value(event).unwrap())
+            .inspect(|event| if event["data"][0] == "Promoted" {
+                assert_eq!(waiting[0].uuid.to_string(), event["user_id"]);
+                assert!(event["data"][1].as_str().unwrap().contains(&waiting[0].uuid.to_string()));
+            })
+            .map(|event| String::from(event["data"][0].as_str().unwrap()))
+            .collect();
+        assert_eq!(vec!["Kicked", "Promoted", "PlayerList", "LobbyStatus"], names);
+        let game_manager = game_manager.read().unwrap();
+        let game = game_manager.game_by_code_read(auth.game_code).unwrap();
+        assert_eq!(vec!["a", "w1"], game.players().iter().map(|player| player.username()).collect::<Vec<_>>());
+        assert_eq!(Some(1), game.waitlist().position(waiting[1].uuid));
+    }
+
+    #[test]
+    fn test_promotion_races_with_join() {
+        let game_manager = RwLock::new(GameManager::new());
+        let auth = full_game_with_waitlist(&game_manager);
+        let _joined = game_manager.write().unwrap().add_player_to_game(auth.game_code, String::from("w1"), None, None, None).unwrap();
+        let barrier = Barrier::new(2);
+        let joined = thread::scope(|scope| {
+            let mut locked_manager = game_manager.write().unwrap();
+            let join = scope.spawn(|| {
+                barrier.wait();
+                game_manager.write().unwrap().add_player_to_game(auth.game_code, String::from("c"), None, None, None).map(|(registration, _)| registration.waitlist_position())
+            });
+            barrier.wait();
+            // the join is now waiting for the lock of the game manager
+            thread::sleep(Duration::from_millis(50));
+            let _events = kick(&mut locked_manager, auth, 2);
+            drop(locked_manager);
+            join.join().unwrap()
+        });
+        // the free seat went to w1, the new user waits instead of taking it
+        assert_eq!(Ok(Some(1)), joined);
+        let game_manager = game_manager.read().unwrap();
+        let game = game_manager.game_by_code_read(auth.game_code).unwrap();
+        assert_eq!(vec!["a", "w1"], game.players().iter().map(|player| player.username()).collect::<Vec<_>>());
+        assert_eq!(1, game.waitlist().users().len());
+    }
+
+    #[test]
+    fn test_closed_waitlist_is_forgotten() {
+        let game_manager = RwLock::new(GameManager::new());
+        let auth = full_game_with_waitlist(&game_manager);
+        let mut game_manager = game_manager.write().unwrap();
+        let waiting = user_auth(game_manager.add_player_to_game(auth.game_code, String::from("w1"), None, None, None).unwrap().0);
+        assert!(game_manager.game_by_uuid(waiting.uuid).is_some());
+        let (_events, removed) = game_manager.game_by_code_write(auth.game_code).unwrap().close_waitlist();
+        game_manager.forget_users(&removed);
+        assert!(game_manager.game_by_uuid(waiting.uuid).is_none());
+        assert_eq!(None, game_manager.game_by_urid(&removed[0].urid));
+
+        // waiting users are also removed with their game
+        let waiting = user_auth(game_manager.add_player_to_game(auth.game_code, String::from("w2"), None, None, None).unwrap().0);
+        assert!(game_manager.delete_game(&auth.game_code));
+        assert!(game_manager.game_by_uuid(waiting.uuid).is_none());
+        assert!(game_manager.used_uuids.is_empty());
+    }
+
+    /// Creates a game with connected players named `names`, the first player is the game master.
+    fn lobby_with_players(names: &[&str]) -> (GameInstance, Vec<Uuid>) {
+        let game_code = random_game_code();
+        let mut game = GameInstance::new(game_code);
+        let uuids: Vec<Uuid> = names.iter().map(|name| {
+            let uuid = Uuid::new_v4();
+            game.add_user(User::new(String::from(*name), uuid, Urid::from_uuid(Uuid::new_v4()), game_code));
+            game.user_connected(uuid);
+            uuid
+        }).collect();
+        game.set_game_master(uuids[0]);
+        (game, uuids)
+    }
+
+    #[test]
+    fn test_idle_master_rotation() {
+        let (mut game, uuids) = lobby_with_players(&["a", "b", "c"]);
+        let later = || Instant::now() + Duration::from_secs(121);
+        // disabled by default
+        assert_eq!(None, game.idle_master_rotation(later()));
+        game.set_master_idle_rotate_secs(120);
+        assert_eq!(None, game.idle_master_rotation(Instant::now()));
+        let bus = EventBus::new(Duration::ZERO);
+        let mut receiver = game.channel().subscribe();
+        // the rotation cycles through all players in seat order
+        for expected in [1, 2, 0, 1] {
+            let generation = game.idle_master_rotation(later()).unwrap();
+            assert_eq!(2, game.rotate_idle_game_master(generation).unwrap().publish(&bus));
+            let event = rocket::serde::json::to_value(receiver.try_recv().unwrap()).unwrap();
+            assert_eq!("NewGameMaster", event["data"][0]);
+            assert_eq!(format!(r#"{{"player_id":{},"reason":"idle_rotation"}}"#, expected + 1), event["data"][1]);
+            assert_eq!("PlayerList", rocket::serde::json::to_value(receiver.try_recv().unwrap()).unwrap()["data"][0]);
+            assert_eq!(Some(uuids[expected]), game.game_master());
+            assert_eq!(None, game.idle_master_rotation(Instant::now()));
+        }
+    }
+
+    #[test]
+    fn test_idle_master_rotation_cancelled() {
+        let (mut game, uuids) = lobby_with_players(&["a", "b"]);
+        game.set_master_idle_rotate_secs(120);
+        let generation = game.idle_master_rotation(Instant::now() + Duration::from_secs(121)).unwrap();
+        game.game_master_active();
+        assert!(game.rotate_idle_game_master(generation).is_none());
+        assert_eq!(Some(uuids[0]), game.game_master());
+        // no rotation without enough connected players
+        game.user_disconnected(uuids[1], VacancyReason::ConnectionLost);
+        assert_eq!(None, game.idle_master_rotation(Instant::now() + Duration::from_secs(121)));
+    }
 }
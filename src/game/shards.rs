@@ -0,0 +1,412 @@
+use std::{collections::HashMap, net::IpAddr, sync::{Arc, RwLock}, thread, time::Duration};
+
+use rocket::tokio::{self, runtime::Handle};
+use uuid::Uuid;
+
+use crate::{authentication::{UserAuth, UserRecovery, Urid}, events::{EventBatch, EventBus, GameChannel}, request_data::UserRegistration, utils::{get_gm_read_guard, get_gm_write_guard}};
+
+use super::{abandonment::{AbandonmentReport, AbandonmentStats}, base_game::VacancyReason, deletion::{DeletionQueue, Shards, UserIndex}, disconnect_user, game_instance::{GameCode, GameInstance}, random_game_code, GameManager, UserDisconnectedStatus, UserRegistrationError};
+
+/// The default number of shards, one shard behaves exactly like a single [GameManager]()
+pub const DEFAULT_SHARDS: usize = 1;
+
+/// Splits the games over multiple [GameManager]()s, each behind its own [RwLock](), managed by rocket.
+///
+/// Games are assigned to a shard by a stable hash of their [GameCode](game_instance/struct.GameCode.html),
+/// so a game is always found on the shard that created it. Users are found by an index that maps
+/// their uuid to the game code of their game.
+///
+/// The number of shards is set with `game_manager_shards` in the rocket configuration, see [DEFAULT_SHARDS]().
+///
+/// Clones share the same games, they are used by background tasks that outlive a request.
+#[derive(Clone)]
+pub struct ShardedGameManager {
+    shards: Shards,
+    /// Maps the uuids of all users to the game code of the game they are assigned to.
+    ///
+    /// The index is split by uuid the same way the games are split so that registering users does not need a global lock.
+    /// It is only used to find the shard, the shard itself is the source of truth.
+    /// Entries of deleted games are removed by the [DeletionQueue](../deletion/struct.DeletionQueue.html).
+    users: UserIndex,
+    /// Deletes the games that are marked for deletion
+    deletions: DeletionQueue,
+    /// Shared by all shards
+    abandonment: Arc<AbandonmentStats>,
+}
+
+impl ShardedGameManager {
+    /// Creates a new manager with `shards` shards, at least one shard is always created.
+    pub fn new(shards: usize) -> Self {
+        let shards = shards.max(1);
+        let users: UserIndex = (0..shards).map(|_| RwLock::new(HashMap::new())).collect();
+        let abandonment = Arc::new(AbandonmentStats::default());
+        let shards: Shards = (0..shards).map(|_| RwLock::new(GameManager::new().with_abandonment_stats(Arc::clone(&abandonment)))).collect();
+        Self {
+            deletions: DeletionQueue::new(Arc::clone(&shards), Arc::clone(&users)),
+            shards,
+            users,
+            abandonment,
+        }
+    }
+
+    /// Returns what happened to the games of all shards after they were abandoned, see [AbandonmentStats](../abandonment/struct.AbandonmentStats.html).
+    pub fn abandonment_report(&self) -> AbandonmentReport {
+        self.abandonment.report()
+    }
+
+    /// Returns all shards.
+    pub fn shards(&self) -> &[RwLock<GameManager>] {
+        &self.shards
+    }
+
+    /// Returns the shard that is responsible for the game with `game_code`.
+    ///
+    /// The game does not need to exist, this can be used to check if it does.
+    pub fn shard(&self, game_code: &GameCode) -> &RwLock<GameManager> {
+        &self.shards[shard_index(game_code, self.shards.len())]
+    }
+
+    /// Returns the shard of the game to which the user with `uuid` is assigned.
+    ///
+    /// # Returns
+    /// `None` when the user is not registered.
+    pub fn shard_by_uuid(&self, uuid: Uuid) -> Option<&RwLock<GameManager>> {
+        let game_code = *self.users_of(uuid).read().unwrap().get(&uuid)?;
+        Some(self.shard(&game_code))
+    }
+
+    /// Creates a new game with an unused game code on the shard of that code, see [GameManager::create_game](struct.GameManager.html#method.create_game).
+    pub fn create_game(&self, username: String, ip_addr: Option<IpAddr>, seed: Option<u64>) -> Option<UserRegistration> {
+        loop {
+            let code = random_game_code();
+            // Codes are unique per shard and a code is always placed on the same shard
+            let registration = get_gm_write_guard(self.shard(&code), "create_game").create_game(code, username.clone(), ip_addr, seed);
+            if let Some(registration) = registration {
+                self.register_user(registration.uuid(), code);
+                return Some(registration);
+            }
+        }
+    }
+
+    /// Adds the player to the game, see [GameManager::add_player_to_game](struct.GameManager.html#method.add_player_to_game).
+    pub fn add_player_to_game(&self, game_code: GameCode, username: String, ur: Option<UserRecovery>, ip_addr: Option<IpAddr>, invite: Option<Uuid>) -> Result<(UserRegistration, EventBatch), UserRegistrationError> {
+        let result = get_gm_write_guard(self.shard(&game_code), "join_game").add_player_to_game(game_code, username, ur, ip_addr, invite);
+        if let Ok((registration, _)) = &result {
+            self.register_user(registration.uuid(), game_code);
+        }
+        result
+    }
+
+    /// Disconnects the user, see [disconnect_user](../fn.disconnect_user.html).
+    ///
+    /// When no player is connected anymore the game is deleted after `delay` (usually [GAME_INSTANCE_TIMEOUT](../constant.GAME_INSTANCE_TIMEOUT.html)),
+    /// unless a player reconnects or joins in the meantime. The delay is waited for in a task, so this returns immediately with
+    /// [DeletionScheduled](../enum.UserDisconnectedStatus.html#variant.DeletionScheduled). When `delay` is zero the game is deleted directly.
+    ///
+    /// When the game is marked for deletion it is queued in the [DeletionQueue](../deletion/struct.DeletionQueue.html),
+    /// which also removes its users from the index. The game is treated as deleted immediately.
+    pub fn disconnect_user(&self, user_auth: UserAuth, reason: VacancyReason, delay: Duration) -> UserDisconnectedStatus {
+        let generation = match disconnect_user(self.shard(&user_auth.game_code), user_auth, reason) {
+            UserDisconnectedStatus::GameAbandoned(generation) => generation,
+            status => return status,
+        };
+        if delay.is_zero() {
+            return self.delete_game_if_abandoned(user_auth.game_code, generation);
+        }
+        let game_manager = self.clone();
+        let check = move || game_manager.delete_game_if_abandoned(user_auth.game_code, generation);
+        match Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    check();
+                });
+            },
+            // Only happens outside of rocket, for example in tests
+            Err(_) => {
+                thread::spawn(move || {
+                    thread::sleep(delay);
+                    check();
+                });
+            },
+        }
+        UserDisconnectedStatus::DeletionScheduled
+    }
+
+    /// Marks the game for deletion and queues it when it is still abandoned and its generation is still `generation`,
+    /// see [GameManager::delete_game_if_abandoned](../struct.GameManager.html#method.delete_game_if_abandoned).
+    ///
+    /// # Returns
+    /// [GameDeleted](../enum.UserDisconnectedStatus.html#variant.GameDeleted) when the game was marked for deletion,
+    /// [GameAlive](../enum.UserDisconnectedStatus.html#variant.GameAlive) otherwise.
+    fn delete_game_if_abandoned(&self, game_code: GameCode, generation: u64) -> UserDisconnectedStatus {
+        if !get_gm_write_guard(self.shard(&game_code), "delete_game_if_abandoned").delete_game_if_abandoned(game_code, generation) {
+            return UserDisconnectedStatus::GameAlive;
+        }
+        self.deletions.push(game_code);
+        UserDisconnectedStatus::GameDeleted
+    }
+
+    /// Disconnects the user whose sse stream was lost like [disconnect_user](#method.disconnect_user)
+    /// and tells the other players about it when the game is still alive.
+    ///
+    /// Users on the waitlist can still follow the game while its deletion is scheduled.
+    pub fn connection_lost(&self, user_auth: UserAuth, event: &EventBus, delay: Duration) -> UserDisconnectedStatus {
+        let status = self.disconnect_user(user_auth, VacancyReason::ConnectionLost, delay);
+        if let UserDisconnectedStatus::GameAlive | UserDisconnectedStatus::DeletionScheduled = status {
+            if let Some(events) = get_gm_read_guard(self.shard(&user_auth.game_code), "connection_lost").user_disconnected_events(user_auth) {
+                events.publish(event);
+            }
+        }
+        status
+    }
+
+    /// Waits until all games that are marked for deletion are deleted or `timeout` has passed, see [DeletionQueue::wait](../deletion/struct.DeletionQueue.html#method.wait).
+    pub fn wait_for_deletions(&self, timeout: Duration) -> bool {
+        self.deletions.wait(timeout)
+    }
+
+    /// Checks if a game with the game code exists.
+    pub fn does_game_exist(&self, game_code: &GameCode) -> bool {
+        get_gm_read_guard(self.shard(game_code), "does_game_exist").does_game_exist(game_code)
+    }
+
+    /// Returns the game codes of all games on all shards.
+    pub fn game_codes(&self) -> Vec<GameCode> {
+        self.shards.iter()
+            .flat_map(|shard| get_gm_read_guard(shard, "game_codes").game_codes())
+            .collect()
+    }
+
+    /// Returns a new receiver for the events of the game, `None` when the game does not exist.
+    #[cfg(test)]
+    pub fn subscribe(&self, game_code: GameCode) -> Option<rocket::tokio::sync::broadcast::Receiver<crate::events::PublishedEvent>> {
+        get_gm_read_guard(self.shard(&game_code), "subscribe").game_by_code_read(game_code).map(|game| game.channel().subscribe())
+    }
+
+    /// Returns the event channels of all games for which `filter` returns `true`.
+    pub fn channels_where(&self, filter: impl Fn(&GameInstance) -> bool) -> Vec<GameChannel> {
+        self.shards.iter()
+            .flat_map(|shard| {
+                let shard = get_gm_read_guard(shard, "channels_where");
+                shard.game_codes().into_iter()
+                    .filter_map(|game_code| shard.game_by_code_read(game_code).filter(|game| filter(game)).map(|game| game.channel().clone()))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Returns the name that was last used with `urid` and the code of the game in which a player currently has the urid.
+    ///
+    /// # Returns
+    /// `None` when no name is known for the urid, for example because the urid has expired.
+    pub fn whoami(&self, urid: &Urid) -> Option<(String, Option<GameCode>)> {
+        // Each shard issues its own urids, so only one shard can know the urid
+        self.shards.iter().find_map(|shard| {
+            let shard = get_gm_read_guard(shard, "whoami");
+            shard.name_by_urid(urid).map(|name| (name, shard.game_by_urid(urid)))
+        })
+    }
+
+    /// Removes the users from the index, used when players are removed from a game that continues.
+    pub fn forget_users(&self, uuids: &[Uuid]) {
+        for uuid in uuids {
+            self.users_of(*uuid).write().unwrap().remove(uuid);
+        }
+    }
+
+    fn register_user(&self, uuid: Uuid, game_code: GameCode) {
+        self.users_of(uuid).write().unwrap().insert(uuid, game_code);
+    }
+
+    /// Returns the part of the user index that contains `uuid`.
+    fn users_of(&self, uuid: Uuid) -> &RwLock<HashMap<Uuid, GameCode>> {
+        &self.users[user_index(uuid, self.users.len())]
+    }
+}
+
+/// Returns the index of the shard for `game_code`, the FNV-1a hash of the code is used so that the index does not change between releases.
+pub(super) fn shard_index(game_code: &GameCode, shards: usize) -> usize {
+    let hash = game_code.to_string().bytes().fold(0xcbf29ce484222325_u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+    (hash % shards as u64) as usize
+}
+
+/// Returns the index of the part of the user index that contains `uuid`.
+pub(super) fn user_index(uuid: Uuid, parts: usize) -> usize {
+    (uuid.as_u128() % parts as u128) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::{Duration, Instant}};
+
+    use rocket::tokio;
+
+    use crate::{authentication::UserAuth, game::{base_game::VacancyReason, game_instance::GameCode, UserDisconnectedStatus}};
+
+    use super::ShardedGameManager;
+
+    fn user_auth(game_manager: &ShardedGameManager, registration: crate::request_data::UserRegistration) -> UserAuth {
+        UserAuth::from_uuid(game_manager, registration.uuid()).unwrap()
+    }
+
+    #[test]
+    fn test_game_is_found_on_its_shard() {
+        let game_manager = ShardedGameManager::new(4);
+        for i in 0..64 {
+            let auth = user_auth(&game_manager, game_manager.create_game(format!("player {}", i), None, None).unwrap());
+            assert!(game_manager.shard(&auth.game_code).read().unwrap().does_game_exist(&auth.game_code));
+            let shards_with_game = game_manager.shards().iter()
+                .filter(|shard| shard.read().unwrap().does_game_exist(&auth.game_code))
+                .count();
+            assert_eq!(1, shards_with_game);
+            let (registration, _) = game_manager.add_player_to_game(auth.game_code, String::from("b"), None, None, None).unwrap();
+            assert_eq!(auth.game_code, user_auth(&game_manager, registration).game_code);
+        }
+        assert_eq!(64, game_manager.game_codes().len());
+        assert!(game_manager.shards().iter().all(|shard| !shard.read().unwrap().game_codes().is_empty()));
+    }
+
+    #[test]
+    fn test_deleted_users_are_forgotten() {
+        let game_manager = ShardedGameManager::new(2);
+        let auth = user_auth(&game_manager, game_manager.create_game(String::from("a"), None, None).unwrap());
+        let _events = game_manager.shard(&auth.game_code).read().unwrap().user_connected(auth);
+        assert_eq!(UserDisconnectedStatus::GameDeleted, game_manager.disconnect_user(auth, VacancyReason::Left, Duration::ZERO));
+        assert!(!game_manager.does_game_exist(&auth.game_code));
+        assert!(game_manager.wait_for_deletions(Duration::from_secs(1)));
+        assert!(game_manager.shard_by_uuid(auth.uuid).is_none());
+        assert!(UserAuth::from_uuid(&game_manager, auth.uuid).is_none());
+    }
+
+    #[test]
+    fn test_deletion_is_not_done_by_the_caller() {
+        let game_manager = ShardedGameManager::new(1);
+        let auth = user_auth(&game_manager, game_manager.create_game(String::from("a"), None, None).unwrap());
+        let _events = game_manager.shard(&auth.game_code).read().unwrap().user_connected(auth);
+        let start = Instant::now();
+        let status = {
+            // The deletion can not finish while the user index is locked
+            let _index = game_manager.users_of(auth.uuid).write().unwrap();
+            let status = game_manager.disconnect_user(auth, VacancyReason::Left, Duration::ZERO);
+            let shard = game_manager.shard(&auth.game_code).read().unwrap();
+            assert!(shard.is_pending_deletion(&auth.game_code));
+            assert!(!shard.does_game_exist(&auth.game_code));
+            assert!(shard.game_by_uuid(auth.uuid).is_none());
+            status
+        };
+        assert_eq!(UserDisconnectedStatus::GameDeleted, status);
+        assert!(start.elapsed() < Duration::from_millis(500));
+        // The game code is only free again when the deletion is done
+        assert!(game_manager.shard(&auth.game_code).write().unwrap().create_game(auth.game_code, String::from("b"), None, None).is_none());
+        assert!(game_manager.wait_for_deletions(Duration::from_secs(1)));
+        assert!(game_manager.shard_by_uuid(auth.uuid).is_none());
+        assert!(game_manager.shard(&auth.game_code).write().unwrap().create_game(auth.game_code, String::from("b"), None, None).is_some());
+    }
+
+    const DELAY: Duration = Duration::from_millis(100);
+
+    /// Creates a game with a connected game master.
+    fn connected_game(game_manager: &ShardedGameManager) -> UserAuth {
+        let auth = user_auth(game_manager, game_manager.create_game(String::from("a"), None, None).unwrap());
+        let _events = game_manager.shard(&auth.game_code).read().unwrap().user_connected(auth);
+        auth
+    }
+
+    #[rocket::async_test]
+    async fn test_abandoned_game_is_deleted_after_delay() {
+        let game_manager = ShardedGameManager::new(1);
+        let auth = connected_game(&game_manager);
+        let start = Instant::now();
+        assert_eq!(UserDisconnectedStatus::DeletionScheduled, game_manager.disconnect_user(auth, VacancyReason::ConnectionLost, DELAY));
+        // the caller does not wait for the delay
+        assert!(start.elapsed() < DELAY / 2);
+        assert!(game_manager.does_game_exist(&auth.game_code));
+        tokio::time::sleep(DELAY * 2).await;
+        assert!(!game_manager.does_game_exist(&auth.game_code));
+        assert!(game_manager.wait_for_deletions(Duration::from_secs(1)));
+        assert!(game_manager.shard_by_uuid(auth.uuid).is_none());
+    }
+
+    #[rocket::async_test]
+    async fn test_reconnect_cancels_deletion() {
+        let game_manager = ShardedGameManager::new(1);
+        let auth = connected_game(&game_manager);
+        assert_eq!(UserDisconnectedStatus::DeletionScheduled, game_manager.disconnect_user(auth, VacancyReason::ConnectionLost, DELAY));
+        tokio::time::sleep(DELAY / 4).await;
+        let _events = game_manager.shard(&auth.game_code).read().unwrap().user_connected(auth);
+        tokio::time::sleep(DELAY * 2).await;
+        assert!(game_manager.does_game_exist(&auth.game_code));
+        // a later disconnect schedules a new deletion
+        assert_eq!(UserDisconnectedStatus::DeletionScheduled, game_manager.disconnect_user(auth, VacancyReason::ConnectionLost, DELAY));
+        tokio::time::sleep(DELAY * 2).await;
+        assert!(!game_manager.does_game_exist(&auth.game_code));
+    }
+
+    #[test]
+    fn test_many_deletions() {
+        let game_manager = ShardedGameManager::new(4);
+        let users: Vec<UserAuth> = (0..400).map(|i| {
+            let auth = user_auth(&game_manager, game_manager.create_game(format!("player {}", i), None, None).unwrap());
+            let _events = game_manager.shard(&auth.game_code).read().unwrap().user_connected(auth);
+            auth
+        }).collect();
+        let game_manager = &game_manager;
+        thread::scope(|scope| {
+            for chunk in users.chunks(100) {
+                scope.spawn(move || {
+                    for auth in chunk {
+                        assert_eq!(UserDisconnectedStatus::GameDeleted, game_manager.disconnect_user(*auth, VacancyReason::Left, Duration::ZERO));
+                    }
+                });
+            }
+        });
+        assert!(game_manager.wait_for_deletions(Duration::from_secs(5)));
+        for shard in game_manager.shards() {
+            let shard = shard.read().unwrap();
+            assert!(shard.games.is_empty() && shard.used_game_codes.is_empty() && shard.used_uuids.is_empty());
+        }
+        assert!(game_manager.users.iter().all(|users| users.read().unwrap().is_empty()));
+    }
+
+    #[test]
+    fn test_single_shard() {
+        let game_manager = ShardedGameManager::new(0);
+        assert_eq!(1, game_manager.shards().len());
+        let code = GameCode::new(['A'; 8]).unwrap();
+        assert!(std::ptr::eq(&game_manager.shards()[0], game_manager.shard(&code)));
+    }
+
+    /// Compares the create and join throughput of one shard with multiple shards.
+    ///
+    /// Run with `cargo test --release -- --ignored --nocapture bench_shard_throughput`.
+    #[test]
+    #[ignore]
+    fn bench_shard_throughput() {
+        const THREADS: usize = 8;
+        const GAMES_PER_THREAD: usize = 2000;
+        let run = |shards: usize| {
+            let game_manager = ShardedGameManager::new(shards);
+            let start = Instant::now();
+            thread::scope(|scope| {
+                for _ in 0..THREADS {
+                    scope.spawn(|| {
+                        for _ in 0..GAMES_PER_THREAD {
+                            let auth = user_auth(&game_manager, game_manager.create_game(String::from("a"), None, None).unwrap());
+                            for name in ["b", "c", "d"] {
+                                let _joined = game_manager.add_player_to_game(auth.game_code, String::from(name), None, None, None).unwrap();
+                            }
+                        }
+                    });
+                }
+            });
+            let elapsed = start.elapsed();
+            let operations = (THREADS * GAMES_PER_THREAD * 4) as f64;
+            println!("{} shard(s): {:.0} operations/s", shards, operations / elapsed.as_secs_f64());
+            elapsed
+        };
+        let single = run(1);
+        let sharded = run(THREADS);
+        println!("speedup: {:.2}x", single.as_secs_f64() / sharded.as_secs_f64());
+    }
+}
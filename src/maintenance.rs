@@ -0,0 +1,77 @@
+use std::sync::RwLock;
+
+use serde::Serialize;
+
+use crate::error::ApiError;
+
+/// Whether the server is in maintenance mode and the message that was announced for it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct MaintenanceStatus {
+    /// While enabled no new games can be created
+    pub enabled: bool,
+    /// Message for the players, for example when the server will be restarted
+    pub message: Option<String>,
+}
+
+/// The maintenance mode of the server, managed by rocket.
+///
+/// The maintenance mode is used to drain the server before it is restarted: no new games can be created,
+/// games that are already running are not affected and can be finished.
+///
+/// The mode can be enabled at startup with `maintenance = true` in the rocket configuration
+/// or later by an admin with [maintenance](../paths/admin/fn.maintenance.html).
+pub struct Maintenance {
+    status: RwLock<MaintenanceStatus>,
+}
+
+impl Maintenance {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            status: RwLock::new(MaintenanceStatus { enabled, message: None }),
+        }
+    }
+
+    /// Returns the current maintenance status.
+    pub fn status(&self) -> MaintenanceStatus {
+        self.status.read().unwrap().clone()
+    }
+
+    /// Replaces the maintenance status.
+    pub fn set(&self, status: MaintenanceStatus) {
+        *self.status.write().unwrap() = status;
+    }
+
+    /// Checks if new games can be created.
+    ///
+    /// # Returns
+    /// - `Ok(())` when the server is not in maintenance mode.
+    /// - `Err(ApiError)` with the code `maintenance` and the announced message as detail otherwise.
+    pub fn allow_new_games(&self) -> Result<(), ApiError> {
+        let status = self.status();
+        if !status.enabled {
+            return Ok(());
+        }
+        let error = ApiError::service_unavailable("maintenance");
+        Err(match status.message {
+            Some(message) => error.with_detail(message),
+            None => error,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Maintenance, MaintenanceStatus};
+
+    #[test]
+    fn test_allow_new_games() {
+        let maintenance = Maintenance::new(false);
+        assert!(maintenance.allow_new_games().is_ok());
+        maintenance.set(MaintenanceStatus { enabled: true, message: Some(String::from("Restart at 10:00")) });
+        let error = maintenance.allow_new_games().unwrap_err();
+        assert_eq!("maintenance", error.code());
+        assert_eq!(Some("Restart at 10:00"), error.detail());
+        maintenance.set(MaintenanceStatus::default());
+        assert!(maintenance.allow_new_games().is_ok());
+    }
+}
@@ -0,0 +1,128 @@
+use rocket::{
+    http::{ContentType, Header},
+    response::{self, Responder},
+    serde::json::Json,
+    Request,
+};
+use serde::Serialize;
+
+use crate::request_data::PlainText;
+
+/// The formats in which a [Negotiated]() response can be send.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    /// An aligned table, see [PlainText](../request_data/trait.PlainText.html)
+    Text,
+}
+
+impl Format {
+    /// Selects the format that the client asked for.
+    ///
+    /// The query parameter `format=text` or `format=json` overrides the `Accept` header.
+    /// Otherwise plain text is only send when `text/plain` is the preferred media type of the `Accept` header,
+    /// json is used for all other and unknown media types.
+    pub fn of(request: &Request<'_>) -> Format {
+        match request.query_value::<&str>("format") {
+            Some(Ok("text")) => return Format::Text,
+            Some(Ok("json")) => return Format::Json,
+            _ => (),
+        }
+        match request.accept() {
+            Some(accept) if accept.preferred().top() == "text" && accept.preferred().sub() == "plain" => Format::Text,
+            _ => Format::Json,
+        }
+    }
+}
+
+/// A response that is send as json or, when the client prefers it, as plain text, see [Format::of]().
+///
+/// Operators can read these responses with curl while the frontend keeps using json:
+///
+/// ```text
+/// curl -H "Accept: text/plain" http://localhost:8000/api/status
+/// ```
+///
+/// The json structure is the same as when the value is wrapped in [Json]().
+pub struct Negotiated<T>(pub T);
+
+impl<'r, T: Serialize + PlainText> Responder<'r, 'static> for Negotiated<T> {
+    fn respond_to(self, request: &'r Request<'_>) -> response::Result<'static> {
+        let mut response = match Format::of(request) {
+            Format::Text => (ContentType::Plain, self.0.plain_text()).respond_to(request)?,
+            Format::Json => Json(self.0).respond_to(request)?,
+        };
+        response.set_header(Header::new("Vary", "Accept"));
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::{
+        http::{Accept, ContentType, Header, Status},
+        local::blocking::{Client, LocalResponse},
+        serde::json::Value,
+    };
+
+    fn client() -> (Client, String) {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        let registration: Value = client.post("/api/create_game").header(ContentType::JSON).body(r#"{"username":"gm"}"#).dispatch().into_json().unwrap();
+        let game_code = String::from(registration["game_code"].as_str().unwrap());
+        (client, game_code)
+    }
+
+    fn players<'c>(client: &'c Client, game_code: &str, uri: &'static str, accept: Option<&'static str>) -> LocalResponse<'c> {
+        let request = client.get(uri).header(Header::new("game_code", String::from(game_code)));
+        match accept {
+            Some(accept) => request.header(Header::new("Accept", accept)).dispatch(),
+            None => request.dispatch(),
+        }
+    }
+
+    #[test]
+    fn test_accept_header() {
+        let (client, game_code) = client();
+        let response = players(&client, &game_code, "/api/players_in_game", Some("text/plain"));
+        assert_eq!(Some(ContentType::Plain), response.content_type());
+        assert_eq!(Some("Accept"), response.headers().get_one("Vary"));
+        // the game master has not opened the sse stream yet
+        assert_eq!("NAME  CONNECTED  MASTER  SEAT\ngm    no         yes     1\n", response.into_string().unwrap());
+
+        let response = players(&client, &game_code, "/api/players_in_game", None);
+        assert_eq!(Some(ContentType::JSON), response.content_type());
+        assert_eq!("[]", response.into_string().unwrap());
+        // the media type with the highest quality wins
+        let accept = Some("application/json; q=0.5, text/plain");
+        assert_eq!(Some(ContentType::Plain), players(&client, &game_code, "/api/players_in_game", accept).content_type());
+        let accept = Some("text/plain; q=0.5, application/json");
+        assert_eq!(Some(ContentType::JSON), players(&client, &game_code, "/api/players_in_game", accept).content_type());
+
+        let response = client.get("/api/status").header(Accept::Plain).dispatch();
+        assert_eq!(Status::Ok, response.status());
+        assert!(response.into_string().unwrap().starts_with("FIELD "));
+    }
+
+    #[test]
+    fn test_format_override() {
+        let (client, game_code) = client();
+        assert_eq!(Some(ContentType::Plain), players(&client, &game_code, "/api/players_in_game?format=text", None).content_type());
+        assert_eq!(Some(ContentType::JSON), players(&client, &game_code, "/api/players_in_game?format=json", Some("text/plain")).content_type());
+        assert_eq!(Some(ContentType::Plain), client.get("/api/status?format=text").header(Accept::JSON).dispatch().content_type());
+        // unknown formats are ignored
+        assert_eq!(Some(ContentType::Plain), players(&client, &game_code, "/api/players_in_game?format=xml", Some("text/plain")).content_type());
+    }
+
+    #[test]
+    fn test_unknown_accept_falls_back_to_json() {
+        let (client, game_code) = client();
+        for accept in ["text/html", "*/*", "text/*", "text/csv", "nonsense"] {
+            let response = players(&client, &game_code, "/api/players_in_game", Some(accept));
+            assert_eq!(Status::Ok, response.status());
+            assert_eq!(Some(ContentType::JSON), response.content_type());
+        }
+        // errors keep their json body
+        let response = players(&client, "AAAA-AAAA", "/api/players_in_game", Some("text/plain"));
+        assert_eq!(Some(ContentType::JSON), response.content_type());
+    }
+}
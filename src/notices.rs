@@ -0,0 +1,168 @@
+use std::{sync::Mutex, time::{Duration, Instant, SystemTime, UNIX_EPOCH}};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{events::{EventBatch, EventBus}, game::{game_instance::{GameInstance, GameState}, shards::ShardedGameManager}, request_data::GameEvent};
+
+/// How long a notice is returned by [notices](../paths/admin/fn.notices.html) when `notice_retention_secs` is not configured
+pub const DEFAULT_NOTICE_RETENTION: Duration = Duration::from_secs(60 * 60);
+
+/// The maximum length of the message of a notice
+pub const MAX_NOTICE_LEN: usize = 500;
+
+/// How important a [Notice]() is, clients use this to decide how prominent the notice is shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    Info,
+    Warning,
+    Critical,
+}
+
+/// The games to which a [Notice]() is send.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AppliesTo {
+    #[default]
+    All,
+    /// Only games that are still in the lobby
+    Lobby,
+    /// Only games that have already started
+    Running,
+}
+
+impl AppliesTo {
+    /// Checks if a notice with this target is send to `game`.
+    pub fn matches(&self, game: &GameInstance) -> bool {
+        match self {
+            AppliesTo::All => true,
+            AppliesTo::Lobby => matches!(game.game_state(), GameState::Lobby),
+            AppliesTo::Running => !matches!(game.game_state(), GameState::Lobby),
+        }
+    }
+}
+
+/// A message of the server operators that affects running games, for example an upcoming restart.
+///
+/// Notices are send with the event `ServerNotice` to the matching games and can be requested later
+/// by clients that were not connected at that time.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Notice {
+    /// Increases with each new notice
+    pub id: u64,
+    pub severity: Severity,
+    pub message: String,
+    pub applies_to: AppliesTo,
+    /// Unix seconds at which the notice was posted
+    pub posted_at: u64,
+}
+
+/// The notices of the last [DEFAULT_NOTICE_RETENTION]() (or `notice_retention_secs`), managed by rocket.
+///
+/// Maintenance announcements are published through this as well, see [maintenance](../paths/admin/fn.maintenance.html).
+pub struct NoticeBoard {
+    retention: Duration,
+    notices: Mutex<Notices>,
+}
+
+struct Notices {
+    /// The retained notices with the time they were posted, the oldest first
+    retained: Vec<(Instant, Notice)>,
+    next_id: u64,
+}
+
+impl NoticeBoard {
+    pub fn new(retention: Duration) -> Self {
+        Self {
+            retention,
+            notices: Mutex::new(Notices { retained: Vec::new(), next_id: 1 }),
+        }
+    }
+
+    /// Posts a new notice.
+    ///
+    /// # Returns
+    /// - `(notice, true)` when the notice is new.
+    /// - `(notice, false)` containing the retained notice when the same notice was already posted within the retention, it is not posted again.
+    pub fn post(&self, severity: Severity, message: String, applies_to: AppliesTo) -> (Notice, bool) {
+        self.post_at(severity, message, applies_to, Instant::now())
+    }
+
+    /// Returns all notices that are still retained, the oldest first.
+    pub fn current(&self) -> Vec<Notice> {
+        self.current_at(Instant::now())
+    }
+
+    /// Posts a notice like [post](#method.post) and sends it with the event `ServerNotice` to all matching games.
+    ///
+    /// Duplicates are not send again.
+    pub fn publish(&self, game_manager: &ShardedGameManager, event: &EventBus, severity: Severity, message: String, applies_to: AppliesTo) -> (Notice, bool) {
+        let (notice, new) = self.post(severity, message, applies_to);
+        if new {
+            for channel in game_manager.channels_where(|game| applies_to.matches(game)) {
+                let mut events = EventBatch::new(&channel);
+                events.push(GameEvent::ServerNotice(notice.clone()));
+                events.publish(event);
+            }
+        }
+        (notice, new)
+    }
+
+    fn post_at(&self, severity: Severity, message: String, applies_to: AppliesTo, now: Instant) -> (Notice, bool) {
+        let mut notices = self.notices.lock().unwrap();
+        self.expire(&mut notices, now);
+        let duplicate = notices.retained.iter()
+            .map(|(_, notice)| notice)
+            .find(|notice| notice.severity == severity && notice.message == message && notice.applies_to == applies_to);
+        if let Some(notice) = duplicate {
+            return (notice.clone(), false);
+        }
+        let notice = Notice { id: notices.next_id, severity, message, applies_to, posted_at: unix_time() };
+        notices.next_id += 1;
+        notices.retained.push((now, notice.clone()));
+        (notice, true)
+    }
+
+    fn current_at(&self, now: Instant) -> Vec<Notice> {
+        let mut notices = self.notices.lock().unwrap();
+        self.expire(&mut notices, now);
+        notices.retained.iter().map(|(_, notice)| notice.clone()).collect()
+    }
+
+    fn expire(&self, notices: &mut Notices, now: Instant) {
+        notices.retained.retain(|(posted, _)| now.duration_since(*posted) < self.retention);
+    }
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::{AppliesTo, NoticeBoard, Severity};
+
+    #[test]
+    fn test_retention_and_deduplication() {
+        let board = NoticeBoard::new(Duration::from_secs(3600));
+        let start = Instant::now();
+        let (first, new) = board.post_at(Severity::Warning, String::from("Restart at 10:00"), AppliesTo::All, start);
+        assert!(new);
+        let (duplicate, new) = board.post_at(Severity::Warning, String::from("Restart at 10:00"), AppliesTo::All, start + Duration::from_secs(60));
+        assert!(!new);
+        assert_eq!(first, duplicate);
+        // a different target is a different notice
+        let (other, new) = board.post_at(Severity::Warning, String::from("Restart at 10:00"), AppliesTo::Lobby, start + Duration::from_secs(60));
+        assert!(new);
+        assert_eq!(first.id + 1, other.id);
+        assert_eq!(vec![first.clone(), other.clone()], board.current_at(start + Duration::from_secs(3599)));
+        assert_eq!(vec![other], board.current_at(start + Duration::from_secs(3600)));
+
+        // an expired notice can be posted again
+        let (again, new) = board.post_at(Severity::Warning, String::from("Restart at 10:00"), AppliesTo::All, start + Duration::from_secs(3600));
+        assert!(new);
+        assert_ne!(first.id, again.id);
+    }
+}
@@ -0,0 +1,207 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rocket::{
+    get, post, routes, Route,
+    log::private::info,
+    State, serde::json::{self, Json},
+};
+
+use crate::{analytics::Analytics, game::shards::ShardedGameManager, request_data::{MaintenanceRequest, NoticeRequest, ServerStatus, ServerTime, PROTOCOL_VERSION}, authentication::{AdminAuth, FromRequestError}, error::ApiError, events::EventBus, maintenance::{Maintenance, MaintenanceStatus}, negotiation::Negotiated, notices::{AppliesTo, Notice, NoticeBoard, Severity, MAX_NOTICE_LEN}, usage::RouteUsage};
+
+/// Returns all routes that are used to administrate the server.
+pub fn routes() -> Vec<Route> {
+    routes![maintenance, notice, notices, status, time]
+}
+
+/// Enables or disables the maintenance mode, see [Maintenance](../../maintenance/struct.Maintenance.html).
+///
+/// When the maintenance mode is enabled with a `message`, the message is posted as warning [Notice](../../notices/struct.Notice.html)
+/// to all games, see [notice]().
+///
+/// # Requires
+/// - Request guard [AdminAuth](../../authentication/struct.AdminAuth.html) to succeed.
+/// - The new mode formatted as json in the post request body, see [MaintenanceRequest](../../request_data/struct.MaintenanceRequest.html).
+#[post("/api/admin/maintenance", data = "<request>")]
+pub fn maintenance(game_manager: &State<ShardedGameManager>, event: &State<EventBus>, maintenance: &State<Maintenance>, notices: &State<NoticeBoard>, admin: Result<AdminAuth, FromRequestError>, request: Result<Json<MaintenanceRequest>, json::Error<'_>>) -> Result<Json<MaintenanceStatus>, ApiError> {
+    admin?;
+    let request = request?.into_inner();
+    let status = MaintenanceStatus { enabled: request.enabled, message: request.message.filter(|_| request.enabled) };
+    maintenance.set(status.clone());
+    info!("Maintenance mode {}", if status.enabled { "enabled" } else { "disabled" });
+    if let Some(message) = &status.message {
+        notices.publish(game_manager, event, Severity::Warning, message.clone(), AppliesTo::All);
+    }
+    Ok(Json(status))
+}
+
+/// Posts a notice of the server operators, see [NoticeBoard](../../notices/struct.NoticeBoard.html).
+///
+/// The notice is send with the event `ServerNotice` to all games that match `applies_to`.
+/// When the same notice is still retained it is not send again, the retained notice is returned instead.
+///
+/// # Requires
+/// - Request guard [AdminAuth](../../authentication/struct.AdminAuth.html) to succeed.
+/// - The notice formatted as json in the post request body, see [NoticeRequest](../../request_data/struct.NoticeRequest.html).
+#[post("/api/admin/notice", data = "<request>")]
+pub fn notice(game_manager: &State<ShardedGameManager>, event: &State<EventBus>, notices: &State<NoticeBoard>, admin: Result<AdminAuth, FromRequestError>, request: Result<Json<NoticeRequest>, json::Error<'_>>) -> Result<Json<Notice>, ApiError> {
+    admin?;
+    let request = request?.into_inner();
+    if request.message.trim().is_empty() || request.message.chars().count() > MAX_NOTICE_LEN {
+        return Err(ApiError::bad_request("invalid_message").with_detail(format!("the message has to contain 1 to {} characters", MAX_NOTICE_LEN)));
+    }
+    let (notice, new) = notices.publish(game_manager, event, request.severity, request.message, request.applies_to);
+    if new {
+        info!("Posted notice {}", notice.id);
+    }
+    Ok(Json(notice))
+}
+
+/// Returns the notices that are still retained, the oldest first.
+///
+/// Clients request these when they connect so that they also learn about notices that were send before.
+#[get("/api/notices")]
+pub fn notices(notices: &State<NoticeBoard>) -> Json<Vec<Notice>> {
+    Json(notices.current())
+}
+
+/// Returns if the server is in maintenance mode and how many games are still active.
+///
+/// When the request guard [AdminAuth](../../authentication/struct.AdminAuth.html) succeeds the usage of all routes is included,
+/// see [RouteUsage](../../usage/struct.RouteUsage.html), and what happened to abandoned games,
+/// see [AbandonmentStats](../../game/abandonment/struct.AbandonmentStats.html), and the [Analytics](../../analytics/struct.Analytics.html).
+///
+/// The status can also be requested as plain text, see [Negotiated](../../negotiation/struct.Negotiated.html).
+#[get("/api/status")]
+pub fn status(game_manager: &State<ShardedGameManager>, maintenance: &State<Maintenance>, usage: &State<RouteUsage>, analytics: &State<Analytics>, admin: Result<AdminAuth, FromRequestError>) -> Negotiated<ServerStatus> {
+    let maintenance = maintenance.status();
+    Negotiated(ServerStatus {
+        protocol_version: PROTOCOL_VERSION,
+        maintenance: maintenance.enabled,
+        maintenance_message: maintenance.message,
+        active_games: game_manager.game_codes().len(),
+        abandonment: admin.as_ref().ok().map(|_| game_manager.abandonment_report()),
+        analytics: admin.as_ref().ok().and_then(|_| analytics.report()),
+        route_usage: admin.ok().map(|_| usage.report()),
+    })
+}
+
+/// Returns the current time of the server in unix milliseconds.
+///
+/// Clients use this to correct the countdowns and relative times they compute from timestamps of the server
+/// when their own clock is wrong, see `sync_clock` in the wasm module.
+#[get("/api/time")]
+pub fn time() -> Json<ServerTime> {
+    let unix_millis = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_millis() as u64).unwrap_or_default();
+    Json(ServerTime { unix_millis })
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::{
+        http::{ContentType, Header, Status},
+        local::blocking::Client,
+        serde::json::{from_str, to_value, Value},
+    };
+
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use crate::game::{game_instance::GameCode, shards::ShardedGameManager};
+
+    fn client() -> Client {
+        let figment = rocket::Config::figment().merge(("admin_token", "secret"));
+        Client::tracked(crate::server(rocket::custom(figment))).unwrap()
+    }
+
+    #[test]
+    fn test_maintenance_mode() {
+        let client = client();
+        let create_game = || client.post("/api/create_game").header(ContentType::JSON).body(r#"{"username":"gm"}"#).dispatch();
+        let maintenance = |token: &'static str, body: &'static str| {
+            client.post("/api/admin/maintenance").header(Header::new("admin_token", token)).header(ContentType::JSON).body(body).dispatch()
+        };
+        let game_master: Value = create_game().into_json().unwrap();
+        let game_code = GameCode::from_string(game_master["game_code"].as_str().unwrap()).unwrap();
+        let mut receiver = client.rocket().state::<ShardedGameManager>().unwrap().subscribe(game_code).unwrap();
+        assert_eq!(Status::Forbidden, maintenance("wrong", r#"{"enabled":true}"#).status());
+        assert_eq!(Status::Ok, maintenance("secret", r#"{"enabled":true,"message":"Restart at 10:00"}"#).status());
+        let announcement = to_value(receiver.try_recv().unwrap()).unwrap();
+        assert_eq!("ServerNotice", announcement["data"][0]);
+        let notice: Value = from_str(announcement["data"][1].as_str().unwrap()).unwrap();
+        assert_eq!("Restart at 10:00", notice["message"]);
+        assert_eq!("warning", notice["severity"]);
+        assert_eq!(game_master["game_code"], announcement["game_code"]);
+
+        let response = create_game();
+        assert_eq!(Status::ServiceUnavailable, response.status());
+        assert_eq!(r#"{"error":"maintenance","detail":"Restart at 10:00"}"#, response.into_string().unwrap());
+        // running games are not affected
+        let response = client.post("/api/join_game")
+            .header(Header::new("game_code", String::from(game_master["game_code"].as_str().unwrap())))
+            .header(ContentType::JSON)
+            .body(r#"{"username":"player"}"#)
+            .dispatch();
+        assert_eq!(Status::Ok, response.status());
+        let status: Value = client.get("/api/status").dispatch().into_json().unwrap();
+        assert_eq!(true, status["maintenance"]);
+        assert_eq!(1, status["active_games"]);
+
+        assert_eq!(Status::Ok, maintenance("secret", r#"{"enabled":false}"#).status());
+        assert_eq!(Status::Ok, create_game().status());
+        let status: Value = client.get("/api/status").dispatch().into_json().unwrap();
+        assert_eq!(false, status["maintenance"]);
+        assert_eq!(2, status["active_games"]);
+    }
+
+    #[test]
+    fn test_admin_routes_disabled_without_token() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        let response = client.post("/api/admin/maintenance").header(Header::new("admin_token", "")).header(ContentType::JSON).body(r#"{"enabled":true}"#).dispatch();
+        assert_eq!(Status::Forbidden, response.status());
+    }
+
+    #[test]
+    fn test_notices() {
+        let client = client();
+        let game_master: Value = client.post("/api/create_game").header(ContentType::JSON).body(r#"{"username":"gm"}"#).dispatch().into_json().unwrap();
+        let notice = |body: &'static str| client.post("/api/admin/notice").header(Header::new("admin_token", "secret")).header(ContentType::JSON).body(body).dispatch();
+        let game_code = GameCode::from_string(game_master["game_code"].as_str().unwrap()).unwrap();
+        let mut receiver = client.rocket().state::<ShardedGameManager>().unwrap().subscribe(game_code).unwrap();
+        assert_eq!(Status::BadRequest, notice(r#"{"severity":"info","message":"  "}"#).status());
+        assert_eq!(Status::Forbidden, client.post("/api/admin/notice").header(ContentType::JSON).body(r#"{"severity":"info","message":"Hi"}"#).dispatch().status());
+
+        // no game has started yet
+        let running: Value = notice(r#"{"severity":"critical","message":"Turn timers are disabled","applies_to":"running"}"#).into_json().unwrap();
+        assert!(receiver.try_recv().is_err());
+        let lobby: Value = notice(r#"{"severity":"info","message":"New lobby settings","applies_to":"lobby"}"#).into_json().unwrap();
+        let event = to_value(receiver.try_recv().unwrap()).unwrap();
+        assert_eq!(game_master["game_code"], event["game_code"]);
+        assert_eq!(lobby, from_str::<Value>(event["data"][1].as_str().unwrap()).unwrap());
+
+        // posting the same notice again does not send it twice
+        let duplicate: Value = notice(r#"{"severity":"info","message":"New lobby settings","applies_to":"lobby"}"#).into_json().unwrap();
+        assert_eq!(lobby, duplicate);
+        assert!(receiver.try_recv().is_err());
+
+        // clients that connect later get all retained notices
+        let notices: Value = client.get("/api/notices").dispatch().into_json().unwrap();
+        assert_eq!(Value::Array(vec![running, lobby]), notices);
+    }
+
+    #[test]
+    fn test_notice_retention() {
+        let figment = rocket::Config::figment().merge(("admin_token", "secret")).merge(("notice_retention_secs", 0));
+        let client = Client::tracked(crate::server(rocket::custom(figment))).unwrap();
+        let response = client.post("/api/admin/notice").header(Header::new("admin_token", "secret")).header(ContentType::JSON).body(r#"{"severity":"info","message":"Hi"}"#).dispatch();
+        assert_eq!(Status::Ok, response.status());
+        assert_eq!("[]", client.get("/api/notices").dispatch().into_string().unwrap());
+    }
+
+    #[test]
+    fn test_time() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        let before = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        let time: Value = client.get("/api/time").dispatch().into_json().unwrap();
+        let after = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        assert!((before..=after).contains(&time["unix_millis"].as_u64().unwrap()));
+    }
+}
@@ -0,0 +1,66 @@
+use std::{path::Path, time::Duration, thread};
+
+use rocket::{
+    fs::NamedFile,
+    get, routes, Route,
+    log::private::info,
+    State,
+};
+use uuid::Uuid;
+
+use crate::{game::{base_game::VacancyReason, shards::ShardedGameManager, GAME_INSTANCE_TIMEOUT}, authentication::UserAuth, caching::CachedFile, utils::get_gm_write_guard};
+
+/// Returns all routes that are only meant for debugging.
+pub fn routes() -> Vec<Route> {
+    routes![debug, debug_busy, debug_game]
+}
+
+#[get("/api/debug/<user_id>")]
+pub fn debug(game_manager: &State<ShardedGameManager>, user_id: Uuid) -> String {
+    let auth = UserAuth::from_uuid(game_manager, user_id).unwrap();
+    let status = game_manager.disconnect_user(auth, VacancyReason::ConnectionLost, GAME_INSTANCE_TIMEOUT);
+    format!("{:?}", status)
+}
+
+/// Acquires the lock of the first game_manager shard and releases it again after 10 seconds.
+/// 
+/// After another `time` seconds the lock is reacquired and held for 5 more seconds.
+/// 
+/// This can be used to check behavior of other function when the `game_manager` lock could not be acquired.
+#[get("/api/debug/keep_busy/<id>/<time>")]
+pub fn debug_busy(game_manager: &State<ShardedGameManager>, id: i32, time: i32) -> String {
+    info!("Starting debug {}", id);
+    let action = format!("Debug {}", id);
+    {
+        // Holding the lock for a long time is the point of this route
+        let _manager = get_gm_write_guard(&game_manager.shards()[0], &action).warn_after(Duration::from_secs(11));
+        info!("Debug {}: Acquired write lock for game manger", id);
+        for i in (1..=10).rev() {
+            info!("Debug {}: Releasing lock in: {} ", id, i);
+            thread::sleep(Duration::from_secs(1));
+        }
+    }
+    info!("Debug {}: Releasing lock for game manager", id);
+    for i in (1..=time).rev() {
+        info!("Debug {}: Seconds left of free game lock: {} ", id, i);
+        thread::sleep(Duration::from_secs(1));
+    }
+    {
+        // Holding the lock for a long time is the point of this route
+        let _manager = get_gm_write_guard(&game_manager.shards()[0], &action).warn_after(Duration::from_secs(11));
+        info!("Debug {}: Acquired write lock for game manger", id);
+        for i in (1..=5).rev() {
+            info!("Debug {}: Releasing lock in: {} ", id, i);
+            thread::sleep(Duration::from_secs(1));
+        }
+    }
+    String::from("Success")
+}
+
+#[get("/api/debug/game")]
+pub async fn debug_game() -> Option<CachedFile> {
+    NamedFile::open(Path::new("web/protected/game.html"))
+        .await
+        .ok()
+        .map(CachedFile::page)
+}
@@ -0,0 +1,593 @@
+use rocket::{get, post, routes, Route, State, serde::json::{self, Json}};
+
+use crate::{authentication::{FromRequestError, UserAuth}, error::ApiError, events::EventBus, game::{game_instance::{GameState, board::{BoardSnapshot, ChainState, PlacedTile}, game_log::GameLogPage}, shards::ShardedGameManager}, request_data::{BuyStockRequest, ChainRequest, EndGameRequest, GameAnnotation, GameNotesRequest, Hand, PlayerStatsEntry, MergerDecisionRequest, PlaceTileRequest, Portfolio, Standing, StockOverview, TilePlacement, TurnStatus}, utils::get_gm_read_guard};
+
+/// Returns all routes that are used while the game is played.
+pub fn routes() -> Vec<Route> {
+    routes![board, chains, hand, stocks, turn, place_tile, found_chain, choose_survivor, buy_stock, merger_decision, end_game, results, game_stats, game_notes, game_log]
+}
+
+/// Returns the tiles on the board of the game where the user is assigned to, see [BoardSnapshot](../../game/game_instance/board/struct.BoardSnapshot.html).
+/// 
+/// The board is empty while the game is in the lobby.
+/// 
+/// # Requires
+/// Request guard [UserAuth]() to succeed.
+#[get("/api/board")]
+pub fn board(game_manager: &State<ShardedGameManager>, user_auth: Result<UserAuth, FromRequestError>) -> Result<Json<BoardSnapshot>, ApiError> {
+    let user_auth = user_auth?;
+    let game_manager = get_gm_read_guard(game_manager.shard(&user_auth.game_code), "board");
+    let game = game_manager
+        .game_by_code_read(user_auth.game_code)
+        .ok_or_else(|| ApiError::not_found("game_not_found"))?;
+    Ok(Json(game.board().snapshot()))
+}
+
+/// Returns the size, price and safety of every hotel chain in the game where the user is assigned to, see [ChainState](../../game/game_instance/board/struct.ChainState.html).
+/// 
+/// Chains that are not founded are included with a size of `0` and no price.
+/// 
+/// # Requires
+/// Request guard [UserAuth]() to succeed.
+#[get("/api/chains")]
+pub fn chains(game_manager: &State<ShardedGameManager>, user_auth: Result<UserAuth, FromRequestError>) -> Result<Json<Vec<ChainState>>, ApiError> {
+    let user_auth = user_auth?;
+    let game_manager = get_gm_read_guard(game_manager.shard(&user_auth.game_code), "chains");
+    let game = game_manager
+        .game_by_code_read(user_auth.game_code)
+        .ok_or_else(|| ApiError::not_found("game_not_found"))?;
+    Ok(Json(game.board().chains()))
+}
+
+/// Returns the tiles of the user and the number of tiles left in the bag, see [Hand](../../request_data/struct.Hand.html).
+/// 
+/// Each player only sees their own tiles, the hand of another player can not be requested.
+/// 
+/// The tiles are not returned as a plain list of positions but wrapped in [Hand](../../request_data/struct.Hand.html)
+/// together with `tiles_remaining`, because no other route reports how many tiles are left in the bag. The tiles are
+/// still formatted like `5E`, clients that expect a plain list read `tiles`.
+/// 
+/// # Requires
+/// Request guard [UserAuth]() to succeed, users on the waitlist get `404 Not Found`.
+/// The tiles are dealt when the game is started, until then `409 Conflict` is returned.
+#[get("/api/hand")]
+pub fn hand(game_manager: &State<ShardedGameManager>, user_auth: Result<UserAuth, FromRequestError>) -> Result<Json<Hand>, ApiError> {
+    let user_auth = user_auth?;
+    let game_manager = get_gm_read_guard(game_manager.shard(&user_auth.game_code), "hand");
+    let game = game_manager
+        .game_by_code_read(user_auth.game_code)
+        .ok_or_else(|| ApiError::not_found("game_not_found"))?;
+    if matches!(game.game_state(), GameState::Lobby) {
+        return Err(ApiError::conflict("game_not_started"));
+    }
+    game.hand(user_auth.uuid).map(Json).ok_or_else(|| ApiError::not_found("player_not_found"))
+}
+
+/// Returns the money and the shares of the user, the shares of the other players and the shares the bank still holds,
+/// see [StockOverview](../../request_data/struct.StockOverview.html).
+/// 
+/// # Requires
+/// Request guard [UserAuth]() to succeed, users on the waitlist get `404 Not Found`.
+#[get("/api/stocks")]
+pub fn stocks(game_manager: &State<ShardedGameManager>, user_auth: Result<UserAuth, FromRequestError>) -> Result<Json<StockOverview>, ApiError> {
+    let user_auth = user_auth?;
+    let game_manager = get_gm_read_guard(game_manager.shard(&user_auth.game_code), "stocks");
+    let game = game_manager
+        .game_by_code_read(user_auth.game_code)
+        .ok_or_else(|| ApiError::not_found("game_not_found"))?;
+    game.stocks(user_auth.uuid).map(Json).ok_or_else(|| ApiError::not_found("player_not_found"))
+}
+
+/// Returns whose turn it is and what they have to do next, see [TurnStatus](../../request_data/struct.TurnStatus.html).
+/// 
+/// Clients that lost their sse stream use this to catch up, every change of the player is announced with `TurnChanged`.
+/// The `turn_token` has to be send with the next gameplay request, clients fetch the turn again after each step.
+/// 
+/// # Requires
+/// Request guard [UserAuth]() to succeed. There is only a turn while the game is running, otherwise `409 Conflict` is returned.
+#[get("/api/turn")]
+pub fn turn(game_manager: &State<ShardedGameManager>, user_auth: Result<UserAuth, FromRequestError>) -> Result<Json<TurnStatus>, ApiError> {
+    let user_auth = user_auth?;
+    let game_manager = get_gm_read_guard(game_manager.shard(&user_auth.game_code), "turn");
+    let game = game_manager
+        .game_by_code_read(user_auth.game_code)
+        .ok_or_else(|| ApiError::not_found("game_not_found"))?;
+    match game.game_state() {
+        GameState::Finished(_) => Err(ApiError::conflict("game_finished")),
+        _ => game.turn_status().map(Json).ok_or_else(|| ApiError::conflict("game_not_started")),
+    }
+}
+
+/// Places a tile from the hand of the user on the board, see [PlaceTileRequest](../../request_data/struct.PlaceTileRequest.html).
+/// 
+/// The event `TilePlaced` is then send to all players in the game, followed by `TurnChanged` when the turn ended.
+/// 
+/// # Returns
+/// The tiles that were placed or changed their chain, see [TilePlacement](../../request_data/struct.TilePlacement.html).
+/// When the tile founds a chain the response lists the chains that can be founded with [found_chain](), when it merges
+/// chains of the same size it lists the chains that can survive with [choose_survivor](). Otherwise it tells if shares
+/// can be bought with [buy_stock](), mergers are then already resolved and announced with `MergerResolved`.
+/// 
+/// # Requires
+/// Request guard [UserAuth]() to succeed and that it is the turn of the user, otherwise `409 Conflict` is returned.
+/// The body of `not_your_turn` lists whose turn it actually is in the field `turn`, the other game actions do the same.
+/// All game actions require the `turn_token` of [turn]() in their body, a token of an earlier step (for example of a
+/// retried request that was already applied) is rejected with `409 Conflict` and the code `stale_turn_token`
+/// before anything is changed.
+/// Tiles that the user does not hold or that the rules do not allow are rejected with `422 Unprocessable Entity`.
+#[post("/api/place_tile", data = "<request>")]
+pub fn place_tile(game_manager: &State<ShardedGameManager>, event: &State<EventBus>, user_auth: Result<UserAuth, FromRequestError>, request: Result<Json<PlaceTileRequest>, json::Error<'_>>) -> Result<Json<TilePlacement>, ApiError> {
+    let user_auth = user_auth?;
+    let request = request?;
+    let (events, placement) = {
+        let game_manager = get_gm_read_guard(game_manager.shard(&user_auth.game_code), "place_tile");
+        let mut game = game_manager
+            .game_by_user_auth_write(user_auth)
+            .ok_or_else(|| ApiError::not_found("game_not_found"))?;
+        game.check_turn_token(request.turn_token)?;
+        game.place_tile(user_auth.uuid, request.tile)?
+    };
+    events.publish(event);
+    Ok(Json(placement))
+}
+
+/// Founds a chain with the tile that the user has placed before, see [ChainRequest](../../request_data/struct.ChainRequest.html).
+/// 
+/// The user receives one free share of the chain and can then buy shares, the event `ChainFounded` is send to all players in the game.
+/// 
+/// # Returns
+/// The tiles that joined the chain.
+/// 
+/// # Requires
+/// Request guard [UserAuth]() to succeed and that the last tile of the user founds a chain, otherwise `409 Conflict` is returned.
+/// Chains that are already on the board are rejected with `422 Unprocessable Entity`.
+#[post("/api/found_chain", data = "<request>")]
+pub fn found_chain(game_manager: &State<ShardedGameManager>, event: &State<EventBus>, user_auth: Result<UserAuth, FromRequestError>, request: Result<Json<ChainRequest>, json::Error<'_>>) -> Result<Json<Vec<PlacedTile>>, ApiError> {
+    let user_auth = user_auth?;
+    let request = request?;
+    let (events, tiles) = {
+        let game_manager = get_gm_read_guard(game_manager.shard(&user_auth.game_code), "found_chain");
+        let mut game = game_manager
+            .game_by_user_auth_write(user_auth)
+            .ok_or_else(|| ApiError::not_found("game_not_found"))?;
+        game.check_turn_token(request.turn_token)?;
+        game.found_chain(user_auth.uuid, request.chain)?
+    };
+    events.publish(event);
+    Ok(Json(tiles))
+}
+
+/// Chooses the chain that survives the merger that the user has started with their last tile, see [ChainRequest](../../request_data/struct.ChainRequest.html).
+/// 
+/// The merger is then resolved and the event `MergerResolved` is send to all players for each defunct chain,
+/// afterwards the shareholders decide about their shares with [merger_decision]() and the user can buy shares.
+/// 
+/// # Returns
+/// The tiles that changed their chain.
+/// 
+/// # Requires
+/// Request guard [UserAuth]() to succeed and that the last tile of the user merges chains of the same size, otherwise `409 Conflict` is returned.
+/// Chains that are not one of the largest chains of the merger are rejected with `422 Unprocessable Entity`.
+#[post("/api/choose_survivor", data = "<request>")]
+pub fn choose_survivor(game_manager: &State<ShardedGameManager>, event: &State<EventBus>, user_auth: Result<UserAuth, FromRequestError>, request: Result<Json<ChainRequest>, json::Error<'_>>) -> Result<Json<Vec<PlacedTile>>, ApiError> {
+    let user_auth = user_auth?;
+    let request = request?;
+    let (events, tiles) = {
+        let game_manager = get_gm_read_guard(game_manager.shard(&user_auth.game_code), "choose_survivor");
+        let mut game = game_manager
+            .game_by_user_auth_write(user_auth)
+            .ok_or_else(|| ApiError::not_found("game_not_found"))?;
+        game.check_turn_token(request.turn_token)?;
+        game.choose_survivor(user_auth.uuid, request.chain)?
+    };
+    events.publish(event);
+    Ok(Json(tiles))
+}
+
+/// Buys shares for the user at the current prices and ends the turn, see [BuyStockRequest](../../request_data/struct.BuyStockRequest.html).
+/// 
+/// The event `StockPurchased` is send to all players in the game when shares were bought, followed by `TurnChanged`.
+/// 
+/// # Returns
+/// The money and the shares of the user after the purchase, see [Portfolio](../../request_data/struct.Portfolio.html).
+/// 
+/// # Requires
+/// Request guard [UserAuth]() to succeed and that the user has placed a tile in their turn, otherwise `409 Conflict` is returned.
+/// Purchases that the rules do not allow or that the user can not afford are rejected with `422 Unprocessable Entity`,
+/// the detail of `not_enough_money` names the missing amount.
+#[post("/api/buy_stock", data = "<request>")]
+pub fn buy_stock(game_manager: &State<ShardedGameManager>, event: &State<EventBus>, user_auth: Result<UserAuth, FromRequestError>, request: Result<Json<BuyStockRequest>, json::Error<'_>>) -> Result<Json<Portfolio>, ApiError> {
+    let user_auth = user_auth?;
+    let request = request?;
+    let (events, portfolio) = {
+        let game_manager = get_gm_read_guard(game_manager.shard(&user_auth.game_code), "buy_stock");
+        let mut game = game_manager
+            .game_by_user_auth_write(user_auth)
+            .ok_or_else(|| ApiError::not_found("game_not_found"))?;
+        game.check_turn_token(request.turn_token)?;
+        game.buy_stock(user_auth.uuid, &request.purchases)?
+    };
+    events.publish(event);
+    Ok(Json(portfolio))
+}
+
+/// Sells, trades and keeps the shares of a defunct chain after a merger, see [MergerDecisionRequest](../../request_data/struct.MergerDecisionRequest.html).
+/// 
+/// The shareholder that has to decide is announced with the event `AwaitingDisposal`, the decision is send to all players
+/// with `MergerDecision`. Sold shares are paid with the price of the defunct chain before the merger and two traded
+/// shares are exchanged for one share of the survivor.
+/// 
+/// # Returns
+/// The money and the shares of the user after the decision, see [Portfolio](../../request_data/struct.Portfolio.html).
+/// 
+/// # Requires
+/// Request guard [UserAuth]() to succeed and that the user is the next shareholder that has to decide, otherwise `409 Conflict` is returned.
+/// Decisions that do not add up to the shares of the user or that the bank can not serve are rejected with `422 Unprocessable Entity`.
+#[post("/api/merger_decision", data = "<request>")]
+pub fn merger_decision(game_manager: &State<ShardedGameManager>, event: &State<EventBus>, user_auth: Result<UserAuth, FromRequestError>, request: Result<Json<MergerDecisionRequest>, json::Error<'_>>) -> Result<Json<Portfolio>, ApiError> {
+    let user_auth = user_auth?;
+    let request = request?;
+    let (events, portfolio) = {
+        let game_manager = get_gm_read_guard(game_manager.shard(&user_auth.game_code), "merger_decision");
+        let mut game = game_manager
+            .game_by_user_auth_write(user_auth)
+            .ok_or_else(|| ApiError::not_found("game_not_found"))?;
+        game.check_turn_token(request.turn_token)?;
+        game.merger_decision(user_auth.uuid, &request.decision())?
+    };
+    events.publish(event);
+    Ok(Json(portfolio))
+}
+
+/// Ends the game in the turn of the user, when one chain has 41 tiles or all chains on the board are safe, see [EndGameRequest](../../request_data/struct.EndGameRequest.html).
+/// 
+/// The final bonuses are paid, all shares are sold at the current prices and the event `GameEnded` with the standings
+/// is send to all players.
+/// 
+/// # Returns
+/// The final standings ordered by place, see [Standing](../../request_data/struct.Standing.html).
+/// 
+/// # Requires
+/// Request guard [UserAuth]() to succeed, that it is the turn of the user and that the conditions are met, otherwise `409 Conflict` is returned.
+#[post("/api/end_game", data = "<request>")]
+pub fn end_game(game_manager: &State<ShardedGameManager>, event: &State<EventBus>, user_auth: Result<UserAuth, FromRequestError>, request: Result<Json<EndGameRequest>, json::Error<'_>>) -> Result<Json<Vec<Standing>>, ApiError> {
+    let user_auth = user_auth?;
+    let request = request?;
+    let (events, standings) = {
+        let game_manager = get_gm_read_guard(game_manager.shard(&user_auth.game_code), "end_game");
+        let mut game = game_manager
+            .game_by_user_auth_write(user_auth)
+            .ok_or_else(|| ApiError::not_found("game_not_found"))?;
+        game.check_turn_token(request.turn_token)?;
+        game.end_game(user_auth.uuid)?
+    };
+    events.publish(event);
+    Ok(Json(standings))
+}
+
+/// Returns the final standings of the game where the user is assigned to, see [Standing](../../request_data/struct.Standing.html).
+/// 
+/// # Requires
+/// Request guard [UserAuth]() to succeed. The standings exist once the game is finished, until then `409 Conflict` is returned.
+#[get("/api/results")]
+pub fn results(game_manager: &State<ShardedGameManager>, user_auth: Result<UserAuth, FromRequestError>) -> Result<Json<Vec<Standing>>, ApiError> {
+    let user_auth = user_auth?;
+    let game_manager = get_gm_read_guard(game_manager.shard(&user_auth.game_code), "results");
+    let game = game_manager
+        .game_by_code_read(user_auth.game_code)
+        .ok_or_else(|| ApiError::not_found("game_not_found"))?;
+    game.results().map(|standings| Json(standings.to_vec())).ok_or_else(|| ApiError::conflict("game_not_finished"))
+}
+
+/// Returns what each player did in the game where the user is assigned to, see [PlayerStatsEntry](../../request_data/struct.PlayerStatsEntry.html).
+/// 
+/// # Requires
+/// Request guard [UserAuth]() to succeed. The stats are only returned once the game is finished, until then `409 Conflict`
+/// is returned because they would reveal the money of the other players.
+#[get("/api/game_stats")]
+pub fn game_stats(game_manager: &State<ShardedGameManager>, user_auth: Result<UserAuth, FromRequestError>) -> Result<Json<Vec<PlayerStatsEntry>>, ApiError> {
+    let user_auth = user_auth?;
+    let game_manager = get_gm_read_guard(game_manager.shard(&user_auth.game_code), "game_stats");
+    let game = game_manager
+        .game_by_code_read(user_auth.game_code)
+        .ok_or_else(|| ApiError::not_found("game_not_found"))?;
+    game.game_stats().map(Json).ok_or_else(|| ApiError::conflict("game_not_finished"))
+}
+
+/// Sets the title and the notes of the finished game where the user is assigned to, notes that where set before are replaced.
+/// 
+/// Markup is removed and the user is recorded as editor, the event `GameAnnotated` with the new notes is send to all players,
+/// see [GameInstance::annotate](../../game/game_instance/struct.GameInstance.html#method.annotate).
+/// 
+/// # Returns
+/// The new notes, see [GameAnnotation](../../request_data/struct.GameAnnotation.html).
+/// 
+/// # Requires
+/// Request guard [UserAuth]() to succeed and the user to be the game master. The game has to be finished, otherwise
+/// `409 Conflict` is returned. A title that is empty or too long and notes that are too long are rejected with `invalid_fields`.
+#[post("/api/game_notes", data = "<request>")]
+pub fn game_notes(game_manager: &State<ShardedGameManager>, event: &State<EventBus>, user_auth: Result<UserAuth, FromRequestError>, request: Result<Json<GameNotesRequest>, json::Error<'_>>) -> Result<Json<GameAnnotation>, ApiError> {
+    let user_auth = user_auth?;
+    let request = request?;
+    let (events, annotation) = {
+        let game_manager = get_gm_read_guard(game_manager.shard(&user_auth.game_code), "game_notes");
+        let mut game = game_manager
+            .game_by_user_auth_write(user_auth)
+            .ok_or_else(|| ApiError::not_found("game_not_found"))?;
+        game.annotate(user_auth.uuid, &request.title, &request.notes)?
+    };
+    events.publish(event);
+    Ok(Json(annotation))
+}
+
+/// Returns a page of what happened in the game where the user is assigned to, the oldest entry first, see [GameLogPage](../../game/game_instance/game_log/struct.GameLogPage.html).
+/// 
+/// Only the last [GAME_LOG_LEN](../../game/game_instance/game_log/constant.GAME_LOG_LEN.html) entries are kept, `gap` is set
+/// when entries after `since` were already removed. A page holds at most
+/// [GAME_LOG_PAGE_LEN](../../game/game_instance/game_log/constant.GAME_LOG_PAGE_LEN.html) entries, `has_more` is set when
+/// the next page can be fetched right away with `next_cursor`.
+/// 
+/// # Params
+/// `since` only entries with at least this index are returned, clients pass the `next_cursor` of the last page
+/// to fetch only what they missed
+/// 
+/// # Requires
+/// Request guard [UserAuth]() to succeed.
+#[get("/api/game_log?<since>")]
+pub fn game_log(game_manager: &State<ShardedGameManager>, user_auth: Result<UserAuth, FromRequestError>, since: Option<usize>) -> Result<Json<GameLogPage>, ApiError> {
+    let user_auth = user_auth?;
+    let game_manager = get_gm_read_guard(game_manager.shard(&user_auth.game_code), "game_log");
+    let game = game_manager
+        .game_by_code_read(user_auth.game_code)
+        .ok_or_else(|| ApiError::not_found("game_not_found"))?;
+    Ok(Json(game.game_log(since.unwrap_or_default())))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use rocket::{
+        http::{ContentType, Status},
+        local::blocking::Client,
+        serde::json::Value,
+    };
+
+    use crate::{game::game_instance::board::Position, paths::test_utils::{create_game, lobby, started_game, turn_token, user_id}};
+
+    #[test]
+    fn test_board() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        assert_eq!(Status::Forbidden, client.get("/api/board").dispatch().status());
+        let registration = create_game(&client);
+        let response = client.get("/api/board").header(user_id(&registration)).dispatch();
+        assert_eq!(Status::Ok, response.status());
+        assert_eq!(r#"{"columns":12,"rows":9,"tiles":[]}"#, response.into_string().unwrap());
+    }
+
+    #[test]
+    fn test_chains() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        assert_eq!(Status::Forbidden, client.get("/api/chains").dispatch().status());
+        let registration = create_game(&client);
+        let chains = client.get("/api/chains").header(user_id(&registration)).dispatch().into_string().unwrap();
+        assert!(chains.starts_with(r#"[{"chain":"tower","tier":"cheap","founded":false,"size":0,"safe":false,"price":null},"#), "{}", chains);
+        assert_eq!(7, rocket::serde::json::from_str::<Vec<Value>>(&chains).unwrap().len());
+    }
+
+    #[test]
+    fn test_stocks() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        assert_eq!(Status::Forbidden, client.get("/api/stocks").dispatch().status());
+        let (game_master, _player) = started_game(&client);
+        let stocks: Value = client.get("/api/stocks").header(user_id(&game_master)).dispatch().into_json().unwrap();
+        assert_eq!(r#"{"money":6000,"shares":{}}"#, stocks["own"].to_string());
+        assert_eq!(2, stocks["players"].as_array().unwrap().len());
+        assert_eq!(7, stocks["bank"].as_object().unwrap().len());
+        assert!(stocks["bank"].as_object().unwrap().values().all(|shares| shares == 25));
+    }
+
+    #[test]
+    fn test_hand() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        let (game_master, player) = lobby(&client);
+        let response = client.get("/api/hand").header(user_id(&player)).dispatch();
+        assert_eq!(Status::Conflict, response.status());
+        assert_eq!("game_not_started", response.into_json::<Value>().unwrap()["error"]);
+        assert_eq!(Status::Ok, client.post("/api/start_game").header(user_id(&game_master)).dispatch().status());
+
+        let tiles = |registration: &Value| -> HashSet<String> {
+            let hand: Value = client.get("/api/hand").header(user_id(registration)).dispatch().into_json().unwrap();
+            assert_eq!(96, hand["tiles_remaining"]);
+            hand["tiles"].as_array().unwrap().iter().map(|tile| String::from(tile.as_str().unwrap())).collect()
+        };
+        let (game_master_tiles, player_tiles) = (tiles(&game_master), tiles(&player));
+        assert_eq!(6, game_master_tiles.len());
+        assert_eq!(6, player_tiles.len());
+        assert!(game_master_tiles.is_disjoint(&player_tiles));
+        // the tiles are formatted like on the board
+        assert!(player_tiles.iter().all(|tile| Position::parse(tile).is_some_and(|position| &position.to_string() == tile)));
+        // the hand is not part of the public board
+        assert_eq!(r#"{"columns":12,"rows":9,"tiles":[]}"#, client.get("/api/board").header(user_id(&player)).dispatch().into_string().unwrap());
+    }
+
+    #[test]
+    fn test_turn() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        let (game_master, player) = lobby(&client);
+        let response = client.get("/api/turn").header(user_id(&player)).dispatch();
+        assert_eq!(Status::Conflict, response.status());
+        assert_eq!("game_not_started", response.into_json::<Value>().unwrap()["error"]);
+        assert_eq!(Status::Ok, client.post("/api/start_game").header(user_id(&game_master)).dispatch().status());
+
+        let turn: Value = client.get("/api/turn").header(user_id(&player)).dispatch().into_json().unwrap();
+        assert_eq!("place_tile", turn["phase"]);
+        assert!(turn["disposal"].is_null());
+        let token = String::from(turn["turn_token"].as_str().unwrap());
+        let waiting = match turn["player_id"].as_u64() {
+            Some(1) => &player,
+            _ => &game_master,
+        };
+        // the conflict names whose turn it actually is
+        let response = client.post("/api/buy_stock").header(user_id(waiting)).header(ContentType::JSON).body(format!(r#"{{"purchases":[],"turn_token":"{}"}}"#, token)).dispatch();
+        assert_eq!(Status::Conflict, response.status());
+        let body: Value = response.into_json().unwrap();
+        assert_eq!("not_your_turn", body["error"]);
+        assert_eq!(turn, body["turn"]);
+
+        // no chain is on the board yet
+        let current = if std::ptr::eq(waiting, &player) { &game_master } else { &player };
+        let response = client.post("/api/end_game").header(user_id(current)).header(ContentType::JSON).body(format!(r#"{{"turn_token":"{}"}}"#, token)).dispatch();
+        assert_eq!(Status::Conflict, response.status());
+        assert_eq!("end_conditions_not_met", response.into_json::<Value>().unwrap()["error"]);
+        let response = client.get("/api/results").header(user_id(current)).dispatch();
+        assert_eq!(Status::Conflict, response.status());
+        assert_eq!("game_not_finished", response.into_json::<Value>().unwrap()["error"]);
+    }
+
+    #[test]
+    fn test_game_log() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        let (game_master, player) = lobby(&client);
+        assert_eq!(Status::Forbidden, client.get("/api/game_log").dispatch().status());
+        assert_eq!(Status::Ok, client.post("/api/start_game").header(user_id(&game_master)).dispatch().status());
+        let log: Value = client.get("/api/game_log").header(user_id(&player)).dispatch().into_json().unwrap();
+        let actions: Vec<&str> = log["entries"].as_array().unwrap().iter().map(|entry| entry["action"].as_str().unwrap()).collect();
+        assert_eq!(vec!["joined", "joined", "game_started", "turn_changed"], actions);
+        assert_eq!(2, log["entries"][1]["player_id"]);
+        assert!(log["entries"][2]["player_id"].is_null());
+        assert_eq!(4, log["next_cursor"]);
+        assert_eq!(false, log["has_more"]);
+        assert_eq!(false, log["gap"]);
+
+        let log: Value = client.get("/api/game_log?since=3").header(user_id(&player)).dispatch().into_json().unwrap();
+        assert_eq!(1, log["entries"].as_array().unwrap().len());
+        assert_eq!(3, log["entries"][0]["index"]);
+    }
+
+    #[test]
+    fn test_game_stats() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        let (game_master, player) = lobby(&client);
+        assert_eq!(Status::Forbidden, client.get("/api/game_stats").dispatch().status());
+        assert_eq!(Status::Ok, client.post("/api/start_game").header(user_id(&game_master)).dispatch().status());
+        let response = client.get("/api/game_stats").header(user_id(&player)).dispatch();
+        assert_eq!(Status::Conflict, response.status());
+        assert_eq!("game_not_finished", response.into_json::<Value>().unwrap()["error"]);
+    }
+
+    #[test]
+    fn test_game_notes() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        let (game_master, player) = lobby(&client);
+        let notes = r#"{"title": "Close game", "notes": "Won by 100"}"#;
+        assert_eq!(Status::Forbidden, client.post("/api/game_notes").header(ContentType::JSON).body(notes).dispatch().status());
+        let response = client.post("/api/game_notes").header(user_id(&player)).header(ContentType::JSON).body(notes).dispatch();
+        assert_eq!(Status::Forbidden, response.status());
+        assert_eq!("not_game_master", response.into_json::<Value>().unwrap()["error"]);
+        assert_eq!(Status::Ok, client.post("/api/start_game").header(user_id(&game_master)).dispatch().status());
+        let response = client.post("/api/game_notes").header(user_id(&game_master)).header(ContentType::JSON).body(notes).dispatch();
+        assert_eq!(Status::Conflict, response.status());
+        assert_eq!("game_not_finished", response.into_json::<Value>().unwrap()["error"]);
+        let response = client.post("/api/game_notes").header(user_id(&game_master)).header(ContentType::JSON).body(r#"{"title": "Close game", "editor": 1}"#).dispatch();
+        assert_eq!(Status::BadRequest, response.status());
+    }
+
+    #[test]
+    fn test_place_tile() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        let (game_master, player) = lobby(&client);
+        let place_tile = |registration: &Value, tile: &str| client.post("/api/place_tile")
+            .header(user_id(registration))
+            .header(ContentType::JSON)
+            .body(format!(r#"{{"tile":"{}","turn_token":"{}"}}"#, tile, turn_token(&client, registration)))
+            .dispatch();
+        let error = |response: rocket::local::blocking::LocalResponse| -> (Status, String) {
+            (response.status(), String::from(response.into_json::<Value>().unwrap()["error"].as_str().unwrap()))
+        };
+        assert_eq!((Status::Conflict, String::from("game_not_started")), error(place_tile(&game_master, "1A")));
+        assert_eq!(Status::Ok, client.post("/api/start_game").header(user_id(&game_master)).dispatch().status());
+
+        let hand = |registration: &Value| -> Vec<String> {
+            let hand: Value = client.get("/api/hand").header(user_id(registration)).dispatch().into_json().unwrap();
+            hand["tiles"].as_array().unwrap().iter().map(|tile| String::from(tile.as_str().unwrap())).collect()
+        };
+        // the order is drawn at random, the game master has the player id 1
+        let turn: Value = client.get("/api/turn").header(user_id(&player)).dispatch().into_json().unwrap();
+        let (game_master, player) = match turn["player_id"].as_u64() {
+            Some(1) => (game_master, player),
+            _ => (player, game_master),
+        };
+        let (tiles, other_tiles) = (hand(&game_master), hand(&player));
+        assert_eq!((Status::Conflict, String::from("not_your_turn")), error(place_tile(&player, &other_tiles[0])));
+        assert_eq!((Status::UnprocessableEntity, String::from("tile_not_in_hand")), error(place_tile(&game_master, &other_tiles[0])));
+        assert_eq!(Status::BadRequest, place_tile(&game_master, "13Z").status());
+        let found_chain = |chain: &str| client.post("/api/found_chain")
+            .header(user_id(&game_master))
+            .header(ContentType::JSON)
+            .body(format!(r#"{{"chain":"{}","turn_token":"{}"}}"#, chain, turn_token(&client, &game_master)))
+            .dispatch();
+        assert_eq!((Status::Conflict, String::from("no_chain_to_found")), error(found_chain("tower")));
+        assert_eq!(Status::BadRequest, found_chain("hilton").status());
+
+        let response = place_tile(&game_master, &tiles[0]);
+        assert_eq!(Status::Ok, response.status());
+        assert_eq!(format!(r#"{{"tiles":[{{"position":"{}","chain":null}}],"buy_stock":false}}"#, tiles[0]), response.into_string().unwrap());
+        // no chain is on the board, so the turn ends right away and a new tile is drawn
+        let refilled = hand(&game_master);
+        assert_eq!(6, refilled.len());
+        assert!(!refilled.contains(&tiles[0]));
+        let board: Value = client.get("/api/board").header(user_id(&player)).dispatch().into_json().unwrap();
+        assert_eq!(tiles[0], board["tiles"][0]["position"]);
+        assert_eq!((Status::Conflict, String::from("not_your_turn")), error(place_tile(&game_master, &tiles[1])));
+        let body = format!(r#"{{"purchases":[],"turn_token":"{}"}}"#, turn_token(&client, &player));
+        let buy_stock = client.post("/api/buy_stock").header(user_id(&player)).header(ContentType::JSON).body(body).dispatch();
+        assert_eq!((Status::Conflict, String::from("not_buy_phase")), error(buy_stock));
+        assert_eq!(Status::Ok, place_tile(&player, &other_tiles[0]).status());
+    }
+
+    #[test]
+    fn test_turn_token() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        let (game_master, player) = started_game(&client);
+        let turn: Value = client.get("/api/turn").header(user_id(&player)).dispatch().into_json().unwrap();
+        let current = match turn["player_id"].as_u64() {
+            Some(1) => &game_master,
+            _ => &player,
+        };
+        let hand = || -> Vec<String> {
+            let hand: Value = client.get("/api/hand").header(user_id(current)).dispatch().into_json().unwrap();
+            hand["tiles"].as_array().unwrap().iter().map(|tile| String::from(tile.as_str().unwrap())).collect()
+        };
+        let board = || client.get("/api/board").header(user_id(current)).dispatch().into_string().unwrap();
+        let place_tile = |tile: &str, token: &str| client.post("/api/place_tile")
+            .header(user_id(current))
+            .header(ContentType::JSON)
+            .body(format!(r#"{{"tile":"{}","turn_token":"{}"}}"#, tile, token))
+            .dispatch();
+        let tiles = hand();
+        // the token of the turn is the one that is required
+        let token = turn_token(&client, current);
+        assert_eq!(turn["turn_token"], token.as_str());
+        assert_eq!(Status::BadRequest, client.post("/api/place_tile").header(user_id(current)).header(ContentType::JSON)
+            .body(format!(r#"{{"tile":"{}"}}"#, tiles[0])).dispatch().status());
+        assert_eq!(Status::Ok, place_tile(&tiles[0], &token).status());
+        let (placed_hand, placed_board) = (hand(), board());
+
+        // the retried request is rejected without placing the second tile
+        for tile in [&tiles[0], &tiles[1]] {
+            let response = place_tile(tile, &token);
+            assert_eq!(Status::Conflict, response.status());
+            assert_eq!(r#"{"error":"stale_turn_token"}"#, response.into_string().unwrap());
+        }
+        assert_eq!((placed_hand, placed_board), (hand(), board()));
+        // the token of the previous turn is rejected for the next player as well
+        let next = if std::ptr::eq(current, &player) { &game_master } else { &player };
+        let next_tiles: Value = client.get("/api/hand").header(user_id(next)).dispatch().into_json().unwrap();
+        let response = client.post("/api/place_tile").header(user_id(next)).header(ContentType::JSON)
+            .body(format!(r#"{{"tile":"{}","turn_token":"{}"}}"#, next_tiles["tiles"][0].as_str().unwrap(), token)).dispatch();
+        assert_eq!(Status::Conflict, response.status());
+        assert_eq!("stale_turn_token", response.into_json::<Value>().unwrap()["error"]);
+        assert_ne!(token, turn_token(&client, next));
+    }
+}
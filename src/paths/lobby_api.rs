@@ -0,0 +1,911 @@
+use std::{sync::RwLockWriteGuard, net::IpAddr, time::Duration};
+
+use rocket::{
+    log::private::info,
+    get, post, patch, delete, routes, Route, Responder,
+    State, serde::json::{self, Json, Value}, http::{CookieJar, Cookie}, tokio::time::sleep,
+};
+
+use uuid::Uuid;
+
+use crate::{analytics::{Analytics, ClientHints}, game::{base_game::VacancyReason, GameManager, User, UserDisconnectedStatus, shards::ShardedGameManager, game_instance::{GameCode, GameInstance, GameState, LobbySettings, invites::InviteInfo, security_log::SecurityEntry}}, request_data::{GameEvent, UserRegistration, Waitlisted, PlayersInGame, JoinGameRequest, CreateGameRequest, CreateInviteRequest, LobbyAdminRequest, LobbyAdminResult, MAX_LOBBY_MESSAGE_LEN, QuickplayRequest, QuickplayTicket, WhoAmI, LobbySettingsUpdate, merge_patch, SettingsPreset, SettingsImport, SETTINGS_PRESET_VERSIONS}, authentication::{UserAuth, UserRecovery, FromRequestError, GameCodeError}, error::ApiError, events::{EventBatch, EventBus}, maintenance::Maintenance, negotiation::Negotiated, quickplay::QuickplayQueue, rate_limit::CodeGuessLimiter, utils::{get_gm_read_guard, get_gm_write_guard}};
+
+/// Returns all routes that are used to create, join and manage a lobby.
+pub fn routes() -> Vec<Route> {
+    routes![create_game, join_game, leave_game, start_game, lobby_settings, patch_lobby_settings, lock_lobby, lobby_admin, export_settings, import_settings, create_invite, invites, revoke_invite, security_log, players_in_game, whoami, quickplay, cancel_quickplay]
+}
+
+/// 
+/// # Requires
+/// The user needs to send a username formatted in a json string in the post request body.
+/// 
+/// In debug builds the body can also contain a `seed` to make the game deterministic.
+/// The optional `source` is counted by [Analytics](../../analytics/struct.Analytics.html).
+/// 
+/// While the server is in maintenance mode `503 Service Unavailable` is returned.
+#[post("/api/create_game", data = "<data>")]
+pub fn create_game(cookies: &CookieJar<'_>, game_manager: &State<ShardedGameManager>, maintenance: &State<Maintenance>, analytics: &State<Analytics>, data: Result<Json<CreateGameRequest>, json::Error<'_>>, ip_addr: Option<IpAddr>, hints: ClientHints) -> Result<Json<UserRegistration>, ApiError> {
+    maintenance.allow_new_games()?;
+    let data = data?.into_inner();
+    // Fixed seeds are only allowed for test games
+    let seed = match data.seed {
+        Some(_) if !cfg!(debug_assertions) => {
+            info!("Ignoring seed for new game, seeds are only allowed in debug builds");
+            None
+        },
+        seed => seed,
+    };
+    let registration = game_manager
+        .create_game(data.username.into_inner(), ip_addr, seed)
+        .ok_or_else(|| ApiError::conflict("game_not_created"))?;
+    analytics.record_created(data.source, &hints);
+    // Set recovery cookie
+    cookies.add(Cookie::new("urid", registration.urid.cookie_value()));
+    Ok(Json(registration))
+}
+
+/// Returns the name that the user used last and the game in which the user is still a player, see [WhoAmI](../../request_data/struct.WhoAmI.html).
+/// 
+/// The user is identified by the `urid` cookie, so the lobby page can fill in the name and offer to rejoin the game.
+/// 
+/// # Requires
+/// The `urid` cookie, when it is missing, unknown or expired `404 Not Found` is returned.
+#[get("/api/whoami")]
+pub fn whoami(game_manager: &State<ShardedGameManager>, ur: Option<UserRecovery>) -> Result<Json<WhoAmI>, ApiError> {
+    let (name, game_code) = ur
+        .and_then(|ur| game_manager.whoami(&ur.urid))
+        .ok_or_else(|| ApiError::not_found("unknown_user"))?;
+    Ok(Json(WhoAmI { name, game_code: game_code.map(|game_code| game_code.to_string()) }))
+}
+
+/// Puts the user into the queue of users that want to be matched with other users into a new game, see [QuickplayQueue](../../quickplay/struct.QuickplayQueue.html).
+/// 
+/// The returned ticket is used to open the stream [quickplay](../sse/fn.quickplay.html) on which the registration for the new game is send.
+/// 
+/// # Requires
+/// The user needs to send a username formatted in a json string in the post request body.
+/// 
+/// While the server is in maintenance mode `503 Service Unavailable` is returned.
+#[post("/api/quickplay", data = "<request>")]
+pub fn quickplay(game_manager: &State<ShardedGameManager>, event: &State<EventBus>, maintenance: &State<Maintenance>, queue: &State<QuickplayQueue>, request: Result<Json<QuickplayRequest>, json::Error<'_>>, ip_addr: Option<IpAddr>) -> Result<Json<QuickplayTicket>, ApiError> {
+    maintenance.allow_new_games()?;
+    let request = request?.into_inner();
+    let ticket = queue.enqueue(request.username.into_inner(), ip_addr);
+    // Start the game right away when enough users are waiting
+    queue.run_matcher(game_manager, event);
+    Ok(Json(QuickplayTicket { ticket }))
+}
+
+/// Removes the ticket from the quickplay queue.
+/// 
+/// When the ticket was already matched into a game `404 Not Found` is returned, the user then has to leave the game instead.
+#[delete("/api/quickplay/<ticket>")]
+pub fn cancel_quickplay(queue: &State<QuickplayQueue>, ticket: Uuid) -> Result<Json<String>, ApiError> {
+    if !queue.cancel(ticket) {
+        return Err(ApiError::not_found("ticket_not_found"));
+    }
+    Ok(Json::from(String::from("Ticket cancelled")))
+}
+
+/// Adds the user to the game with the game code of the `game_code` header.
+/// 
+/// When the request contains a `urid` cookie of a player of this game, the session of that player is recovered.
+/// Cookies that belong to other games are ignored and a new registration is created.
+/// 
+/// # Requires
+/// The user needs to send a username formatted in a json string in the post request body.
+/// When the lobby requires an invite the body also has to contain the `invite` token.
+///
+/// When the lobby is full and has a waitlist, the user is put on the waitlist and `202 Accepted` is returned
+/// with the position on the waitlist, see [Waitlisted](../../request_data/struct.Waitlisted.html).
+///
+/// Rejected joins are delayed by [CodeGuessLimiter::failure_delay](../../rate_limit/struct.CodeGuessLimiter.html#method.failure_delay)
+/// so that existing games can not be found by measuring the response time.
+#[post("/api/join_game", data = "<request>")]
+#[allow(clippy::too_many_arguments)]
+pub async fn join_game(cookies: &CookieJar<'_>, game_manager: &State<ShardedGameManager>, event: &State<EventBus>, limiter: &State<CodeGuessLimiter>, analytics: &State<Analytics>, request: Result<Json<JoinGameRequest>, json::Error<'_>>, game_code: Result<GameCode, GameCodeError>, ur: Option<UserRecovery>, client_ip: Option<IpAddr>, hints: ClientHints) -> Result<JoinResponse, ApiError> {
+    let game_code = game_code?;
+    let request = request?.into_inner();
+    let username = request.username.into_inner();
+    let ur = ur.map(|mut ur| {
+        ur.name = Some(username.clone());
+        ur
+    });
+    let ip_addr = ur.as_ref().and_then(|ur| ur.ip_addr);
+    let result = game_manager.add_player_to_game(game_code, username.clone(), ur, ip_addr, request.invite);
+    let (registration, events) = match result {
+        Ok(result) => result,
+        Err(err) => {
+            if let Some(alert) = get_gm_read_guard(game_manager.shard(&game_code), "join_game: security log").record_rejection(game_code, &err, username, client_ip) {
+                alert.publish(event);
+            }
+            sleep(limiter.failure_delay()).await;
+            return Err(err.into());
+        },
+    };
+    events.publish(event);
+    analytics.record_joined(request.source, &hints);
+    // Set recovery cookie
+    cookies.add(Cookie::new("urid", registration.urid.cookie_value()));
+    Ok(match registration.waitlist_position() {
+        Some(position) => JoinResponse::Waitlisted(Json(Waitlisted { code: "waitlisted", position, registration })),
+        None => JoinResponse::Joined(Json(registration)),
+    })
+}
+
+/// The response of [join_game]().
+#[derive(Responder)]
+pub enum JoinResponse {
+    Joined(Json<UserRegistration>),
+    #[response(status = 202)]
+    Waitlisted(Json<Waitlisted>),
+}
+
+/// Makes the user leave the game where they are assigned to.
+/// 
+/// An event is then send to all other players in the game to notify them that the player left,
+/// the seat of the player is marked as vacant with the reason `left`.
+/// 
+/// When the last player disconnects using this function, the game is deleted instantly, without waiting for a reconnect.
+/// # Requires
+/// Request guard [UserAuth]() to succeed.
+#[post("/api/leave_game")]
+pub fn leave_game(game_manager: &State<ShardedGameManager>, event: &State<EventBus>, user_auth: Result<UserAuth, FromRequestError>) -> Result<Json<String>, ApiError> {
+    let user_auth = user_auth?;
+    // A user whose stream was closed before is already disconnected, only the reason changes
+    if let UserDisconnectedStatus::GameAlive | UserDisconnectedStatus::AlreadyDisconnected = game_manager.disconnect_user(user_auth, VacancyReason::Left, Duration::ZERO) {
+        if let Some(events) = get_gm_read_guard(game_manager.shard(&user_auth.game_code), "leave_game").user_disconnected_events(user_auth) {
+            events.publish(event);
+        }
+    }
+    Ok(Json::from(String::from("User marked as disconnected")))
+}
+
+/// Updates the lobby settings of the game where the user is assigned to.
+/// 
+/// The update is either applied completely or not at all, when fields are invalid all of them are
+/// returned with `422 Unprocessable Entity` and the code `invalid_fields`.
+/// 
+/// The new [LobbyStatus](../../game/game_instance/struct.LobbyStatus.html) is then send to all players in the game.
+/// 
+/// # Requires
+/// - Request guard [UserAuth]() to succeed and the user to be the game master.
+/// - The settings that should be changed formatted as json in the post request body, see [LobbySettingsUpdate](../../request_data/struct.LobbySettingsUpdate.html).
+#[post("/api/lobby_settings", data = "<update>")]
+pub fn lobby_settings(game_manager: &State<ShardedGameManager>, event: &State<EventBus>, user_auth: Result<UserAuth, FromRequestError>, update: Result<Json<LobbySettingsUpdate>, json::Error<'_>>) -> Result<Json<String>, ApiError> {
+    let user_auth = user_auth?;
+    let update = update?;
+    let (events, removed) = {
+        let game_manager = get_gm_read_guard(game_manager.shard(&user_auth.game_code), "lobby_settings");
+        let mut game = game_master_lobby(&game_manager, user_auth)?;
+        let settings = update.apply(game.settings());
+        apply_settings(&mut game, settings)?
+    };
+    forget_removed(game_manager, &user_auth.game_code, &removed);
+    events.publish(event);
+    Ok(Json::from(String::from("Lobby settings updated")))
+}
+
+/// Changes the lobby settings with a JSON merge patch, see [merge_patch](../../request_data/fn.merge_patch.html).
+/// 
+/// The patch is applied to the current settings document: fields that are missing are not changed
+/// and fields that are `null` are set back to their default value.
+/// The merged settings are validated as a whole, when any field is invalid nothing is changed.
+/// 
+/// All players receive the event `LobbySettings` containing the new settings.
+/// 
+/// # Requires
+/// - `user_auth` of the game master, the game has to be in the lobby
+/// 
+/// # Returns
+/// The complete settings after the patch was applied.
+#[patch("/api/lobby_settings", data = "<patch>")]
+pub fn patch_lobby_settings(game_manager: &State<ShardedGameManager>, event: &State<EventBus>, user_auth: Result<UserAuth, FromRequestError>, patch: Result<Json<Value>, json::Error<'_>>) -> Result<Json<LobbySettings>, ApiError> {
+    let user_auth = user_auth?;
+    let patch = patch?.into_inner();
+    // Any other patch would replace the whole settings document
+    if !patch.is_object() {
+        return Err(ApiError::bad_request("invalid_request").with_detail("the patch has to be a json object"));
+    }
+    let (events, removed, settings) = {
+        let game_manager = get_gm_read_guard(game_manager.shard(&user_auth.game_code), "patch_lobby_settings");
+        let mut game = game_master_lobby(&game_manager, user_auth)?;
+        let mut document = json::to_value(game.settings()).expect("lobby settings can always be serialized");
+        merge_patch(&mut document, &patch);
+        let settings: LobbySettings = json::from_value(document)
+            .map_err(|err| ApiError::bad_request("invalid_request").with_detail(err.to_string()))?;
+        let (events, removed) = apply_settings(&mut game, settings)?;
+        (events, removed, game.settings().clone())
+    };
+    forget_removed(game_manager, &user_auth.game_code, &removed);
+    events.publish(event);
+    Ok(Json(settings))
+}
+
+/// Starts the game where the user is assigned to.
+/// 
+/// The event `GameStarted` is then send to all players in the game, the clients open the game page when they receive it.
+/// Users that wait for a seat receive the event `WaitlistClosed`.
+/// 
+/// Starting a game that has already started fails with `409 Conflict`, as does starting a game in which less than
+/// [min_players](../../game/game_instance/struct.LobbySettings.html#structfield.min_players) players are connected.
+/// 
+/// # Requires
+/// Request guard [UserAuth]() to succeed and the user to be the game master.
+#[post("/api/start_game")]
+pub fn start_game(game_manager: &State<ShardedGameManager>, event: &State<EventBus>, user_auth: Result<UserAuth, FromRequestError>) -> Result<Json<String>, ApiError> {
+    let user_auth = user_auth?;
+    let (events, removed) = {
+        let game_manager = get_gm_read_guard(game_manager.shard(&user_auth.game_code), "start_game");
+        let mut game = game_master_lobby(&game_manager, user_auth)?;
+        game.start()?
+    };
+    forget_removed(game_manager, &user_auth.game_code, &removed);
+    events.publish(event);
+    Ok(Json::from(String::from("Game started")))
+}
+
+/// Locks the lobby of the game where the user is assigned to or unlocks it when it is already locked.
+/// 
+/// While the lobby is locked new players can not join the game, players that are already part of the game can still reconnect.
+/// 
+/// The event `LobbyLocked` or `LobbyUnlocked` is then send to all players in the game.
+/// 
+/// # Requires
+/// Request guard [UserAuth]() to succeed and the user to be the game master.
+#[post("/api/lock_lobby")]
+pub fn lock_lobby(game_manager: &State<ShardedGameManager>, event: &State<EventBus>, user_auth: Result<UserAuth, FromRequestError>) -> Result<Json<String>, ApiError> {
+    let user_auth = user_auth?;
+    let (mut events, locked) = {
+        let game_manager = get_gm_read_guard(game_manager.shard(&user_auth.game_code), "lock_lobby");
+        let mut game = game_master_lobby(&game_manager, user_auth)?;
+        let locked = !game.is_locked();
+        game.set_locked(locked);
+        (EventBatch::new(game.channel()), locked)
+    };
+    events.push(if locked { GameEvent::LobbyLocked } else { GameEvent::LobbyUnlocked });
+    events.publish(event);
+    Ok(Json::from(String::from(if locked { "Lobby locked" } else { "Lobby unlocked" })))
+}
+
+/// Applies several lobby operations of the game master at once, see [LobbyAdminRequest](../../request_data/struct.LobbyAdminRequest.html).
+/// 
+/// The operations are applied in the order lock, kicks, message while the game manager is locked,
+/// so players that try to join at the same time see either none or all of the changes.
+/// The response contains the outcome of each operation, see [LobbyAdminResult](../../request_data/struct.LobbyAdminResult.html).
+/// 
+/// All events are send together after the operations where applied, kicked players receive the event `Kicked`
+/// and their sse stream is closed.
+/// 
+/// # Requires
+/// - Request guard [UserAuth]() to succeed and the user to be the game master.
+/// - The operations formatted as json in the post request body, a request without any operation is rejected with `422 Unprocessable Entity`.
+#[post("/api/lobby_admin", data = "<request>")]
+pub fn lobby_admin(game_manager: &State<ShardedGameManager>, event: &State<EventBus>, user_auth: Result<UserAuth, FromRequestError>, request: Result<Json<LobbyAdminRequest>, json::Error<'_>>) -> Result<Json<LobbyAdminResult>, ApiError> {
+    let user_auth = user_auth?;
+    let request = request?.into_inner();
+    if request.is_empty() {
+        return Err(ApiError::unprocessable_entity("empty_batch"));
+    }
+    if request.message.as_ref().is_some_and(|message| message.chars().count() > MAX_LOBBY_MESSAGE_LEN) {
+        return Err(ApiError::bad_request("message_too_long")
+            .with_detail(format!("the message can have at most {} characters", MAX_LOBBY_MESSAGE_LEN)));
+    }
+    let (result, events, kicked) = {
+        // The write lock keeps joins out until the whole batch is applied
+        let mut shard = get_gm_write_guard(game_manager.shard(&user_auth.game_code), "lobby_admin");
+        let (result, events, kicked) = game_master_lobby(&shard, user_auth)?.lobby_admin(user_auth.uuid, &request);
+        shard.forget_users(&kicked);
+        (result, events, kicked)
+    };
+    game_manager.forget_users(&kicked.iter().map(|user| user.uuid()).collect::<Vec<_>>());
+    events.publish(event);
+    Ok(Json(result))
+}
+
+/// Returns the lobby settings of the game where the user is assigned to as [SettingsPreset](../../request_data/struct.SettingsPreset.html).
+/// 
+/// The preset can be imported into another lobby with [import_settings](fn.import_settings.html).
+/// 
+/// # Requires
+/// Request guard [UserAuth]() to succeed and the user to be the game master.
+#[get("/api/settings/export")]
+pub fn export_settings(game_manager: &State<ShardedGameManager>, user_auth: Result<UserAuth, FromRequestError>) -> Result<Json<SettingsPreset>, ApiError> {
+    let user_auth = user_auth?;
+    let game_manager = get_gm_read_guard(game_manager.shard(&user_auth.game_code), "export_settings");
+    let game = game_manager
+        .game_by_code_read(user_auth.game_code)
+        .ok_or_else(|| ApiError::not_found("game_not_found"))?;
+    if !game.is_game_master(user_auth.uuid) {
+        return Err(ApiError::forbidden("not_game_master"));
+    }
+    Ok(Json(SettingsPreset::from_settings(game.settings())))
+}
+
+/// Replaces the lobby settings of the game where the user is assigned to with the settings of a [SettingsPreset](../../request_data/struct.SettingsPreset.html).
+/// 
+/// The preset is validated like individual settings updates and either applied completely or not at all.
+/// Fields that are not known are ignored and listed in the `warnings` of the response.
+/// 
+/// The new [LobbyStatus](../../game/game_instance/struct.LobbyStatus.html) is then send to all players in the game.
+/// 
+/// # Requires
+/// - Request guard [UserAuth]() to succeed and the user to be the game master.
+/// - The preset formatted as json in the post request body, presets with an unsupported version are rejected with `422 Unprocessable Entity`.
+#[post("/api/settings/import", data = "<preset>")]
+pub fn import_settings(game_manager: &State<ShardedGameManager>, event: &State<EventBus>, user_auth: Result<UserAuth, FromRequestError>, preset: Result<Json<Value>, json::Error<'_>>) -> Result<Json<SettingsImport>, ApiError> {
+    let user_auth = user_auth?;
+    let preset = preset?.into_inner();
+    // The version is checked first because presets of other versions might not deserialize
+    match preset["version"].as_u64() {
+        Some(version) if SETTINGS_PRESET_VERSIONS.contains(&version) => (),
+        _ => return Err(ApiError::unprocessable_entity("unsupported_version")
+            .with_detail(format!("supported versions: {} to {}", SETTINGS_PRESET_VERSIONS.start(), SETTINGS_PRESET_VERSIONS.end()))),
+    }
+    let preset: SettingsPreset = json::from_value(preset)
+        .map_err(|err| ApiError::bad_request("invalid_request").with_detail(err.to_string()))?;
+    let (events, removed) = {
+        let game_manager = get_gm_read_guard(game_manager.shard(&user_auth.game_code), "import_settings");
+        let mut game = game_master_lobby(&game_manager, user_auth)?;
+        let settings = preset.settings(game.settings());
+        apply_settings(&mut game, settings)?
+    };
+    forget_removed(game_manager, &user_auth.game_code, &removed);
+    events.publish(event);
+    let warnings = preset.unknown.keys().map(|field| format!("unknown field `{}` was ignored", field)).collect();
+    Ok(Json(SettingsImport { warnings }))
+}
+
+/// Creates a new invite for the game where the user is assigned to.
+/// 
+/// The invite can be shared as link `/lobby/<game_code>?invite=<id>`, it is only checked when
+/// the lobby setting `require_invite` is set.
+/// 
+/// # Requires
+/// - Request guard [UserAuth]() to succeed and the user to be the game master.
+/// - The properties of the invite formatted as json in the post request body, see [CreateInviteRequest](../../request_data/struct.CreateInviteRequest.html).
+#[post("/api/invites", data = "<request>")]
+pub fn create_invite(game_manager: &State<ShardedGameManager>, user_auth: Result<UserAuth, FromRequestError>, request: Result<Json<CreateInviteRequest>, json::Error<'_>>) -> Result<Json<InviteInfo>, ApiError> {
+    let user_auth = user_auth?;
+    let request = request?.into_inner();
+    if request.uses == 0 || request.valid_for_secs == 0 {
+        return Err(ApiError::bad_request("invalid_invite").with_detail("uses and valid_for_secs have to be larger than 0"));
+    }
+    let game_manager = get_gm_read_guard(game_manager.shard(&user_auth.game_code), "create_invite");
+    let mut game = game_master_lobby(&game_manager, user_auth)?;
+    game.invites_mut()
+        .create(request.uses, Duration::from_secs(request.valid_for_secs))
+        .map(|invite| Json(invite.info()))
+        .ok_or_else(|| ApiError::conflict("too_many_invites"))
+}
+
+/// Returns all invites of the game where the user is assigned to that can still be used.
+/// 
+/// # Requires
+/// Request guard [UserAuth]() to succeed and the user to be the game master.
+#[get("/api/invites")]
+pub fn invites(game_manager: &State<ShardedGameManager>, user_auth: Result<UserAuth, FromRequestError>) -> Result<Json<Vec<InviteInfo>>, ApiError> {
+    let user_auth = user_auth?;
+    let game_manager = get_gm_read_guard(game_manager.shard(&user_auth.game_code), "invites");
+    let game = game_master_lobby(&game_manager, user_auth)?;
+    Ok(Json(game.invites().active().iter().map(|invite| invite.info()).collect()))
+}
+
+/// Revokes the invite with `id` so that it can no longer be used to join the game.
+/// 
+/// # Requires
+/// Request guard [UserAuth]() to succeed and the user to be the game master.
+#[delete("/api/invites/<id>")]
+pub fn revoke_invite(game_manager: &State<ShardedGameManager>, user_auth: Result<UserAuth, FromRequestError>, id: Uuid) -> Result<Json<String>, ApiError> {
+    let user_auth = user_auth?;
+    let game_manager = get_gm_read_guard(game_manager.shard(&user_auth.game_code), "revoke_invite");
+    let mut game = game_master_lobby(&game_manager, user_auth)?;
+    if !game.invites_mut().revoke(id) {
+        return Err(ApiError::not_found("invite_not_found"));
+    }
+    Ok(Json::from(String::from("Invite revoked")))
+}
+
+/// Returns the join requests for the game where the user is assigned to that where rejected, see [SecurityLog](../../game/game_instance/security_log/struct.SecurityLog.html).
+/// 
+/// # Requires
+/// Request guard [UserAuth]() to succeed and the user to be the game master.
+#[get("/api/security_log")]
+pub fn security_log(game_manager: &State<ShardedGameManager>, user_auth: Result<UserAuth, FromRequestError>) -> Result<Json<Vec<SecurityEntry>>, ApiError> {
+    let user_auth = user_auth?;
+    let game_manager = get_gm_read_guard(game_manager.shard(&user_auth.game_code), "security_log");
+    let game = game_manager
+        .game_by_code_read(user_auth.game_code)
+        .ok_or_else(|| ApiError::not_found("game_not_found"))?;
+    if !game.is_game_master(user_auth.uuid) {
+        return Err(ApiError::forbidden("not_game_master"));
+    }
+    Ok(Json(game.security_log().entries()))
+}
+
+/// Applies `settings` to the lobby of `game`.
+/// 
+/// A larger maximum promotes users from the waitlist, a disabled waitlist removes all waiting users.
+/// 
+/// # Returns
+/// - The events `LobbySettings` and `LobbyStatus` followed by the events of the promoted and removed users.
+/// - The users that were removed from the waitlist, see [forget_removed]().
+/// - `Err(ApiError)` with all invalid fields when the settings are not valid, the settings of the game are not changed in this case.
+fn apply_settings(game: &mut GameInstance, settings: LobbySettings) -> Result<(EventBatch, Vec<User>), ApiError> {
+    game.set_settings(settings).map_err(ApiError::invalid_fields)?;
+    let mut events = game.lobby_settings_events();
+    events.append(game.lobby_status_events());
+    events.append(game.promote_waitlisted());
+    let mut removed = Vec::new();
+    if !game.settings().enable_waitlist() {
+        let (closed, users) = game.close_waitlist();
+        events.append(closed);
+        removed = users;
+    }
+    Ok((events, removed))
+}
+
+/// Frees the ids of the users that were removed from the waitlist of the game with `game_code`.
+/// 
+/// This needs the write lock of the shard, so it can only be called after the game is no longer borrowed.
+fn forget_removed(game_manager: &ShardedGameManager, game_code: &GameCode, removed: &[User]) {
+    if removed.is_empty() {
+        return;
+    }
+    get_gm_write_guard(game_manager.shard(game_code), "lobby_settings: close waitlist").forget_users(removed);
+    game_manager.forget_users(&removed.iter().map(|user| user.uuid()).collect::<Vec<_>>());
+}
+
+/// Returns the game of the user when the user is the game master and the game is still in the lobby.
+/// 
+/// The request counts as action of the game master, see [GameInstance::game_master_active](../../game/game_instance/struct.GameInstance.html#method.game_master_active).
+fn game_master_lobby<'a>(game_manager: &'a GameManager, user_auth: UserAuth) -> Result<RwLockWriteGuard<'a, GameInstance>, ApiError> {
+    let mut game = game_manager
+        .game_by_user_auth_write(user_auth)
+        .ok_or_else(|| ApiError::not_found("game_not_found"))?;
+    if !game.is_game_master(user_auth.uuid) {
+        return Err(ApiError::forbidden("not_game_master"));
+    }
+    if !matches!(game.game_state(), GameState::Lobby) {
+        return Err(ApiError::conflict("game_already_started"));
+    }
+    game.game_master_active();
+    Ok(game)
+}
+
+/// Return the names of the connected players as json.
+/// 
+/// When the client prefers `text/plain` all players are returned as table instead, see [Negotiated](../../negotiation/struct.Negotiated.html).
+/// 
+/// # Requires
+/// - `game_code` header with valid [GameCode](../../game/struct.GameCode.html)
+#[get("/api/players_in_game")]
+pub fn players_in_game(game_manager: &State<ShardedGameManager>, game_code: Result<GameCode, GameCodeError>) -> Result<Negotiated<PlayersInGame>, ApiError> {
+    let game_code = game_code?;
+    let game_manager = get_gm_read_guard(game_manager.shard(&game_code), "players_in_game");
+    info!("{}", game_code.to_string());
+    game_manager
+        .players_in_game(game_code)
+        .map(Negotiated)
+        .ok_or_else(|| ApiError::not_found("game_not_found"))
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::{
+        http::{ContentType, Cookie, Header, Status},
+        local::blocking::{Client, LocalResponse},
+        serde::json::{from_str, to_value, Value},
+        tokio::sync::broadcast::Receiver,
+    };
+
+    use uuid::Uuid;
+
+    use crate::{events::PublishedEvent, game::{game_instance::GameCode, shards::ShardedGameManager}, paths::test_utils::{connect, create_game, join_game, join_game_as, user_id}};
+
+    /// Sends a request to join the game of `registration` as `username` with an invite.
+    fn join_game_with_invite<'c>(client: &'c Client, registration: &Value, username: &str, invite: &Value) -> LocalResponse<'c> {
+        client.post("/api/join_game")
+            .header(Header::new("game_code", String::from(registration["game_code"].as_str().unwrap())))
+            .header(ContentType::JSON)
+            .body(format!(r#"{{"username":"{}","invite":{}}}"#, username, invite))
+            .dispatch()
+    }
+
+    /// Returns a new receiver for the events of the game of `registration`.
+    fn subscribe(client: &Client, registration: &Value) -> Receiver<PublishedEvent> {
+        let game_code = GameCode::from_string(registration["game_code"].as_str().unwrap()).unwrap();
+        client.rocket().state::<ShardedGameManager>().unwrap().subscribe(game_code).unwrap()
+    }
+
+    /// Returns the next event named `name`, other events are skipped.
+    fn next_event(receiver: &mut Receiver<PublishedEvent>, name: &str) -> Value {
+        while let Ok(event) = receiver.try_recv() {
+            let event = to_value(event).unwrap();
+            if event["data"][0] == name {
+                return event;
+            }
+        }
+        panic!("no {} event was send", name);
+    }
+
+    /// Returns the data of the next `LobbyStatus` event.
+    fn next_lobby_status(receiver: &mut Receiver<PublishedEvent>) -> Value {
+        from_str(next_event(receiver, "LobbyStatus")["data"][1].as_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_lobby_settings() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        let game_master = create_game(&client);
+        let player = join_game(&client, &game_master);
+        let mut receiver = subscribe(&client, &game_master);
+        let update = |registration: &Value, body: &'static str| {
+            client.post("/api/lobby_settings").header(user_id(registration)).header(ContentType::JSON).body(body).dispatch()
+        };
+        assert_eq!(Status::Ok, update(&game_master, r#"{"min_players":3}"#).status());
+        assert_eq!(3, next_lobby_status(&mut receiver)["min_players"]);
+        let response = update(&game_master, r#"{"min_players":7}"#);
+        assert_eq!(Status::UnprocessableEntity, response.status());
+        let error: Value = response.into_json().unwrap();
+        assert_eq!("invalid_fields", error["error"]);
+        assert_eq!(from_str::<Value>(r#"[{"field":"min_players","code":"out_of_range","min":2,"max":6}]"#).unwrap(), error["fields"]);
+        // all invalid fields are reported and nothing is applied
+        let response = update(&game_master, r#"{"min_players":1,"max_players":1,"require_invite":true}"#);
+        let error: Value = response.into_json().unwrap();
+        let fields: Vec<&str> = error["fields"].as_array().unwrap().iter().map(|field| field["field"].as_str().unwrap()).collect();
+        assert_eq!(vec!["max_players", "min_players"], fields);
+        assert_eq!(Status::Ok, update(&game_master, r#"{"min_players":4,"max_players":4}"#).status());
+        let status = next_lobby_status(&mut receiver);
+        assert_eq!((4, 4), (status["min_players"].as_u64().unwrap(), status["max_players"].as_u64().unwrap()));
+        let response = update(&player, r#"{"min_players":2}"#);
+        assert_eq!(Status::Forbidden, response.status());
+        assert_eq!("not_game_master", response.into_json::<Value>().unwrap()["error"]);
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_patch_lobby_settings() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        let game_master = create_game(&client);
+        let mut receiver = subscribe(&client, &game_master);
+        let patch = |body: &str| {
+            client.patch("/api/lobby_settings").header(user_id(&game_master)).header(ContentType::JSON).body(body).dispatch()
+        };
+        let changed = r#"{"min_players":3,"max_players":5,"require_invite":true,"master_idle_rotate_secs":60,"enable_waitlist":true}"#;
+        let response = patch(changed);
+        assert_eq!(Status::Ok, response.status());
+        let settings: Value = response.into_json().unwrap();
+        assert_eq!(from_str::<Value>(changed).unwrap(), settings);
+        // the event contains the complete settings
+        let event = next_event(&mut receiver, "LobbySettings");
+        assert_eq!(settings, from_str::<Value>(event["data"][1].as_str().unwrap()).unwrap());
+
+        // missing fields are not changed
+        let settings: Value = patch(r#"{"max_players":6}"#).into_json().unwrap();
+        assert_eq!(6, settings["max_players"]);
+        assert_eq!(3, settings["min_players"]);
+        assert_eq!(true, settings["require_invite"]);
+        assert_eq!(settings, patch("{}").into_json::<Value>().unwrap());
+
+        // null sets each field back to its default
+        for (field, default) in [("min_players", Value::from(2)), ("require_invite", false.into()), ("master_idle_rotate_secs", 0.into()), ("enable_waitlist", false.into()), ("max_players", 6.into())] {
+            let settings: Value = patch(&format!(r#"{{"{}":null}}"#, field)).into_json().unwrap();
+            assert_eq!(default, settings[field], "{}", field);
+        }
+
+        // the merged settings are validated as a whole and nothing is applied when they are invalid
+        let before: Value = patch(r#"{"min_players":4,"require_invite":true}"#).into_json().unwrap();
+        let response = patch(r#"{"require_invite":false,"max_players":3}"#);
+        assert_eq!(Status::UnprocessableEntity, response.status());
+        let error: Value = response.into_json().unwrap();
+        assert_eq!("greater_than_max_players", error["fields"][0]["code"]);
+        assert_eq!(before, patch("{}").into_json::<Value>().unwrap());
+        for body in [r#"{"min_players":"3"}"#, r#"{"spectators":true}"#, "[]"] {
+            assert_eq!(Status::BadRequest, patch(body).status(), "{}", body);
+        }
+    }
+
+    #[test]
+    fn test_invalid_requests() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        for body in [r#"{"username":""}"#, r#"{"username":"gm","unknown":1}"#, "not json"] {
+            let response = client.post("/api/create_game").header(ContentType::JSON).body(body).dispatch();
+            assert_eq!(Status::BadRequest, response.status(), "{}", body);
+            assert_eq!("invalid_request", response.into_json::<Value>().unwrap()["error"]);
+        }
+        let game_master = create_game(&client);
+        let response = client.post("/api/lobby_settings").header(user_id(&game_master)).header(ContentType::JSON).body(r#"{"max":3}"#).dispatch();
+        assert_eq!(Status::BadRequest, response.status());
+    }
+
+    #[test]
+    fn test_lock_lobby() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        let game_master = create_game(&client);
+        connect(&client, &game_master);
+        let player = join_game(&client, &game_master);
+        let mut receiver = subscribe(&client, &game_master);
+        let lock = |registration: &Value| client.post("/api/lock_lobby").header(user_id(registration)).dispatch();
+        assert_eq!(Status::Forbidden, lock(&player).status());
+        assert_eq!(Status::Ok, lock(&game_master).status());
+        next_event(&mut receiver, "LobbyLocked");
+        let response = join_game_as(&client, &game_master, "latecomer");
+        assert_eq!(Status::Forbidden, response.status());
+        assert_eq!("lobby_locked", response.into_json::<Value>().unwrap()["error"]);
+        // players that are already part of the game can still rejoin
+        assert_eq!(Status::Ok, join_game_as(&client, &game_master, "player").status());
+        assert_eq!(Status::Ok, lock(&game_master).status());
+        next_event(&mut receiver, "LobbyUnlocked");
+        assert_eq!(Status::Ok, join_game_as(&client, &game_master, "latecomer").status());
+    }
+
+    #[test]
+    fn test_start_game() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        let game_master = create_game(&client);
+        connect(&client, &game_master);
+        let player = join_game(&client, &game_master);
+        let start = |registration: &Value| client.post("/api/start_game").header(user_id(registration)).dispatch();
+        let response = start(&game_master);
+        assert_eq!(Status::Conflict, response.status());
+        assert_eq!("not_enough_players", response.into_json::<Value>().unwrap()["error"]);
+        connect(&client, &player);
+        assert_eq!(Status::Forbidden, start(&player).status());
+        let mut receiver = subscribe(&client, &game_master);
+        assert_eq!(Status::Ok, start(&game_master).status());
+        next_event(&mut receiver, "GameStarted");
+        let response = start(&game_master);
+        assert_eq!(Status::Conflict, response.status());
+        assert_eq!("game_already_started", response.into_json::<Value>().unwrap()["error"]);
+        assert_eq!("game_already_started", join_game_as(&client, &game_master, "latecomer").into_json::<Value>().unwrap()["error"]);
+    }
+
+    #[test]
+    fn test_lobby_admin() {
+        let figment = rocket::Config::figment().merge(("event_coalesce_window_ms", 0));
+        let client = Client::tracked(crate::server(rocket::custom(figment))).unwrap();
+        let game_master = create_game(&client);
+        let player = join_game(&client, &game_master);
+        join_game_as(&client, &game_master, "other");
+        let mut receiver = subscribe(&client, &game_master);
+        let admin = |registration: &Value, body: &str| {
+            client.post("/api/lobby_admin").header(user_id(registration)).header(ContentType::JSON).body(body).dispatch()
+        };
+        assert_eq!(Status::Forbidden, admin(&player, r#"{"lock":true}"#).status());
+        let response = admin(&game_master, "{}");
+        assert_eq!(Status::UnprocessableEntity, response.status());
+        assert_eq!("empty_batch", response.into_json::<Value>().unwrap()["error"]);
+        let response = admin(&game_master, &format!(r#"{{"message":"{}"}}"#, "a".repeat(501)));
+        assert_eq!(Status::BadRequest, response.status());
+        assert_eq!("message_too_long", response.into_json::<Value>().unwrap()["error"]);
+        assert!(receiver.try_recv().is_err());
+
+        let response = admin(&game_master, r#"{"kick":[2,1,2,99],"lock":true,"message":"Behave"}"#);
+        assert_eq!(Status::Ok, response.status());
+        let expected = r#"{"locked":true,"kicks":[{"player_id":2,"outcome":"kicked"},{"player_id":1,"outcome":"skipped"},{"player_id":2,"outcome":"not_found"},{"player_id":99,"outcome":"not_found"}],"message_sent":true}"#;
+        assert_eq!(expected, response.into_string().unwrap());
+        // the events are send in the order in which the operations where applied
+        let mut events = Vec::new();
+        while let Ok(event) = receiver.try_recv() {
+            events.push(to_value(event).unwrap());
+        }
+        let names: Vec<&str> = events.iter().map(|event| event["data"][0].as_str().unwrap()).collect();
+        assert_eq!(vec!["LobbyLocked", "Kicked", "PlayerList", "LobbyStatus", "LobbyMessage"], names);
+        assert_eq!(player["uuid"], events[1]["user_id"]);
+        assert!(!events[2]["data"][1].as_str().unwrap().contains(r#""name":"player""#));
+        assert_eq!("Behave", events[4]["data"][1]);
+
+        // the kicked player can no longer use their session
+        let response = client.post("/api/leave_game").header(user_id(&player)).dispatch();
+        assert_eq!(Status::Forbidden, response.status());
+        assert_eq!("lobby_locked", join_game_as(&client, &game_master, "latecomer").into_json::<Value>().unwrap()["error"]);
+    }
+
+    #[test]
+    fn test_waitlist() {
+        let figment = rocket::Config::figment().merge(("event_coalesce_window_ms", 0));
+        let client = Client::tracked(crate::server(rocket::custom(figment))).unwrap();
+        let game_master = create_game(&client);
+        let settings = |body: &str| client.post("/api/lobby_settings").header(user_id(&game_master)).header(ContentType::JSON).body(String::from(body)).dispatch().status();
+        let import = |max_players: usize| {
+            let body = format!(r#"{{"version":1,"min_players":2,"max_players":{}}}"#, max_players);
+            client.post("/api/settings/import").header(user_id(&game_master)).header(ContentType::JSON).body(body).dispatch().status()
+        };
+        assert_eq!(Status::Ok, import(2));
+        join_game(&client, &game_master);
+        assert_eq!("game_full", join_game_as(&client, &game_master, "w1").into_json::<Value>().unwrap()["error"]);
+
+        assert_eq!(Status::Ok, settings(r#"{"enable_waitlist":true}"#));
+        let response = join_game_as(&client, &game_master, "w1");
+        assert_eq!(Status::Accepted, response.status());
+        let waiting: Value = response.into_json().unwrap();
+        assert_eq!("waitlisted", waiting["code"]);
+        assert_eq!(1, waiting["position"]);
+        assert_eq!(game_master["game_code"], waiting["game_code"]);
+        // waiting users can follow the lobby
+        connect(&client, &waiting);
+        let mut receiver = subscribe(&client, &game_master);
+        assert_eq!(Status::Ok, settings(r#"{"enable_waitlist":false}"#));
+        let closed = next_event(&mut receiver, "WaitlistClosed");
+        assert_eq!(waiting["uuid"], closed["user_id"]);
+        assert_eq!(Status::Forbidden, client.post("/api/leave_game").header(user_id(&waiting)).dispatch().status());
+
+        // raising the maximum number of players promotes the waiting users
+        assert_eq!(Status::Ok, settings(r#"{"enable_waitlist":true}"#));
+        let waiting: Value = join_game_as(&client, &game_master, "w2").into_json().unwrap();
+        assert_eq!(Status::Ok, import(3));
+        let promoted = next_event(&mut receiver, "Promoted");
+        assert_eq!(waiting["uuid"], promoted["user_id"]);
+        let registration: Value = from_str(promoted["data"][1].as_str().unwrap()).unwrap();
+        assert_eq!(waiting["uuid"], registration["uuid"]);
+        assert!(next_event(&mut receiver, "PlayerList")["data"][1].as_str().unwrap().contains(r#""name":"w2""#));
+    }
+
+    #[test]
+    fn test_whoami() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        let whoami = || client.get("/api/whoami").dispatch();
+        assert_eq!(Status::NotFound, whoami().status());
+        let first = create_game(&client);
+        let expected = format!(r#"{{"name":"gm","game_code":"{}"}}"#, first["game_code"].as_str().unwrap());
+        assert_eq!(expected, whoami().into_string().unwrap());
+        // the name is kept when the game is deleted
+        connect(&client, &first);
+        assert_eq!(Status::Ok, client.post("/api/leave_game").header(user_id(&first)).dispatch().status());
+        assert_eq!(r#"{"name":"gm","game_code":null}"#, whoami().into_string().unwrap());
+        let second: Value = client.post("/api/create_game").header(ContentType::JSON).body(r#"{"username":"Alice"}"#).dispatch().into_json().unwrap();
+        let expected = format!(r#"{{"name":"Alice","game_code":"{}"}}"#, second["game_code"].as_str().unwrap());
+        assert_eq!(expected, whoami().into_string().unwrap());
+
+        let client = Client::untracked(crate::rocket()).unwrap();
+        let unknown = Cookie::new("urid", Uuid::new_v4().to_string());
+        let response = client.get("/api/whoami").cookie(unknown).dispatch();
+        assert_eq!(Status::NotFound, response.status());
+        assert_eq!(r#"{"error":"unknown_user"}"#, response.into_string().unwrap());
+    }
+
+    #[test]
+    fn test_settings_export_import() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        let game_master = create_game(&client);
+        let player = join_game(&client, &game_master);
+        let export = |registration: &Value| client.get("/api/settings/export").header(user_id(registration)).dispatch();
+        let import = |body: String| client.post("/api/settings/import").header(user_id(&game_master)).header(ContentType::JSON).body(body).dispatch();
+        assert_eq!(Status::Forbidden, export(&player).status());
+        let mut preset: Value = export(&game_master).into_json().unwrap();
+        assert_eq!(1, preset["version"]);
+        assert_eq!(2, preset["min_players"]);
+        assert_eq!(6, preset["max_players"]);
+
+        // round trip with a field from a newer version
+        preset["min_players"] = 3.into();
+        preset["max_players"] = 4.into();
+        preset["future_setting"] = true.into();
+        let response = import(preset.to_string());
+        assert_eq!(Status::Ok, response.status());
+        assert_eq!(r#"{"warnings":["unknown field `future_setting` was ignored"]}"#, response.into_string().unwrap());
+        let exported: Value = export(&game_master).into_json().unwrap();
+        assert_eq!(from_str::<Value>(r#"{"version":1,"min_players":3,"max_players":4}"#).unwrap(), exported);
+
+        // nothing is applied when a part of the preset is invalid
+        let response = import(String::from(r#"{"version":1,"min_players":2,"max_players":1}"#));
+        assert_eq!(Status::UnprocessableEntity, response.status());
+        let error: Value = response.into_json().unwrap();
+        assert_eq!("invalid_fields", error["error"]);
+        assert_eq!("max_players", error["fields"][0]["field"]);
+        assert_eq!(exported, export(&game_master).into_json::<Value>().unwrap());
+
+        for body in [r#"{"version":2,"min_players":2,"max_players":6}"#, r#"{"min_players":2}"#] {
+            let response = import(String::from(body));
+            assert_eq!(Status::UnprocessableEntity, response.status());
+            let error: Value = response.into_json().unwrap();
+            assert_eq!("unsupported_version", error["error"]);
+            assert_eq!("supported versions: 1 to 1", error["detail"]);
+        }
+    }
+
+    #[test]
+    fn test_lobby_status_on_join_and_leave() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        let game_master = create_game(&client);
+        let mut receiver = subscribe(&client, &game_master);
+        connect(&client, &game_master);
+        assert_eq!(1, next_lobby_status(&mut receiver)["current_players"]);
+        let player = join_game(&client, &game_master);
+        connect(&client, &player);
+        let status = next_lobby_status(&mut receiver);
+        assert_eq!(2, status["current_players"]);
+        assert_eq!(true, status["can_start"]);
+        let response = client.post("/api/leave_game").header(user_id(&player)).dispatch();
+        assert_eq!(Status::Ok, response.status());
+        let status = next_lobby_status(&mut receiver);
+        assert_eq!(1, status["current_players"]);
+        assert_eq!(false, status["can_start"]);
+    }
+
+    #[test]
+    fn test_invites() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        let game_master = create_game(&client);
+        let player = join_game(&client, &game_master);
+        let create = |body: &'static str| client.post("/api/invites").header(user_id(&game_master)).header(ContentType::JSON).body(body).dispatch();
+        let error = |response: LocalResponse<'_>| response.into_json::<Value>().unwrap()["error"].clone();
+        // invites are not checked until the setting is enabled
+        let invite: Value = create("{}").into_json().unwrap();
+        assert_eq!(1, invite["uses_left"]);
+        assert_eq!(Status::Ok, join_game_as(&client, &game_master, "open").status());
+        let response = client.post("/api/lobby_settings").header(user_id(&game_master)).header(ContentType::JSON).body(r#"{"require_invite":true}"#).dispatch();
+        assert_eq!(Status::Ok, response.status());
+        assert_eq!("invite_required", error(join_game_as(&client, &game_master, "uninvited")));
+        assert_eq!("invite_invalid", error(join_game_with_invite(&client, &game_master, "guessed", &Value::from(uuid::Uuid::new_v4().to_string()))));
+        // single use
+        assert_eq!(Status::Ok, join_game_with_invite(&client, &game_master, "invited", &invite["id"]).status());
+        assert_eq!("invite_invalid", error(join_game_with_invite(&client, &game_master, "second", &invite["id"])));
+        // existing players can rejoin without an invite
+        assert_eq!(Status::Ok, join_game_as(&client, &game_master, "player").status());
+        // listing and revoking
+        let invite: Value = create(r#"{"uses":2,"valid_for_secs":60}"#).into_json().unwrap();
+        let listed: Value = client.get("/api/invites").header(user_id(&game_master)).dispatch().into_json().unwrap();
+        assert_eq!(vec![invite["id"].clone()], listed.as_array().unwrap().iter().map(|invite| invite["id"].clone()).collect::<Vec<Value>>());
+        assert_eq!(Status::Forbidden, client.get("/api/invites").header(user_id(&player)).dispatch().status());
+        let revoke = || client.delete(format!("/api/invites/{}", invite["id"].as_str().unwrap())).header(user_id(&game_master)).dispatch();
+        assert_eq!(Status::Ok, revoke().status());
+        assert_eq!(Status::NotFound, revoke().status());
+        assert_eq!("invite_invalid", error(join_game_with_invite(&client, &game_master, "revoked", &invite["id"])));
+        assert_eq!(Status::BadRequest, create(r#"{"uses":0}"#).status());
+    }
+
+    #[test]
+    fn test_join_with_recovery_cookie() {
+        let client = Client::untracked(crate::rocket()).unwrap();
+        let urid = |response: &LocalResponse<'_>| String::from(response.cookies().get("urid").unwrap().value());
+        let join = |registration: &Value, username: &str, urid: Option<&String>| {
+            let request = client.post("/api/join_game")
+                .header(Header::new("game_code", String::from(registration["game_code"].as_str().unwrap())))
+                .header(ContentType::JSON)
+                .body(format!(r#"{{"username":"{}"}}"#, username));
+            match urid {
+                Some(urid) => request.cookie(Cookie::new("urid", urid.clone())).dispatch(),
+                None => request.dispatch(),
+            }
+        };
+        let old_game = client.post("/api/create_game").header(ContentType::JSON).body(r#"{"username":"gm"}"#).dispatch();
+        let stale = urid(&old_game);
+        let old_game: Value = old_game.into_json().unwrap();
+        let game_master = create_game(&client);
+        // a cookie of another game behaves like a fresh join
+        let response = join(&game_master, "player", Some(&stale));
+        assert_eq!(Status::Ok, response.status());
+        let cookie = urid(&response);
+        let player: Value = response.into_json().unwrap();
+        assert_ne!(old_game["uuid"], player["uuid"]);
+        connect(&client, &player);
+        // the matching cookie recovers the session of the connected player
+        let recovered: Value = join(&game_master, "player", Some(&cookie)).into_json().unwrap();
+        assert_eq!(player["uuid"], recovered["uuid"]);
+        for urid in [None, Some(&stale)] {
+            let response = join(&game_master, "player", urid);
+            assert_eq!(Status::Forbidden, response.status());
+            assert_eq!("name_taken", response.into_json::<Value>().unwrap()["error"]);
+        }
+    }
+
+    #[test]
+    fn test_security_log() {
+        let client = Client::untracked(crate::rocket()).unwrap();
+        let game_master = create_game(&client);
+        let player = join_game(&client, &game_master);
+        connect(&client, &player);
+        let security_log = |registration: &Value| client.get("/api/security_log").header(user_id(registration)).dispatch();
+        assert_eq!(Status::Forbidden, security_log(&player).status());
+        // joining with the name of a connected player without the recovery cookie
+        assert_eq!(Status::Forbidden, join_game_as(&client, &game_master, "player").status());
+        client.post("/api/lock_lobby").header(user_id(&game_master)).dispatch();
+        assert_eq!(Status::Forbidden, join_game_as(&client, &game_master, "latecomer").status());
+        let entries: Value = security_log(&game_master).into_json().unwrap();
+        let kinds: Vec<&str> = entries.as_array().unwrap().iter().map(|entry| entry["kind"].as_str().unwrap()).collect();
+        assert_eq!(vec!["recovery_failed", "lobby_locked"], kinds);
+        assert_eq!("player", entries[0]["username"]);
+    }
+}
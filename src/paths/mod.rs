@@ -0,0 +1,95 @@
+use rocket::Route;
+
+/// The html pages and the static files of the frontend
+pub mod pages;
+
+/// Routes that are used to create, join and manage a lobby
+pub mod lobby_api;
+
+/// Routes that are used while the game is played
+pub mod game_api;
+
+/// The server send event stream that is used to push events to the players
+pub mod sse;
+
+/// Routes that are only meant for debugging
+pub mod debug;
+
+/// Routes that are used to administrate the server
+pub mod admin;
+
+/// Helpers that the tests of the routes share
+#[cfg(test)]
+mod test_utils;
+
+/// Returns the routes of all modules, these are mounted at `/` by [rocket()](../fn.rocket.html).
+/// 
+/// When a new module with routes is added it has to be added here.
+pub fn all_routes() -> Vec<Route> {
+    [pages::routes(), lobby_api::routes(), game_api::routes(), sse::routes(), debug::routes(), admin::routes()].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::all_routes;
+
+    /// All routes that are expected to be mounted, formatted as `<method> <uri>`.
+    const ROUTES: &[&str] = &[
+        "GET /<path..>",
+        "GET /lobby",
+        "GET /lobby/<game_code>",
+        "GET /lobby/<game_code>/game",
+        "POST /api/create_game",
+        "POST /api/join_game",
+        "POST /api/leave_game",
+        "POST /api/start_game",
+        "POST /api/lobby_settings",
+        "PATCH /api/lobby_settings",
+        "POST /api/lock_lobby",
+        "POST /api/lobby_admin",
+        "GET /api/settings/export",
+        "POST /api/settings/import",
+        "POST /api/invites",
+        "GET /api/invites",
+        "DELETE /api/invites/<id>",
+        "GET /api/security_log",
+        "GET /api/players_in_game",
+        "GET /api/whoami",
+        "POST /api/quickplay",
+        "DELETE /api/quickplay/<ticket>",
+        "GET /api/board",
+        "GET /api/chains",
+        "GET /api/hand",
+        "GET /api/stocks",
+        "GET /api/turn",
+        "POST /api/place_tile",
+        "POST /api/found_chain",
+        "POST /api/choose_survivor",
+        "POST /api/buy_stock",
+        "POST /api/merger_decision",
+        "POST /api/end_game",
+        "GET /api/results",
+        "GET /api/game_stats",
+        "POST /api/game_notes",
+        "GET /api/game_log?<since>",
+        "GET /sse/<_>/<user_id>?<encoding>",
+        "GET /sse/quickplay/<ticket>",
+        "GET /api/debug/<user_id>",
+        "GET /api/debug/keep_busy/<id>/<time>",
+        "GET /api/debug/game",
+        "POST /api/admin/maintenance",
+        "POST /api/admin/notice",
+        "GET /api/notices",
+        "GET /api/status",
+        "GET /api/time",
+    ];
+
+    #[test]
+    fn test_route_inventory() {
+        let mut mounted: Vec<String> = all_routes().iter().map(|route| format!("{} {}", route.method, route.uri)).collect();
+        let mut expected: Vec<String> = ROUTES.iter().map(|route| String::from(*route)).collect();
+        mounted.sort();
+        expected.sort();
+        assert_eq!(expected, mounted);
+    }
+}
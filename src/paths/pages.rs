@@ -0,0 +1,237 @@
+use std::path::{Path, PathBuf};
+
+use rocket::{
+    fs::{NamedFile, relative},
+    get, routes, Route,
+    State, response::Redirect, tokio::fs,
+};
+
+use crate::{authentication::UserRecovery, game::{base_game::VacancyReason, shards::ShardedGameManager, game_instance::{GameCode, LobbyPage, PlayerListEntry}}, caching::{CachedFile, RenderedPage}, utils::get_gm_read_guard};
+
+/// Returns all routes that serve pages.
+pub fn routes() -> Vec<Route> {
+    routes![static_files, lobby, lobby_join, game_page]
+}
+
+/// Serves the static files of the frontend located in `web/public`.
+///
+/// When a directory is requested the `index.html` contained within is served.
+#[get("/<path..>", rank = 10)]
+pub async fn static_files(path: PathBuf) -> Option<CachedFile> {
+    let mut path = Path::new(relative!("web/public")).join(path);
+    if path.is_dir() {
+        path.push("index.html");
+    }
+    let file = NamedFile::open(&path).await.ok()?;
+    if path.extension().is_some_and(|extension| extension == "html") {
+        Some(CachedFile::page(file))
+    } else {
+        Some(CachedFile::asset(file))
+    }
+}
+
+#[get("/lobby")]
+pub async fn lobby() -> Option<RenderedPage> {
+    let template = fs::read_to_string(Path::new("web/protected/lobby.html")).await.ok()?;
+    Some(RenderedPage(render(&template, &lobby_values(None))))
+}
+
+/// Serves the lobby page with the current state of the lobby already filled in.
+///
+/// The game code, the player list and a summary of the settings are rendered into the page, so the lobby is shown even when
+/// the scripts or the wasm module can not be loaded. Rendered players are marked with `data-ssr="true"`, the wasm module
+/// updates them in place once it receives the first `PlayerList` event.
+///
+/// When the `urid` cookie belongs to a player of the game the page offers to rejoin as that player.
+///
+/// Unknown games redirect to `/lobby`.
+#[get("/lobby/<game_code>")]
+pub async fn lobby_join(game_manager: &State<ShardedGameManager>, game_code: &str, ur: Option<UserRecovery>) -> Result<Option<RenderedPage>, Redirect> {
+    let page = match lobby_page(game_manager, game_code, ur) {
+        Some(page) => page,
+        None => return Err(Redirect::to("/lobby")),
+    };
+    let template = match fs::read_to_string(Path::new("web/protected/lobby.html")).await {
+        Ok(template) => template,
+        Err(_) => return Ok(None),
+    };
+    Ok(Some(RenderedPage(render(&template, &lobby_values(Some(&page))))))
+}
+
+/// Serves the game page with the game code and the player list filled in, see [lobby_join]().
+#[get("/lobby/<game_code>/game")]
+pub async fn game_page(game_manager: &State<ShardedGameManager>, game_code: &str, ur: Option<UserRecovery>) -> Result<Option<RenderedPage>, Redirect> {
+    let page = match lobby_page(game_manager, game_code, ur) {
+        Some(page) => page,
+        None => return Err(Redirect::to(String::from("/lobby/"))),
+    };
+    let template = match fs::read_to_string(Path::new("web/protected/game.html")).await {
+        Ok(template) => template,
+        Err(_) => return Ok(None),
+    };
+    let values = [
+        ("game_code", page.game_code.to_string()),
+        ("player_list", player_list(&page.players, page.rejoin_as.as_deref())),
+    ];
+    Ok(Some(RenderedPage(render(&template, &values))))
+}
+
+/// Returns the [LobbyPage]() of the game with `game_code`, `None` when the game code is invalid or the game does not exist.
+fn lobby_page(game_manager: &ShardedGameManager, game_code: &str, ur: Option<UserRecovery>) -> Option<LobbyPage> {
+    let game_code = GameCode::from_string(game_code)?;
+    let game_manager = get_gm_read_guard(game_manager.shard(&game_code), "lobby page");
+    let page = game_manager.game_by_code_read(game_code)?.lobby_page(ur.as_ref().map(|ur| &ur.urid));
+    Some(page)
+}
+
+/// Returns the values of the placeholders in `lobby.html`.
+///
+/// Without a page the lobby is rendered like before a game was created or joined.
+fn lobby_values(page: Option<&LobbyPage>) -> Vec<(&'static str, String)> {
+    let page = match page {
+        Some(page) => page,
+        None => return vec![
+            ("hidden_without_game", String::from("hidden")),
+            ("game_code", String::from("GAME-CODE")),
+            ("locked_hidden", String::from("hidden")),
+        ],
+    };
+    let mut summary = format!("{} of {} players, {} needed to start", page.players.len(), page.status.max_players, page.status.min_players);
+    if page.require_invite {
+        summary.push_str(", invite required");
+    }
+    let mut values = vec![
+        ("hidden_with_game", String::from("hidden")),
+        ("game_code", page.game_code.to_string()),
+        ("locked_hidden", String::from(if page.locked { "" } else { "hidden" })),
+        ("settings_summary", summary),
+        ("player_list", player_list(&page.players, page.rejoin_as.as_deref())),
+    ];
+    match &page.rejoin_as {
+        Some(name) => {
+            values.push(("player_name", escape_html(name)));
+            values.push(("rejoin_href", format!("/lobby/{}", page.game_code)));
+            values.push(("rejoin_text", format!("Rejoin as {}", escape_html(name))));
+        },
+        None => values.push(("rejoin_hidden", String::from("hidden"))),
+    }
+    values
+}
+
+/// Renders the entries of the player list like the wasm module does, the entry of `user_name` is highlighted.
+fn player_list(players: &[PlayerListEntry], user_name: Option<&str>) -> String {
+    players.iter().map(|player| {
+        let mut class_name = String::from("list-group-item");
+        if Some(player.name.as_str()) == user_name {
+            class_name.push_str(" list-group-item-primary");
+        }
+        if player.game_master {
+            class_name.push_str(" fw-bold");
+        }
+        if !player.connected {
+            class_name.push_str(" text-muted");
+        }
+        let mut text = escape_html(&player.name);
+        if player.game_master {
+            text.push_str(" \u{2605}");
+        }
+        match player.vacancy.as_ref().map(|vacancy| vacancy.reason) {
+            // the wasm module adds how long the seat is vacant
+            Some(VacancyReason::Left) if !player.connected => text.push_str(" (left)"),
+            Some(VacancyReason::ConnectionLost) if !player.connected => text.push_str(" (connection lost)"),
+            _ if !player.connected => text.push_str(" (disconnected)"),
+            _ => (),
+        }
+        format!(r#"<li class="{}" data-player-id="{}" data-ssr="true">{}</li>"#, class_name, player.player_id, text)
+    }).collect()
+}
+
+/// Replaces each placeholder `{{key}}` in `template` with the value of `key`, placeholders without a value are removed.
+///
+/// Values are inserted as they are, text has to be escaped with [escape_html]() first.
+fn render(template: &str, values: &[(&str, String)]) -> String {
+    let mut html = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let end = match rest[start..].find("}}") {
+            Some(end) => start + end,
+            None => break,
+        };
+        html.push_str(&rest[..start]);
+        let key = rest[start + 2..end].trim();
+        if let Some((_, value)) = values.iter().find(|(name, _)| *name == key) {
+            html.push_str(value);
+        }
+        rest = &rest[end + 2..];
+    }
+    html.push_str(rest);
+    html
+}
+
+/// Escapes the characters that have a meaning in html, so that `text` can be used as content and in attribute values.
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::{
+        http::{ContentType, Cookie, Header, Status},
+        local::blocking::Client,
+        serde::json::Value,
+    };
+
+    use super::{escape_html, render};
+
+    #[test]
+    fn test_render() {
+        let values = [("name", escape_html("<b>\"Bob\" & 'Al'</b>")), ("empty", String::new())];
+        assert_eq!("a &lt;b&gt;&quot;Bob&quot; &amp; &#39;Al&#39;&lt;/b&gt; b  c", render("a {{name}} b {{ empty }} c{{unknown}}", &values));
+        // unterminated placeholders are kept
+        assert_eq!("a {{name", render("a {{name", &values));
+    }
+
+    #[test]
+    fn test_lobby_page_contains_lobby() {
+        let client = Client::untracked(crate::rocket()).unwrap();
+        let game_master: Value = client.post("/api/create_game").header(ContentType::JSON).body(r#"{"username":"Al<i>ce"}"#).dispatch().into_json().unwrap();
+        let game_code = game_master["game_code"].as_str().unwrap();
+        let response = client.post("/api/join_game").header(Header::new("game_code", String::from(game_code))).header(ContentType::JSON).body(r#"{"username":"Bob"}"#).dispatch();
+        assert_eq!(Status::Ok, response.status());
+
+        let html = client.get(format!("/lobby/{}", game_code)).dispatch().into_string().unwrap();
+        assert!(html.contains(game_code));
+        assert!(html.contains(r#"<li class="list-group-item fw-bold text-muted" data-player-id="1" data-ssr="true">Al&lt;i&gt;ce"#), "{}", html);
+        assert!(html.contains(r#"data-player-id="2" data-ssr="true">Bob (disconnected)</li>"#));
+        assert!(html.contains("2 of 6 players, 2 needed to start"), "{}", html);
+        assert!(!html.contains("Rejoin as"));
+        assert!(!html.contains("{{"));
+
+        // the urid cookie of a player offers to rejoin
+        let urid = &game_master["urid"];
+        let cookie = Cookie::new("urid", format!("{}:{}", urid["uuid"].as_str().unwrap(), urid["issued_at"]));
+        let member = client.get(format!("/lobby/{}", game_code)).cookie(cookie.clone()).dispatch().into_string().unwrap();
+        assert!(member.contains("Rejoin as Al&lt;i&gt;ce"));
+        assert!(member.contains(r#"value="Al&lt;i&gt;ce""#));
+        assert!(member.contains("list-group-item list-group-item-primary fw-bold"));
+        assert_ne!(html, member);
+        let game = client.get(format!("/lobby/{}/game", game_code)).cookie(cookie).dispatch().into_string().unwrap();
+        assert!(game.contains(r#"data-player-id="2" data-ssr="true">Bob"#));
+
+        let response = client.get("/lobby/AAAA-AAAA").dispatch();
+        assert_eq!(Status::SeeOther, response.status());
+        let empty = client.get("/lobby").dispatch().into_string().unwrap();
+        assert!(!empty.contains("data-ssr") && !empty.contains("{{"));
+    }
+}
@@ -0,0 +1,564 @@
+use std::{collections::VecDeque, convert::Infallible, net::IpAddr, time::Duration};
+
+use rocket::{
+    get, routes, Route, Request,
+    log::private::info,
+    request::{FromRequest, Outcome},
+    State, response::stream::{EventStream, Event}, Shutdown,
+    tokio::{sync::broadcast::error::RecvError, select, time::{interval, interval_at, Instant, MissedTickBehavior}},
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{game::{rotate_idle_game_master, shards::ShardedGameManager, GAME_INSTANCE_TIMEOUT}, request_data::{EventData, GameEvent}, authentication::UserAuth, connections::ConnectionTracker, error::ApiError, events::{to_msgpack, EventBus, PublishedEvent}, quickplay::{QuickplayQueue, MATCHER_INTERVAL}, utils::get_gm_read_guard};
+
+/// How often an open stream checks if the game master of its game has idled for too long,
+/// see [rotate_idle_game_master](../../game/fn.rotate_idle_game_master.html).
+pub const MASTER_IDLE_CHECK: Duration = Duration::from_secs(1);
+
+/// Returns the route of the sse stream.
+pub fn routes() -> Vec<Route> {
+    routes![events, quickplay]
+}
+
+/// The reason why the server closed a sse stream.
+///
+/// Send to the client as the last event of the stream by [close_stream]().
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CloseReason {
+    /// The server is shutting down, the client can try to reconnect later.
+    Shutdown,
+    /// The user has opened a newer stream that replaced this one, the client should not reconnect.
+    Replaced,
+    /// The game master removed the user from the game, the client should not reconnect.
+    Kicked,
+    /// The user waited for a seat but the waitlist was closed, the client should not reconnect.
+    WaitlistClosed,
+}
+
+impl CloseReason {
+    /// Checks if the client should try to open a new stream after the stream was closed for this reason.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::Shutdown)
+    }
+}
+
+/// How the events are written into the data field of the [events]() stream, chosen with its `encoding` query parameter.
+///
+/// Both encodings are created from the same [EventData](../../request_data/struct.EventData.html).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EventEncoding {
+    /// Json, used when no or an unknown encoding is requested
+    #[default]
+    Json,
+    /// Base64 encoded msgpack, see [to_msgpack](../../events/fn.to_msgpack.html), decoded by `decode_event` of the wasm module.
+    ///
+    /// The legacy `data` pair is left out, the player list of a full lobby takes 620 instead of 1353 bytes.
+    /// The sse `event` field names the event, so that clients can route it before decoding it.
+    Msgpack,
+}
+
+impl EventEncoding {
+    /// Returns the encoding with the name `encoding`, unknown or missing names fall back to json.
+    pub fn from_query(encoding: Option<&str>) -> Self {
+        match encoding {
+            Some("msgpack") => Self::Msgpack,
+            _ => Self::Json,
+        }
+    }
+
+    /// Creates the sse event for `data`.
+    pub fn event(&self, data: &EventData) -> Event {
+        match self {
+            Self::Json => Event::json(data),
+            Self::Msgpack => Event::data(to_msgpack(data)).event(data.event().name()),
+        }
+    }
+
+    /// Creates the sse event for `msg` with its id, the encoded event is shared with the other streams.
+    pub fn published(&self, msg: &PublishedEvent) -> Event {
+        let event = match self {
+            Self::Json => Event::data(String::from(msg.json())),
+            Self::Msgpack => Event::data(String::from(msg.msgpack())).event(msg.event().name()),
+        };
+        event.id(msg.id().to_string())
+    }
+}
+
+/// Creates the final event that is send before the server closes the stream of the user.
+///
+/// Every path in the [events]() loop on which the server ends the stream has to yield this event before breaking,
+/// so that the client can decide if it should reconnect.
+pub fn close_stream(user_auth: UserAuth, reason: CloseReason, encoding: EventEncoding) -> Event {
+    let event = GameEvent::StreamClosing { reason, retryable: reason.is_retryable() };
+    let data = EventData::new(Some(user_auth.uuid), user_auth.game_code, event)
+        .expect("StreamClosing is a known event with short data");
+    encoding.event(&data)
+}
+
+/// Creates the event that tells a resumed stream that the events it missed are no longer known.
+fn resync(user_auth: UserAuth, encoding: EventEncoding) -> Event {
+    let data = EventData::new(Some(user_auth.uuid), user_auth.game_code, GameEvent::Resync)
+        .expect("Resync is a known event without data");
+    encoding.event(&data)
+}
+
+/// The id of the last event a client received, send by the browser in the `Last-Event-ID` header when it reconnects.
+///
+/// `None` when the header is missing or is not a valid id.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LastEventId(pub Option<u64>);
+
+#[rocket::async_trait]
+impl<'r> FromRequest<'r> for LastEventId {
+    type Error = Infallible;
+
+    async fn from_request(request: &'r Request<'_>) -> Outcome<Self, Self::Error> {
+        Outcome::Success(LastEventId(request.headers().get_one("Last-Event-ID").and_then(|id| id.trim().parse().ok())))
+    }
+}
+
+/// Server send events
+/// 
+/// For each game and user a separate sse stream exists, these streams are accessed by submitting a get request to `/sse/<game_code>/<user_id>`.
+/// 
+/// This makes it possible to have multiple games run in parallel without interferences in the sse streams.
+/// 
+/// The stream subscribes to the [GameChannel](../../events/struct.GameChannel.html) of the game of the user, so it only receives the events of that game.
+/// Targeted events are only transmitted when they match the `user_id`.
+/// 
+/// The number of streams that can be open at the same time is limited by the [ConnectionTracker](../../connections/struct.ConnectionTracker.html),
+/// when a limit is exceeded `429 Too Many Requests` is returned.
+/// 
+/// When the server closes the stream a `StreamClosing` event is send last, see [CloseReason]().
+/// 
+/// With `?encoding=msgpack` the events are send as base64 encoded msgpack instead of json, see [EventEncoding]().
+/// A missing or unknown encoding falls back to json.
+/// 
+/// Each event carries its sequence number in the game as sse id. When a client reconnects with the `Last-Event-ID` header
+/// the events it missed are send before the live events. When they are no longer known a single `Resync` event is send instead
+/// and the client has to fetch the state of the game again, see [GameChannel::resume](../../events/struct.GameChannel.html#method.resume).
+/// 
+/// While the stream is open it checks every [MASTER_IDLE_CHECK]() if the game master has to be rotated.
+/// 
+/// When no event was send for [keep_alive](../../connections/struct.ConnectionTracker.html#method.keep_alive) a `keep-alive` comment is send,
+/// so that proxies do not close quiet streams. Clients ignore comments.
+/// 
+/// Everything the stream sends counts as heartbeat of the user, users whose streams stop sending are disconnected
+/// by [DisconnectDeadStreams](../../connections/struct.DisconnectDeadStreams.html).
+
+// Ranked below the quickplay stream, which uses the same segments
+#[get("/sse/<_>/<user_id>?<encoding>", rank = 2)]
+#[allow(clippy::too_many_arguments)]
+pub fn events<'a>(event: &'a State<EventBus>, game_manager: &'a State<ShardedGameManager>, connections: &'a State<ConnectionTracker>, mut end: Shutdown, user_id: Uuid, encoding: Option<&str>, ip_addr: Option<IpAddr>, last_event_id: LastEventId) -> Result<EventStream![Event + 'a], ApiError> {
+    let encoding = EventEncoding::from_query(encoding);
+    match UserAuth::from_uuid(game_manager, user_id) {
+        Some(user_auth) => {
+            // Subscribed before the user is marked as connected, so that the stream receives the resulting events
+            let (max_players, (mut rx, missed)) = match get_gm_read_guard(game_manager.shard(&user_auth.game_code), "subscribe sse event").game_by_code_read(user_auth.game_code) {
+                Some(game) => (game.settings().max_players(), match last_event_id.0 {
+                    Some(last_id) => game.channel().resume(last_id),
+                    None => (game.channel().subscribe(), Some(Vec::new())),
+                }),
+                None => return Err(ApiError::not_found("game_not_found")),
+            };
+            let slot = connections.open(user_id, ip_addr, user_auth.game_code, max_players)?;
+            // Mark user as connected
+            let events = get_gm_read_guard(game_manager.shard(&user_auth.game_code), "Set user connected").user_connected(user_auth);
+            if let Some(events) = events {
+                events.publish(event);
+            }
+            Ok(EventStream! {
+                // The slot is freed when the stream is dropped
+                let slot = slot;
+                let period = connections.keep_alive();
+                let mut keep_alive = interval_at(Instant::now() + period, period);
+                keep_alive.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                let mut master_idle = interval_at(Instant::now() + MASTER_IDLE_CHECK, MASTER_IDLE_CHECK);
+                master_idle.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                if missed.is_none() {
+                    info!("Events missed by user {} are no longer known, requesting resync", user_id);
+                    yield resync(user_auth, encoding);
+                }
+                // Send before the events that are received live
+                let mut missed = VecDeque::from(missed.unwrap_or_default());
+                loop {
+                    let msg = match missed.pop_front() {
+                        Some(msg) => msg,
+                        None => select! {
+                            msg = rx.recv() => match msg {
+                                Ok(msg) => msg,
+                                Err(RecvError::Closed) => {
+                                    info!("User disconnected {}", user_id);
+                                    game_manager.connection_lost(user_auth, event, GAME_INSTANCE_TIMEOUT);
+                                    break
+                                },
+                                Err(RecvError::Lagged(_)) => continue,
+                            },
+                            _ = &mut end => {
+                                info!("End: User disconnected {}", user_id);
+                                yield close_stream(user_auth, CloseReason::Shutdown, encoding);
+                                break
+                            },
+                            _ = slot.replaced() => {
+                                info!("Stream of user {} was replaced by a newer stream", user_id);
+                                yield close_stream(user_auth, CloseReason::Replaced, encoding);
+                                break
+                            },
+                            _ = keep_alive.tick() => {
+                                slot.touch();
+                                yield Event::comment("keep-alive");
+                                continue
+                            },
+                            _ = master_idle.tick() => {
+                                if let Some(events) = rotate_idle_game_master(game_manager.shard(&user_auth.game_code), user_auth.game_code) {
+                                    events.publish(event);
+                                }
+                                continue
+                            },
+                        },
+                    };
+                    if msg.is_for(user_auth.game_code, user_id) {
+                        // A keep-alive is only needed when nothing else was send
+                        keep_alive.reset();
+                        slot.touch();
+                        // The event was serialized once by the bus, only the encoded event is copied for each stream
+                        yield encoding.published(&msg);
+                        if matches!(msg.event(), GameEvent::Kicked) && msg.recipient().is_some() {
+                            info!("User {} was kicked, closing stream", user_id);
+                            yield close_stream(user_auth, CloseReason::Kicked, encoding);
+                            break
+                        }
+                        if matches!(msg.event(), GameEvent::WaitlistClosed) && msg.recipient().is_some() {
+                            info!("Waitlist of user {} was closed, closing stream", user_id);
+                            yield close_stream(user_auth, CloseReason::WaitlistClosed, encoding);
+                            break
+                        }
+                    }
+                }
+            }.heartbeat(None))
+        },
+        None => Err(ApiError::not_found("user_not_found")),
+    }
+}
+
+/// Stream on which a user that waits in the [QuickplayQueue](../../quickplay/struct.QuickplayQueue.html) is told the game they where matched into.
+/// 
+/// While the stream is open the matcher runs every [MATCHER_INTERVAL](../../quickplay/constant.MATCHER_INTERVAL.html).
+/// When the game was created the targeted event `QuickplayMatched` containing the [UserRegistration](../../request_data/struct.UserRegistration.html)
+/// is send and the stream ends, the client then continues like after [join_game](../lobby_api/fn.join_game.html).
+/// The stream also ends without an event when the ticket was cancelled.
+/// 
+/// `404 Not Found` is returned when the ticket is not known.
+#[get("/sse/quickplay/<ticket>")]
+pub fn quickplay<'a>(queue: &'a State<QuickplayQueue>, game_manager: &'a State<ShardedGameManager>, event: &'a State<EventBus>, mut end: Shutdown, ticket: Uuid) -> Result<EventStream![Event + 'a], ApiError> {
+    if !queue.is_known(ticket) {
+        return Err(ApiError::not_found("ticket_not_found"));
+    }
+    Ok(EventStream! {
+        let mut matcher = interval(MATCHER_INTERVAL);
+        loop {
+            if let Some(found) = queue.take_match(ticket) {
+                let uuid = found.registration.uuid();
+                let data = EventData::new(Some(uuid), found.game_code, GameEvent::QuickplayMatched(found.registration))
+                    .expect("QuickplayMatched is a known event with short data");
+                yield Event::json(&data);
+                break
+            }
+            if !queue.is_queued(ticket) {
+                info!("Quickplay ticket {} was cancelled", ticket);
+                break
+            }
+            select! {
+                _ = matcher.tick() => {
+                    queue.run_matcher(game_manager, event);
+                },
+                _ = queue.wait_for_match() => (),
+                _ = &mut end => break,
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Read, thread, time::{Duration, Instant}};
+
+    use rocket::{
+        http::{ContentType, Header, Status},
+        local::blocking::Client,
+        serde::json::Value,
+    };
+    use uuid::Uuid;
+
+    use crate::{connections::ConnectionTracker, events::{from_msgpack, EventBatch, EventBus, GameChannel}, game::{game_instance::GameCode, shards::ShardedGameManager}, paths::test_utils::{create_game, join_game, user_id}, request_data::GameEvent};
+
+    fn client(keep_alive_ms: u64) -> Client {
+        let figment = rocket::Config::figment()
+            .merge(("sse_keep_alive_ms", keep_alive_ms))
+            .merge(("event_coalesce_window_ms", 0));
+        Client::tracked(crate::server(rocket::custom(figment))).unwrap()
+    }
+
+    /// Reads from `stream` until `frame` was received.
+    /// 
+    /// # Returns
+    /// The time at which the frame was read and everything that was read before it.
+    fn read_until(stream: &mut impl Read, buf: &mut String, frame: &str) -> (Instant, String) {
+        let mut chunk = [0; 1024];
+        loop {
+            if let Some(pos) = buf.find(frame) {
+                let before = buf[..pos].to_string();
+                buf.drain(..pos + frame.len());
+                return (Instant::now(), before);
+            }
+            let read = stream.read(&mut chunk).unwrap();
+            assert!(read > 0, "stream ended before {} was received", frame);
+            buf.push_str(&String::from_utf8_lossy(&chunk[..read]));
+        }
+    }
+
+    /// Returns the event channel of the game of `registration`.
+    fn channel(client: &Client, registration: &Value) -> GameChannel {
+        let game_code = GameCode::from_string(registration["game_code"].as_str().unwrap()).unwrap();
+        let game_manager = client.rocket().state::<ShardedGameManager>().unwrap();
+        let channel = game_manager.shard(&game_code).read().unwrap().game_by_code_read(game_code).unwrap().channel().clone();
+        channel
+    }
+
+    #[test]
+    fn test_keep_alive_on_quiet_stream() {
+        let client = client(100);
+        let registration = create_game(&client);
+        let path = format!("/sse/{}/{}", registration["game_code"].as_str().unwrap(), registration["uuid"].as_str().unwrap());
+        let start = Instant::now();
+        let mut stream = client.get(path).dispatch();
+        let mut buf = String::new();
+        read_until(&mut stream, &mut buf, "keep-alive");
+        let (second, _) = read_until(&mut stream, &mut buf, "keep-alive");
+        assert!(second - start >= Duration::from_millis(190), "{:?}", second - start);
+        assert!(second - start < Duration::from_secs(2), "{:?}", second - start);
+        let uuid = Uuid::parse_str(registration["uuid"].as_str().unwrap()).unwrap();
+        assert!(client.rocket().state::<ConnectionTracker>().unwrap().last_seen(uuid).unwrap() > start);
+    }
+
+    #[test]
+    fn test_events_postpone_keep_alive() {
+        let client = client(300);
+        let registration = create_game(&client);
+        let path = format!("/sse/{}/{}", registration["game_code"].as_str().unwrap(), registration["uuid"].as_str().unwrap());
+        let mut stream = client.get(path).dispatch();
+        let mut buf = String::new();
+        read_until(&mut stream, &mut buf, "LobbyStatus");
+        thread::sleep(Duration::from_millis(150));
+        let mut events = EventBatch::new(&channel(&client, &registration));
+        events.push(GameEvent::LobbyLocked);
+        events.publish(client.rocket().state::<EventBus>().unwrap());
+        let (event, before) = read_until(&mut stream, &mut buf, "LobbyLocked");
+        assert!(!before.contains("keep-alive"));
+        // without the event the keep-alive would be send 150ms later
+        let (keep_alive, _) = read_until(&mut stream, &mut buf, "keep-alive");
+        assert!(keep_alive - event >= Duration::from_millis(250), "{:?}", keep_alive - event);
+    }
+
+    #[test]
+    fn test_dead_stream_is_disconnected() {
+        let client = client(100);
+        let registration = create_game(&client);
+        let path = format!("/sse/{}/{}", registration["game_code"].as_str().unwrap(), registration["uuid"].as_str().unwrap());
+        let connected = || {
+            let game_code = GameCode::from_string(registration["game_code"].as_str().unwrap()).unwrap();
+            let game_manager = client.rocket().state::<ShardedGameManager>().unwrap();
+            let connected = game_manager.shard(&game_code).read().unwrap().game_by_code_read(game_code).unwrap().connected_players();
+            connected
+        };
+        let mut stream = client.get(path).dispatch();
+        let mut buf = String::new();
+        // a stream that keeps sending stays connected
+        for _ in 0..5 {
+            read_until(&mut stream, &mut buf, "keep-alive");
+        }
+        assert_eq!(1, connected());
+        // the stream is no longer read, like the stream of a client that is gone
+        let start = Instant::now();
+        while connected() == 1 {
+            assert!(start.elapsed() < Duration::from_secs(2), "the dead stream was not disconnected");
+            thread::sleep(Duration::from_millis(20));
+        }
+        assert!(start.elapsed() >= Duration::from_millis(100), "{:?}", start.elapsed());
+    }
+
+    #[test]
+    fn test_streams_only_receive_events_of_their_game() {
+        let figment = rocket::Config::figment().merge(("event_coalesce_window_ms", 0));
+        let client = Client::tracked(crate::server(rocket::custom(figment))).unwrap();
+        let (registration, other_game) = (create_game(&client), create_game(&client));
+        let bus = client.rocket().state::<EventBus>().unwrap();
+        let path = format!("/sse/{}/{}", registration["game_code"].as_str().unwrap(), registration["uuid"].as_str().unwrap());
+        let mut stream = client.get(path).dispatch();
+        let mut buf = String::new();
+        read_until(&mut stream, &mut buf, "LobbyStatus");
+        assert_eq!(1, channel(&client, &registration).receiver_count());
+        assert_eq!(0, channel(&client, &other_game).receiver_count());
+        let mut events = EventBatch::new(&channel(&client, &other_game));
+        events.push(GameEvent::LobbyLocked);
+        events.publish(bus);
+        let mut events = EventBatch::new(&channel(&client, &registration));
+        events.push(GameEvent::LobbyUnlocked);
+        events.publish(bus);
+        let (_, before) = read_until(&mut stream, &mut buf, "LobbyUnlocked");
+        assert!(!before.contains("LobbyLocked"), "{}", before);
+    }
+
+    /// Returns the sse id and the event type of each complete event in `frames`.
+    fn ids_and_types(frames: &str) -> Vec<(Option<u64>, String)> {
+        frames.split("\n\n")
+            .filter_map(|frame| {
+                let data = frame.lines().find_map(|line| line.strip_prefix("data:"))?;
+                let id = frame.lines().find_map(|line| line.strip_prefix("id:")).map(|id| id.trim().parse().unwrap());
+                // The last frame is cut off when it was read up to an event name
+                let data: Value = rocket::serde::json::from_str(data).ok()?;
+                Some((id, String::from(data["event"]["type"].as_str().unwrap())))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_msgpack_encoding() {
+        let client = client(60_000);
+        let registration = create_game(&client);
+        let path = format!("/sse/{}/{}", registration["game_code"].as_str().unwrap(), registration["uuid"].as_str().unwrap());
+        let mut stream = client.get(format!("{}?encoding=msgpack", path)).dispatch();
+        let mut buf = String::new();
+        // the name of the event is send in plain text before the encoded data
+        read_until(&mut stream, &mut buf, "event:LobbyStatus\n");
+        let (_, frame) = read_until(&mut stream, &mut buf, "\n\n");
+        let event = from_msgpack(frame.strip_prefix("data:").unwrap()).unwrap();
+        assert_eq!("LobbyStatus", event["event"]["type"]);
+        assert!(event.get("data").is_none());
+        drop(stream);
+
+        // missing and unknown encodings fall back to json
+        for path in [path.clone(), format!("{}?encoding=xml", path)] {
+            let mut stream = client.get(path).dispatch();
+            let mut buf = String::new();
+            let (_, before) = read_until(&mut stream, &mut buf, "LobbyStatus");
+            assert!(before.lines().last().unwrap().starts_with("data:{") && !before.contains("event:"), "{}", before);
+        }
+    }
+
+    #[test]
+    fn test_resume_with_last_event_id() {
+        let client = client(60_000);
+        let registration = create_game(&client);
+        let path = format!("/sse/{}/{}", registration["game_code"].as_str().unwrap(), registration["uuid"].as_str().unwrap());
+        let bus = client.rocket().state::<EventBus>().unwrap();
+        let mut stream = client.get(path.clone()).dispatch();
+        let mut buf = String::new();
+        let (_, before) = read_until(&mut stream, &mut buf, "LobbyStatus");
+        // the id of the event is send before its data
+        let last_id: u64 = before.lines().rev().find_map(|line| line.strip_prefix("id:")).unwrap().trim().parse().unwrap();
+        // the client loses the connection
+        drop(stream);
+        let mut events = EventBatch::new(&channel(&client, &registration));
+        for text in ["1", "2", "3"] {
+            events.push(GameEvent::LobbyMessage(String::from(text)));
+        }
+        events.publish(bus);
+
+        let mut stream = client.get(path.clone()).header(Header::new("Last-Event-ID", last_id.to_string())).dispatch();
+        let mut buf = String::new();
+        let mut events = EventBatch::new(&channel(&client, &registration));
+        events.push(GameEvent::LobbyLocked);
+        events.publish(bus);
+        let (_, before) = read_until(&mut stream, &mut buf, "LobbyLocked");
+        let received = ids_and_types(&before);
+        let messages = (1..=3).map(|i| (Some(last_id + i), String::from("LobbyMessage"))).collect::<Vec<_>>();
+        assert_eq!(messages, received[..3]);
+        // the events caused by the reconnect are send live after the missed events
+        assert!(received[3..].iter().all(|(id, event)| id.unwrap() > last_id + 3 && event != "LobbyMessage"), "{:?}", received);
+        drop(stream);
+
+        // the client has to fetch the state again when the events it missed are not known
+        let mut stream = client.get(path).header(Header::new("Last-Event-ID", "100000")).dispatch();
+        let mut buf = String::new();
+        let (_, before) = read_until(&mut stream, &mut buf, "\n\n");
+        assert_eq!(vec![(None, String::from("Resync"))], ids_and_types(&before));
+    }
+
+    #[test]
+    fn test_closing_event_on_shutdown() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        let registration = create_game(&client);
+        let path = format!("/sse/{}/{}", registration["game_code"].as_str().unwrap(), registration["uuid"].as_str().unwrap());
+        let stream = client.get(path).dispatch();
+        client.rocket().shutdown().notify();
+        let body = stream.into_string().unwrap();
+        let last = body.lines().rev().find(|line| line.starts_with("data:")).unwrap();
+        let last: Value = rocket::serde::json::from_str(last.trim_start_matches("data:")).unwrap();
+        assert_eq!("StreamClosing", last["data"][0]);
+        assert_eq!(r#"{"reason":"shutdown","retryable":true}"#, last["data"][1]);
+    }
+
+    #[test]
+    fn test_closing_event_on_kick() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        let game_master = create_game(&client);
+        let player = join_game(&client, &game_master);
+        let path = format!("/sse/{}/{}", player["game_code"].as_str().unwrap(), player["uuid"].as_str().unwrap());
+        let stream = client.get(path).dispatch();
+        let response = client.post("/api/lobby_admin")
+            .header(user_id(&game_master))
+            .header(ContentType::JSON)
+            .body(r#"{"kick":[2]}"#)
+            .dispatch();
+        assert_eq!(Status::Ok, response.status());
+        // the stream ends after the closing event
+        let body = stream.into_string().unwrap();
+        let last = body.lines().rev().find(|line| line.starts_with("data:")).unwrap();
+        let last: Value = rocket::serde::json::from_str(last.trim_start_matches("data:")).unwrap();
+        assert_eq!("StreamClosing", last["data"][0]);
+        assert_eq!(r#"{"reason":"kicked","retryable":false}"#, last["data"][1]);
+    }
+
+    #[test]
+    fn test_quickplay_stream() {
+        let figment = rocket::Config::figment().merge(("quickplay_target_size", 2));
+        let client = Client::tracked(crate::server(rocket::custom(figment))).unwrap();
+        let queue = |username: &str| -> Value {
+            client.post("/api/quickplay").header(ContentType::JSON).body(format!(r#"{{"username":"{}"}}"#, username)).dispatch().into_json().unwrap()
+        };
+        assert_eq!(Status::NotFound, client.get(format!("/sse/quickplay/{}", Uuid::new_v4())).dispatch().status());
+        let first = queue("a");
+        let second = queue("b");
+        let body = client.get(format!("/sse/quickplay/{}", first["ticket"].as_str().unwrap())).dispatch().into_string().unwrap();
+        let data = body.lines().find(|line| line.starts_with("data:")).unwrap();
+        let data: Value = rocket::serde::json::from_str(data.trim_start_matches("data:")).unwrap();
+        assert_eq!("QuickplayMatched", data["data"][0]);
+        let registration: Value = rocket::serde::json::from_str(data["data"][1].as_str().unwrap()).unwrap();
+        assert_eq!(registration["uuid"], data["user_id"]);
+        let game_code = GameCode::from_string(registration["game_code"].as_str().unwrap()).unwrap();
+        let game_manager = client.rocket().state::<ShardedGameManager>().unwrap();
+        assert_eq!(2, game_manager.shard(&game_code).read().unwrap().game_by_code_read(game_code).unwrap().players().len());
+        // matched tickets can no longer be cancelled
+        assert_eq!(Status::NotFound, client.delete(format!("/api/quickplay/{}", second["ticket"].as_str().unwrap())).dispatch().status());
+    }
+
+    #[test]
+    fn test_stream_limit_per_user() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        let registration = create_game(&client);
+        let path = format!("/sse/{}/{}", registration["game_code"].as_str().unwrap(), registration["uuid"].as_str().unwrap());
+        let first = client.get(path.clone()).dispatch();
+        let second = client.get(path.clone()).dispatch();
+        assert_eq!(Status::Ok, first.status());
+        assert_eq!(Status::Ok, second.status());
+        let rejected = client.get(path.clone()).dispatch();
+        assert_eq!(Status::TooManyRequests, rejected.status());
+        assert_eq!("too_many_streams_user", rejected.into_json::<Value>().unwrap()["error"]);
+        // closing a stream frees the slot for a reconnect
+        drop(first);
+        assert_eq!(Status::Ok, client.get(path).dispatch().status());
+    }
+}
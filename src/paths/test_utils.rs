@@ -0,0 +1,65 @@
+use rocket::{
+    http::{ContentType, Header, Status},
+    local::blocking::{Client, LocalResponse},
+    serde::json::Value,
+};
+
+/// Creates a new game and returns the registration of the game master.
+pub fn create_game(client: &Client) -> Value {
+    client.post("/api/create_game").header(ContentType::JSON).body(r#"{"username":"gm"}"#).dispatch().into_json().unwrap()
+}
+
+/// Joins the game of `registration` with a second player and returns their registration.
+pub fn join_game(client: &Client, registration: &Value) -> Value {
+    join_game_as(client, registration, "player").into_json().unwrap()
+}
+
+/// Sends a request to join the game of `registration` as `username`.
+pub fn join_game_as<'c>(client: &'c Client, registration: &Value, username: &str) -> LocalResponse<'c> {
+    client.post("/api/join_game")
+        .header(Header::new("game_code", String::from(registration["game_code"].as_str().unwrap())))
+        .header(ContentType::JSON)
+        .body(format!(r#"{{"username":"{}"}}"#, username))
+        .dispatch()
+}
+
+/// Opens the sse stream for the user, this marks the user as connected.
+pub fn connect(client: &Client, registration: &Value) {
+    let path = format!("/sse/{}/{}", registration["game_code"].as_str().unwrap(), registration["uuid"].as_str().unwrap());
+    assert_eq!(Status::Ok, client.get(path).dispatch().status());
+}
+
+/// Returns the `user_id` header that authenticates the user of `registration`.
+pub fn user_id(registration: &Value) -> Header<'static> {
+    Header::new("user_id", String::from(registration["uuid"].as_str().unwrap()))
+}
+
+/// Creates a game with a second player, connects both and starts the game.
+///
+/// # Returns
+/// The registrations of the game master and of the player.
+pub fn started_game(client: &Client) -> (Value, Value) {
+    let (game_master, player) = lobby(client);
+    assert_eq!(Status::Ok, client.post("/api/start_game").header(user_id(&game_master)).dispatch().status());
+    (game_master, player)
+}
+
+/// Creates a game with a second player and connects both, the game is not started.
+///
+/// # Returns
+/// The registrations of the game master and of the player.
+pub fn lobby(client: &Client) -> (Value, Value) {
+    let game_master = create_game(client);
+    let player = join_game(client, &game_master);
+    for registration in [&game_master, &player] {
+        connect(client, registration);
+    }
+    (game_master, player)
+}
+
+/// Returns the `turn_token` that the next gameplay request of the user of `registration` has to send,
+/// the nil uuid while the game is not running.
+pub fn turn_token(client: &Client, registration: &Value) -> String {
+    let turn: Value = client.get("/api/turn").header(user_id(registration)).dispatch().into_json().unwrap();
+    String::from(turn["turn_token"].as_str().unwrap_or("00000000-0000-0000-0000-000000000000"))
+}
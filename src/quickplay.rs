@@ -0,0 +1,280 @@
+use std::{collections::{HashMap, HashSet, VecDeque}, net::IpAddr, sync::Mutex, time::{Duration, Instant}};
+
+use rocket::{log::private::{error, info}, tokio::sync::Notify};
+use uuid::Uuid;
+
+use crate::{authentication::UserAuth, events::EventBus, game::{shards::ShardedGameManager, game_instance::{GameCode, MAX_PLAYERS, MIN_PLAYERS}}, request_data::UserRegistration, utils::get_gm_read_guard};
+
+/// The number of players with which a game is started when it is not set in the configuration.
+pub const DEFAULT_TARGET_SIZE: usize = 4;
+
+/// The time after which a game is started with fewer players when it is not set in the configuration.
+pub const DEFAULT_MAX_WAIT: Duration = Duration::from_secs(60);
+
+/// How often the matcher runs while a ticket waits for its game, see [QuickplayQueue::run_matcher]().
+pub const MATCHER_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The [master_idle_rotate_secs](../game/game_instance/struct.LobbySettings.html#method.master_idle_rotate_secs) of quickplay games,
+/// players that do not know each other can not ask the game master to start.
+pub const MASTER_IDLE_ROTATE_SECS: u64 = 120;
+
+/// How long a match is kept when the stream of its ticket does not pick it up.
+const MATCH_RETENTION: Duration = Duration::from_secs(60);
+
+/// A user that waits for a game.
+struct QueueEntry {
+    ticket: Uuid,
+    username: String,
+    ip_addr: Option<IpAddr>,
+    queued_at: Instant,
+}
+
+/// The game that was created for a ticket.
+pub struct QuickplayMatch {
+    pub game_code: GameCode,
+    /// The registration of the user in the new game, used like the response of [join_game](../paths/lobby_api/fn.join_game.html)
+    pub registration: UserRegistration,
+}
+
+#[derive(Default)]
+struct QueueState {
+    /// The waiting users, the user that waits the longest first
+    entries: VecDeque<QueueEntry>,
+    /// Matches that have not been picked up yet and the time at which they where made
+    matches: HashMap<Uuid, (QuickplayMatch, Instant)>,
+}
+
+/// The queue of users that want to be matched with other users into a new game, managed by rocket.
+///
+/// Users are put into the queue with [quickplay](../paths/lobby_api/fn.quickplay.html) and receive a ticket.
+/// [run_matcher](#method.run_matcher) groups the waiting users into games of the target size or, when the user that waits the longest
+/// has waited for the maximum wait time, into a game with all waiting users (at least [MIN_PLAYERS]()).
+///
+/// The matcher runs whenever a user is queued and periodically while the [quickplay stream](../paths/sse/fn.quickplay.html)
+/// of a ticket is open, the stream then sends the registration for the new game.
+///
+/// The target size and the maximum wait time can be set with `quickplay_target_size` and `quickplay_max_wait_ms`
+/// in the rocket configuration, see [DEFAULT_TARGET_SIZE]() and [DEFAULT_MAX_WAIT]().
+pub struct QuickplayQueue {
+    target_size: usize,
+    max_wait: Duration,
+    state: Mutex<QueueState>,
+    /// Wakes the waiting streams when new matches where made
+    matched: Notify,
+}
+
+impl QuickplayQueue {
+    /// Creates a new empty queue, `target_size` is limited to the allowed number of players of a game.
+    pub fn new(target_size: usize, max_wait: Duration) -> Self {
+        Self {
+            target_size: target_size.clamp(MIN_PLAYERS, MAX_PLAYERS),
+            max_wait,
+            state: Mutex::new(QueueState::default()),
+            matched: Notify::new(),
+        }
+    }
+
+    /// Puts the user into the queue.
+    ///
+    /// # Returns
+    /// The ticket with which the user can wait for the game or cancel waiting.
+    pub fn enqueue(&self, username: String, ip_addr: Option<IpAddr>) -> Uuid {
+        let ticket = Uuid::new_v4();
+        self.state.lock().unwrap().entries.push_back(QueueEntry { ticket, username, ip_addr, queued_at: Instant::now() });
+        ticket
+    }
+
+    /// Removes the ticket from the queue.
+    ///
+    /// # Returns
+    /// `false` when the ticket is not waiting, for example because it was already matched.
+    pub fn cancel(&self, ticket: Uuid) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let len = state.entries.len();
+        state.entries.retain(|entry| entry.ticket != ticket);
+        state.entries.len() != len
+    }
+
+    /// Checks if the ticket is still waiting for a game.
+    pub fn is_queued(&self, ticket: Uuid) -> bool {
+        self.state.lock().unwrap().entries.iter().any(|entry| entry.ticket == ticket)
+    }
+
+    /// Checks if the ticket is waiting or was matched and the match was not picked up yet.
+    pub fn is_known(&self, ticket: Uuid) -> bool {
+        self.is_queued(ticket) || self.state.lock().unwrap().matches.contains_key(&ticket)
+    }
+
+    /// Removes and returns the match of the ticket if one was made.
+    pub fn take_match(&self, ticket: Uuid) -> Option<QuickplayMatch> {
+        self.state.lock().unwrap().matches.remove(&ticket).map(|(found, _)| found)
+    }
+
+    /// Waits until the matcher made new matches.
+    pub async fn wait_for_match(&self) {
+        self.matched.notified().await;
+    }
+
+    /// Groups the waiting users into new games.
+    ///
+    /// The queue is locked the whole time, so a ticket is either cancelled or matched but never both.
+    ///
+    /// # Returns
+    /// The number of games that where created.
+    pub fn run_matcher(&self, game_manager: &ShardedGameManager, event: &EventBus) -> usize {
+        let mut state = self.state.lock().unwrap();
+        state.matches.retain(|_, (_, matched_at)| matched_at.elapsed() < MATCH_RETENTION);
+        let mut groups = Vec::new();
+        while state.entries.len() >= self.target_size {
+            groups.push(state.entries.drain(..self.target_size).collect::<Vec<_>>());
+        }
+        let waited_long_enough = state.entries.front().is_some_and(|entry| entry.queued_at.elapsed() >= self.max_wait);
+        if waited_long_enough && state.entries.len() >= MIN_PLAYERS {
+            groups.push(state.entries.drain(..).collect());
+        }
+        let games = groups.len();
+        for group in groups {
+            for (ticket, found) in start_game(game_manager, event, group) {
+                state.matches.insert(ticket, (found, Instant::now()));
+            }
+        }
+        if games > 0 {
+            self.matched.notify_waiters();
+        }
+        games
+    }
+}
+
+/// Creates a game for the users of `group`, the first user becomes the game master.
+///
+/// Users with the same name are renamed by adding a number, users that can not join are logged and dropped.
+fn start_game(game_manager: &ShardedGameManager, event: &EventBus, group: Vec<QueueEntry>) -> Vec<(Uuid, QuickplayMatch)> {
+    let mut names = HashSet::new();
+    let mut matches = Vec::new();
+    let mut game_code = None;
+    for entry in group {
+        let username = unique_name(entry.username, &names);
+        names.insert(username.clone());
+        let registration = match game_code {
+            None => game_manager.create_game(username, entry.ip_addr, None).inspect(|registration| {
+                if let Some(user_auth) = UserAuth::from_uuid(game_manager, registration.uuid()) {
+                    let shard = get_gm_read_guard(game_manager.shard(&user_auth.game_code), "quickplay settings");
+                    if let Some(mut game) = shard.game_by_code_write(user_auth.game_code) {
+                        game.set_master_idle_rotate_secs(MASTER_IDLE_ROTATE_SECS);
+                    };
+                }
+            }),
+            Some(game_code) => match game_manager.add_player_to_game(game_code, username, None, entry.ip_addr, None) {
+                Ok((registration, events)) => {
+                    events.publish(event);
+                    Some(registration)
+                },
+                Err(err) => {
+                    error!("Quickplay ticket {} could not join game {}: {}", entry.ticket, game_code, err);
+                    None
+                },
+            },
+        };
+        if let Some(registration) = registration {
+            if let Some(user_auth) = UserAuth::from_uuid(game_manager, registration.uuid()) {
+                game_code = Some(user_auth.game_code);
+                matches.push((entry.ticket, QuickplayMatch { game_code: user_auth.game_code, registration }));
+            }
+        }
+    }
+    if let Some(game_code) = game_code {
+        info!("Quickplay started game {} with {} players", game_code, matches.len());
+    }
+    matches
+}
+
+/// Returns `name` or, when it is already in `names`, `name` followed by the lowest free number.
+fn unique_name(name: String, names: &HashSet<String>) -> String {
+    if !names.contains(&name) {
+        return name;
+    }
+    (2..).map(|number| format!("{} {}", name, number)).find(|name| !names.contains(name)).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use crate::{events::EventBus, game::{game_instance::GameCode, shards::ShardedGameManager}};
+
+    use super::{QuickplayQueue, DEFAULT_MAX_WAIT};
+
+    /// Returns the names of all players in `game_code`.
+    fn names(game_manager: &ShardedGameManager, game_code: GameCode) -> Vec<String> {
+        let shard = game_manager.shard(&game_code).read().unwrap();
+        let game = shard.game_by_code_read(game_code).unwrap();
+        game.player_list().into_iter().map(|player| player.name).collect()
+    }
+
+    /// Returns the names of the players in the game of the match of `ticket`.
+    fn players(game_manager: &ShardedGameManager, queue: &QuickplayQueue, ticket: uuid::Uuid) -> Vec<String> {
+        names(game_manager, queue.take_match(ticket).unwrap().game_code)
+    }
+
+    #[test]
+    fn test_match_at_target_size() {
+        let (game_manager, event) = (ShardedGameManager::new(1), EventBus::new(Duration::ZERO));
+        let queue = QuickplayQueue::new(3, DEFAULT_MAX_WAIT);
+        let tickets: Vec<_> = ["a", "b", "a", "c"].iter().map(|name| queue.enqueue(String::from(*name), None)).collect();
+        assert_eq!(1, queue.run_matcher(&game_manager, &event));
+        assert_eq!(vec!["a", "b", "a 2"], players(&game_manager, &queue, tickets[0]));
+        assert!(queue.take_match(tickets[1]).is_some());
+        assert!(queue.take_match(tickets[3]).is_none());
+        assert!(queue.is_queued(tickets[3]));
+        assert_eq!(1, game_manager.game_codes().len());
+    }
+
+    #[test]
+    fn test_partial_match_after_max_wait() {
+        let (game_manager, event) = (ShardedGameManager::new(1), EventBus::new(Duration::ZERO));
+        let queue = QuickplayQueue::new(4, Duration::from_millis(50));
+        let first = queue.enqueue(String::from("a"), None);
+        assert_eq!(0, queue.run_matcher(&game_manager, &event));
+        thread::sleep(Duration::from_millis(60));
+        // a single user is never matched
+        assert_eq!(0, queue.run_matcher(&game_manager, &event));
+        queue.enqueue(String::from("b"), None);
+        queue.enqueue(String::from("c"), None);
+        assert_eq!(1, queue.run_matcher(&game_manager, &event));
+        assert_eq!(vec!["a", "b", "c"], players(&game_manager, &queue, first));
+    }
+
+    #[test]
+    fn test_cancel() {
+        let (game_manager, event) = (ShardedGameManager::new(1), EventBus::new(Duration::ZERO));
+        let queue = QuickplayQueue::new(2, DEFAULT_MAX_WAIT);
+        let ticket = queue.enqueue(String::from("a"), None);
+        assert!(queue.cancel(ticket));
+        assert!(!queue.is_known(ticket));
+        assert!(!queue.cancel(ticket));
+        queue.enqueue(String::from("b"), None);
+        assert_eq!(0, queue.run_matcher(&game_manager, &event));
+    }
+
+    #[test]
+    fn test_cancel_matcher_race() {
+        for _ in 0..50 {
+            let (game_manager, event) = (ShardedGameManager::new(1), EventBus::new(Duration::ZERO));
+            let queue = QuickplayQueue::new(2, DEFAULT_MAX_WAIT);
+            let tickets: Vec<_> = (0..4).map(|i| queue.enqueue(format!("player {}", i), None)).collect();
+            let cancelled: Vec<bool> = thread::scope(|scope| {
+                let cancel = scope.spawn(|| tickets.iter().map(|ticket| queue.cancel(*ticket)).collect());
+                scope.spawn(|| queue.run_matcher(&game_manager, &event));
+                cancel.join().unwrap()
+            });
+            let matched: Vec<bool> = tickets.iter().map(|ticket| queue.take_match(*ticket).is_some()).collect();
+            for (cancelled, matched) in cancelled.iter().zip(&matched) {
+                assert!(cancelled != matched, "a ticket has to be either cancelled or matched");
+            }
+            let registered: usize = game_manager.game_codes().iter()
+                .map(|game_code| names(&game_manager, *game_code).len())
+                .sum();
+            assert_eq!(matched.iter().filter(|matched| **matched).count(), registered);
+        }
+    }
+}
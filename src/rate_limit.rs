@@ -0,0 +1,233 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    net::IpAddr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+use rand::{thread_rng, Rng};
+use serde::Deserialize;
+
+/// Limits for guessing game codes.
+///
+/// Without these limits the existing game codes could be found by trying many codes or by measuring
+/// how long it takes until a request is rejected, and these lobbies could then be spammed.
+///
+/// The limits can be changed in the `code_guess_limits` table of the rocket configuration, for example in `Rocket.toml`:
+///
+/// ```toml
+/// [default.code_guess_limits]
+/// max_failures = 50
+/// failure_delay = false
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct CodeGuessLimits {
+    /// Failed code lookups per client ip address within [window_secs](#structfield.window_secs) after which further lookups are rejected
+    pub max_failures: usize,
+    pub window_secs: u64,
+    /// When enabled failed code lookups and rejected joins are delayed by a random time
+    /// between [min_delay_ms](#structfield.min_delay_ms) and [max_delay_ms](#structfield.max_delay_ms),
+    /// so that they take about as long as the lookup of an existing game
+    pub failure_delay: bool,
+    pub min_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+impl Default for CodeGuessLimits {
+    fn default() -> Self {
+        Self {
+            max_failures: 20,
+            window_secs: 600,
+            failure_delay: true,
+            min_delay_ms: 50,
+            max_delay_ms: 150,
+        }
+    }
+}
+
+/// Counts the failed game code lookups per client ip address, managed by rocket.
+///
+/// Used by the [GameCode](../game/game_instance/struct.GameCode.html) request guard.
+pub struct CodeGuessLimiter {
+    limits: CodeGuessLimits,
+    failures: Mutex<Failures>,
+}
+
+struct Failures {
+    /// The times of the failed lookups within the window, per ip address
+    by_ip: HashMap<IpAddr, VecDeque<Instant>>,
+    last_prune: Instant,
+}
+
+impl CodeGuessLimiter {
+    pub fn new(limits: CodeGuessLimits) -> Self {
+        Self {
+            limits,
+            failures: Mutex::new(Failures { by_ip: HashMap::new(), last_prune: Instant::now() }),
+        }
+    }
+
+    /// Checks if the ip address has failed too many lookups within the window.
+    ///
+    /// Requests without ip address are never locked out.
+    pub fn is_locked(&self, ip_addr: Option<IpAddr>) -> bool {
+        self.is_locked_at(ip_addr, Instant::now())
+    }
+
+    /// Counts a failed lookup of the ip address, requests without ip address are not counted.
+    pub fn record_failure(&self, ip_addr: Option<IpAddr>) {
+        self.record_failure_at(ip_addr, Instant::now());
+    }
+
+    /// Returns the time by which a failed request should be delayed, see [CodeGuessLimits::failure_delay]().
+    pub fn failure_delay(&self) -> Duration {
+        if !self.limits.failure_delay {
+            return Duration::ZERO;
+        }
+        let min = self.limits.min_delay_ms.min(self.limits.max_delay_ms);
+        Duration::from_millis(thread_rng().gen_range(min..=self.limits.max_delay_ms))
+    }
+
+    fn window(&self) -> Duration {
+        Duration::from_secs(self.limits.window_secs)
+    }
+
+    fn is_locked_at(&self, ip_addr: Option<IpAddr>, now: Instant) -> bool {
+        let ip_addr = match ip_addr {
+            Some(ip_addr) => ip_addr,
+            None => return false,
+        };
+        let failures = self.failures.lock().unwrap();
+        match failures.by_ip.get(&ip_addr) {
+            Some(times) => times.iter().filter(|time| now.duration_since(**time) < self.window()).count() >= self.limits.max_failures,
+            None => false,
+        }
+    }
+
+    fn record_failure_at(&self, ip_addr: Option<IpAddr>, now: Instant) {
+        let ip_addr = match ip_addr {
+            Some(ip_addr) => ip_addr,
+            None => return,
+        };
+        let window = self.window();
+        let mut failures = self.failures.lock().unwrap();
+        // Addresses that stopped guessing are removed once per window so that the map does not grow forever
+        if now.duration_since(failures.last_prune) >= window {
+            failures.by_ip.retain(|_, times| times.back().is_some_and(|time| now.duration_since(*time) < window));
+            failures.last_prune = now;
+        }
+        let times = failures.by_ip.entry(ip_addr).or_default();
+        while times.front().is_some_and(|time| now.duration_since(*time) >= window) {
+            times.pop_front();
+        }
+        // Older failures are not needed to decide about the lockout
+        if times.len() >= self.limits.max_failures.max(1) {
+            times.pop_front();
+        }
+        times.push_back(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{net::{IpAddr, Ipv4Addr, SocketAddr}, time::{Duration, Instant}};
+
+    use rocket::{figment::Figment, http::{ContentType, Header, Status}, local::blocking::Client, serde::json::Value};
+
+    use super::{CodeGuessLimiter, CodeGuessLimits};
+
+    /// Creates a client with a single game and returns the registration of the game master.
+    fn client(limits: Figment) -> (Client, Value) {
+        let client = Client::tracked(crate::server(rocket::custom(limits))).unwrap();
+        let registration: Value = client.post("/api/create_game").header(ContentType::JSON).body(r#"{"username":"gm"}"#).dispatch().into_json().unwrap();
+        (client, registration)
+    }
+
+    /// Requests the players of the game with `game_code` from `remote`.
+    fn lookup(client: &Client, game_code: &str, remote: &str) -> (Status, Duration) {
+        let start = Instant::now();
+        let response = client.get("/api/players_in_game")
+            .header(Header::new("game_code", String::from(game_code)))
+            .remote(remote.parse::<SocketAddr>().unwrap())
+            .dispatch();
+        (response.status(), start.elapsed())
+    }
+
+    #[test]
+    fn test_lockout_expires() {
+        let limiter = CodeGuessLimiter::new(CodeGuessLimits::default());
+        let ip = Some(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 7)));
+        let other = Some(IpAddr::V4(Ipv4Addr::new(203, 0, 113, 8)));
+        let start = Instant::now();
+        for i in 0..20 {
+            assert!(!limiter.is_locked_at(ip, start));
+            limiter.record_failure_at(ip, start + Duration::from_secs(i));
+        }
+        assert!(limiter.is_locked_at(ip, start + Duration::from_secs(20)));
+        assert!(!limiter.is_locked_at(other, start + Duration::from_secs(20)));
+        assert!(!limiter.is_locked_at(None, start + Duration::from_secs(20)));
+        // The first failure leaves the window
+        assert!(!limiter.is_locked_at(ip, start + Duration::from_secs(600)));
+
+        // Old entries are pruned
+        limiter.record_failure_at(other, start + Duration::from_secs(1300));
+        assert_eq!(1, limiter.failures.lock().unwrap().by_ip.len());
+    }
+
+    #[test]
+    fn test_failure_delay() {
+        let limiter = CodeGuessLimiter::new(CodeGuessLimits::default());
+        for _ in 0..100 {
+            let delay = limiter.failure_delay();
+            assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(150));
+        }
+        let limiter = CodeGuessLimiter::new(CodeGuessLimits { failure_delay: false, ..Default::default() });
+        assert_eq!(Duration::ZERO, limiter.failure_delay());
+    }
+
+    #[test]
+    fn test_only_failures_are_delayed() {
+        let figment = rocket::Config::figment()
+            .merge(("code_guess_limits.min_delay_ms", 400))
+            .merge(("code_guess_limits.max_delay_ms", 500));
+        let (client, registration) = client(figment);
+        let game_code = registration["game_code"].as_str().unwrap();
+        let (status, elapsed) = lookup(&client, "ZZZZZZZZ", "203.0.113.7:8000");
+        assert_eq!(Status::Forbidden, status);
+        assert!(elapsed >= Duration::from_millis(400));
+        let (status, elapsed) = lookup(&client, "invalid", "203.0.113.7:8000");
+        assert_eq!(Status::Forbidden, status);
+        assert!(elapsed >= Duration::from_millis(400));
+        let (status, elapsed) = lookup(&client, game_code, "203.0.113.7:8000");
+        assert_eq!(Status::Ok, status);
+        assert!(elapsed < Duration::from_millis(400));
+
+        // Rejected joins are delayed as well
+        let user_id = String::from(registration["uuid"].as_str().unwrap());
+        assert_eq!(Status::Ok, client.post("/api/lock_lobby").header(Header::new("user_id", user_id)).dispatch().status());
+        let start = Instant::now();
+        let response = client.post("/api/join_game")
+            .header(Header::new("game_code", String::from(game_code)))
+            .header(ContentType::JSON)
+            .body(r#"{"username":"player"}"#)
+            .dispatch();
+        assert_eq!(Status::Forbidden, response.status());
+        assert!(start.elapsed() >= Duration::from_millis(400));
+    }
+
+    #[test]
+    fn test_lockout_after_threshold() {
+        let figment = rocket::Config::figment()
+            .merge(("code_guess_limits.max_failures", 3))
+            .merge(("code_guess_limits.failure_delay", false));
+        let (client, registration) = client(figment);
+        let game_code = registration["game_code"].as_str().unwrap();
+        for _ in 0..3 {
+            assert_eq!(Status::Forbidden, lookup(&client, "ZZZZZZZZ", "203.0.113.7:8000").0);
+        }
+        // Even existing codes are rejected now
+        assert_eq!(Status::TooManyRequests, lookup(&client, game_code, "203.0.113.7:8000").0);
+        assert_eq!(Status::Ok, lookup(&client, game_code, "203.0.113.8:8000").0);
+    }
+}
@@ -1,11 +1,20 @@
-use rocket::FromForm;
+use std::{collections::BTreeMap, ops::RangeInclusive};
+
+use rocket::serde::json::Value;
 use serde::{Serialize, Deserialize};
+use thiserror::Error;
 use uuid::Uuid;
 
-use crate::{game::{game_instance::GameCode, User}, authentication::Urid};
+use crate::{analytics::{AnalyticsReport, JoinSource}, game::{abandonment::AbandonmentReport, base_game::{PlayerStats, Tile}, game_instance::{GameCode, LobbySettings, LobbyStatus, NewGameMaster, PlayerListEntry, SeatVacancyChange, board::{HotelChain, PlacedTile, Position}, turns::TurnPhase}, User}, authentication::Urid, notices::{AppliesTo, Notice, Severity}, paths::sse::CloseReason, rules::{validate_player_name, PlayerNameError}, usage::RouteUsageReport};
+
+/// The version of the json format that is used between server and client.
+/// 
+/// It has to be increased whenever the format of a message changes, the wire format tests
+/// (see `tests/fixtures/wire_format`) fail when a fixture changed without increasing the version.
+pub const PROTOCOL_VERSION: u32 = 5;
 
 /// Used to transmit data back to the user when a new game is joined
-#[derive(Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserRegistration {
     /// Unique user id for the user
     uuid: Uuid,
@@ -13,6 +22,9 @@ pub struct UserRegistration {
     pub urid: Urid,
     /// Game code of the game where the user is assigned to
     game_code: String,
+    /// The position on the waitlist when the user has to wait for a seat, see [Waitlisted]()
+    #[serde(skip)]
+    waitlist_position: Option<usize>,
 }
 
 impl UserRegistration {
@@ -22,34 +34,230 @@ impl UserRegistration {
             uuid,
             urid,
             game_code: game_code.to_string(),
+            waitlist_position: None,
         }
     }
 
+    /// Marks the registration as registration of a user on the waitlist at `position`.
+    pub fn waitlisted(mut self, position: usize) -> Self {
+        self.waitlist_position = Some(position);
+        self
+    }
+
+    /// Returns the position on the waitlist, `None` when the user is a player.
+    pub fn waitlist_position(&self) -> Option<usize> {
+        self.waitlist_position
+    }
+
+    /// Returns the uuid of the registered user
+    pub fn uuid(&self) -> Uuid {
+        self.uuid
+    }
+
     /// Constructs a new `UserRegistration` from an existing user
     pub fn from_user(user: &User) -> Self {
         Self {
             uuid: user.uuid(),
             urid: user.urid(),
             game_code: user.game_code().to_string(),
+            waitlist_position: None,
         }
     }
 }
 
+/// Send back with `202 Accepted` when a user joins a full lobby and was put on the waitlist.
+///
+/// The client can open the sse stream with the registration right away, the user receives
+/// the event `Promoted` when they get a seat or `WaitlistClosed` when they can no longer get one.
+#[derive(Serialize)]
+pub struct Waitlisted {
+    /// Always `waitlisted`
+    pub code: &'static str,
+    /// The position on the waitlist, the first user has the position 1
+    pub position: usize,
+    #[serde(flatten)]
+    pub registration: UserRegistration,
+}
+
+/// An event that is send to the clients, see [EventData]().
+///
+/// Serialized as `{"type": "TilePlaced", "data": "5E"}`, the `data` field is missing for events without data.
+/// Clients switch on `type`, the names are the same as in the `[name, payload]` pair of protocol version 4.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "data")]
+pub enum GameEvent {
+    PlayerList(Vec<PlayerListEntry>),
+    LobbyStatus(LobbyStatus),
+    LobbyLocked,
+    LobbyUnlocked,
+    GameStarted,
+    /// Only send to the player that drew the tiles
+    TilesDrawn(TilesDrawn),
+    TurnEnded(TurnEnded),
+    /// Contains the id of the player whose turn it is now
+    TurnChanged(u32),
+    GameEnded(Vec<Standing>),
+    TilePlaced(Position),
+    ChainFounded(HotelChain),
+    MergerResolved(MergerResolved),
+    AwaitingDisposal(PendingDisposal),
+    MergerDecision(MergerDecision),
+    StockPurchased(StockPurchased),
+    /// Only send to the game master
+    SecurityAlert(String),
+    ServerNotice(Notice),
+    /// The last event of a stream, see [CloseReason](../paths/sse/enum.CloseReason.html)
+    StreamClosing { reason: CloseReason, retryable: bool },
+    Kicked,
+    LobbyMessage(String),
+    QuickplayMatched(UserRegistration),
+    NewGameMaster(NewGameMaster),
+    Promoted(UserRegistration),
+    WaitlistClosed,
+    LobbySettings(LobbySettings),
+    SeatVacancyChanged(SeatVacancyChange),
+    /// Only send to the game master, once for each buffer
+    BufferWarning(BufferWarning),
+    /// The game master changed the notes of the finished game
+    GameAnnotated(GameAnnotation),
+    /// Send to a resumed stream instead of the events it missed when they are no longer known, the client has to fetch the state again,
+    /// see [events](../paths/sse/fn.events.html)
+    Resync,
+}
+
+impl GameEvent {
+    /// # Returns
+    /// The name of the event, the same as the `type` it is serialized with
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::PlayerList(_) => "PlayerList",
+            Self::LobbyStatus(_) => "LobbyStatus",
+            Self::LobbyLocked => "LobbyLocked",
+            Self::LobbyUnlocked => "LobbyUnlocked",
+            Self::GameStarted => "GameStarted",
+            Self::TilesDrawn(_) => "TilesDrawn",
+            Self::TurnEnded(_) => "TurnEnded",
+            Self::TurnChanged(_) => "TurnChanged",
+            Self::GameEnded(_) => "GameEnded",
+            Self::TilePlaced(_) => "TilePlaced",
+            Self::ChainFounded(_) => "ChainFounded",
+            Self::MergerResolved(_) => "MergerResolved",
+            Self::AwaitingDisposal(_) => "AwaitingDisposal",
+            Self::MergerDecision(_) => "MergerDecision",
+            Self::StockPurchased(_) => "StockPurchased",
+            Self::SecurityAlert(_) => "SecurityAlert",
+            Self::ServerNotice(_) => "ServerNotice",
+            Self::StreamClosing { .. } => "StreamClosing",
+            Self::Kicked => "Kicked",
+            Self::LobbyMessage(_) => "LobbyMessage",
+            Self::QuickplayMatched(_) => "QuickplayMatched",
+            Self::NewGameMaster(_) => "NewGameMaster",
+            Self::Promoted(_) => "Promoted",
+            Self::WaitlistClosed => "WaitlistClosed",
+            Self::LobbySettings(_) => "LobbySettings",
+            Self::SeatVacancyChanged(_) => "SeatVacancyChanged",
+            Self::BufferWarning(_) => "BufferWarning",
+            Self::GameAnnotated(_) => "GameAnnotated",
+            Self::Resync => "Resync",
+        }
+    }
+
+    /// Converts the data of the event into the payload string of protocol version 4.
+    ///
+    /// Strings, positions, chains and ids are send as they are, all other data as json.
+    ///
+    /// # Returns
+    /// `None` for events without data
+    pub fn legacy_payload(&self) -> Option<String> {
+        fn json<T: Serialize>(data: &T) -> Option<String> {
+            rocket::serde::json::to_string(data).ok()
+        }
+        match self {
+            Self::LobbyLocked | Self::LobbyUnlocked | Self::GameStarted | Self::Kicked | Self::WaitlistClosed | Self::Resync => None,
+            Self::SecurityAlert(text) | Self::LobbyMessage(text) => Some(text.clone()),
+            Self::TurnChanged(player_id) => Some(player_id.to_string()),
+            Self::TilePlaced(position) => Some(position.to_string()),
+            Self::ChainFounded(chain) => Some(chain.to_string()),
+            Self::PlayerList(players) => json(players),
+            Self::LobbyStatus(status) => json(status),
+            Self::TilesDrawn(drawn) => json(drawn),
+            Self::TurnEnded(ended) => json(ended),
+            Self::GameEnded(standings) => json(standings),
+            Self::MergerResolved(resolved) => json(resolved),
+            Self::AwaitingDisposal(pending) => json(pending),
+            Self::MergerDecision(decision) => json(decision),
+            Self::StockPurchased(purchased) => json(purchased),
+            Self::ServerNotice(notice) => json(notice),
+            Self::StreamClosing { reason, retryable } => json(&StreamClosing { reason: *reason, retryable: *retryable }),
+            Self::QuickplayMatched(registration) | Self::Promoted(registration) => json(registration),
+            Self::NewGameMaster(data) => json(data),
+            Self::LobbySettings(settings) => json(settings),
+            Self::SeatVacancyChanged(change) => json(change),
+            Self::BufferWarning(warning) => json(warning),
+            Self::GameAnnotated(annotation) => json(annotation),
+        }
+    }
+}
+
+/// The bounded buffers of a game, old entries are removed once they are full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GameBuffer {
+    /// The events that resumed streams receive, see [GameChannel::resume](../events/struct.GameChannel.html#method.resume)
+    EventHistory,
+    /// The entries of [game_log](../paths/game_api/fn.game_log.html)
+    GameLog,
+}
+
+/// The data of the event `BufferWarning`, send to the game master when a buffer of the game is almost full,
+/// see [GameChannel::watch_buffer](../events/struct.GameChannel.html#method.watch_buffer).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BufferWarning {
+    pub buffer: GameBuffer,
+    /// The number of entries the buffer can hold
+    pub capacity: usize,
+    /// The number of entries the buffer holds
+    pub used: usize,
+}
+
+/// The data of the event `StreamClosing` in protocol version 4.
+#[derive(Serialize)]
+struct StreamClosing {
+    reason: CloseReason,
+    retryable: bool,
+}
+
+/// The largest number of bytes the data of a single event can have.
+pub const MAX_EVENT_DATA_LEN: usize = 64 * 1024;
+
+/// The reasons why [EventData]() could not be constructed.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum EventDataError {
+    /// The data is longer than [MAX_EVENT_DATA_LEN](), contains the length of the data.
+    #[error("event data is too long ({0} bytes)")]
+    DataTooLong(usize),
+}
+
 /// Used to transmit data to the client with server side events
-#[derive(Debug, Clone, FromForm, Serialize, Deserialize)]
+/// 
+/// Event data is only constructed by the server, it is never accepted from clients.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EventData {
     /// Indicates to which player this request is directed.
     ///
     /// When this is empty the message is meant to be relevant for all players.
     /// 
-    /// [Uuid]() is not used here because it does not implement FromForm.
+    /// [Uuid]() is not used here to keep the json format, see [PROTOCOL_VERSION]().
     user_id: String,
     /// Indicates for what game this request is relevant
     ///
     /// Stores the value of [GameCode::to_string()](../game/struct.GameCode.html#method.to_string)
     game_code: String,
-    /// Additional data
+    /// The name and the payload of the event as they were send in protocol version 4, see [GameEvent::legacy_payload]().
+    ///
+    /// Kept next to `event` so that clients that still read this pair keep working until they switch on the `type` of `event`.
     data: (String, Option<String>),
+    event: GameEvent,
 }
 
 impl EventData {
@@ -58,35 +266,939 @@ impl EventData {
     /// # Arguments
     /// - `uuid` The user to which the message is directed, if `None` the message is directed to everyone.
     /// - `game_code` The game code for the game instance to which this event is directed.
-    /// - `data` Some data that should be sent.
-    pub fn new(uuid: Option<Uuid>, game_code: GameCode, data: (String, Option<String>)) -> Self {
+    /// - `event` The event that should be sent.
+    /// 
+    /// # Returns
+    /// `Err(EventDataError)` when the payload of the event is longer than [MAX_EVENT_DATA_LEN]().
+    pub fn new(uuid: Option<Uuid>, game_code: GameCode, event: GameEvent) -> Result<Self, EventDataError> {
+        let payload = event.legacy_payload();
+        if let Some(len) = payload.as_ref().map(String::len).filter(|len| *len > MAX_EVENT_DATA_LEN) {
+            return Err(EventDataError::DataTooLong(len));
+        }
         let user_id = match uuid {
             None => String::new(),
             Some(uuid) => uuid.to_string(),
         };
-        Self {
+        Ok(Self {
             user_id,
             game_code: game_code.to_string(),
-            data,
-        }
+            data: (String::from(event.name()), payload),
+            event,
+        })
     }
 
     /// # Returns
     /// The game code to which this data event belongs
+    #[cfg(test)]
     pub fn game_code(&self) -> String {
         self.game_code.to_string()
     }
 
+    /// # Returns
+    /// The name of the event
+    pub fn name(&self) -> &str {
+        self.event.name()
+    }
+
+    /// # Returns
+    /// The event that is send
+    pub fn event(&self) -> &GameEvent {
+        &self.event
+    }
+
+    /// # Returns
+    /// The event data without the legacy `data` pair, see [CompactEventData]().
+    pub fn compact(&self) -> CompactEventData<'_> {
+        CompactEventData { user_id: &self.user_id, game_code: &self.game_code, event: &self.event }
+    }
+
+    /// # Returns
+    /// The additional data of the event
+    #[cfg(test)]
+    pub fn payload(&self) -> Option<&str> {
+        self.data.1.as_deref()
+    }
+
     /// # Returns
     /// The user id for which the event is relevant
+    #[cfg(test)]
     pub fn user_id(&self) -> String {
         self.user_id.clone()
     }
 }
 
+/// [EventData]() without the name and payload pair of protocol version 4.
+///
+/// Used for the msgpack encoding of the sse stream, clients that opt into it switch on the `type` of `event`.
+#[derive(Debug, Serialize)]
+pub struct CompactEventData<'a> {
+    user_id: &'a str,
+    game_code: &'a str,
+    event: &'a GameEvent,
+}
+
+/// The name of a player as send by the client.
+/// 
+/// The name is validated when it is deserialized, so a `PlayerName` is always valid:
+/// - leading and trailing whitespace is removed
+/// - the name is not empty and at most [MAX_PLAYER_NAME_LENGTH](../rules/constant.MAX_PLAYER_NAME_LENGTH.html) characters long
+/// - the name does not contain control characters
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct PlayerName(String);
+
+impl PlayerName {
+    /// Returns the name.
+    pub fn into_inner(self) -> String {
+        self.0
+    }
+}
+
+impl TryFrom<String> for PlayerName {
+    type Error = PlayerNameError;
+
+    /// Validates the name with [validate_player_name](../rules/fn.validate_player_name.html), the wasm module uses the same rules.
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        validate_player_name(&name).map(|name| Self(String::from(name)))
+    }
+}
+
+/// Used to get the username from a join game request formatted as json
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct JoinGameRequest {
+    pub username: PlayerName,
+    /// The invite token, only required when the lobby requires an invite
+    #[serde(default)]
+    pub invite: Option<Uuid>,
+    /// The page from which the game was joined, only used for [Analytics](../analytics/struct.Analytics.html)
+    #[serde(default)]
+    pub source: Option<JoinSource>,
+}
+
+/// Used to get the data that is required to create a new game from a request formatted as json
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CreateGameRequest {
+    /// The name of the user that creates the game
+    pub username: PlayerName,
+    /// The seed that is used for the rng of the game, a random seed is used when this is not set.
+    /// 
+    /// Only respected in debug builds so that test games can be made deterministic.
+    #[serde(default)]
+    pub seed: Option<u64>,
+    /// The page from which the game was created, only used for [Analytics](../analytics/struct.Analytics.html)
+    #[serde(default)]
+    pub source: Option<JoinSource>,
+}
+
+/// Used to transmit the name that a returning user used last back to the user, see [whoami](../paths/lobby_api/fn.whoami.html)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WhoAmI {
+    /// The name that was last used with the `urid` cookie
+    pub name: String,
+    /// The game in which the user is still a player, the user can rejoin this game with the name
+    pub game_code: Option<String>,
+}
+
+/// Used to transmit the tiles of a player, see [hand](../paths/game_api/fn.hand.html)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Hand {
+    /// The tiles the player holds, empty until the game is started
+    pub tiles: Vec<Tile>,
+    /// The number of tiles that can still be drawn
+    pub tiles_remaining: usize,
+}
+
+/// The tiles a player has drawn at the end of their turn, the data of the event `TilesDrawn` that only this player receives.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TilesDrawn {
+    /// The tiles that were drawn, tiles that were discarded right away are included
+    pub drawn: Vec<Tile>,
+    /// The tiles that were taken out of the hand because they can never be placed
+    pub discarded: Vec<Tile>,
+}
+
+/// The data of the event `TurnEnded`, send to all players when a player has drawn their tiles.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TurnEnded {
+    pub player_id: u32,
+    /// The tiles the player had to discard, see [TilesDrawn]()
+    pub discarded: Vec<Tile>,
+}
+
+/// The statistics of one player, see [game_stats](../paths/game_api/fn.game_stats.html).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerStatsEntry {
+    pub player_id: u32,
+    #[serde(flatten)]
+    pub stats: PlayerStats,
+}
+
+/// The final place of a player, returned by [results](../paths/game_api/fn.results.html) and the data of the event `GameEnded`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Standing {
+    /// Starts at 1, players with the same money share the place and the next place is skipped
+    pub place: usize,
+    pub player_id: u32,
+    pub name: String,
+    /// The money after the final bonuses were paid and all shares were sold
+    pub money: u32,
+}
+
+/// The tile a player wants to place, send to [place_tile](../paths/game_api/fn.place_tile.html) formatted as json.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PlaceTileRequest {
+    /// The tile from the hand of the player, written like the position, for example `5D`
+    pub tile: Tile,
+    /// The `turn_token` of the [TurnStatus]()
+    pub turn_token: Uuid,
+}
+
+/// The result of [place_tile](../paths/game_api/fn.place_tile.html).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TilePlacement {
+    /// The tiles that were placed or changed their chain, row by row
+    pub tiles: Vec<PlacedTile>,
+    /// Set when the tile founds a new chain, the player has to choose one of these chains with
+    /// [found_chain](../paths/game_api/fn.found_chain.html) before the turn ends
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub choose_chain: Option<Vec<HotelChain>>,
+    /// Set when the tile merges several chains of the largest size, the player has to choose the chain that survives with
+    /// [choose_survivor](../paths/game_api/fn.choose_survivor.html)
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub choose_survivor: Option<Vec<HotelChain>>,
+    /// The player can now buy shares with [buy_stock](../paths/game_api/fn.buy_stock.html), the turn ends afterwards
+    pub buy_stock: bool,
+}
+
+/// The chain a player wants to found or that should survive a merger, send to [found_chain](../paths/game_api/fn.found_chain.html)
+/// and [choose_survivor](../paths/game_api/fn.choose_survivor.html) formatted as json.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChainRequest {
+    pub chain: HotelChain,
+    /// The `turn_token` of the [TurnStatus]()
+    pub turn_token: Uuid,
+}
+
+/// Used to get the notes of a finished game from a request formatted as json, see [game_notes](../paths/game_api/fn.game_notes.html)
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GameNotesRequest {
+    pub title: String,
+    #[serde(default)]
+    pub notes: String,
+}
+
+/// The notes of a finished game, returned by [game_notes](../paths/game_api/fn.game_notes.html) and the data of the event `GameAnnotated`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GameAnnotation {
+    /// At most [MAX_NOTE_TITLE_LENGTH](../game/game_instance/constant.MAX_NOTE_TITLE_LENGTH.html) characters without markup
+    pub title: String,
+    /// At most [MAX_NOTES_LENGTH](../game/game_instance/constant.MAX_NOTES_LENGTH.html) characters without markup
+    pub notes: String,
+    /// The public id of the player that changed the notes last
+    pub editor: u32,
+}
+
+/// The data of the event `MergerResolved`, send once for each chain that was taken over.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MergerResolved {
+    pub survivor: HotelChain,
+    pub defunct: HotelChain,
+    /// The number of tiles of the defunct chain before the merger
+    pub defunct_size: usize,
+    /// The bonuses that were paid to the shareholders of the defunct chain
+    pub payouts: Vec<Payout>,
+}
+
+/// A shareholder of a defunct chain that has to decide what happens to their shares, the data of the event `AwaitingDisposal`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingDisposal {
+    /// The public id of the player that has to decide
+    pub player_id: u32,
+    pub defunct: HotelChain,
+    pub survivor: HotelChain,
+    /// The number of shares of the defunct chain the player holds
+    pub shares: u32,
+    /// The price of one share of the defunct chain before the merger, sold shares are paid with it
+    pub price: u32,
+}
+
+/// What a shareholder does with their shares of a defunct chain, send to
+/// [merger_decision](../paths/game_api/fn.merger_decision.html) formatted as json.
+/// 
+/// The numbers have to add up to the shares the player holds, traded shares are exchanged two for one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DisposalDecision {
+    pub sell: u32,
+    pub trade: u32,
+    pub keep: u32,
+}
+
+/// The decision of a shareholder together with the `turn_token` of the [TurnStatus](), send to
+/// [merger_decision](../paths/game_api/fn.merger_decision.html) formatted as json.
+/// 
+/// `{"sell": 2, "trade": 2, "keep": 0, "turn_token": "…"}`
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MergerDecisionRequest {
+    pub sell: u32,
+    pub trade: u32,
+    pub keep: u32,
+    pub turn_token: Uuid,
+}
+
+impl MergerDecisionRequest {
+    /// Returns the decision without the token.
+    pub fn decision(&self) -> DisposalDecision {
+        DisposalDecision { sell: self.sell, trade: self.trade, keep: self.keep }
+    }
+}
+
+/// The `turn_token` of the [TurnStatus](), send to [end_game](../paths/game_api/fn.end_game.html) formatted as json.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EndGameRequest {
+    pub turn_token: Uuid,
+}
+
+/// The data of the event `MergerDecision`, send to all players after a shareholder has decided.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MergerDecision {
+    pub player_id: u32,
+    pub defunct: HotelChain,
+    #[serde(flatten)]
+    pub decision: DisposalDecision,
+    /// The number of decisions that are still missing, the merging player can buy shares when it is `0`
+    pub remaining: usize,
+}
+
+/// Whose turn it is and what they have to do next, returned by [turn](../paths/game_api/fn.turn.html).
+/// 
+/// The phase is flattened into this struct, phases that belong to a placed tile carry its `position`:
+/// 
+/// `{"player_id": 2, "phase": "found_chain", "position": "3A", "disposal": null, "turn_token": "…"}`
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TurnStatus {
+    /// The public id of the player whose turn it is
+    pub player_id: u32,
+    #[serde(flatten)]
+    pub phase: TurnPhase,
+    /// The shareholder that has to decide next while the phase is `merger_disposal`
+    pub disposal: Option<PendingDisposal>,
+    /// Has to be send with the next gameplay request, it changes with every step of the turn.
+    /// Requests with an older token are rejected with `409 Conflict` and the code `stale_turn_token`.
+    pub turn_token: Uuid,
+}
+
+/// Money that was paid to a player, see [MergerResolved]().
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Payout {
+    pub player_id: u32,
+    pub amount: u32,
+}
+
+/// Shares of one chain that a player buys, see [BuyStockRequest]().
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StockPurchase {
+    pub chain: HotelChain,
+    pub quantity: u32,
+}
+
+/// The shares a player wants to buy, send to [buy_stock](../paths/game_api/fn.buy_stock.html) formatted as json.
+/// 
+/// An empty list ends the turn without buying shares.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BuyStockRequest {
+    pub purchases: Vec<StockPurchase>,
+    /// The `turn_token` of the [TurnStatus]()
+    pub turn_token: Uuid,
+}
+
+/// The data of the event `StockPurchased`, send to all players so that they can update the shares the bank holds.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StockPurchased {
+    /// The public id of the player that bought the shares
+    pub player_id: u32,
+    pub purchases: Vec<StockPurchase>,
+}
+
+/// The money and the shares of a player, only send to that player.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Portfolio {
+    pub money: u32,
+    /// The number of shares of each chain, chains without shares are not listed
+    pub shares: BTreeMap<HotelChain, u32>,
+}
+
+/// The shares of all players and the bank, returned by [stocks](../paths/game_api/fn.stocks.html).
+/// 
+/// The shares of each chain in `players` and `bank` always add up to [SHARES_PER_CHAIN](../game/base_game/constant.SHARES_PER_CHAIN.html).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StockOverview {
+    /// The money and the shares of the requesting player
+    pub own: Portfolio,
+    /// The shares of every player including the requesting one in seat order, the number of shares is public
+    pub players: Vec<PlayerShares>,
+    /// The shares of each chain that no player holds
+    pub bank: BTreeMap<HotelChain, u32>,
+}
+
+/// The shares of one player, see [StockOverview]().
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PlayerShares {
+    pub player_id: u32,
+    /// Chains without shares are not listed
+    pub shares: BTreeMap<HotelChain, u32>,
+}
+
+/// Used to get the username of a user that wants to be matched into a game from a request formatted as json
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QuickplayRequest {
+    pub username: PlayerName,
+}
+
+/// Used to transmit the ticket of a user that waits for a quickplay game back to the user
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QuickplayTicket {
+    /// Used to open the stream on which the game is announced and to cancel waiting
+    pub ticket: Uuid,
+}
 
-/// Used to get the username from a request formatted as json
+/// Used to get the lobby settings that should be changed from a request formatted as json
+/// 
+/// Settings that are not set are not changed.
 #[derive(Deserialize)]
-pub struct Username<'a> {
-    pub username: &'a str,
+#[serde(deny_unknown_fields)]
+pub struct LobbySettingsUpdate {
+    pub min_players: Option<usize>,
+    /// A larger maximum promotes users from the waitlist
+    pub max_players: Option<usize>,
+    pub require_invite: Option<bool>,
+    pub master_idle_rotate_secs: Option<u64>,
+    /// Disabling the waitlist removes all users from it
+    pub enable_waitlist: Option<bool>,
+}
+
+impl LobbySettingsUpdate {
+    /// Returns the `current` settings with the changes of this update.
+    /// 
+    /// The result is not validated, see [LobbySettings::validate](../game/game_instance/struct.LobbySettings.html#method.validate).
+    pub fn apply(&self, current: &LobbySettings) -> LobbySettings {
+        LobbySettings::new(
+            self.min_players.unwrap_or(current.min_players()),
+            self.max_players.unwrap_or(current.max_players()),
+            self.require_invite.unwrap_or(current.require_invite()),
+        )
+            .with_master_idle_rotate_secs(self.master_idle_rotate_secs.unwrap_or(current.master_idle_rotate_secs()))
+            .with_enable_waitlist(self.enable_waitlist.unwrap_or(current.enable_waitlist()))
+    }
+}
+
+/// Applies `patch` to `target` as JSON merge patch, see [RFC 7396](https://www.rfc-editor.org/rfc/rfc7396).
+/// 
+/// - Fields that are missing in the patch are not changed.
+/// - Fields that are `null` in the patch are removed from the target.
+/// - Objects are merged recursively, all other values replace the value of the target.
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let patch = match patch {
+        Value::Object(patch) => patch,
+        _ => {
+            *target = patch.clone();
+            return;
+        }
+    };
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+    if let Value::Object(target) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+/// A single invalid field of a request, all invalid fields are send together with the error `invalid_fields`.
+///
+/// The client can use this to highlight every invalid field of a form at once.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldError {
+    /// The name of the field in the request
+    pub field: String,
+    /// Machine readable reason, for example `out_of_range` or `greater_than_max_players`
+    pub code: String,
+    /// The smallest value that would be allowed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<usize>,
+    /// The largest value that would be allowed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<usize>,
+}
+
+impl FieldError {
+    /// Creates a new error for `field` that names the range of values that would be `allowed`.
+    pub fn new(field: &str, code: &str, allowed: RangeInclusive<usize>) -> Self {
+        Self {
+            field: String::from(field),
+            code: String::from(code),
+            min: Some(*allowed.start()),
+            max: Some(*allowed.end()),
+        }
+    }
+}
+
+/// The default number of uses of an invite
+fn default_invite_uses() -> u32 {
+    1
+}
+
+/// The default time for which an invite is valid
+fn default_invite_valid_for_secs() -> u64 {
+    60 * 60
+}
+
+/// Used to get the properties of a new invite from a request formatted as json
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CreateInviteRequest {
+    /// How often the invite can be used, defaults to a single use
+    #[serde(default = "default_invite_uses")]
+    pub uses: u32,
+    /// For how many seconds the invite is valid, defaults to one hour
+    #[serde(default = "default_invite_valid_for_secs")]
+    pub valid_for_secs: u64,
+}
+
+/// The versions of [SettingsPreset]() that can be imported.
+pub const SETTINGS_PRESET_VERSIONS: RangeInclusive<u64> = 1..=1;
+
+/// Lobby settings that are exported as standalone json document so that they can be imported into another lobby.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SettingsPreset {
+    /// The version of the document format, see [SETTINGS_PRESET_VERSIONS]()
+    pub version: u64,
+    pub min_players: usize,
+    pub max_players: usize,
+    /// Fields that this version does not know, for example settings that where added in a newer version.
+    /// 
+    /// These fields are ignored when the preset is imported.
+    #[serde(flatten, skip_serializing)]
+    pub unknown: BTreeMap<String, Value>,
+}
+
+impl SettingsPreset {
+    /// Creates a new preset in the newest version from `settings`.
+    pub fn from_settings(settings: &LobbySettings) -> Self {
+        Self {
+            version: *SETTINGS_PRESET_VERSIONS.end(),
+            min_players: settings.min_players(),
+            max_players: settings.max_players(),
+            unknown: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the lobby settings that are stored in this preset.
+    /// 
+    /// Settings that are not part of a preset, like [require_invite](../game/game_instance/struct.LobbySettings.html#method.require_invite),
+    /// are taken from `current`.
+    pub fn settings(&self, current: &LobbySettings) -> LobbySettings {
+        LobbySettings::new(self.min_players, self.max_players, current.require_invite())
+            .with_master_idle_rotate_secs(current.master_idle_rotate_secs())
+            .with_enable_waitlist(current.enable_waitlist())
+    }
+}
+
+/// Used to get the new maintenance mode from a request formatted as json
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MaintenanceRequest {
+    pub enabled: bool,
+    /// Announced to all running games when the maintenance mode is enabled
+    #[serde(default)]
+    pub message: Option<String>,
+}
+
+/// Used to get a new notice from a request formatted as json, see [Notice](../notices/struct.Notice.html)
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NoticeRequest {
+    pub severity: Severity,
+    pub message: String,
+    /// Defaults to all games
+    #[serde(default)]
+    pub applies_to: AppliesTo,
+}
+
+/// Used to transmit the state of the server, see [status](../paths/admin/fn.status.html)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServerStatus {
+    /// See [PROTOCOL_VERSION](constant.PROTOCOL_VERSION.html), clients can use this to detect that they have to be reloaded
+    pub protocol_version: u32,
+    pub maintenance: bool,
+    /// The message that was announced for the maintenance
+    pub maintenance_message: Option<String>,
+    /// The number of games that still exist, the server can be restarted without interrupting anyone when this is 0
+    pub active_games: usize,
+    /// How often each route was used, only included when the request contains a valid `admin_token`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub route_usage: Option<RouteUsageReport>,
+    /// What happened to abandoned games, only included when the request contains a valid `admin_token`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub abandonment: Option<AbandonmentReport>,
+    /// How people arrive at games, only included when the request contains a valid `admin_token` and analytics are enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub analytics: Option<AnalyticsReport>,
+}
+
+/// The current time of the server, see [time](../paths/admin/fn.time.html)
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ServerTime {
+    pub unix_millis: u64,
+}
+
+impl PlainText for ServerStatus {
+    fn plain_text(&self) -> String {
+        let fields = vec![
+            vec![String::from("protocol_version"), self.protocol_version.to_string()],
+            vec![String::from("maintenance"), self.maintenance.to_string()],
+            vec![String::from("maintenance_message"), self.maintenance_message.clone().unwrap_or_else(|| String::from("-"))],
+            vec![String::from("active_games"), self.active_games.to_string()],
+        ];
+        let mut text = text_table(&["field", "value"], &fields);
+        if let Some(usage) = &self.route_usage {
+            let routes: Vec<Vec<String>> = usage.active.iter().map(|entry| (entry, false))
+                .chain(usage.deprecated.iter().map(|entry| (entry, true)))
+                .map(|(entry, deprecated)| vec![
+                    entry.route.clone(),
+                    entry.count.to_string(),
+                    entry.last_used.map(|time| time.to_string()).unwrap_or_else(|| String::from("-")),
+                    yes_no(deprecated),
+                ])
+                .collect();
+            text.push('\n');
+            text.push_str(&text_table(&["route", "count", "last_used", "deprecated"], &routes));
+        }
+        if let Some(abandonment) = &self.abandonment {
+            let mut outcomes: Vec<Vec<String>> = abandonment.buckets.iter()
+                .map(|bucket| {
+                    let outcome = match bucket.below_secs {
+                        Some(secs) => format!("recovered within {}s", secs),
+                        None => String::from("recovered later"),
+                    };
+                    vec![outcome, bucket.count.to_string()]
+                })
+                .collect();
+            outcomes.push(vec![format!("deleted after {}s", abandonment.timeout_secs), abandonment.deleted.to_string()]);
+            text.push('\n');
+            text.push_str(&text_table(&["outcome", "games"], &outcomes));
+        }
+        if let Some(analytics) = &self.analytics {
+            let sources = vec![
+                vec![String::from("main_page"), analytics.created.main_page.to_string(), analytics.joined.main_page.to_string()],
+                vec![String::from("deep_link"), analytics.created.deep_link.to_string(), analytics.joined.deep_link.to_string()],
+                vec![String::from("unknown"), analytics.created.unknown.to_string(), analytics.joined.unknown.to_string()],
+            ];
+            let languages: Vec<Vec<String>> = analytics.languages.iter().map(|(language, count)| vec![language.clone(), count.to_string()]).collect();
+            text.push('\n');
+            text.push_str(&text_table(&["source", "created", "joined"], &sources));
+            text.push('\n');
+            text.push_str(&text_table(&["language", "count"], &languages));
+        }
+        text
+    }
+}
+
+/// The players of a game, see [players_in_game](../paths/lobby_api/fn.players_in_game.html).
+///
+/// As json only the names of the connected players are send, the plain text table contains all players.
+#[derive(Debug)]
+pub struct PlayersInGame(pub Vec<PlayerListEntry>);
+
+impl Serialize for PlayersInGame {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.0.iter().filter(|player| player.connected).map(|player| &player.name))
+    }
+}
+
+impl PlainText for PlayersInGame {
+    fn plain_text(&self) -> String {
+        let rows: Vec<Vec<String>> = self.0.iter()
+            .map(|player| vec![player.name.clone(), yes_no(player.connected), yes_no(player.game_master), player.seat.to_string()])
+            .collect();
+        text_table(&["name", "connected", "master", "seat"], &rows)
+    }
+}
+
+/// A response that can also be send as plain text table, see [Negotiated](../negotiation/struct.Negotiated.html).
+pub trait PlainText {
+    /// Formats this response for humans, for example with [text_table]().
+    fn plain_text(&self) -> String;
+}
+
+/// Formats `rows` as a table with aligned columns below the `header` line.
+///
+/// Each column is as wide as its longest cell, columns are separated by two spaces
+/// and each line ends with a line break without trailing spaces.
+pub fn text_table(header: &[&str], rows: &[Vec<String>]) -> String {
+    let header: Vec<String> = header.iter().map(|column| column.to_uppercase()).collect();
+    let mut widths: Vec<usize> = header.iter().map(|column| column.chars().count()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut text = String::new();
+    for row in std::iter::once(&header).chain(rows) {
+        let line = row.iter().zip(&widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        text.push_str(line.trim_end());
+        text.push('\n');
+    }
+    text
+}
+
+fn yes_no(value: bool) -> String {
+    String::from(if value { "yes" } else { "no" })
+}
+
+/// Used to transmit the result of a settings import back to the user
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SettingsImport {
+    /// Fields of the imported document that where ignored
+    pub warnings: Vec<String>,
+}
+
+/// The longest message that the game master can send to the lobby, in characters.
+pub const MAX_LOBBY_MESSAGE_LEN: usize = 500;
+
+/// Used to get several lobby operations of the game master from a request formatted as json.
+///
+/// The operations are applied in the order lock, kicks, message, see [lobby_admin](../paths/lobby_api/fn.lobby_admin.html).
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LobbyAdminRequest {
+    /// The ids of the players that should be removed from the game, see [Player::id](../game/base_game/struct.Player.html#method.id)
+    #[serde(default)]
+    pub kick: Vec<u32>,
+    /// Locks or unlocks the lobby, the lock is not changed when this is not set
+    pub lock: Option<bool>,
+    /// Message that is send to all players with the event `LobbyMessage`
+    pub message: Option<String>,
+}
+
+impl LobbyAdminRequest {
+    /// Checks if the request does not contain any operation.
+    pub fn is_empty(&self) -> bool {
+        self.kick.is_empty() && self.lock.is_none() && self.message.is_none()
+    }
+}
+
+/// What happened to a single player of [LobbyAdminRequest::kick]().
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KickOutcome {
+    /// The player was removed from the game
+    Kicked,
+    /// No player with this id is part of the game
+    NotFound,
+    /// The player was not removed because the game master can not kick themselves
+    Skipped,
+}
+
+/// The outcome of kicking a single player.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KickResult {
+    pub player_id: u32,
+    pub outcome: KickOutcome,
+}
+
+/// Used to transmit the outcome of each operation of a [LobbyAdminRequest]() back to the game master
+#[derive(Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LobbyAdminResult {
+    /// The new lock state of the lobby when it was changed
+    pub locked: Option<bool>,
+    /// One entry for each requested kick, in the order of the request
+    pub kicks: Vec<KickResult>,
+    /// `true` when the message was send
+    pub message_sent: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::serde::json::{from_str, json, to_string, to_value, Value};
+
+    use crate::{analytics::{AnalyticsReport, SourceCounts}, events::{from_msgpack, to_msgpack}, game::{abandonment::{AbandonmentReport, RecoveryBucket}, game_instance::{GameCode, PlayerListEntry, board::{HotelChain, Position}}}, usage::{RouteUsageEntry, RouteUsageReport}};
+
+    use super::{CreateGameRequest, DisposalDecision, EventData, EventDataError, GameEvent, JoinGameRequest, MergerDecisionRequest, PlainText, merge_patch, PlayerName, PlayerNameError, PlayersInGame, ServerStatus, MAX_EVENT_DATA_LEN, PROTOCOL_VERSION};
+
+    /// Contains the expected plain text of the responses that can be requested as text.
+    const PLAIN_TEXT_FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/plain_text");
+
+    fn assert_plain_text(name: &str, value: &impl PlainText) {
+        let fixture = std::fs::read_to_string(format!("{}/{}.txt", PLAIN_TEXT_FIXTURES, name)).unwrap();
+        assert_eq!(fixture, value.plain_text(), "plain text of {} does not match the fixture", name);
+    }
+
+    #[test]
+    fn test_player_name_validation() {
+        assert_eq!(Ok(PlayerName(String::from("Alice"))), PlayerName::try_from(String::from("  Alice ")));
+        assert_eq!(Err(PlayerNameError::Empty), PlayerName::try_from(String::from("   ")));
+        assert_eq!(Err(PlayerNameError::TooLong), PlayerName::try_from("a".repeat(21)));
+        assert!(PlayerName::try_from("ä".repeat(20)).is_ok());
+        assert_eq!(Err(PlayerNameError::InvalidCharacter), PlayerName::try_from(String::from("a\nb")));
+    }
+
+    #[test]
+    fn test_request_deserialization() {
+        let request: JoinGameRequest = from_str(r#"{"username": "Bob"}"#).unwrap();
+        assert_eq!("Bob", request.username.into_inner());
+        // escaped characters could not be borrowed from the request body
+        let request: JoinGameRequest = from_str(r#"{"username": "B\u00f6b"}"#).unwrap();
+        assert_eq!("Böb", request.username.into_inner());
+        assert!(from_str::<JoinGameRequest>(r#"{"username": ""}"#).is_err());
+        assert!(from_str::<JoinGameRequest>(r#"{"username": "Bob", "admin": true}"#).is_err());
+        let request: CreateGameRequest = from_str(r#"{"username": "Bob"}"#).unwrap();
+        assert_eq!(None, request.seed);
+        let request: CreateGameRequest = from_str(r#"{"username": "Bob", "seed": 3}"#).unwrap();
+        assert_eq!(Some(3), request.seed);
+        let token = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+        let request: MergerDecisionRequest = from_str(&format!(r#"{{"sell": 1, "trade": 2, "keep": 0, "turn_token": "{}"}}"#, token)).unwrap();
+        assert_eq!((DisposalDecision { sell: 1, trade: 2, keep: 0 }, token), (request.decision(), request.turn_token.to_string().as_str()));
+        assert!(from_str::<MergerDecisionRequest>(r#"{"sell": 1, "trade": 2, "keep": 0}"#).is_err());
+        assert!(from_str::<MergerDecisionRequest>(&format!(r#"{{"sell": 1, "trade": 2, "keep": 0, "turn_token": "{}", "all": true}}"#, token)).is_err());
+    }
+
+    #[test]
+    fn test_event_data_validation() {
+        let game_code = GameCode::new(['A'; 8]).unwrap();
+        assert!(EventData::new(None, game_code, GameEvent::LobbyMessage("a".repeat(MAX_EVENT_DATA_LEN))).is_ok());
+        let err = EventData::new(None, game_code, GameEvent::LobbyMessage("a".repeat(MAX_EVENT_DATA_LEN + 1))).unwrap_err();
+        assert_eq!(EventDataError::DataTooLong(MAX_EVENT_DATA_LEN + 1), err);
+    }
+
+    #[test]
+    fn test_game_event_round_trip() {
+        // one event of each variant
+        let events = [
+            r#"{"type":"PlayerList","data":[{"player_id":1,"seat":1,"name":"Alice","connected":false,"game_master":true,"vacancy":{"reason":"connection_lost","since":1700000000}}]}"#,
+            r#"{"type":"LobbyStatus","data":{"current_players":1,"min_players":2,"max_players":6,"can_start":false}}"#,
+            r#"{"type":"LobbyLocked"}"#,
+            r#"{"type":"LobbyUnlocked"}"#,
+            r#"{"type":"GameStarted"}"#,
+            r#"{"type":"TilesDrawn","data":{"drawn":["5E"],"discarded":["12I"]}}"#,
+            r#"{"type":"TurnEnded","data":{"player_id":2,"discarded":[]}}"#,
+            r#"{"type":"TurnChanged","data":3}"#,
+            r#"{"type":"GameEnded","data":[{"place":1,"player_id":2,"name":"Bob","money":12000}]}"#,
+            r#"{"type":"TilePlaced","data":"1A"}"#,
+            r#"{"type":"ChainFounded","data":"imperial"}"#,
+            r#"{"type":"MergerResolved","data":{"survivor":"tower","defunct":"luxor","defunct_size":3,"payouts":[{"player_id":1,"amount":3000}]}}"#,
+            r#"{"type":"AwaitingDisposal","data":{"player_id":1,"defunct":"luxor","survivor":"tower","shares":4,"price":300}}"#,
+            r#"{"type":"MergerDecision","data":{"player_id":1,"defunct":"luxor","sell":1,"trade":2,"keep":1,"remaining":0}}"#,
+            r#"{"type":"StockPurchased","data":{"player_id":1,"purchases":[{"chain":"festival","quantity":3}]}}"#,
+            r#"{"type":"SecurityAlert","data":"More than 5 join requests where rejected within the last minute"}"#,
+            r#"{"type":"ServerNotice","data":{"id":1,"severity":"warning","message":"Restart","applies_to":"all","posted_at":1700000000}}"#,
+            r#"{"type":"StreamClosing","data":{"reason":"shutdown","retryable":true}}"#,
+            r#"{"type":"Kicked"}"#,
+            r#"{"type":"LobbyMessage","data":"Behave"}"#,
+            r#"{"type":"QuickplayMatched","data":{"uuid":"67e55044-10b1-426f-9247-bb680e5fe0c8","urid":{"uuid":"9a1b2c3d-4e5f-4a6b-8c7d-0e1f2a3b4c5d","issued_at":1700000000},"game_code":"ABCD-1234"}}"#,
+            r#"{"type":"NewGameMaster","data":{"player_id":2,"reason":"idle_rotation"}}"#,
+            r#"{"type":"Promoted","data":{"uuid":"67e55044-10b1-426f-9247-bb680e5fe0c8","urid":{"uuid":"9a1b2c3d-4e5f-4a6b-8c7d-0e1f2a3b4c5d","issued_at":1700000000},"game_code":"ABCD-1234"}}"#,
+            r#"{"type":"WaitlistClosed"}"#,
+            r#"{"type":"LobbySettings","data":{"min_players":2,"max_players":6,"require_invite":false,"master_idle_rotate_secs":0,"enable_waitlist":true}}"#,
+            r#"{"type":"SeatVacancyChanged","data":{"player_id":1,"vacancy":null}}"#,
+            r#"{"type":"BufferWarning","data":{"buffer":"game_log","capacity":10000,"used":8000}}"#,
+            r#"{"type":"GameAnnotated","data":{"title":"Close game","notes":"","editor":1}}"#,
+            r#"{"type":"Resync"}"#,
+        ];
+        for json in events {
+            let event: GameEvent = from_str(json).unwrap();
+            assert_eq!(json, to_string(&event).unwrap());
+            assert_eq!(from_str::<Value>(json).unwrap()["type"], event.name());
+            // the msgpack encoding decodes to the same values
+            let data = EventData::new(None, GameCode::new(['A'; 8]).unwrap(), event).unwrap();
+            assert_eq!(Some(to_value(data.compact()).unwrap()), from_msgpack(&to_msgpack(&data)), "{}", json);
+        }
+        assert!(from_str::<GameEvent>(r#"{"type":"AddPlayer","data":"Bob"}"#).is_err());
+    }
+
+    #[test]
+    fn test_legacy_payload() {
+        let event: GameEvent = from_str(r#"{"type":"StreamClosing","data":{"reason":"kicked","retryable":false}}"#).unwrap();
+        assert_eq!(Some(r#"{"reason":"kicked","retryable":false}"#), event.legacy_payload().as_deref());
+        assert_eq!(Some("5E"), GameEvent::TilePlaced(Position::parse("5E").unwrap()).legacy_payload().as_deref());
+        assert_eq!(Some("imperial"), GameEvent::ChainFounded(HotelChain::Imperial).legacy_payload().as_deref());
+        assert_eq!(Some("3"), GameEvent::TurnChanged(3).legacy_payload().as_deref());
+        assert_eq!(Some("\"quoted\""), GameEvent::LobbyMessage(String::from("\"quoted\"")).legacy_payload().as_deref());
+        assert_eq!(None, GameEvent::GameStarted.legacy_payload());
+        // both formats are send side by side
+        let data = to_value(EventData::new(None, GameCode::new(['A'; 8]).unwrap(), GameEvent::TurnChanged(3)).unwrap()).unwrap();
+        assert_eq!(json!(["TurnChanged", "3"]), data["data"]);
+        assert_eq!(json!({"type": "TurnChanged", "data": 3}), data["event"]);
+    }
+
+    #[test]
+    fn test_plain_text_tables() {
+        let players = PlayersInGame(vec![
+            PlayerListEntry { player_id: 1, seat: 1, name: String::from("Alice"), connected: true, game_master: true, vacancy: None },
+            PlayerListEntry { player_id: 4, seat: 2, name: String::from("Bartholomew"), connected: false, game_master: false, vacancy: None },
+            PlayerListEntry { player_id: 2, seat: 3, name: String::from("Zoë"), connected: true, game_master: false, vacancy: None },
+        ]);
+        assert_plain_text("players_in_game", &players);
+        // disconnected players are not part of the json
+        assert_eq!(r#"["Alice","Zoë"]"#, rocket::serde::json::to_string(&players).unwrap());
+
+        let usage = RouteUsageReport {
+            active: vec![RouteUsageEntry { route: String::from("GET /api/status"), count: 12, last_used: Some(1700000000) }],
+            deprecated: vec![RouteUsageEntry { route: String::from("GET /api/players_in_game"), count: 0, last_used: None }],
+        };
+        let abandonment = AbandonmentReport {
+            timeout_secs: 20,
+            buckets: vec![RecoveryBucket { below_secs: Some(5), count: 3 }, RecoveryBucket { below_secs: None, count: 1 }],
+            recovered: 4,
+            deleted: 12,
+            recovery_ratio: Some(0.25),
+        };
+        let analytics = AnalyticsReport {
+            created: SourceCounts { main_page: 5, deep_link: 0, unknown: 1 },
+            joined: SourceCounts { main_page: 2, deep_link: 9, unknown: 0 },
+            languages: [(String::from("de"), 10), (String::from("en"), 7)].into_iter().collect(),
+        };
+        let status = ServerStatus { protocol_version: PROTOCOL_VERSION, maintenance: true, maintenance_message: Some(String::from("Restart at 10:00")), active_games: 4, route_usage: Some(usage), abandonment: Some(abandonment), analytics: Some(analytics) };
+        assert_plain_text("server_status", &status);
+    }
+
+    #[test]
+    fn test_merge_patch() {
+        // examples of RFC 7396, appendix A
+        let cases = [
+            (json!({"a": "b"}), json!({"a": "c"}), json!({"a": "c"})),
+            (json!({"a": "b"}), json!({"b": "c"}), json!({"a": "b", "b": "c"})),
+            (json!({"a": "b"}), json!({"a": null}), json!({})),
+            (json!({"a": "b", "b": "c"}), json!({"a": null}), json!({"b": "c"})),
+            (json!({"a": ["b"]}), json!({"a": "c"}), json!({"a": "c"})),
+            (json!({"a": {"b": "c"}}), json!({"a": {"b": "d", "c": null}}), json!({"a": {"b": "d"}})),
+            (json!({"a": [{"b": "c"}]}), json!({"a": [1]}), json!({"a": [1]})),
+            (json!({"e": null}), json!({"a": 1}), json!({"e": null, "a": 1})),
+            (json!([1, 2]), json!({"a": "b", "c": null}), json!({"a": "b"})),
+            (json!({}), json!({"a": {"bb": {"ccc": null}}}), json!({"a": {"bb": {}}})),
+            (json!({"a": "foo"}), json!(null), json!(null)),
+        ];
+        for (mut target, patch, expected) in cases {
+            merge_patch(&mut target, &patch);
+            assert_eq!(expected, target, "patch {}", patch);
+        }
+    }
 }
\ No newline at end of file
@@ -0,0 +1,89 @@
+use std::fmt::{self, Display, Formatter};
+
+/// The longest name a player can have, in characters
+pub const MAX_PLAYER_NAME_LENGTH: usize = 20;
+
+/// All characters that can be used to generate a game code
+pub const GAME_CODE_CHARSET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWZ";
+
+/// Checks that `name` can be used as name of a player.
+///
+/// - leading and trailing whitespace is removed
+/// - the name is not empty and at most [MAX_PLAYER_NAME_LENGTH]() characters long
+/// - the name does not contain control characters
+///
+/// # Returns
+/// The trimmed name.
+pub fn validate_player_name(name: &str) -> Result<&str, PlayerNameError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(PlayerNameError::Empty);
+    }
+    if name.chars().count() > MAX_PLAYER_NAME_LENGTH {
+        return Err(PlayerNameError::TooLong);
+    }
+    if name.chars().any(char::is_control) {
+        return Err(PlayerNameError::InvalidCharacter);
+    }
+    Ok(name)
+}
+
+/// The reasons why a player name is invalid, see [validate_player_name]().
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayerNameError {
+    Empty,
+    TooLong,
+    InvalidCharacter,
+}
+
+impl Display for PlayerNameError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            PlayerNameError::Empty => write!(f, "the username is empty"),
+            PlayerNameError::TooLong => write!(f, "the username is longer than {} characters", MAX_PLAYER_NAME_LENGTH),
+            PlayerNameError::InvalidCharacter => write!(f, "the username contains invalid characters"),
+        }
+    }
+}
+
+impl std::error::Error for PlayerNameError {}
+
+/// Parses a game code formatted like `A23B-9FRT`.
+///
+/// Surrounding whitespace is ignored and lowercase letters are accepted, the code has to consist of two groups of
+/// four characters of the [GAME_CODE_CHARSET]() separated by `-`.
+///
+/// # Returns
+/// The eight characters of the code without the separator, `None` when `input` is not a valid game code.
+pub fn parse_game_code(input: &str) -> Option<[char; 8]> {
+    let input = input.trim();
+    let (first, second) = input.split_once('-')?;
+    let mut game_code = ['0'; 8];
+    let mut chars = first.chars().chain(second.chars());
+    for slot in &mut game_code {
+        let char = chars.next()?.to_ascii_uppercase();
+        if !char.is_ascii() || !GAME_CODE_CHARSET.contains(&(char as u8)) {
+            return None;
+        }
+        *slot = char;
+    }
+    if first.chars().count() != 4 || chars.next().is_some() {
+        return None;
+    }
+    Some(game_code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_game_code;
+
+    #[test]
+    fn test_parse_game_code() {
+        let code = ['A', '2', '3', 'B', '9', 'F', 'R', 'T'];
+        assert_eq!(Some(code), parse_game_code("A23B-9FRT"));
+        assert_eq!(Some(code), parse_game_code(" a23b-9frt\n"));
+        for invalid in ["", "A23B9FRT", "A23-B9FRT", "A23B-9FR", "A23B-9FRTT", "AB", "A23B-9FRX", "A23B-9FR\u{c4}", "A23B--9FR"] {
+            assert_eq!(None, parse_game_code(invalid), "{}", invalid);
+        }
+    }
+}
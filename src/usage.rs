@@ -0,0 +1,257 @@
+use std::{collections::{BTreeMap, HashMap}, path::{Path, PathBuf}, sync::{atomic::{AtomicU64, Ordering}, Arc}, time::{Duration, SystemTime, UNIX_EPOCH}};
+
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    http::Header,
+    log::private::{info, warn},
+    serde::json::{from_str, to_string},
+    tokio::{self, time::{interval, MissedTickBehavior}},
+    Orbit, Request, Response, Rocket, Route,
+};
+use serde::{Deserialize, Serialize};
+
+/// The default interval in which the counters are written to the usage file
+pub const DEFAULT_SAVE_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Routes that are planned to be removed, formatted as `<method> <uri>`.
+///
+/// Responses of these routes get a `Deprecation` header and they are listed separately in the [RouteUsageReport]().
+pub const DEPRECATED_ROUTES: &[&str] = &[
+    // Superseded by the `PlayerList` event of the sse stream
+    "GET /api/players_in_game",
+];
+
+/// How often a single route was used.
+#[derive(Debug, Default)]
+struct RouteCounter {
+    count: AtomicU64,
+    /// Unix seconds of the last request, 0 when the route was never used
+    last_used: AtomicU64,
+}
+
+/// The usage of a single route as it is stored in the usage file and shown in the [RouteUsageReport]().
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteUsageEntry {
+    /// The route formatted as `<method> <uri>`
+    pub route: String,
+    pub count: u64,
+    /// Unix seconds of the last request
+    pub last_used: Option<u64>,
+}
+
+/// The usage of all mounted routes, split into deprecated and active routes.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteUsageReport {
+    pub active: Vec<RouteUsageEntry>,
+    pub deprecated: Vec<RouteUsageEntry>,
+}
+
+/// Anonymous usage counters for all mounted routes, managed by rocket.
+///
+/// Only the number of requests and the time of the last request are counted, nothing about the users is stored.
+/// The counters are used to check that a deprecated route is no longer used before it is removed.
+///
+/// When `usage_file` is set in the rocket configuration the counters are loaded from this file on startup
+/// and written back every `usage_save_interval_secs` (see [DEFAULT_SAVE_INTERVAL]()) and on shutdown.
+/// Writing the file is best-effort, failures are only logged.
+///
+/// Clones share the same counters.
+#[derive(Clone)]
+pub struct RouteUsage {
+    counters: Arc<HashMap<String, RouteCounter>>,
+    file: Option<PathBuf>,
+}
+
+impl RouteUsage {
+    /// Creates counters for `routes`, the previous counts are loaded from `file` if it exists.
+    pub fn new(routes: &[Route], file: Option<PathBuf>) -> Self {
+        let mut counters: HashMap<String, RouteCounter> = routes.iter().map(|route| (route_key(route), RouteCounter::default())).collect();
+        if let Some(path) = &file {
+            for entry in load(path) {
+                if let Some(counter) = counters.get_mut(&entry.route) {
+                    *counter.count.get_mut() = entry.count;
+                    *counter.last_used.get_mut() = entry.last_used.unwrap_or_default();
+                }
+            }
+        }
+        Self { counters: Arc::new(counters), file }
+    }
+
+    /// Counts a request to `route`, requests to routes that are not known are ignored.
+    pub fn record(&self, route: &Route) {
+        if let Some(counter) = self.counters.get(&route_key(route)) {
+            counter.count.fetch_add(1, Ordering::Relaxed);
+            counter.last_used.store(unix_time(), Ordering::Relaxed);
+        }
+    }
+
+    /// Returns the usage of all routes sorted by route.
+    pub fn report(&self) -> RouteUsageReport {
+        let entries: BTreeMap<&String, &RouteCounter> = self.counters.iter().collect();
+        let (deprecated, active) = entries.into_iter()
+            .map(|(route, counter)| RouteUsageEntry {
+                route: route.clone(),
+                count: counter.count.load(Ordering::Relaxed),
+                last_used: Some(counter.last_used.load(Ordering::Relaxed)).filter(|time| *time > 0),
+            })
+            .partition(|entry| is_deprecated(&entry.route));
+        RouteUsageReport { active, deprecated }
+    }
+
+    /// Writes the counters to the usage file, does nothing when no file is configured.
+    pub fn save(&self) {
+        let path = match &self.file {
+            Some(path) => path,
+            None => return,
+        };
+        let report = self.report();
+        let entries: Vec<RouteUsageEntry> = report.active.into_iter().chain(report.deprecated).collect();
+        let result = to_string(&entries)
+            .map_err(|err| err.to_string())
+            .and_then(|json| std::fs::write(path, json).map_err(|err| err.to_string()));
+        match result {
+            Ok(()) => info!("Route usage saved to {}", path.display()),
+            Err(err) => warn!("Unable to save route usage to {}: {}", path.display(), err),
+        }
+    }
+}
+
+/// Fairing that counts the requests to each route in [RouteUsage]() and marks the responses of [DEPRECATED_ROUTES]().
+pub struct UsageCounter {
+    pub save_interval: Duration,
+}
+
+#[rocket::async_trait]
+impl Fairing for UsageCounter {
+    fn info(&self) -> Info {
+        Info {
+            name: "Route usage counter",
+            kind: Kind::Liftoff | Kind::Response | Kind::Shutdown,
+        }
+    }
+
+    async fn on_liftoff(&self, rocket: &Rocket<Orbit>) {
+        let usage = match rocket.state::<RouteUsage>() {
+            Some(usage) if usage.file.is_some() => usage.clone(),
+            _ => return,
+        };
+        let mut shutdown = rocket.shutdown();
+        let mut save = interval(self.save_interval);
+        save.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        // The first tick completes immediately
+        save.tick().await;
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    _ = save.tick() => usage.save(),
+                    _ = &mut shutdown => break,
+                }
+            }
+        });
+    }
+
+    async fn on_response<'r>(&self, request: &'r Request<'_>, response: &mut Response<'r>) {
+        let route = match request.route() {
+            Some(route) => route,
+            None => return,
+        };
+        if let Some(usage) = request.rocket().state::<RouteUsage>() {
+            usage.record(route);
+        }
+        if is_deprecated(&route_key(route)) {
+            response.set_header(Header::new("Deprecation", "true"));
+        }
+    }
+
+    async fn on_shutdown(&self, rocket: &Rocket<Orbit>) {
+        if let Some(usage) = rocket.state::<RouteUsage>() {
+            usage.save();
+        }
+    }
+}
+
+/// Returns the name of the route as it is used in the counters, formatted as `<method> <uri>`.
+fn route_key(route: &Route) -> String {
+    format!("{} {}", route.method, route.uri)
+}
+
+fn is_deprecated(route: &str) -> bool {
+    DEPRECATED_ROUTES.contains(&route)
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_secs()).unwrap_or_default()
+}
+
+/// Reads the entries of the usage file at `path`.
+///
+/// A missing or corrupted file is treated as empty so that the server can always start.
+fn load(path: &Path) -> Vec<RouteUsageEntry> {
+    match std::fs::read_to_string(path) {
+        Ok(json) => from_str(&json).unwrap_or_else(|err| {
+            warn!("Ignoring corrupted route usage file {}: {}", path.display(), err);
+            Vec::new()
+        }),
+        Err(_) => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rocket::{http::{Header, Status}, local::blocking::Client};
+    use uuid::Uuid;
+
+    use crate::paths::all_routes;
+
+    use super::{RouteUsage, RouteUsageEntry};
+
+    fn count(entries: &[RouteUsageEntry], route: &str) -> u64 {
+        entries.iter().find(|entry| entry.route == route).unwrap().count
+    }
+
+    #[test]
+    fn test_only_hit_routes_are_counted() {
+        let figment = rocket::Config::figment().merge(("admin_token", "secret"));
+        let client = Client::tracked(crate::server(rocket::custom(figment))).unwrap();
+        assert_eq!(Status::NotFound, client.get("/api/whoami").dispatch().status());
+        assert_eq!(Status::NotFound, client.get("/api/whoami").dispatch().status());
+        let report = client.rocket().state::<RouteUsage>().unwrap().report();
+        assert_eq!(2, count(&report.active, "GET /api/whoami"));
+        assert!(report.active.iter().all(|entry| entry.route == "GET /api/whoami" || (entry.count == 0 && entry.last_used.is_none())));
+        assert_eq!(0, count(&report.deprecated, "GET /api/players_in_game"));
+
+        // The report is only included for admins
+        let status: rocket::serde::json::Value = client.get("/api/status").dispatch().into_json().unwrap();
+        assert!(status.get("route_usage").is_none());
+        let status: rocket::serde::json::Value = client.get("/api/status").header(Header::new("admin_token", "secret")).dispatch().into_json().unwrap();
+        assert_eq!(2, status["route_usage"]["active"].as_array().unwrap().iter().find(|entry| entry["route"] == "GET /api/whoami").unwrap()["count"]);
+    }
+
+    #[test]
+    fn test_deprecation_header() {
+        let client = Client::tracked(crate::rocket()).unwrap();
+        let response = client.get("/api/players_in_game").header(Header::new("game_code", "AAAAAAAA")).dispatch();
+        assert_eq!(Some("true"), response.headers().get_one("Deprecation"));
+        let response = client.get("/api/status").dispatch();
+        assert_eq!(None, response.headers().get_one("Deprecation"));
+    }
+
+    #[test]
+    fn test_counts_survive_restart() {
+        let path = std::env::temp_dir().join(format!("acquire_rs_route_usage_{}.json", Uuid::new_v4()));
+        let routes = all_routes();
+        let usage = RouteUsage::new(&routes, Some(path.clone()));
+        let status = routes.iter().find(|route| route.uri == "/api/status").unwrap();
+        usage.record(status);
+        usage.record(status);
+        usage.save();
+
+        let restarted = RouteUsage::new(&routes, Some(path.clone()));
+        assert_eq!(usage.report(), restarted.report());
+        assert_eq!(2, count(&restarted.report().active, "GET /api/status"));
+        // A corrupted file does not prevent the start
+        std::fs::write(&path, "{").unwrap();
+        assert_eq!(0, count(&RouteUsage::new(&routes, Some(path.clone())).report().active, "GET /api/status"));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,176 @@
+use std::{
+    ops::{Deref, DerefMut},
+    sync::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+    time::{Duration, Instant},
+};
+
+use rocket::log::private::{info, warn};
+
+use crate::game::GameManager;
+
+/// Removes all tags like `<b>` from `text`, a `<` without a closing `>` is kept.
+///
+/// Used for texts that players write for other players, clients show them as plain text.
+pub fn strip_markup(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        let end = match rest[start..].find('>') {
+            Some(end) => start + end,
+            None => break,
+        };
+        stripped.push_str(&rest[..start]);
+        rest = &rest[end + 1..];
+    }
+    stripped.push_str(rest);
+    stripped
+}
+
+/// The time a game_manager lock can be held before a warning is logged when the lock is released.
+pub const GUARD_HELD_WARN_THRESHOLD: Duration = Duration::from_millis(500);
+
+/// Read guard for the game_manager, see [TimedGuard]().
+pub type GmReadGuard<'a> = TimedGuard<'a, RwLockReadGuard<'a, GameManager>>;
+
+/// Write guard for the game_manager, see [TimedGuard]().
+pub type GmWriteGuard<'a> = TimedGuard<'a, RwLockWriteGuard<'a, GameManager>>;
+
+/// Lock guard that remembers for what action and when it was acquired.
+/// 
+/// When the guard is dropped after it was held for longer than its threshold
+/// (by default [GUARD_HELD_WARN_THRESHOLD]()) a warning containing the action is logged.
+/// This makes it easy to find places where slow work is done while the game_manager is locked.
+pub struct TimedGuard<'a, G> {
+    guard: G,
+    action: &'a str,
+    acquired_at: Instant,
+    threshold: Duration,
+}
+
+impl<'a, G> TimedGuard<'a, G> {
+    fn new(guard: G, action: &'a str) -> Self {
+        Self {
+            guard,
+            action,
+            acquired_at: Instant::now(),
+            threshold: GUARD_HELD_WARN_THRESHOLD,
+        }
+    }
+
+    /// Sets the time after which a warning is logged when this guard is released.
+    pub fn warn_after(mut self, threshold: Duration) -> Self {
+        self.threshold = threshold;
+        self
+    }
+
+    /// Returns the warning that is logged when this guard is released now.
+    /// 
+    /// # Returns
+    /// `None` when the guard was not held longer than its threshold.
+    pub fn held_warning(&self) -> Option<String> {
+        let held = self.acquired_at.elapsed();
+        if held <= self.threshold {
+            return None;
+        }
+        Some(format!("{}: game_manager lock was held for {}ms (threshold {}ms)", self.action, held.as_millis(), self.threshold.as_millis()))
+    }
+}
+
+impl<G: Deref> Deref for TimedGuard<'_, G> {
+    type Target = G::Target;
+
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<G: DerefMut> DerefMut for TimedGuard<'_, G> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+impl<G> Drop for TimedGuard<'_, G> {
+    fn drop(&mut self) {
+        if let Some(warning) = self.held_warning() {
+            warn!("{}", warning);
+        }
+    }
+}
+
+/// Tries to acquire the game_manager write lock.
+/// 
+/// If successful the game_manager is returned.
+/// 
+/// Otherwise the following message is send to console: 
+/// 
+/// `{action}: Waiting for game_manager write lock...`
+/// 
+/// After that the game_manager is returned when the write lock can be acquired.
+pub fn get_gm_write_guard<'a>(game_manager: &'a RwLock<GameManager>, action: &'a str) -> GmWriteGuard<'a> {
+    let guard = match game_manager.try_write() {
+        Ok(manager) => manager,
+        Err(_err) => {
+            info!("{}: Waiting for game_manager write lock...", action);
+            game_manager.write().unwrap()
+        }
+    };
+    TimedGuard::new(guard, action)
+}
+
+/// Tries to acquire the game_manager read lock.
+/// 
+/// If successful the game_manager is returned.
+/// 
+/// Otherwise the following message is send to console: 
+/// 
+/// `{action}: Waiting for game_manager read lock...`
+/// 
+/// After that the game_manager is returned when the read lock can be acquired.
+pub fn get_gm_read_guard<'a>(game_manager: &'a RwLock<GameManager>, action: &'a str) -> GmReadGuard<'a> {
+    let guard = match game_manager.try_read() {
+        Ok(manager) => manager,
+        Err(_err) => {
+            info!("{}: Waiting for game_manager read lock...", action);
+            game_manager.read().unwrap()
+        }
+    };
+    TimedGuard::new(guard, action)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::RwLock, thread, time::Duration};
+
+    use crate::game::GameManager;
+
+    use super::{get_gm_read_guard, get_gm_write_guard, strip_markup};
+
+    #[test]
+    fn test_slow_guard_warns() {
+        let game_manager = RwLock::new(GameManager::new());
+        let guard = get_gm_write_guard(&game_manager, "slow action").warn_after(Duration::from_millis(5));
+        thread::sleep(Duration::from_millis(20));
+        let warning = guard.held_warning().unwrap();
+        assert!(warning.starts_with("slow action: "), "{}", warning);
+    }
+
+    #[test]
+    fn test_fast_guard_does_not_warn() {
+        let game_manager = RwLock::new(GameManager::new());
+        let guard = get_gm_read_guard(&game_manager, "fast action");
+        assert!(!guard.does_game_exist(&crate::game::game_instance::GameCode::new(['A'; 8]).unwrap()));
+        assert_eq!(None, guard.held_warning());
+        drop(guard);
+        assert!(game_manager.try_write().is_ok());
+    }
+
+    #[test]
+    fn test_strip_markup() {
+        assert_eq!("Close game", strip_markup("<b>Close</b> game"));
+        assert_eq!("alert(1)", strip_markup("<script src=\"a.js\">alert(1)</script>"));
+        assert_eq!("1 < 2", strip_markup("1 < 2"));
+        assert_eq!("a > b", strip_markup("a > b"));
+        assert_eq!("", strip_markup("<>"));
+    }
+}
@@ -0,0 +1,114 @@
+use std::fs;
+
+use rocket::serde::json::{from_str, to_string, Value};
+use serde::{de::DeserializeOwned, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    authentication::Urid,
+    error::{ApiError, ApiErrorBody},
+    game::{base_game::{Vacancy, VacancyReason}, game_instance::{invites::InviteInfo, GameCode, LobbyStatus, PlayerListEntry}},
+    paths::sse::CloseReason,
+    request_data::{EventData, FieldError, GameEvent, ServerStatus, UserRegistration, PROTOCOL_VERSION},
+};
+
+/// Contains one json file per payload and the file `VERSIONS`.
+///
+/// `VERSIONS` records for each [PROTOCOL_VERSION]() the hash of all fixtures, a new line has to be added whenever a fixture changes.
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/wire_format");
+
+const UUID: &str = "67e55044-10b1-426f-9247-bb680e5fe0c8";
+
+/// Parses and serializes `json` again, this removes whitespace and sorts the keys of all objects.
+fn canonical(json: &str) -> String {
+    to_string(&from_str::<Value>(json).unwrap()).unwrap()
+}
+
+fn fixture(name: &str) -> String {
+    fs::read_to_string(format!("{}/{}.json", FIXTURES, name)).unwrap_or_else(|err| panic!("fixture {} could not be read: {}", name, err))
+}
+
+/// Checks that `value` serializes to the fixture `name` and that the fixture deserializes to the same value.
+fn assert_wire_format<T: Serialize + DeserializeOwned>(name: &str, value: &T) {
+    let fixture = canonical(&fixture(name));
+    assert_eq!(fixture, canonical(&to_string(value).unwrap()), "serialization of {} does not match the fixture", name);
+    let parsed: T = from_str(&fixture).unwrap_or_else(|err| panic!("fixture {} could not be deserialized: {}", name, err));
+    assert_eq!(fixture, canonical(&to_string(&parsed).unwrap()), "fixture {} does not survive a round trip", name);
+}
+
+fn game_code() -> GameCode {
+    GameCode::from_string("ABCD-1234").unwrap()
+}
+
+#[test]
+fn test_user_registration() {
+    let urid = Urid::from_cookie_value("9a1b2c3d-4e5f-4a6b-8c7d-0e1f2a3b4c5d:1700000000").unwrap();
+    assert_wire_format("user_registration", &UserRegistration::new(Uuid::parse_str(UUID).unwrap(), urid, game_code()));
+}
+
+#[test]
+fn test_event_data() {
+    assert_wire_format("event_broadcast", &EventData::new(None, game_code(), GameEvent::LobbyLocked).unwrap());
+    let targeted = EventData::new(Some(Uuid::parse_str(UUID).unwrap()), game_code(), GameEvent::StreamClosing { reason: CloseReason::Replaced, retryable: false }).unwrap();
+    assert_wire_format("event_targeted", &targeted);
+}
+
+#[test]
+fn test_error_bodies() {
+    let error = ApiError::bad_request("invalid_min_players").with_detail("min_players has to be between 2 and 6");
+    assert_wire_format::<ApiErrorBody>("error", &error.body());
+    assert_wire_format::<ApiErrorBody>("error_without_detail", &ApiError::forbidden("name_taken").body());
+    let fields = vec![FieldError::new("max_players", "out_of_range", 2..=6), FieldError::new("min_players", "greater_than_max_players", 2..=4)];
+    assert_wire_format::<ApiErrorBody>("error_with_fields", &ApiError::invalid_fields(fields).body());
+}
+
+#[test]
+fn test_player_lists() {
+    assert_wire_format("players_in_game", &vec![String::from("Alice"), String::from("Bob")]);
+    let players = vec![
+        PlayerListEntry { player_id: 1, seat: 1, name: String::from("Alice"), connected: true, game_master: true, vacancy: None },
+        PlayerListEntry { player_id: 2, seat: 2, name: String::from("Bob"), connected: false, game_master: false, vacancy: Some(Vacancy { reason: VacancyReason::ConnectionLost, since: 1700000000 }) },
+    ];
+    assert_wire_format("player_list", &players);
+}
+
+#[test]
+fn test_lobby_payloads() {
+    assert_wire_format("lobby_status", &LobbyStatus { current_players: 2, min_players: 3, max_players: 6, can_start: false });
+    let invite = InviteInfo { id: Uuid::parse_str("0f8fad5b-d9cb-469f-a165-70867728950e").unwrap(), uses_left: 3, expires_in_secs: 3600 };
+    assert_wire_format("invite_info", &invite);
+    let status = ServerStatus { protocol_version: PROTOCOL_VERSION, maintenance: true, maintenance_message: Some(String::from("Restart at 10:00")), active_games: 4, route_usage: None, abandonment: None, analytics: None };
+    assert_wire_format("server_status", &status);
+}
+
+/// Fails when a fixture was changed without adding a new [PROTOCOL_VERSION]() to `VERSIONS`.
+#[test]
+fn test_protocol_version_matches_fixtures() {
+    let mut names: Vec<String> = fs::read_dir(FIXTURES).unwrap()
+        .filter_map(|entry| entry.unwrap().file_name().into_string().ok())
+        .filter_map(|name| name.strip_suffix(".json").map(String::from))
+        .collect();
+    names.sort();
+    let hash = names.iter()
+        .flat_map(|name| [name.clone(), canonical(&fixture(name))])
+        .flat_map(|part| part.into_bytes())
+        .fold(0xcbf29ce484222325_u64, |hash, byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3));
+    let versions: Vec<(u32, String)> = fs::read_to_string(format!("{}/VERSIONS", FIXTURES)).unwrap()
+        .lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let (version, hash) = line.split_once(' ').unwrap();
+            (version.parse().unwrap(), String::from(hash.trim()))
+        })
+        .collect();
+    assert!(versions.windows(2).all(|pair| pair[0].0 < pair[1].0), "versions in VERSIONS have to increase");
+    let (version, recorded) = versions.last().unwrap();
+    let hash = format!("{:016x}", hash);
+    assert_eq!(&hash, recorded, "the fixtures changed, increase PROTOCOL_VERSION and add `{} {}` to VERSIONS", PROTOCOL_VERSION + 1, hash);
+    assert_eq!(PROTOCOL_VERSION, *version, "PROTOCOL_VERSION does not match the last version in VERSIONS");
+}
+
+#[test]
+fn test_canonical_ignores_formatting() {
+    assert_eq!(canonical(r#"{"b": 1, "a": [1, 2]}"#), canonical("{\"a\":[1,2],\n \"b\":1}"));
+}
@@ -0,0 +1,48 @@
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use js_sys::JSON;
+use serde::Deserialize;
+use serde_json::Value;
+use wasm_bindgen::prelude::*;
+
+/// Decodes the data of an event that was received on a sse stream opened with `?encoding=msgpack`.
+///
+/// The data is base64 encoded msgpack, the name of the event is already known from the `event` field of the sse event.
+///
+/// # Returns
+/// The event as object `{user_id, game_code, event}`, like the json form of the event without the legacy `data` pair,
+/// or `null` when `data` is not a valid event.
+#[wasm_bindgen]
+pub fn decode_event(data: &str) -> JsValue {
+    match decode(data) {
+        Some(event) => JSON::parse(&event.to_string()).unwrap_or(JsValue::NULL),
+        None => JsValue::NULL,
+    }
+}
+
+fn decode(data: &str) -> Option<Value> {
+    let bytes = BASE64.decode(data.trim()).ok()?;
+    // The server writes uuids as strings like in json
+    let mut deserializer = rmp_serde::Deserializer::new(&bytes[..]).with_human_readable();
+    Value::deserialize(&mut deserializer).ok().filter(Value::is_object)
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::Engine;
+    use serde_json::json;
+    use wasm_bindgen_test::*;
+
+    use super::{decode, decode_event, BASE64};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[wasm_bindgen_test]
+    fn test_decode_event() {
+        let event = json!({"user_id": "", "game_code": "ABCD-1234", "event": {"type": "TurnChanged", "data": 3}});
+        let encoded = BASE64.encode(rmp_serde::to_vec_named(&event).unwrap());
+        assert_eq!(Some(event), decode(&encoded));
+        assert!(!decode_event(&encoded).is_null());
+        assert_eq!(None, decode("not base64"));
+        assert!(decode_event(&BASE64.encode([0xc0])).is_null());
+    }
+}
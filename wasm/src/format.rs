@@ -0,0 +1,198 @@
+use std::cell::Cell;
+
+use js_sys::{Array, Date, Intl, Object, Reflect};
+use wasm_bindgen::prelude::*;
+
+/// The units used by [format_relative](), with their length in seconds, the largest unit first
+const RELATIVE_UNITS: [(&str, f64); 4] = [("day", 86400.0), ("hour", 3600.0), ("minute", 60.0), ("second", 1.0)];
+
+thread_local! {
+    /// How many milliseconds the clock of the server is ahead of the clock of the browser, see [sync_clock]()
+    static CLOCK_OFFSET: Cell<f64> = const { Cell::new(0.0) };
+}
+
+/// Corrects all following relative times for the difference between the clock of the browser and the clock of the server.
+///
+/// `server_millis` is the time returned by `/api/time`, `sent_at` and `received_at` are the times of the browser
+/// at which the request was send and the response arrived. The server time is assumed to be taken halfway in between.
+/// Invalid times are ignored.
+#[wasm_bindgen]
+pub fn sync_clock(server_millis: f64, sent_at: f64, received_at: f64) {
+    if let Some(offset) = clock_offset(server_millis, sent_at, received_at) {
+        CLOCK_OFFSET.with(|clock_offset| clock_offset.set(offset));
+    }
+}
+
+/// Returns the current time of the server in unix milliseconds as estimated by [sync_clock]().
+#[wasm_bindgen]
+pub fn server_now() -> f64 {
+    Date::now() + CLOCK_OFFSET.with(Cell::get)
+}
+
+fn clock_offset(server_millis: f64, sent_at: f64, received_at: f64) -> Option<f64> {
+    if !server_millis.is_finite() || !sent_at.is_finite() || !received_at.is_finite() || received_at < sent_at {
+        return None;
+    }
+    Some(server_millis - (sent_at + received_at) / 2.0)
+}
+
+/// Formats the point in time `unix_millis` as date and time in the locale of the browser, for example `16.10.2026, 14:05:00`.
+///
+/// When `Intl.DateTimeFormat` is not available the date is formatted as ISO 8601 string instead.
+///
+/// # Returns
+/// An empty string when `unix_millis` is not a valid point in time, for example when it is `NaN`.
+#[wasm_bindgen]
+pub fn format_timestamp(unix_millis: f64) -> String {
+    format_timestamp_with(unix_millis, intl_supports("DateTimeFormat"))
+}
+
+/// Formats a duration compactly with its two largest units, for example `1h 23m`, `12m 5s` or `45s`.
+///
+/// Seconds are dropped once the duration is longer than an hour and minutes once it is longer than a day.
+#[wasm_bindgen]
+pub fn format_duration(seconds: u32) -> String {
+    let units = [(seconds / 86400, "d"), (seconds / 3600 % 24, "h"), (seconds / 60 % 60, "m"), (seconds % 60, "s")];
+    match units.iter().position(|(value, _)| *value > 0) {
+        Some(first) => units[first..units.len().min(first + 2)].iter()
+            .filter(|(value, _)| *value > 0)
+            .map(|(value, unit)| format!("{}{}", value, unit))
+            .collect::<Vec<_>>()
+            .join(" "),
+        None => String::from("0s"),
+    }
+}
+
+/// Formats the point in time `unix_millis` of the server relative to now, for example `2 minutes ago` or `in 3 hours`.
+///
+/// The browser locale is used through `Intl.RelativeTimeFormat`, when it is not available the text is english.
+///
+/// # Returns
+/// An empty string when `unix_millis` is not a finite number.
+#[wasm_bindgen]
+pub fn format_relative(unix_millis: f64) -> String {
+    format_relative_with(unix_millis, server_now(), intl_supports("RelativeTimeFormat"))
+}
+
+fn format_timestamp_with(unix_millis: f64, use_intl: bool) -> String {
+    let date = Date::new(&JsValue::from_f64(unix_millis));
+    // Dates outside of the supported range are invalid, formatting them would throw
+    if date.get_time().is_nan() {
+        return String::new();
+    }
+    if use_intl {
+        let format = Intl::DateTimeFormat::new(&Array::new(), &Object::new()).format();
+        if let Some(text) = format.call1(&JsValue::UNDEFINED, &date).ok().and_then(|text| text.as_string()) {
+            return text;
+        }
+    }
+    date.to_iso_string().into()
+}
+
+fn format_relative_with(unix_millis: f64, now: f64, use_intl: bool) -> String {
+    if !unix_millis.is_finite() || !now.is_finite() {
+        return String::new();
+    }
+    let (value, unit) = relative_value((unix_millis - now) / 1000.0);
+    if use_intl {
+        let options = Object::new();
+        let _e = Reflect::set(&options, &"numeric".into(), &"auto".into());
+        return Intl::RelativeTimeFormat::new(&Array::new(), &options).format(value, unit).into();
+    }
+    english_relative(value, unit)
+}
+
+/// Returns the largest unit of which the difference is at least one, and the whole number of these units.
+fn relative_value(diff_seconds: f64) -> (f64, &'static str) {
+    for (unit, length) in RELATIVE_UNITS {
+        let value = (diff_seconds / length).trunc();
+        if value != 0.0 {
+            return (value, unit);
+        }
+    }
+    (0.0, "second")
+}
+
+fn english_relative(value: f64, unit: &str) -> String {
+    if value == 0.0 {
+        return String::from("now");
+    }
+    let amount = value.abs();
+    let plural = if amount == 1.0 { "" } else { "s" };
+    if value < 0.0 {
+        format!("{} {}{} ago", amount, unit, plural)
+    } else {
+        format!("in {} {}{}", amount, unit, plural)
+    }
+}
+
+/// Checks if the browser provides the constructor `Intl.<name>`.
+fn intl_supports(name: &str) -> bool {
+    Reflect::get(&js_sys::global(), &"Intl".into()).ok()
+        .filter(|intl| intl.is_object())
+        .and_then(|intl| Reflect::has(&intl, &name.into()).ok())
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use wasm_bindgen_test::*;
+
+    use js_sys::Date;
+
+    use super::{clock_offset, format_duration, format_relative, format_relative_with, format_timestamp, format_timestamp_with, server_now, sync_clock};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    #[wasm_bindgen_test]
+    fn test_format_duration() {
+        assert_eq!("0s", format_duration(0));
+        assert_eq!("45s", format_duration(45));
+        assert_eq!("12m 5s", format_duration(725));
+        assert_eq!("1h 23m", format_duration(4980));
+        assert_eq!("1h", format_duration(3605));
+        assert_eq!("2d 3h", format_duration(2 * 86400 + 3 * 3600 + 59));
+        assert_eq!("49710d 6h", format_duration(u32::MAX));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_fallback_without_intl() {
+        assert_eq!("1970-01-01T00:00:00.000Z", format_timestamp_with(0.0, false));
+        let now = 1_000_000_000.0;
+        assert_eq!("now", format_relative_with(now + 400.0, now, false));
+        assert_eq!("2 minutes ago", format_relative_with(now - 150_000.0, now, false));
+        assert_eq!("in 1 hour", format_relative_with(now + 3_600_000.0, now, false));
+        assert_eq!("3 days ago", format_relative_with(now - 3.0 * 86_400_000.0, now, false));
+    }
+
+    #[wasm_bindgen_test]
+    fn test_clock_offset() {
+        // the server is an hour ahead, the request took 200ms
+        assert_eq!(Some(3_600_000.0), clock_offset(1_003_600_100.0, 1_000_000_000.0, 1_000_000_200.0));
+        assert_eq!(Some(-500.0), clock_offset(999_999_500.0, 1_000_000_000.0, 1_000_000_000.0));
+        assert_eq!(None, clock_offset(f64::NAN, 0.0, 0.0));
+        assert_eq!(None, clock_offset(0.0, 10.0, 0.0));
+
+        let now = Date::now();
+        sync_clock(now + 3_600_000.0, now, now);
+        assert!(server_now() - Date::now() >= 3_599_000.0);
+        assert_eq!("now", format_relative_with(Date::now() + 3_600_000.0, server_now(), false));
+        sync_clock(f64::NAN, now, now);
+        assert!(server_now() - Date::now() >= 3_599_000.0);
+        sync_clock(now, now, now);
+    }
+
+    #[wasm_bindgen_test]
+    fn test_invalid_input() {
+        for invalid in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY, 1e300] {
+            assert_eq!("", format_timestamp(invalid));
+            assert_eq!("", format_timestamp_with(invalid, false));
+        }
+        assert_eq!("", format_relative(f64::NAN));
+        assert_eq!("", format_relative_with(f64::NAN, 0.0, false));
+        // Dates before 1970 are valid
+        assert_eq!("1969-12-31T23:59:59.000Z", format_timestamp_with(-1000.0, false));
+        assert!(!format_timestamp(-1000.0).is_empty());
+        assert!(!format_relative(-1000.0).is_empty());
+    }
+}
@@ -4,7 +4,14 @@ use wasm_bindgen::prelude::*;
 use wasm_bindgen::JsCast;
 use web_sys::console;
 
+mod events;
+mod format;
 mod lobby;
+mod validation;
+
+/// The rules for player names and game codes of the server, compiled into the module so that both use the same implementation
+#[path = "../../src/rules.rs"]
+mod rules;
 
 #[cfg(test)]
 mod tests {
@@ -1,6 +1,11 @@
-use web_sys::console;
+use std::collections::HashMap;
+
+use serde::Deserialize;
+use web_sys::{console, Document, Element};
 use wasm_bindgen::prelude::*;
 
+use crate::format::format_relative;
+
 /// Initialize the main lobby state
 #[no_mangle]
 pub extern fn init() {
@@ -15,8 +20,9 @@ pub extern fn init_lobby() {
 
 /// Adds the player to the player list
 /// `highlighted` -  set true the player will be added highlighted
+/// `player_id` - the id of the player send by the server, stored in the `data-player-id` attribute of the entry
 #[wasm_bindgen]
-pub fn add_player(name: &str, highlighted: bool) {
+pub fn add_player(name: &str, highlighted: bool, player_id: Option<u32>) {
     console::log_1(&name.into());
     let document = web_sys::window().unwrap().document().unwrap();
     let div = document.create_element("li").unwrap();
@@ -27,6 +33,201 @@ pub fn add_player(name: &str, highlighted: bool) {
         div.set_class_name("list-group-item");
         console::log_1(&"Not Highlighted".into());
     }
+    if let Some(player_id) = player_id {
+        let _e = div.set_attribute("data-player-id", &player_id.to_string());
+    }
     div.set_inner_html(name);
     let _e = document.get_element_by_id("player-list").unwrap().append_child(&div);
-}
\ No newline at end of file
+}
+
+/// Enables or disables the start game button depending on the lobby status send by the server.
+/// 
+/// When the game can not be started yet the button shows how many players are still missing.
+#[wasm_bindgen]
+pub fn update_start_button(can_start: bool, current_players: usize, min_players: usize) {
+    let document = web_sys::window().unwrap().document().unwrap();
+    let button = document.get_element_by_id("start-game-button").unwrap();
+    if can_start {
+        let _e = button.remove_attribute("disabled");
+        button.set_inner_html("Start game");
+    } else {
+        let _e = button.set_attribute("disabled", "");
+        let missing = min_players.saturating_sub(current_players);
+        button.set_inner_html(&format!("Waiting for {} more player{}", missing, if missing == 1 { "" } else { "s" }));
+    }
+}
+
+/// A player of the `PlayerList` event send by the server.
+#[derive(Deserialize)]
+struct PlayerListEntry {
+    player_id: u32,
+    name: String,
+    connected: bool,
+    #[serde(default)]
+    game_master: bool,
+    #[serde(default)]
+    vacancy: Option<Vacancy>,
+}
+
+/// Why the seat of a disconnected player is vacant.
+#[derive(Deserialize)]
+struct Vacancy {
+    /// `left` or `connection_lost`
+    reason: String,
+    /// Unix seconds at which the seat became vacant
+    since: f64,
+}
+
+impl Vacancy {
+    /// Returns the badge text, for example `left 2 minutes ago`.
+    fn text(&self) -> String {
+        let reason = match self.reason.as_str() {
+            "left" => "left",
+            _ => "connection lost",
+        };
+        format!("{} {}", reason, format_relative(self.since * 1000.0))
+    }
+}
+
+/// Updates the player list to match the snapshot of a `PlayerList` event.
+/// 
+/// Instead of rebuilding the list, entries are matched by their `data-player-id` attribute:
+/// existing entries are updated in place, missing entries are inserted in seat order and entries of players that are no longer part of the snapshot are removed.
+/// Entries that did not change are not touched, this prevents flickering and keeps hover and focus state.
+/// 
+/// `snapshot_json` - the data of the `PlayerList` event
+/// `user_name` - the name of the own player, this entry is highlighted
+#[wasm_bindgen]
+pub fn sync_player_list(snapshot_json: &str, user_name: &str) -> Result<(), JsValue> {
+    let snapshot: Vec<PlayerListEntry> = serde_json::from_str(snapshot_json).map_err(|err| JsValue::from_str(&err.to_string()))?;
+    let document = web_sys::window().unwrap().document().unwrap();
+    let list = document.get_element_by_id("player-list").ok_or_else(|| JsValue::from_str("player-list not found"))?;
+    sync_list(&document, &list, &snapshot, user_name)
+}
+
+fn sync_list(document: &Document, list: &Element, snapshot: &[PlayerListEntry], user_name: &str) -> Result<(), JsValue> {
+    // Collect the entries that are kept and remove all others
+    let mut existing: HashMap<u32, Element> = HashMap::new();
+    let children = list.children();
+    let mut stale = Vec::new();
+    for i in 0..children.length() {
+        let child = children.item(i).unwrap();
+        match child.get_attribute("data-player-id").and_then(|id| id.parse::<u32>().ok()) {
+            Some(id) if snapshot.iter().any(|player| player.player_id == id) && !existing.contains_key(&id) => {
+                existing.insert(id, child);
+            },
+            _ => stale.push(child),
+        }
+    }
+    for child in stale {
+        child.remove();
+    }
+    // Walk the snapshot in seat order and only move entries that are not already at the right position
+    let mut previous: Option<Element> = None;
+    for player in snapshot {
+        let entry = match existing.remove(&player.player_id) {
+            Some(entry) => entry,
+            None => {
+                let entry = document.create_element("li")?;
+                entry.set_attribute("data-player-id", &player.player_id.to_string())?;
+                entry
+            },
+        };
+        update_entry(&entry, player, user_name);
+        let expected = match &previous {
+            Some(previous) => previous.next_element_sibling(),
+            None => list.first_element_child(),
+        };
+        if !expected.as_ref().is_some_and(|expected| expected.is_same_node(Some(&entry))) {
+            list.insert_before(&entry, expected.as_deref())?;
+        }
+        previous = Some(entry);
+    }
+    Ok(())
+}
+
+/// Sets the text and styling of `entry`, properties that did not change are not written.
+fn update_entry(entry: &Element, player: &PlayerListEntry, user_name: &str) {
+    let mut class_name = String::from("list-group-item");
+    if player.name == user_name {
+        class_name.push_str(" list-group-item-primary");
+    }
+    if player.game_master {
+        class_name.push_str(" fw-bold");
+    }
+    if !player.connected {
+        class_name.push_str(" text-muted");
+    }
+    if entry.class_name() != class_name {
+        entry.set_class_name(&class_name);
+    }
+    let mut text = player.name.clone();
+    if player.game_master {
+        text.push_str(" \u{2605}");
+    }
+    match &player.vacancy {
+        Some(vacancy) if !player.connected => text.push_str(&format!(" ({})", vacancy.text())),
+        _ if !player.connected => text.push_str(" (disconnected)"),
+        _ => (),
+    }
+    if entry.text_content().as_deref() != Some(text.as_str()) {
+        entry.set_text_content(Some(&text));
+    }
+    // entries rendered by the server are now kept up to date by the events
+    let _e = entry.remove_attribute("data-ssr");
+}
+
+#[cfg(test)]
+mod tests {
+    use wasm_bindgen_test::*;
+    use web_sys::Element;
+
+    use super::{sync_list, PlayerListEntry, Vacancy};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    fn player(player_id: u32, name: &str, connected: bool) -> PlayerListEntry {
+        PlayerListEntry { player_id, name: String::from(name), connected, game_master: player_id == 1, vacancy: None }
+    }
+
+    fn entries(list: &Element) -> Vec<Element> {
+        let children = list.children();
+        (0..children.length()).map(|i| children.item(i).unwrap()).collect()
+    }
+
+    #[wasm_bindgen_test]
+    fn test_sync_keeps_unchanged_nodes() {
+        let document = web_sys::window().unwrap().document().unwrap();
+        let list = document.create_element("ul").unwrap();
+        sync_list(&document, &list, &[player(1, "a", true), player(2, "b", true), player(3, "c", true)], "b").unwrap();
+        let initial = entries(&list);
+        assert_eq!(3, initial.len());
+        assert_eq!(Some(String::from("a \u{2605}")), initial[0].text_content());
+        assert!(initial[1].class_name().contains("list-group-item-primary"));
+
+        // b disconnects, c leaves, d joins and a renames
+        sync_list(&document, &list, &[player(1, "x", true), player(2, "b", false), player(4, "d", true)], "b").unwrap();
+        let synced = entries(&list);
+        assert_eq!(3, synced.len());
+        assert!(synced[0].is_same_node(Some(&initial[0])));
+        assert!(synced[1].is_same_node(Some(&initial[1])));
+        assert!(!synced.iter().any(|entry| entry.is_same_node(Some(&initial[2]))));
+        assert_eq!(Some(String::from("x \u{2605}")), synced[0].text_content());
+        assert_eq!(Some(String::from("b (disconnected)")), synced[1].text_content());
+        assert!(synced[1].class_name().contains("text-muted"));
+        assert_eq!(Some(String::from("4")), synced[2].get_attribute("data-player-id"));
+
+        // a new player is inserted in seat order
+        sync_list(&document, &list, &[player(1, "x", true), player(2, "b", false), player(3, "e", true), player(4, "d", true)], "b").unwrap();
+        let reordered = entries(&list);
+        assert_eq!(4, reordered.len());
+        assert!(reordered[3].is_same_node(Some(&synced[2])));
+        assert_eq!(Some(String::from("e")), reordered[2].text_content());
+
+        // the reason of a vacant seat replaces the generic text
+        let mut left = player(2, "b", false);
+        left.vacancy = Some(Vacancy { reason: String::from("left"), since: js_sys::Date::now() / 1000.0 - 120.0 });
+        sync_list(&document, &list, &[player(1, "x", true), left], "b").unwrap();
+        assert!(entries(&list)[1].text_content().unwrap().starts_with("b (left "));
+    }
+}
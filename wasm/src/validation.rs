@@ -0,0 +1,71 @@
+use js_sys::{Object, Reflect};
+use wasm_bindgen::prelude::*;
+
+use crate::rules::{parse_game_code, validate_player_name, PlayerNameError};
+
+/// Checks the username that is entered in the lobby with the same rules the server uses.
+///
+/// # Returns
+/// An object `{valid, code, message}`, `code` and `message` are `null` when the name is valid.
+/// The codes are `username_empty`, `username_too_long` and `username_invalid_character`.
+#[wasm_bindgen]
+pub fn check_username(input: &str) -> JsValue {
+    let result = Object::new();
+    let error = validate_player_name(input).err();
+    let _e = Reflect::set(&result, &"valid".into(), &error.is_none().into());
+    let _e = Reflect::set(&result, &"code".into(), &error.map(name_error_code).into());
+    let _e = Reflect::set(&result, &"message".into(), &error.map(|error| error.to_string()).into());
+    result.into()
+}
+
+/// Checks a game code with the same rules the server uses.
+///
+/// # Returns
+/// The game code in its normalized form, for example `A23B-9FRT` for ` a23b-9frt`, or `null` when the code is invalid.
+#[wasm_bindgen]
+pub fn check_game_code(input: &str) -> Option<String> {
+    let game_code: String = parse_game_code(input)?.iter().collect();
+    Some(format!("{}-{}", &game_code[..4], &game_code[4..]))
+}
+
+fn name_error_code(error: PlayerNameError) -> &'static str {
+    match error {
+        PlayerNameError::Empty => "username_empty",
+        PlayerNameError::TooLong => "username_too_long",
+        PlayerNameError::InvalidCharacter => "username_invalid_character",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use js_sys::Reflect;
+    use wasm_bindgen::JsValue;
+    use wasm_bindgen_test::*;
+
+    use super::{check_game_code, check_username};
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    fn field(value: &JsValue, name: &str) -> JsValue {
+        Reflect::get(value, &name.into()).unwrap()
+    }
+
+    #[wasm_bindgen_test]
+    fn test_check_username() {
+        let valid = check_username("  Alice ");
+        assert_eq!(Some(true), field(&valid, "valid").as_bool());
+        assert!(field(&valid, "code").is_null());
+        for (input, code) in [("   ", "username_empty"), ("aaaaaaaaaaaaaaaaaaaaa", "username_too_long"), ("a\nb", "username_invalid_character")] {
+            let result = check_username(input);
+            assert_eq!(Some(false), field(&result, "valid").as_bool());
+            assert_eq!(Some(String::from(code)), field(&result, "code").as_string());
+            assert!(field(&result, "message").as_string().is_some());
+        }
+    }
+
+    #[wasm_bindgen_test]
+    fn test_check_game_code() {
+        assert_eq!(Some(String::from("A23B-9FRT")), check_game_code(" a23b-9frt"));
+        assert_eq!(None, check_game_code("A23B9FRT"));
+    }
+}
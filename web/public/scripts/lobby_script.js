@@ -39,28 +39,97 @@ async function createGame() {
         return;
     }
     let username = document.getElementById("player-name").value;
-    let response = await postData("../api/create_game", null, {username: username})
+    let response = await postData("../api/create_game", null, {username: username, source: "main_page"})
+    if (response.error == "maintenance") {
+        showMaintenance(response.detail);
+        return;
+    }
     console.info("Saving uuid and user_name to local storage before redirect");
     localStorage.setItem('uuid', response.uuid);
     localStorage.setItem('user_name', username);
     window.location.href = "/lobby/" + response.game_code;
 }
 
+/**
+ * Shows the maintenance alert
+ * @param {string} message the message that was announced for the maintenance, can be undefined
+ */
+function showMaintenance(message) {
+    let text = "The server is in maintenance, no new games can be created.";
+    if (message != undefined) {
+        text += " " + message;
+    }
+    document.getElementById("maintenance-text").textContent = text;
+    document.getElementById("maintenance-alert").hidden = false;
+}
+
 /**
  * Join a game
  */
+/**
+ * Shows a notice of the server operators, the color depends on the severity
+ */
+function showNotice(notice) {
+    let alert = document.getElementById("server-notice-alert");
+    alert.classList.remove("alert-info", "alert-warning", "alert-danger");
+    if (notice.severity == "critical") {
+        alert.classList.add("alert-danger");
+    } else if (notice.severity == "warning") {
+        alert.classList.add("alert-warning");
+    } else {
+        alert.classList.add("alert-info");
+    }
+    document.getElementById("server-notice-text").textContent = notice.message;
+    alert.hidden = false;
+}
+
+/**
+ * Shows the notices that where posted before the page was opened
+ */
+async function loadNotices() {
+    let response = await fetch('../api/notices');
+    if (!response.ok) {
+        return;
+    }
+    let notices = await response.json();
+    notices.filter(notice => notice.applies_to != "running").forEach(showNotice);
+}
+
 async function joinGame() {
     if (!usernameEntered()) {
         return;
     }
     let username = document.getElementById("player-name").value;
-    let response = await postData("../api/join_game", null, {username: username}, new Map([["game_code", gameCodeFromURL()]]));
+    // Games are only joined from the lobby page of the game, usually opened with a shared link
+    let request = {username: username, source: "deep_link"};
+    // Set when the lobby was opened with an invite link
+    let invite = new URLSearchParams(window.location.search).get("invite");
+    if (invite != null) {
+        request.invite = invite;
+    }
+    let response = await postData("../api/join_game", null, request, new Map([["game_code", gameCodeFromURL()]]));
     console.log(response);
-    if (response == "name_taken") {
+    if (response.error == "name_taken") {
         document.getElementById("username-taken-alert").hidden = false;
         return;
     }
-    document.getElementById("username-taken-alert").hidden = true;
+    if (response.error == "lobby_locked") {
+        document.getElementById("lobby-locked-alert").hidden = false;
+        return;
+    }
+    if (response.error == "recovery_expired") {
+        document.getElementById("recovery-expired-alert").hidden = false;
+        return;
+    }
+    if (response.error == "invite_required" || response.error == "invite_invalid") {
+        document.getElementById("invite-alert").hidden = false;
+        return;
+    }
+    dismissAlerts();
+    if (response.code == "waitlisted") {
+        document.getElementById("waitlist-position").textContent = response.position;
+        document.getElementById("waitlist-alert").hidden = false;
+    }
     window.user_name = username;
     window.uuid = response.uuid;
     window.game_code = response.game_code;
@@ -79,11 +148,27 @@ async function leaveGame() {
     window.location.href = "/lobby/" + window.game_code;
 }
 
+/**
+ * Asks the server to start the game, the game page is opened when the `GameStarted` event arrives
+ */
+async function startGame() {
+    await postData("../api/start_game", window.uuid);
+}
+
+/**
+ * Opens the game page, the local storage is used to restore the player on the next page
+ */
+function openGamePage() {
+    localStorage.setItem('uuid', window.uuid);
+    localStorage.setItem('user_name', window.user_name);
+    window.location.href = "/lobby/" + window.game_code + "/game";
+}
+
 /**
  * Some debug functions to test starting the game
  */
 async function startGameDebug() {
-    wasm_bindgen.add_player("Rust", false);
+    wasm_bindgen.add_player("Rust", false, undefined);
    // localStorage.setItem('uuid', window.uuid);
    // localStorage.setItem('user_name', window.user_name);
    // localStorage.setItem('game_code', window.game_code);
@@ -97,6 +182,16 @@ async function startGameDebug() {
 function dismissAlerts() {
     document.getElementById("leave-game-alert").hidden = true;
     document.getElementById("username-taken-alert").hidden = true;
+    document.getElementById("recovery-expired-alert").hidden = true;
+    document.getElementById("lobby-locked-alert").hidden = true;
+    document.getElementById("stream-closed-alert").hidden = true;
+    document.getElementById("invite-alert").hidden = true;
+    document.getElementById("maintenance-alert").hidden = true;
+    document.getElementById("server-notice-alert").hidden = true;
+    document.getElementById("security-alert").hidden = true;
+    document.getElementById("lobby-message-alert").hidden = true;
+    document.getElementById("kicked-alert").hidden = true;
+    document.getElementById("waitlist-closed-alert").hidden = true;
 }
 
 /**
@@ -107,9 +202,12 @@ async function revealInnerContainer() {
     document.getElementById("game-code").innerHTML = gameCodeFromURL();
     document.getElementById("game-code").hidden = false;
     document.getElementById("game-code-placeholder").hidden = true;
-    var response = await fetchData('../api/players_in_game', new Map([["game_code", gameCodeFromURL()]]));
-    for (const user of response) {
-        wasm_bindgen.add_player(user, user == window.user_name);
+    // the server already rendered the players into the page
+    if (document.querySelector("#player-list [data-ssr]") == null) {
+        var response = await fetchData('../api/players_in_game', new Map([["game_code", gameCodeFromURL()]]));
+        for (const user of response) {
+            wasm_bindgen.add_player(user, user == window.user_name, undefined);
+        }
     }
     document.getElementById("player-list").hidden = false;
     document.getElementById("player-list-placeholder").hidden = true;
@@ -147,10 +245,18 @@ async function reloadPlayerList() {
     var response = await fetchData('../api/players_in_game', new Map([["game_code", gameCodeFromURL()]]));
     document.getElementById("player-list").innerHTML = "";
     for (const user of response) {
-        wasm_bindgen.add_player(user, user == window.user_name);
+        wasm_bindgen.add_player(user, user == window.user_name, undefined);
     }
 }
 
+/**
+ * Updates the list of joined players to match the snapshot of a `PlayerList` event
+ * @param {string} snapshot the data of the event, an array of players with `player_id`, `name`, `connected` and `game_master`
+ */
+function renderPlayerList(snapshot) {
+    wasm_bindgen.sync_player_list(snapshot, window.user_name);
+}
+
 /**
  * Subscribes to the event listener at /sse
  */
@@ -159,16 +265,50 @@ function subscribeEvents(uuid) {
     let game_code = gameCodeFromURL();
     let path = game_code + "/" + uuid;
     const events = new EventSource("/sse/" + path);
+    // Set when the server tells us why it closes the stream
+    let closing = null;
 
     events.addEventListener("message", (env) => {
       var data = env.data;
       var msg = JSON.parse(data);
       console.log(msg);
-      switch (msg.data[0]) {
-        case "AddPlayer":
-            wasm_bindgen.add_player(msg.data[1], false);
+      const event = msg.event;
+      switch (event.type) {
+        case "PlayerList":
+            renderPlayerList(JSON.stringify(event.data));
+            break;
+        case "LobbyLocked":
+            document.getElementById("lobby-locked").hidden = false;
+            break;
+        case "LobbyUnlocked":
+            document.getElementById("lobby-locked").hidden = true;
+            break;
+        case "LobbyStatus":
+            let status = event.data;
+            wasm_bindgen.update_start_button(status.can_start, status.current_players, status.min_players);
+            break;
+        case "SecurityAlert":
+            document.getElementById("security-alert").hidden = false;
+            break;
+        case "ServerNotice":
+            showNotice(event.data);
             break;
-        case "ReloadPlayerList":
+        case "LobbyMessage":
+            document.getElementById("lobby-message-text").textContent = event.data;
+            document.getElementById("lobby-message-alert").hidden = false;
+            break;
+        case "GameStarted":
+            events.close();
+            openGamePage();
+            break;
+        case "Promoted":
+            document.getElementById("waitlist-alert").hidden = true;
+            break;
+        case "StreamClosing":
+            closing = event.data;
+            break;
+        case "Resync":
+            // The events we missed while reconnecting are no longer known
             reloadPlayerList();
             break;
       }
@@ -180,8 +320,23 @@ function subscribeEvents(uuid) {
 
     events.addEventListener("error", () => {
       console.error("connection to event stream at /sse/" + path + " lost");
+      if (closing == null) {
+        // The browser reconnects by itself and sends Last-Event-ID, the server then replays the missed events
+        return;
+      }
       console.info("Closing event stream for /sse/" + path);
       events.close();
+      if (closing.retryable) {
+        console.info("Server closed the event stream (" + closing.reason + "), reconnecting in 5 seconds");
+        setTimeout(connect, 5000);
+      } else if (closing.reason == "kicked") {
+        document.getElementById("kicked-alert").hidden = false;
+      } else if (closing.reason == "waitlist_closed") {
+        document.getElementById("waitlist-alert").hidden = true;
+        document.getElementById("waitlist-closed-alert").hidden = false;
+      } else {
+        document.getElementById("stream-closed-alert").hidden = false;
+      }
     });
   }
 
@@ -189,6 +344,55 @@ function subscribeEvents(uuid) {
   reloadPlayerList();
 }
 
+/**
+ * Fills in the name that was used last with the recovery cookie and offers to rejoin the game of that name
+ */
+async function prefillUsername() {
+    let response = await fetch('../api/whoami');
+    if (!response.ok) {
+        return;
+    }
+    let whoami = await response.json();
+    let input = document.getElementById("player-name");
+    if (input.value == "") {
+        input.value = whoami.name;
+    }
+    if (whoami.game_code != null && whoami.game_code != gameCodeFromURL()) {
+        let rejoin = document.getElementById("rejoin-game");
+        rejoin.innerText = "Rejoin " + whoami.game_code + " as " + whoami.name;
+        rejoin.href = "/lobby/" + whoami.game_code;
+        rejoin.hidden = false;
+    }
+}
+
+/**
+ * Checks the entered username with the rules of the server while it is typed
+ * The buttons to create or join a game are disabled while the name is invalid
+ */
+function validateUsername() {
+    let input = document.getElementById("player-name");
+    let result = wasm_bindgen.check_username(input.value);
+    // An empty field is reported by usernameEntered when a button is clicked
+    let invalid = !result.valid && result.code != "username_empty";
+    input.classList.toggle("is-invalid", invalid);
+    document.getElementById("player-name-feedback").innerText = invalid ? result.message : "";
+    document.getElementById("create-game").disabled = invalid;
+    document.getElementById("join-game").disabled = invalid;
+}
+
+/**
+ * Lets the wasm module correct relative times for the difference between the clock of the browser and the server
+ */
+async function syncClock() {
+    let sentAt = Date.now();
+    let response = await fetch('../api/time');
+    if (!response.ok) {
+        return;
+    }
+    let time = await response.json();
+    wasm_bindgen.sync_clock(time.unix_millis, sentAt, Date.now());
+}
+
 /**
  * This will initialize the page and add the action to the buttons
  */
@@ -196,7 +400,19 @@ function initPage() {
     document.getElementById("create-game").addEventListener('click', createGame);
     document.getElementById("join-game").addEventListener('click', joinGame);
     document.getElementById("leave-game").addEventListener('click', leaveGame);
+    document.getElementById("start-game-button").addEventListener('click', startGame);
     document.getElementById("debug").addEventListener('click', startGameDebug);
+    // Offered by the server when the recovery cookie belongs to a player of this game
+    document.getElementById("rejoin-game").addEventListener('click', function(event) {
+        if (event.target.pathname == window.location.pathname) {
+            event.preventDefault();
+            joinGame();
+        }
+    });
+    document.getElementById("player-name").addEventListener('input', validateUsername);
+    syncClock();
+    prefillUsername();
+    loadNotices();
 }
 
 document.addEventListener("DOMContentLoaded", async function(){